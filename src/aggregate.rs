@@ -0,0 +1,474 @@
+use std::{
+    collections::{HashMap, HashSet, hash_map::DefaultHasher},
+    fmt::Display,
+    hash::{Hash, Hasher},
+    io,
+    path::PathBuf,
+    vec,
+};
+
+use crate::{
+    errors::{self, DBError},
+    expr::{BinaryOp, Expr},
+    numeric::Numeric,
+    serializer::BinaryWriter,
+    spill::{SpillFile, row_memory_size},
+    tuple::Tuple,
+    values::{DBType, DBValue},
+};
+
+/// Number of files the groups that don't fit in memory are split into.
+const SPILL_PARTITIONS: u64 = 16;
+/// Partitions are split recursively until this depth, after which the hash table grows
+/// past the memory limit instead, e.g. for a partition holding a single huge group.
+const MAX_SPILL_DEPTH: u32 = 4;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub(crate) enum AggregateFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunc {
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            AggregateFunc::Count,
+            AggregateFunc::Sum,
+            AggregateFunc::Avg,
+            AggregateFunc::Min,
+            AggregateFunc::Max,
+        ]
+        .into_iter()
+        .find(|func| name.eq_ignore_ascii_case(&func.to_string()))
+    }
+
+    /// Result type of the function applied to an argument of the given type, if it is defined.
+    /// `None` arguments are NULL literals.
+    pub fn result_type(self, arg: Option<DBType>) -> Option<DBType> {
+        match (self, arg) {
+            (AggregateFunc::Count, _) => Some(DBType::Int),
+            (AggregateFunc::Sum, None) => Some(DBType::BigInt),
+            (AggregateFunc::Sum, Some(dtype)) if dtype.is_numeric() => Some(sum_type(dtype)),
+            (AggregateFunc::Avg, None) => Some(DBType::Double),
+            // Numerics are averaged exactly.
+            (AggregateFunc::Avg, Some(DBType::Numeric(_))) => Some(DBType::Numeric(None)),
+            (AggregateFunc::Avg, Some(dtype)) if dtype.is_numeric() => Some(DBType::Double),
+            (AggregateFunc::Min | AggregateFunc::Max, _) => arg.or(Some(DBType::Int)),
+            _ => None,
+        }
+    }
+}
+
+/// Type sums of values of the type are added up in, wider than it so that sums of many
+/// values don't overflow.
+fn sum_type(dtype: DBType) -> DBType {
+    match dtype {
+        DBType::SmallInt | DBType::Int => DBType::BigInt,
+        DBType::BigInt => DBType::Numeric(None),
+        DBType::Real => DBType::Double,
+        dtype => dtype.unconstrained(),
+    }
+}
+
+impl Display for AggregateFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AggregateFunc::Count => write!(f, "count"),
+            AggregateFunc::Sum => write!(f, "sum"),
+            AggregateFunc::Avg => write!(f, "avg"),
+            AggregateFunc::Min => write!(f, "min"),
+            AggregateFunc::Max => write!(f, "max"),
+        }
+    }
+}
+
+/// An aggregate function call. `count(*)` is `count` of a non-null literal.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct Aggregate {
+    pub func: AggregateFunc,
+    pub arg: Expr,
+    pub distinct: bool,
+}
+
+enum Accumulator {
+    Count(i32),
+    Sum(Option<DBValue>),
+    /// `exact` is the sum of numeric values, the others are added up in `sum`.
+    Avg {
+        sum: f64,
+        exact: Option<Numeric>,
+        count: u64,
+    },
+    Min(Option<DBValue>),
+    Max(Option<DBValue>),
+}
+
+impl Accumulator {
+    fn new(func: AggregateFunc) -> Self {
+        match func {
+            AggregateFunc::Count => Accumulator::Count(0),
+            AggregateFunc::Sum => Accumulator::Sum(None),
+            AggregateFunc::Avg => Accumulator::Avg {
+                sum: 0.,
+                exact: None,
+                count: 0,
+            },
+            AggregateFunc::Min => Accumulator::Min(None),
+            AggregateFunc::Max => Accumulator::Max(None),
+        }
+    }
+
+    /// Adds a non-null value.
+    fn update(&mut self, value: DBValue) -> errors::Result<()> {
+        let out_of_range = || DBError::Execution("Integer out of range".to_owned());
+        match self {
+            Accumulator::Count(count) => *count = count.checked_add(1).ok_or_else(out_of_range)?,
+            Accumulator::Sum(sum) => {
+                // Values are added up in their `sum_type`.
+                let value = match value {
+                    DBValue::SmallInt(_) | DBValue::Int(_) => {
+                        DBValue::BigInt(value.as_i64().unwrap())
+                    }
+                    DBValue::BigInt(v) => DBValue::Numeric(Numeric::from_i64(v)),
+                    DBValue::Real(v) => DBValue::Double(v as f64),
+                    value => value,
+                };
+                *sum = Some(match (sum.take(), value) {
+                    (None, value) => value,
+                    (Some(sum), value) => BinaryOp::Add.apply(sum, value)?,
+                })
+            }
+            Accumulator::Avg { sum, exact, count } => {
+                match value {
+                    DBValue::Numeric(value) => {
+                        let total = exact.unwrap_or(Numeric::from_i64(0)).checked_add(value);
+                        *exact = Some(total.ok_or_else(|| DBType::Numeric(None).out_of_range())?);
+                    }
+                    value => *sum += value.as_f64().unwrap(),
+                }
+                *count += 1;
+            }
+            Accumulator::Min(min) => {
+                if min.as_ref().is_none_or(|min| value < *min) {
+                    *min = Some(value);
+                }
+            }
+            Accumulator::Max(max) => {
+                if max.as_ref().is_none_or(|max| value > *max) {
+                    *max = Some(value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> DBValue {
+        match self {
+            Accumulator::Count(count) => DBValue::Int(count),
+            Accumulator::Avg { count: 0, .. } => DBValue::Null,
+            // Dividing by a non-zero integer can't overflow.
+            Accumulator::Avg {
+                exact: Some(sum),
+                count,
+                ..
+            } => DBValue::Numeric(sum.checked_div(Numeric::from_i64(count as i64)).unwrap()),
+            Accumulator::Avg { sum, count, .. } => DBValue::Double(sum / count as f64),
+            Accumulator::Sum(value) | Accumulator::Min(value) | Accumulator::Max(value) => {
+                value.unwrap_or(DBValue::Null)
+            }
+        }
+    }
+}
+
+/// Encodes values so that equal values (including NULLs) have equal encodings.
+fn encode_values(values: &[DBValue]) -> Vec<u8> {
+    let mut writer = BinaryWriter::new(vec![]);
+    for value in values {
+        // Writing to a vector can't fail.
+        value.write_key(&mut writer).unwrap();
+    }
+    writer.into_inner()
+}
+
+struct Group {
+    key: Vec<DBValue>,
+    accumulators: Vec<Accumulator>,
+    /// Encoded values seen by each DISTINCT aggregate.
+    seen: Vec<Option<HashSet<Vec<u8>>>>,
+}
+
+/// Hash aggregation of rows consisting of the group key values followed by the argument of each
+/// aggregate. Produces rows of the group key values followed by the aggregate results.
+///
+/// Once the hash table exceeds `memory_limit` bytes, rows of groups not in the table are written
+/// to temp files partitioned by the key hash, and each partition is aggregated separately after
+/// the groups in memory are returned.
+pub(crate) struct HashAggregate {
+    key_count: usize,
+    aggregates: Vec<(AggregateFunc, bool)>,
+    temp_dir: PathBuf,
+    memory_limit: usize,
+}
+
+impl HashAggregate {
+    pub fn new(
+        key_count: usize,
+        aggregates: Vec<(AggregateFunc, bool)>,
+        temp_dir: PathBuf,
+        memory_limit: usize,
+    ) -> Self {
+        Self {
+            key_count,
+            aggregates,
+            temp_dir,
+            memory_limit,
+        }
+    }
+
+    pub fn execute(
+        self,
+        rows: impl Iterator<Item = errors::Result<Tuple>>,
+    ) -> errors::Result<AggregateRows> {
+        let (mut groups, partitions) = self.aggregate(rows, 0)?;
+        // Without GROUP BY there is exactly one group, even if there are no rows.
+        if self.key_count == 0 && groups.is_empty() {
+            groups.push(self.new_group(vec![]).finish());
+        }
+        Ok(AggregateRows {
+            aggregate: self,
+            groups: groups.into_iter(),
+            partitions: partitions.into_iter().map(|file| (file, 1)).collect(),
+        })
+    }
+
+    fn new_group(&self, key: Vec<DBValue>) -> Group {
+        Group {
+            key,
+            accumulators: self
+                .aggregates
+                .iter()
+                .map(|(func, _)| Accumulator::new(*func))
+                .collect(),
+            seen: self
+                .aggregates
+                .iter()
+                .map(|(_, distinct)| distinct.then(HashSet::new))
+                .collect(),
+        }
+    }
+
+    fn partition(&self, key: &[u8], depth: u32) -> usize {
+        let mut hasher = DefaultHasher::new();
+        depth.hash(&mut hasher);
+        key.hash(&mut hasher);
+        (hasher.finish() % SPILL_PARTITIONS) as usize
+    }
+
+    /// Aggregates the rows, returning the finished groups and the partitions that were spilled.
+    fn aggregate(
+        &self,
+        rows: impl Iterator<Item = errors::Result<Tuple>>,
+        depth: u32,
+    ) -> errors::Result<(Vec<Tuple>, Vec<SpillFile>)> {
+        let mut groups: HashMap<Vec<u8>, Group> = HashMap::new();
+        let mut memory_used = 0;
+        let mut partitions: Vec<Option<SpillFile>> = vec![];
+        for row in rows {
+            let row = row?;
+            let encoded_key = encode_values(&row.values[..self.key_count]);
+            let group = match groups.get_mut(&encoded_key) {
+                Some(group) => group,
+                None if memory_used < self.memory_limit || depth >= MAX_SPILL_DEPTH => {
+                    memory_used += encoded_key.len() + row_memory_size(&row);
+                    let key = row.values[..self.key_count].to_vec();
+                    groups
+                        .entry(encoded_key)
+                        .or_insert_with(|| self.new_group(key))
+                }
+                None => {
+                    let partition = self.partition(&encoded_key, depth);
+                    if partitions.is_empty() {
+                        partitions.resize_with(SPILL_PARTITIONS as usize, || None);
+                    }
+                    let file = match &mut partitions[partition] {
+                        Some(file) => file,
+                        file => file.insert(SpillFile::create(&self.temp_dir)?),
+                    };
+                    file.write_row(&row)?;
+                    continue;
+                }
+            };
+            let args = row.values.into_iter().skip(self.key_count);
+            for ((accumulator, seen), value) in
+                group.accumulators.iter_mut().zip(&mut group.seen).zip(args)
+            {
+                if value.is_null() {
+                    continue;
+                }
+                if let Some(seen) = seen {
+                    let encoded = encode_values(std::slice::from_ref(&value));
+                    let len = encoded.len();
+                    if !seen.insert(encoded) {
+                        continue;
+                    }
+                    memory_used += len;
+                }
+                accumulator.update(value)?;
+            }
+        }
+        let groups = groups.into_values().map(Group::finish).collect();
+        Ok((groups, partitions.into_iter().flatten().collect()))
+    }
+}
+
+impl Group {
+    fn finish(self) -> Tuple {
+        let mut values = self.key;
+        values.extend(self.accumulators.into_iter().map(Accumulator::finish));
+        Tuple::new(values)
+    }
+}
+
+/// Groups aggregated in memory, followed by the groups of the spilled partitions.
+pub(crate) struct AggregateRows {
+    aggregate: HashAggregate,
+    groups: vec::IntoIter<Tuple>,
+    /// Spilled partitions with their depth.
+    partitions: Vec<(SpillFile, u32)>,
+}
+
+impl AggregateRows {
+    fn next_partition(&mut self) -> errors::Result<bool> {
+        let Some((file, depth)) = self.partitions.pop() else {
+            return Ok(false);
+        };
+        let rows = file
+            .into_rows()
+            .map_err(|err: io::Error| DBError::IO(err))?;
+        let (groups, partitions) = self.aggregate.aggregate(rows, depth)?;
+        self.groups = groups.into_iter();
+        self.partitions
+            .extend(partitions.into_iter().map(|file| (file, depth + 1)));
+        Ok(true)
+    }
+}
+
+impl Iterator for AggregateRows {
+    type Item = errors::Result<Tuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.groups.next() {
+                return Some(Ok(row));
+            }
+            match self.next_partition() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(err) => {
+                    self.partitions.clear();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use temp_dir::TempDir;
+
+    use crate::{tuple::Tuple, values::DBValue};
+
+    use super::{AggregateFunc, HashAggregate};
+
+    fn int(v: i32) -> DBValue {
+        DBValue::Int(v)
+    }
+
+    #[test]
+    fn test_aggregate_functions() {
+        let dir = TempDir::new().unwrap();
+        let aggregate = HashAggregate::new(
+            0,
+            vec![
+                (AggregateFunc::Count, false),
+                (AggregateFunc::Count, true),
+                (AggregateFunc::Sum, false),
+                (AggregateFunc::Avg, false),
+                (AggregateFunc::Min, false),
+                (AggregateFunc::Max, false),
+            ],
+            dir.path().to_owned(),
+            1 << 20,
+        );
+        let rows = [Some(3), None, Some(1), Some(3)].map(|v| {
+            let v = v.map_or(DBValue::Null, int);
+            Ok(Tuple::new(vec![v; 6]))
+        });
+        let result = aggregate
+            .execute(rows.into_iter())
+            .unwrap()
+            .map(|row| row.unwrap().into_values())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![vec![
+                int(3),
+                int(2),
+                DBValue::BigInt(7),
+                DBValue::Double(7. / 3.),
+                int(1),
+                int(3)
+            ]]
+        );
+
+        let aggregate = HashAggregate::new(
+            0,
+            vec![(AggregateFunc::Count, false), (AggregateFunc::Sum, false)],
+            dir.path().to_owned(),
+            1 << 20,
+        );
+        let result = aggregate
+            .execute(std::iter::empty())
+            .unwrap()
+            .map(|row| row.unwrap().into_values())
+            .collect::<Vec<_>>();
+        assert_eq!(result, vec![vec![int(0), DBValue::Null]]);
+    }
+
+    #[test]
+    fn test_aggregate_spills_groups() {
+        let dir = TempDir::new().unwrap();
+        let aggregate = HashAggregate::new(
+            1,
+            vec![(AggregateFunc::Sum, false), (AggregateFunc::Count, true)],
+            dir.path().to_owned(),
+            1000,
+        );
+        let rows = (0..20_000).map(|i| Ok(Tuple::new(vec![int(i % 3000), int(i % 7), int(i)])));
+        let mut result = HashMap::new();
+        for row in aggregate.execute(rows).unwrap() {
+            let values = row.unwrap().into_values();
+            let DBValue::Int(key) = values[0] else {
+                panic!("Expected int key");
+            };
+            assert!(result.insert(key, values).is_none());
+        }
+        assert_eq!(result.len(), 3000);
+        for key in 0..3000 {
+            let ids = (key..20_000).step_by(3000);
+            let sum = ids.clone().map(|i| i % 7).sum::<i32>();
+            let count = ids.count() as i32;
+            assert_eq!(
+                result[&key],
+                vec![int(key), DBValue::BigInt(sum as i64), int(count)]
+            );
+        }
+        assert_eq!(dir.path().read_dir().unwrap().count(), 0);
+    }
+}
@@ -17,9 +17,16 @@ fn print_table_row(row: &[String], max_lengths: &[usize]) {
 }
 
 fn print_table(header: Vec<String>, iterator: impl Iterator<Item = errors::Result<Tuple>>) {
-    let rows = iterator
-        .map(|t| t.unwrap().into_values())
-        .collect::<Vec<_>>();
+    let rows = match iterator
+        .map(|t| t.map(|t| t.into_values()))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
     let string_rows = rows
         .iter()
         .map(|row| {
@@ -59,8 +66,6 @@ fn main() {
     let _ = rl.load_history(&hist_file);
 
     println!("Use \\q to exit");
-    println!("Use lowercase for SQL commands");
-    println!("Don't use ;");
     loop {
         let readline = rl.readline("> ");
         match readline {
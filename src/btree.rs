@@ -0,0 +1,577 @@
+use std::{
+    cmp::Ordering,
+    io::{Cursor, Read, Write},
+    ops::Bound,
+    path::{Path, PathBuf},
+    vec,
+};
+
+use crate::{
+    errors::{self, DBError},
+    page::PAGE_SIZE,
+    pager::Pager,
+    serializer::{BinaryReader, BinaryWriter},
+    sort::SortKey,
+    values::DBValue,
+};
+
+/// Maximum size of an encoded entry, so that a node split in two always fits in pages.
+pub(crate) const MAX_ENTRY_SIZE: usize = PAGE_SIZE / 4;
+
+const META_PAGE_ID: u32 = 0;
+const NO_PAGE: u32 = u32::MAX;
+const LEAF_TAG: u8 = 1;
+const INTERNAL_TAG: u8 = 0;
+
+/// Ordering of index keys, NULLs are after all other values. Only the first
+/// `min(a.len(), b.len())` values are compared, so a prefix of a key compares equal to it.
+pub(crate) fn compare_keys(a: &[DBValue], b: &[DBValue]) -> Ordering {
+    let key = SortKey {
+        descending: false,
+        nulls_first: false,
+    };
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| key.compare(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Values of the indexed columns of a tuple with the id of the tuple. The tuple id makes the
+/// entries unique, entries with equal values are ordered by it.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct IndexEntry {
+    pub values: Vec<DBValue>,
+    pub tuple_id: (u32, u16),
+}
+
+impl IndexEntry {
+    fn compare(&self, other: &IndexEntry) -> Ordering {
+        compare_keys(&self.values, &other.values).then(self.tuple_id.cmp(&other.tuple_id))
+    }
+
+    fn read(reader: &mut BinaryReader<impl Read>, key_len: usize) -> errors::Result<Self> {
+        let values = (0..key_len)
+            .map(|_| DBValue::read_tagged(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+        let tuple_id = (reader.read_u32()?, reader.read_u16()?);
+        Ok(Self { values, tuple_id })
+    }
+
+    fn write(&self, writer: &mut BinaryWriter<impl Write>) -> errors::Result<()> {
+        for value in &self.values {
+            value.write_tagged(writer)?;
+        }
+        writer.write_u32(self.tuple_id.0)?;
+        writer.write_u16(self.tuple_id.1)?;
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        let values = self.values.iter().map(|v| 1 + v.len()).sum::<usize>();
+        values + 4 + 2
+    }
+}
+
+/// Fails if an entry with the given values can't be stored in an index.
+pub(crate) fn check_entry_size(index_name: &str, values: &[DBValue]) -> errors::Result<()> {
+    let entry = IndexEntry {
+        values: values.to_vec(),
+        tuple_id: (0, 0),
+    };
+    if entry.size() > MAX_ENTRY_SIZE {
+        return Err(DBError::Execution(format!(
+            "Index row size {} exceeds maximum {} for index {}",
+            entry.size(),
+            MAX_ENTRY_SIZE,
+            index_name
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf {
+        entries: Vec<IndexEntry>,
+        /// Next leaf in key order.
+        next: Option<u32>,
+    },
+    /// `children[i]` has the entries between `keys[i - 1]` (inclusive) and `keys[i]`.
+    Internal {
+        keys: Vec<IndexEntry>,
+        children: Vec<u32>,
+    },
+}
+
+impl Node {
+    const HEADER_SIZE: usize = 1 + 2 + 4;
+
+    fn read(reader: &mut BinaryReader<impl Read>, key_len: usize) -> errors::Result<Self> {
+        let tag = reader.read_u8()?;
+        let count = reader.read_u16()? as usize;
+        let first = reader.read_u32()?;
+        match tag {
+            LEAF_TAG => {
+                let entries = (0..count)
+                    .map(|_| IndexEntry::read(reader, key_len))
+                    .collect::<errors::Result<Vec<_>>>()?;
+                let next = (first != NO_PAGE).then_some(first);
+                Ok(Node::Leaf { entries, next })
+            }
+            INTERNAL_TAG => {
+                let mut keys = Vec::with_capacity(count);
+                let mut children = vec![first];
+                for _ in 0..count {
+                    keys.push(IndexEntry::read(reader, key_len)?);
+                    children.push(reader.read_u32()?);
+                }
+                Ok(Node::Internal { keys, children })
+            }
+            tag => Err(DBError::Integrity(format!(
+                "Invalid index node tag {}",
+                tag
+            ))),
+        }
+    }
+
+    fn write(&self, writer: &mut BinaryWriter<impl Write>) -> errors::Result<()> {
+        match self {
+            Node::Leaf { entries, next } => {
+                writer.write_u8(LEAF_TAG)?;
+                writer.write_u16(entries.len() as u16)?;
+                writer.write_u32(next.unwrap_or(NO_PAGE))?;
+                for entry in entries {
+                    entry.write(writer)?;
+                }
+            }
+            Node::Internal { keys, children } => {
+                writer.write_u8(INTERNAL_TAG)?;
+                writer.write_u16(keys.len() as u16)?;
+                writer.write_u32(children[0])?;
+                for (key, child) in keys.iter().zip(&children[1..]) {
+                    key.write(writer)?;
+                    writer.write_u32(*child)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Node::Leaf { entries, .. } => {
+                Self::HEADER_SIZE + entries.iter().map(IndexEntry::size).sum::<usize>()
+            }
+            Node::Internal { keys, .. } => {
+                Self::HEADER_SIZE + keys.iter().map(|key| key.size() + 4).sum::<usize>()
+            }
+        }
+    }
+}
+
+/// Index of the entry to split a node at, so both halves have about the same size.
+fn split_point(entries: &[IndexEntry]) -> usize {
+    let total = entries.iter().map(IndexEntry::size).sum::<usize>();
+    let mut size = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        size += entry.size();
+        if size * 2 >= total {
+            return (i + 1).clamp(1, entries.len() - 1);
+        }
+    }
+    entries.len() / 2
+}
+
+/// B+tree of [`IndexEntry`]s stored in a file of [`PAGE_SIZE`] pages. The first page holds
+/// the root page id and the number of key values, the other pages are tree nodes.
+///
+/// Entries are removed from the leaves without merging underfull nodes, so the tree never
+/// shrinks until it is rebuilt.
+#[derive(Clone)]
+pub(crate) struct BTree<'a> {
+    pager: &'a Pager,
+    file_path: PathBuf,
+    key_len: usize,
+    root: u32,
+    page_count: u32,
+}
+
+impl<'a> BTree<'a> {
+    pub fn create(pager: &'a Pager, file_path: &Path, key_len: usize) -> errors::Result<Self> {
+        if pager.exists(file_path)? {
+            return Err(DBError::Integrity(format!(
+                "Index file {} already exists",
+                file_path.display()
+            )));
+        }
+        let mut tree = Self {
+            pager,
+            file_path: file_path.to_owned(),
+            key_len,
+            root: 0,
+            page_count: 0,
+        };
+        tree.init()?;
+        Ok(tree)
+    }
+
+    /// Removes all entries, cutting the file to an empty root.
+    pub fn clear(&mut self) -> errors::Result<()> {
+        self.pager.truncate(&self.file_path, 0)?;
+        self.init()
+    }
+
+    fn init(&mut self) -> errors::Result<()> {
+        self.page_count = 1;
+        self.root = self.allocate_page();
+        self.save_meta()?;
+        self.write_node(
+            self.root,
+            &Node::Leaf {
+                entries: vec![],
+                next: None,
+            },
+        )
+    }
+
+    pub fn open(pager: &'a Pager, file_path: PathBuf) -> errors::Result<Self> {
+        let page_count = pager.page_count(&file_path)?;
+        let meta = pager.read_page(&file_path, META_PAGE_ID)?;
+        let mut reader = BinaryReader::new(Cursor::new(&*meta));
+        let root = reader.read_u32()?;
+        let key_len = reader.read_u16()? as usize;
+        Ok(Self {
+            pager,
+            file_path,
+            key_len,
+            root,
+            page_count,
+        })
+    }
+
+    fn save_meta(&self) -> errors::Result<()> {
+        let mut page = vec![];
+        let mut writer = BinaryWriter::new(&mut page);
+        writer.write_u32(self.root)?;
+        writer.write_u16(self.key_len as u16)?;
+        self.write_page(META_PAGE_ID, page)
+    }
+
+    fn allocate_page(&mut self) -> u32 {
+        self.page_count += 1;
+        self.page_count - 1
+    }
+
+    fn write_page(&self, page_id: u32, data: Vec<u8>) -> errors::Result<()> {
+        self.pager.write_page(&self.file_path, page_id, data);
+        Ok(())
+    }
+
+    fn read_node(&self, page_id: u32) -> errors::Result<Node> {
+        if page_id == META_PAGE_ID || page_id >= self.page_count {
+            return Err(DBError::Integrity(format!(
+                "Attempted to load invalid index page {}, only {} pages exist",
+                page_id, self.page_count
+            )));
+        }
+        let buf = self.pager.read_page(&self.file_path, page_id)?;
+        Node::read(&mut BinaryReader::new(Cursor::new(&*buf)), self.key_len)
+    }
+
+    fn write_node(&self, page_id: u32, node: &Node) -> errors::Result<()> {
+        let mut data = Vec::with_capacity(PAGE_SIZE);
+        node.write(&mut BinaryWriter::new(&mut data))?;
+        self.write_page(page_id, data)
+    }
+
+    /// Writes the node, splitting it in two if it doesn't fit in a page. Returns the first key
+    /// of the new right node and its page id after a split.
+    fn write_or_split(
+        &mut self,
+        page_id: u32,
+        mut node: Node,
+    ) -> errors::Result<Option<(IndexEntry, u32)>> {
+        if node.size() <= PAGE_SIZE {
+            self.write_node(page_id, &node)?;
+            return Ok(None);
+        }
+        let right_id = self.allocate_page();
+        let (separator, right) = match &mut node {
+            Node::Leaf { entries, next } => {
+                let right_entries = entries.split_off(split_point(entries));
+                let separator = right_entries[0].clone();
+                let right = Node::Leaf {
+                    entries: right_entries,
+                    next: next.replace(right_id),
+                };
+                (separator, right)
+            }
+            Node::Internal { keys, children } => {
+                let mid = split_point(keys);
+                let right_keys = keys.split_off(mid + 1);
+                let right_children = children.split_off(mid + 1);
+                // The middle key moves up to the parent.
+                let separator = keys.pop().unwrap();
+                let right = Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                };
+                (separator, right)
+            }
+        };
+        self.write_node(right_id, &right)?;
+        self.write_node(page_id, &node)?;
+        Ok(Some((separator, right_id)))
+    }
+
+    pub fn insert(&mut self, entry: IndexEntry) -> errors::Result<()> {
+        debug_assert_eq!(entry.values.len(), self.key_len);
+        if let Some((separator, right)) = self.insert_into(self.root, entry)? {
+            let new_root = self.allocate_page();
+            let node = Node::Internal {
+                keys: vec![separator],
+                children: vec![self.root, right],
+            };
+            self.write_node(new_root, &node)?;
+            self.root = new_root;
+            self.save_meta()?;
+        }
+        Ok(())
+    }
+
+    fn insert_into(
+        &mut self,
+        page_id: u32,
+        entry: IndexEntry,
+    ) -> errors::Result<Option<(IndexEntry, u32)>> {
+        let mut node = self.read_node(page_id)?;
+        match &mut node {
+            Node::Leaf { entries, .. } => {
+                let position = entries.partition_point(|e| e.compare(&entry).is_lt());
+                if entries
+                    .get(position)
+                    .is_some_and(|e| e.compare(&entry).is_eq())
+                {
+                    return Err(DBError::Integrity(format!(
+                        "Index already has an entry for tuple {:?}",
+                        entry.tuple_id
+                    )));
+                }
+                entries.insert(position, entry);
+            }
+            Node::Internal { keys, children } => {
+                let child = keys.partition_point(|key| key.compare(&entry).is_le());
+                let Some((separator, right)) = self.insert_into(children[child], entry)? else {
+                    return Ok(None);
+                };
+                keys.insert(child, separator);
+                children.insert(child + 1, right);
+            }
+        }
+        self.write_or_split(page_id, node)
+    }
+
+    /// Removes the entry, returning whether it was in the tree.
+    pub fn delete(&mut self, entry: &IndexEntry) -> errors::Result<bool> {
+        let mut page_id = self.root;
+        loop {
+            match self.read_node(page_id)? {
+                Node::Internal { keys, children } => {
+                    page_id = children[keys.partition_point(|key| key.compare(entry).is_le())];
+                }
+                Node::Leaf { mut entries, next } => {
+                    let position = entries.partition_point(|e| e.compare(entry).is_lt());
+                    if !entries
+                        .get(position)
+                        .is_some_and(|e| e.compare(entry).is_eq())
+                    {
+                        return Ok(false);
+                    }
+                    entries.remove(position);
+                    self.write_node(page_id, &Node::Leaf { entries, next })?;
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    /// Returns the ids of the tuples with keys between the bounds, in key order. The bounds
+    /// may be prefixes of the keys, see [`compare_keys`].
+    pub fn range(
+        &self,
+        lower: Bound<Vec<DBValue>>,
+        upper: Bound<Vec<DBValue>>,
+    ) -> errors::Result<BTreeRange<'a>> {
+        let mut page_id = self.root;
+        // Descends to the first leaf that may have keys not below the lower bound.
+        while let Node::Internal { keys, children } = self.read_node(page_id)? {
+            let child = match &lower {
+                Bound::Included(lower) | Bound::Excluded(lower) => {
+                    keys.partition_point(|key| compare_keys(&key.values, lower).is_lt())
+                }
+                Bound::Unbounded => 0,
+            };
+            page_id = children[child];
+        }
+        Ok(BTreeRange {
+            tree: self.clone(),
+            entries: vec![].into_iter(),
+            next: Some(page_id),
+            lower,
+            upper,
+        })
+    }
+}
+
+/// Lazily reads the leaves of a range scan.
+pub(crate) struct BTreeRange<'a> {
+    tree: BTree<'a>,
+    entries: vec::IntoIter<IndexEntry>,
+    next: Option<u32>,
+    lower: Bound<Vec<DBValue>>,
+    upper: Bound<Vec<DBValue>>,
+}
+
+impl Iterator for BTreeRange<'_> {
+    type Item = errors::Result<(u32, u16)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(entry) = self.entries.next() else {
+                let page_id = self.next.take()?;
+                match self.tree.read_node(page_id) {
+                    Ok(Node::Leaf { entries, next }) => {
+                        self.entries = entries.into_iter();
+                        self.next = next;
+                    }
+                    Ok(Node::Internal { .. }) => {
+                        return Some(Err(DBError::Integrity(format!(
+                            "Index page {} is not a leaf",
+                            page_id
+                        ))));
+                    }
+                    Err(err) => return Some(Err(err)),
+                }
+                continue;
+            };
+            let below = match &self.lower {
+                Bound::Included(lower) => compare_keys(&entry.values, lower).is_lt(),
+                Bound::Excluded(lower) => compare_keys(&entry.values, lower).is_le(),
+                Bound::Unbounded => false,
+            };
+            if below {
+                continue;
+            }
+            let above = match &self.upper {
+                Bound::Included(upper) => compare_keys(&entry.values, upper).is_gt(),
+                Bound::Excluded(upper) => compare_keys(&entry.values, upper).is_ge(),
+                Bound::Unbounded => false,
+            };
+            if above {
+                self.entries = vec![].into_iter();
+                return None;
+            }
+            return Some(Ok(entry.tuple_id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeSet, ops::Bound};
+
+    use rand::seq::SliceRandom;
+    use temp_dir::TempDir;
+
+    use crate::{pager::Pager, values::DBValue};
+
+    use super::{BTree, IndexEntry};
+
+    fn entry(key: i32, name: &str, id: u32) -> IndexEntry {
+        IndexEntry {
+            values: vec![DBValue::Int(key), DBValue::String(name.to_owned())],
+            tuple_id: (id, 0),
+        }
+    }
+
+    fn range(tree: &BTree, lower: Bound<Vec<DBValue>>, upper: Bound<Vec<DBValue>>) -> Vec<u32> {
+        tree.range(lower, upper)
+            .unwrap()
+            .map(|id| id.unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn test_btree() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.idx");
+        let pager = Pager::new(dir.path().join("wal"));
+        let mut tree = BTree::create(&pager, &path, 2).unwrap();
+        // Long names to get a few levels of internal nodes.
+        let name = |i: u32| format!("{:0>200}", i % 7);
+        let mut ids = (0..3000).collect::<Vec<u32>>();
+        ids.shuffle(&mut rand::rng());
+        for &id in &ids {
+            tree.insert(entry((id / 10) as i32, &name(id), id)).unwrap();
+        }
+        assert!(tree.insert(entry(0, &name(3), 3)).is_err());
+        let mut expected = BTreeSet::new();
+        for &id in &ids {
+            if id % 3 == 0 {
+                assert!(
+                    tree.delete(&entry((id / 10) as i32, &name(id), id))
+                        .unwrap()
+                );
+            } else {
+                expected.insert(id);
+            }
+        }
+        assert!(!tree.delete(&entry(0, &name(0), 0)).unwrap());
+
+        pager.commit_statement().unwrap();
+        pager.checkpoint().unwrap();
+        let pager = Pager::new(dir.path().join("wal"));
+        let tree = BTree::open(&pager, path).unwrap();
+        let int = |v: i32| vec![DBValue::Int(v)];
+        let sorted = |ids: &mut dyn Iterator<Item = &u32>| {
+            let mut ids = ids.copied().collect::<Vec<_>>();
+            ids.sort_by_key(|&id| (id / 10, name(id), id));
+            ids
+        };
+        assert_eq!(
+            range(&tree, Bound::Unbounded, Bound::Unbounded),
+            sorted(&mut expected.iter())
+        );
+        assert_eq!(
+            range(&tree, Bound::Included(int(42)), Bound::Included(int(42))),
+            sorted(&mut expected.iter().filter(|&&id| id / 10 == 42))
+        );
+        assert_eq!(
+            range(&tree, Bound::Excluded(int(100)), Bound::Excluded(int(150))),
+            sorted(
+                &mut expected
+                    .iter()
+                    .filter(|&&id| id / 10 > 100 && id / 10 < 150)
+            )
+        );
+        assert_eq!(
+            range(&tree, Bound::Included(int(290)), Bound::Unbounded),
+            sorted(&mut expected.iter().filter(|&&id| id / 10 >= 290))
+        );
+        let key = vec![DBValue::Int(7), DBValue::String(name(1))];
+        assert_eq!(
+            range(&tree, Bound::Included(key.clone()), Bound::Included(key)),
+            sorted(&mut expected.iter().filter(|&&id| id / 10 == 7 && id % 7 == 1))
+        );
+        // Doubles compare with the int keys.
+        assert_eq!(
+            range(
+                &tree,
+                Bound::Excluded(vec![DBValue::Double(9.5)]),
+                Bound::Excluded(vec![DBValue::Double(11.)])
+            ),
+            sorted(&mut expected.iter().filter(|&&id| id / 10 == 10))
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    errors::{self, DBError},
+    page::PAGE_SIZE,
+};
+
+/// Default number of pages in the buffer pool, 8 MiB.
+pub(crate) const DEFAULT_CAPACITY: usize = 1024;
+
+/// How the buffer pool chooses the page to evict when it's full.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EvictionPolicy {
+    /// Evicts the page that was used least recently.
+    Lru,
+    /// Evicts the first page not used since the clock hand passed it last time, an
+    /// approximation of LRU that doesn't need to find the oldest page.
+    Clock,
+}
+
+/// Counters of the buffer pool since the database was opened.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct BufferPoolStats {
+    /// Reads of pages that were in the pool.
+    pub hits: u64,
+    /// Reads of pages that had to be read from disk.
+    pub misses: u64,
+    /// Pages removed from the pool to make room for other pages.
+    pub evictions: u64,
+    /// Pages in the pool now.
+    pub pages: usize,
+    /// Pages in the pool now that are newer than on disk.
+    pub dirty_pages: usize,
+}
+
+struct Frame {
+    path: PathBuf,
+    page_id: u32,
+    data: Rc<Vec<u8>>,
+    /// The page was changed by a commit and not written to the file yet.
+    dirty: bool,
+    pins: usize,
+    /// Tick of the last use, for LRU.
+    last_used: u64,
+    /// The page was used since the clock hand passed it, for CLOCK.
+    referenced: bool,
+    /// The page was dropped while pinned. It's not in the lookup, and the frame is freed
+    /// when the last pin is released.
+    dropped: bool,
+}
+
+/// Cache of committed pages of table and index files. Pages changed by commits stay in the
+/// pool as dirty pages, and are written to the files when they are evicted or when
+/// [`BufferPool::flush`] is called. Pinned pages are never evicted.
+pub(crate) struct BufferPool {
+    capacity: usize,
+    policy: EvictionPolicy,
+    frames: Vec<Frame>,
+    lookup: HashMap<(PathBuf, u32), usize>,
+    clock_hand: usize,
+    tick: u64,
+    /// Files with pages written since the last flush, that have to be synced.
+    unsynced: HashSet<PathBuf>,
+    /// Number of pages of the files including the dirty pages, which may be past the end of
+    /// the files on disk.
+    page_counts: HashMap<PathBuf, u32>,
+    /// Number of frames dropped while pinned.
+    dropped: usize,
+    stats: BufferPoolStats,
+}
+
+impl BufferPool {
+    pub fn new(capacity: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policy,
+            frames: vec![],
+            lookup: HashMap::new(),
+            clock_hand: 0,
+            tick: 0,
+            unsynced: HashSet::new(),
+            page_counts: HashMap::new(),
+            dropped: 0,
+            stats: BufferPoolStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            pages: self.frames.len(),
+            dirty_pages: self.frames.iter().filter(|frame| frame.dirty).count(),
+            ..self.stats
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: EvictionPolicy) {
+        self.policy = policy;
+    }
+
+    /// Changes the number of pages in the pool, evicting pages if there are too many.
+    pub fn set_capacity(&mut self, capacity: usize) -> errors::Result<()> {
+        self.capacity = capacity.max(1);
+        while self.frames.len() > self.capacity {
+            let victim = self.choose_victim()?;
+            self.evict(victim)?;
+            self.free(victim);
+        }
+        self.clock_hand = 0;
+        Ok(())
+    }
+
+    /// Returns the number of pages of the file if dirty pages were added to it.
+    pub fn page_count(&self, path: &Path) -> Option<u32> {
+        self.page_counts.get(path).copied()
+    }
+
+    fn touch(&mut self, frame: usize) {
+        self.tick += 1;
+        let frame = &mut self.frames[frame];
+        frame.last_used = self.tick;
+        frame.referenced = true;
+    }
+
+    /// Returns the frame of the page if it's in the pool, counting a hit or a miss.
+    pub fn find(&mut self, path: &Path, page_id: u32) -> Option<usize> {
+        match self.lookup.get(&(path.to_owned(), page_id)) {
+            Some(&frame) => {
+                self.stats.hits += 1;
+                self.touch(frame);
+                Some(frame)
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn data(&self, frame: usize) -> Rc<Vec<u8>> {
+        self.frames[frame].data.clone()
+    }
+
+    pub fn pin(&mut self, frame: usize) {
+        self.frames[frame].pins += 1;
+    }
+
+    /// Unpins the page with the data returned by [`BufferPool::data`]. Frames move when pages
+    /// are removed, so the page is found by its id, or by its data if it was dropped while
+    /// pinned, as the file may have a new page with the same id by now.
+    pub fn unpin(&mut self, path: &Path, page_id: u32, data: &Rc<Vec<u8>>) {
+        let dropped = match self.dropped {
+            0 => None,
+            _ => self
+                .frames
+                .iter()
+                .position(|frame| frame.dropped && Rc::ptr_eq(&frame.data, data)),
+        };
+        if let Some(frame) = dropped {
+            self.frames[frame].pins -= 1;
+            if self.frames[frame].pins == 0 {
+                self.dropped -= 1;
+                self.free(frame);
+            }
+        } else if let Some(&frame) = self.lookup.get(&(path.to_owned(), page_id)) {
+            self.frames[frame].pins -= 1;
+        }
+    }
+
+    /// Adds a page to the pool or replaces the cached page, returning its frame.
+    pub fn insert(
+        &mut self,
+        path: &Path,
+        page_id: u32,
+        data: Vec<u8>,
+        dirty: bool,
+    ) -> errors::Result<usize> {
+        if dirty {
+            let page_count = self.page_counts.entry(path.to_owned()).or_default();
+            *page_count = (*page_count).max(page_id + 1);
+        }
+        let key = (path.to_owned(), page_id);
+        if let Some(&frame) = self.lookup.get(&key) {
+            let frame_data = &mut self.frames[frame];
+            frame_data.data = Rc::new(data);
+            frame_data.dirty |= dirty;
+            self.touch(frame);
+            return Ok(frame);
+        }
+        let new_frame = Frame {
+            path: path.to_owned(),
+            page_id,
+            data: Rc::new(data),
+            dirty,
+            pins: 0,
+            last_used: 0,
+            referenced: false,
+            dropped: false,
+        };
+        let frame = if self.frames.len() < self.capacity {
+            self.frames.push(new_frame);
+            self.frames.len() - 1
+        } else {
+            let victim = self.choose_victim()?;
+            self.evict(victim)?;
+            self.frames[victim] = new_frame;
+            victim
+        };
+        self.lookup.insert(key, frame);
+        self.touch(frame);
+        Ok(frame)
+    }
+
+    fn choose_victim(&mut self) -> errors::Result<usize> {
+        let victim = match self.policy {
+            EvictionPolicy::Lru => self
+                .frames
+                .iter()
+                .enumerate()
+                .filter(|(_, frame)| frame.pins == 0)
+                .min_by_key(|(_, frame)| frame.last_used)
+                .map(|(i, _)| i),
+            EvictionPolicy::Clock => {
+                // The first round clears the reference bits, so the second one finds a page
+                // unless all of them are pinned.
+                let mut victim = None;
+                for _ in 0..2 * self.frames.len() {
+                    let hand = self.clock_hand % self.frames.len();
+                    self.clock_hand = hand + 1;
+                    let frame = &mut self.frames[hand];
+                    if frame.pins > 0 {
+                        continue;
+                    }
+                    if frame.referenced {
+                        frame.referenced = false;
+                        continue;
+                    }
+                    victim = Some(hand);
+                    break;
+                }
+                victim
+            }
+        };
+        victim.ok_or_else(|| {
+            DBError::Execution(format!(
+                "All {} pages of the buffer pool are pinned",
+                self.frames.len()
+            ))
+        })
+    }
+
+    /// Writes the page of the frame if it's dirty and removes it from the lookup.
+    fn evict(&mut self, frame: usize) -> io::Result<()> {
+        self.write_back(frame)?;
+        let frame = &self.frames[frame];
+        self.lookup.remove(&(frame.path.clone(), frame.page_id));
+        self.stats.evictions += 1;
+        Ok(())
+    }
+
+    fn write_back(&mut self, frame: usize) -> io::Result<()> {
+        let frame = &mut self.frames[frame];
+        if !frame.dirty {
+            return Ok(());
+        }
+        let mut file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&frame.path)?;
+        file.seek(SeekFrom::Start(PAGE_SIZE as u64 * frame.page_id as u64))?;
+        file.write_all(&frame.data)?;
+        frame.dirty = false;
+        self.unsynced.insert(frame.path.clone());
+        Ok(())
+    }
+
+    /// Writes all dirty pages and syncs the files they were written to.
+    pub fn flush(&mut self) -> io::Result<()> {
+        for frame in 0..self.frames.len() {
+            self.write_back(frame)?;
+        }
+        for path in std::mem::take(&mut self.unsynced) {
+            File::open(path)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Drops the pages of a removed file without writing them.
+    pub fn remove_file(&mut self, path: &Path) {
+        self.unsynced.remove(path);
+        self.page_counts.remove(path);
+        self.drop_pages(path, 0);
+    }
+
+    /// Drops the pages of a file from `page_count` on, as the file was cut there.
+    pub fn truncate_file(&mut self, path: &Path, page_count: u32) {
+        if let Some(count) = self.page_counts.get_mut(path) {
+            *count = (*count).min(page_count);
+        }
+        self.drop_pages(path, page_count);
+    }
+
+    fn drop_pages(&mut self, path: &Path, from: u32) {
+        let mut i = 0;
+        while i < self.frames.len() {
+            let frame = &mut self.frames[i];
+            if frame.dropped || frame.path != path || frame.page_id < from {
+                i += 1;
+            } else if frame.pins > 0 {
+                // Still read by a scan, but must not be found or written back.
+                frame.dirty = false;
+                frame.dropped = true;
+                self.dropped += 1;
+                self.lookup.remove(&(path.to_owned(), frame.page_id));
+                i += 1;
+            } else {
+                self.free(i);
+            }
+        }
+    }
+
+    /// Removes the frame from the pool, moving the last frame in its place.
+    fn free(&mut self, frame: usize) {
+        let removed = self.frames.swap_remove(frame);
+        if !removed.dropped {
+            self.lookup.remove(&(removed.path, removed.page_id));
+        }
+        if let Some(moved) = self.frames.get(frame)
+            && !moved.dropped
+        {
+            self.lookup
+                .insert((moved.path.clone(), moved.page_id), frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::Path, rc::Rc};
+
+    use temp_dir::TempDir;
+
+    use crate::page::PAGE_SIZE;
+
+    use super::{BufferPool, EvictionPolicy};
+
+    fn cached(pool: &BufferPool, path: &Path) -> Vec<u32> {
+        let mut pages = pool
+            .lookup
+            .keys()
+            .filter(|(p, _)| p == path)
+            .map(|(_, page_id)| *page_id)
+            .collect::<Vec<_>>();
+        pages.sort();
+        pages
+    }
+
+    #[test]
+    fn test_lru() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test");
+        let mut pool = BufferPool::new(3, EvictionPolicy::Lru);
+        for page_id in 0..3 {
+            pool.insert(&path, page_id, vec![page_id as u8; PAGE_SIZE], false)
+                .unwrap();
+        }
+        assert!(pool.find(&path, 0).is_some());
+        pool.insert(&path, 3, vec![3; PAGE_SIZE], false).unwrap();
+        assert_eq!(cached(&pool, &path), [0, 2, 3]);
+
+        // Pinned pages stay even if they are the oldest.
+        let frame = pool.find(&path, 0).unwrap();
+        pool.pin(frame);
+        assert!(pool.find(&path, 2).is_some());
+        assert!(pool.find(&path, 3).is_some());
+        pool.insert(&path, 4, vec![4; PAGE_SIZE], false).unwrap();
+        assert_eq!(cached(&pool, &path), [0, 3, 4]);
+        assert!(pool.find(&path, 1).is_none());
+
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (4, 1, 2));
+        assert_eq!(stats.pages, 3);
+    }
+
+    #[test]
+    fn test_clock() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test");
+        let mut pool = BufferPool::new(3, EvictionPolicy::Clock);
+        for page_id in 0..3 {
+            pool.insert(&path, page_id, vec![0; PAGE_SIZE], false)
+                .unwrap();
+        }
+        // All pages are referenced, so the hand goes around once and evicts page 0.
+        pool.insert(&path, 3, vec![0; PAGE_SIZE], false).unwrap();
+        assert_eq!(cached(&pool, &path), [1, 2, 3]);
+        // Page 1 is used again, so page 2 is the first one without a reference.
+        assert!(pool.find(&path, 1).is_some());
+        pool.insert(&path, 4, vec![0; PAGE_SIZE], false).unwrap();
+        assert_eq!(cached(&pool, &path), [1, 3, 4]);
+    }
+
+    #[test]
+    fn test_dirty_pages_are_written_on_eviction() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test");
+        let mut pool = BufferPool::new(2, EvictionPolicy::Lru);
+        pool.insert(&path, 1, vec![1; PAGE_SIZE], true).unwrap();
+        pool.insert(&path, 0, vec![0; PAGE_SIZE], true).unwrap();
+        assert_eq!(pool.stats().dirty_pages, 2);
+        assert!(!path.exists());
+
+        pool.insert(&path, 5, vec![5; PAGE_SIZE], false).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data.len(), 2 * PAGE_SIZE);
+        assert_eq!(data[PAGE_SIZE], 1);
+        pool.flush().unwrap();
+        assert_eq!(pool.stats().dirty_pages, 0);
+        assert_eq!(std::fs::read(&path).unwrap()[0], 0);
+
+        let frames = [pool.find(&path, 0).unwrap(), pool.find(&path, 5).unwrap()];
+        frames.iter().for_each(|&frame| pool.pin(frame));
+        let err = pool.insert(&path, 6, vec![6; PAGE_SIZE], false);
+        assert_eq!(
+            err.err().unwrap().to_string(),
+            "Failed to execute the query: All 2 pages of the buffer pool are pinned"
+        );
+        let data = pool.data(frames[0]);
+        pool.unpin(&path, 0, &data);
+        pool.insert(&path, 6, vec![6; PAGE_SIZE], false).unwrap();
+        assert_eq!(cached(&pool, &path), [5, 6]);
+    }
+
+    #[test]
+    fn test_drop_pinned_pages() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test");
+        let mut pool = BufferPool::new(3, EvictionPolicy::Lru);
+        pool.insert(&path, 0, vec![0; PAGE_SIZE], true).unwrap();
+        let frame = pool.insert(&path, 1, vec![1; PAGE_SIZE], true).unwrap();
+        pool.pin(frame);
+        let old = pool.data(frame);
+
+        // A dropped page isn't found while it's still pinned.
+        pool.truncate_file(&path, 1);
+        assert_eq!(cached(&pool, &path), [0]);
+        assert!(pool.find(&path, 1).is_none());
+        assert_eq!(pool.stats().pages, 2);
+        let frame = pool.insert(&path, 1, vec![2; PAGE_SIZE], true).unwrap();
+        pool.pin(frame);
+        let new = pool.data(frame);
+        assert_eq!(pool.stats().dirty_pages, 2);
+
+        // Releasing the old pin frees its frame and leaves the new page pinned.
+        pool.unpin(&path, 1, &old);
+        assert_eq!(pool.stats().pages, 2);
+        let frame = pool.find(&path, 1).unwrap();
+        assert!(Rc::ptr_eq(&pool.data(frame), &new));
+        assert_eq!(pool.frames[frame].pins, 1);
+        pool.unpin(&path, 1, &new);
+        assert_eq!(pool.frames[frame].pins, 0);
+        pool.flush().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap()[PAGE_SIZE], 2);
+    }
+}
@@ -0,0 +1,928 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+    io::{self, Read, Write},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    numeric::Numeric,
+    serializer::{BinaryReader, BinaryWriter},
+};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// Length of a month when comparing intervals, as in `1 mon = 30 days`.
+const DAYS_PER_MONTH: i64 = 30;
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Years start in March, so the leap day is the last day of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year, month and day of the date `days` after 1970-01-01, see [`days_from_civil`].
+const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Dates are between 0001-01-01 and 9999-12-31, so that years have four digits.
+const MIN_DAYS: i64 = days_from_civil(1, 1, 1);
+const MAX_DAYS: i64 = days_from_civil(9999, 12, 31);
+
+fn invalid_data(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}", what))
+}
+
+/// Parses an unsigned number of `min_digits` to `max_digits` digits.
+fn parse_digits(s: &str, min_digits: usize, max_digits: usize) -> Option<i64> {
+    let valid =
+        (min_digits..=max_digits).contains(&s.len()) && s.bytes().all(|c| c.is_ascii_digit());
+    valid.then(|| s.parse().ok()).flatten()
+}
+
+/// Parses the digits after a decimal point as microseconds.
+fn parse_fraction(s: &str) -> Option<i64> {
+    let digits = parse_digits(s, 1, 6)?;
+    Some(digits * 10_i64.pow(6 - s.len() as u32))
+}
+
+/// Parses `hh:mm[:ss[.ffffff]]` as microseconds, with hours up to `max_hours`.
+fn parse_clock(s: &str, max_hours: i64) -> Option<i64> {
+    let mut parts = s.splitn(3, ':');
+    let hours = parse_digits(parts.next()?, 1, 9).filter(|&h| h <= max_hours)?;
+    let minutes = parse_digits(parts.next()?, 2, 2).filter(|&m| m < 60)?;
+    let micros = match parts.next() {
+        None => 0,
+        Some(seconds) => {
+            let (seconds, fraction) = match seconds.split_once('.') {
+                Some((seconds, fraction)) => (seconds, parse_fraction(fraction)?),
+                None => (seconds, 0),
+            };
+            parse_digits(seconds, 2, 2).filter(|&s| s < 60)? * MICROS_PER_SECOND + fraction
+        }
+    };
+    Some(hours * MICROS_PER_HOUR + minutes * MICROS_PER_MINUTE + micros)
+}
+
+/// Writes `hh:mm:ss` of the microseconds, followed by the fraction of a second if any.
+fn write_clock(f: &mut fmt::Formatter<'_>, micros: u64) -> fmt::Result {
+    let (seconds, fraction) = (micros / 1_000_000, micros % 1_000_000);
+    write!(
+        f,
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )?;
+    match fraction {
+        0 => Ok(()),
+        fraction => write!(f, ".{}", format!("{:06}", fraction).trim_end_matches('0')),
+    }
+}
+
+/// A number of microseconds, or of milliseconds if `scale` is 3, or of seconds if it's 6.
+fn fixed(value: i64, scale: u8) -> Numeric {
+    Numeric::new(value.into(), scale).unwrap()
+}
+
+/// Part of a date, time or interval, for `extract` and `date_trunc`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum DateField {
+    /// Seconds with their fraction, in microseconds.
+    Microseconds,
+    /// Seconds with their fraction, in milliseconds.
+    Milliseconds,
+    /// Seconds with their fraction.
+    Second,
+    Minute,
+    Hour,
+    Day,
+    /// ISO 8601 week of the year, weeks start on Monday.
+    Week,
+    Month,
+    Quarter,
+    Year,
+    /// Day of the week, 0 for Sunday.
+    DayOfWeek,
+    /// Day of the year, from 1.
+    DayOfYear,
+    /// Seconds since 1970-01-01 00:00:00, or the length of an interval in seconds.
+    Epoch,
+}
+
+impl DateField {
+    /// Whether timestamps can be truncated to the unit with `date_trunc`.
+    pub fn can_truncate(self) -> bool {
+        !matches!(
+            self,
+            DateField::DayOfWeek | DateField::DayOfYear | DateField::Epoch
+        )
+    }
+
+    /// The field of `hh:mm:ss` that's `micros` long, hours may exceed a day.
+    fn of_clock(self, micros: i64) -> Option<Numeric> {
+        let in_minute = micros % MICROS_PER_MINUTE;
+        match self {
+            DateField::Microseconds => Some(fixed(in_minute, 0)),
+            DateField::Milliseconds => Some(fixed(in_minute, 3)),
+            DateField::Second => Some(fixed(in_minute, 6)),
+            DateField::Minute => Some(fixed(micros / MICROS_PER_MINUTE % 60, 0)),
+            DateField::Hour => Some(fixed(micros / MICROS_PER_HOUR, 0)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DateField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DateField::Microseconds => "microseconds",
+            DateField::Milliseconds => "milliseconds",
+            DateField::Second => "second",
+            DateField::Minute => "minute",
+            DateField::Hour => "hour",
+            DateField::Day => "day",
+            DateField::Week => "week",
+            DateField::Month => "month",
+            DateField::Quarter => "quarter",
+            DateField::Year => "year",
+            DateField::DayOfWeek => "dow",
+            DateField::DayOfYear => "doy",
+            DateField::Epoch => "epoch",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DateField {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "microseconds" => Ok(DateField::Microseconds),
+            "milliseconds" => Ok(DateField::Milliseconds),
+            "second" => Ok(DateField::Second),
+            "minute" => Ok(DateField::Minute),
+            "hour" => Ok(DateField::Hour),
+            "day" => Ok(DateField::Day),
+            "week" => Ok(DateField::Week),
+            "month" => Ok(DateField::Month),
+            "quarter" => Ok(DateField::Quarter),
+            "year" => Ok(DateField::Year),
+            "dow" => Ok(DateField::DayOfWeek),
+            "doy" => Ok(DateField::DayOfYear),
+            "epoch" => Ok(DateField::Epoch),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Calendar date, stored as the number of days since 1970-01-01.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub struct Date(i32);
+
+impl Date {
+    /// `None` if the date doesn't exist or is out of range.
+    pub fn from_ymd(year: i64, month: i64, day: i64) -> Option<Self> {
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+        Self::from_days(days_from_civil(year, month, day))
+    }
+
+    /// The date `days` after 1970-01-01, `None` if it's out of range.
+    pub fn from_days(days: i64) -> Option<Self> {
+        (MIN_DAYS..=MAX_DAYS)
+            .contains(&days)
+            .then_some(Self(days as i32))
+    }
+
+    /// Today's date in UTC.
+    pub fn today() -> Self {
+        Timestamp::now().date()
+    }
+
+    pub fn days(self) -> i32 {
+        self.0
+    }
+
+    pub fn ymd(self) -> (i64, i64, i64) {
+        civil_from_days(self.0.into())
+    }
+
+    /// Day of the week, 0 for Sunday.
+    pub fn weekday(self) -> i64 {
+        // 1970-01-01 was a Thursday.
+        (i64::from(self.0) + 4).rem_euclid(7)
+    }
+
+    pub fn day_of_year(self) -> i64 {
+        let (year, _, _) = self.ymd();
+        i64::from(self.0) - days_from_civil(year, 1, 1) + 1
+    }
+
+    /// ISO 8601 week number. Weeks start on Monday and the first week of a year is the one
+    /// with its first Thursday.
+    pub fn iso_week(self) -> i64 {
+        let thursday = i64::from(self.0) + 3 - (self.weekday() + 6) % 7;
+        let (year, _, _) = civil_from_days(thursday);
+        (thursday - days_from_civil(year, 1, 1)) / 7 + 1
+    }
+
+    pub fn checked_add_days(self, days: i64) -> Option<Self> {
+        Self::from_days(i64::from(self.0).checked_add(days)?)
+    }
+
+    /// The same day `months` later, or the last day of that month if it's shorter.
+    pub fn checked_add_months(self, months: i64) -> Option<Self> {
+        let (year, month, day) = self.ymd();
+        let month_index = (year * 12 + month - 1).checked_add(months)?;
+        let (year, month) = (month_index.div_euclid(12), month_index.rem_euclid(12) + 1);
+        Self::from_ymd(year, month, day.min(days_in_month(year, month)))
+    }
+
+    pub fn at_midnight(self) -> Timestamp {
+        Timestamp(i64::from(self.0) * MICROS_PER_DAY)
+    }
+
+    /// The field of the date, `None` for the fields of a time of day.
+    pub fn extract(self, field: DateField) -> Option<Numeric> {
+        match field {
+            DateField::Epoch => Some(fixed(i64::from(self.0) * 86_400, 0)),
+            DateField::Microseconds
+            | DateField::Milliseconds
+            | DateField::Second
+            | DateField::Minute
+            | DateField::Hour => None,
+            field => Some(self.at_midnight().extract(field)),
+        }
+    }
+
+    pub fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        Self::from_days(reader.read_i32()?.into()).ok_or_else(|| invalid_data("date"))
+    }
+
+    pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_i32(self.0)
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+/// Parses `yyyy-mm-dd`.
+impl FromStr for Date {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '-');
+        let mut part = |min_digits, max_digits| {
+            parts
+                .next()
+                .and_then(|part| parse_digits(part, min_digits, max_digits))
+                .ok_or(())
+        };
+        let (year, month, day) = (part(4, 4)?, part(1, 2)?, part(1, 2)?);
+        Self::from_ymd(year, month, day).ok_or(())
+    }
+}
+
+/// Time of day, stored as the number of microseconds since midnight.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub struct Time(i64);
+
+impl Time {
+    /// `None` unless `micros` is less than a day.
+    pub fn from_micros(micros: i64) -> Option<Self> {
+        (0..MICROS_PER_DAY)
+            .contains(&micros)
+            .then_some(Self(micros))
+    }
+
+    pub fn micros(self) -> i64 {
+        self.0
+    }
+
+    /// The time `micros` later, wrapping around midnight.
+    pub fn wrapping_add(self, micros: i64) -> Self {
+        Self((self.0 + micros.rem_euclid(MICROS_PER_DAY)) % MICROS_PER_DAY)
+    }
+
+    /// The field of the time, `None` for the fields of a date.
+    pub fn extract(self, field: DateField) -> Option<Numeric> {
+        match field {
+            DateField::Epoch => Some(fixed(self.0, 6)),
+            field => field.of_clock(self.0),
+        }
+    }
+
+    pub fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        Self::from_micros(reader.read_i64()?).ok_or_else(|| invalid_data("time"))
+    }
+
+    pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_i64(self.0)
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_clock(f, self.0 as u64)
+    }
+}
+
+/// Parses `hh:mm[:ss[.ffffff]]`.
+impl FromStr for Time {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_clock(s, 23).map(Self).ok_or(())
+    }
+}
+
+/// Date and time of day without a time zone, stored as the number of microseconds since
+/// 1970-01-01 00:00:00.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn new(date: Date, time: Time) -> Self {
+        Self(date.at_midnight().0 + time.0)
+    }
+
+    /// `None` if the timestamp is out of the range of dates.
+    pub fn from_micros(micros: i64) -> Option<Self> {
+        Date::from_days(micros.div_euclid(MICROS_PER_DAY)).map(|_| Self(micros))
+    }
+
+    /// The current time in UTC.
+    pub fn now() -> Self {
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Self(elapsed.as_micros() as i64)
+    }
+
+    pub fn date(self) -> Date {
+        Date(self.0.div_euclid(MICROS_PER_DAY) as i32)
+    }
+
+    pub fn time(self) -> Time {
+        Time(self.0.rem_euclid(MICROS_PER_DAY))
+    }
+
+    /// Adds the months of the interval, then its days and then its time.
+    pub fn checked_add(self, interval: Interval) -> Option<Self> {
+        let date = self
+            .date()
+            .checked_add_months(interval.months.into())?
+            .checked_add_days(interval.days.into())?;
+        Self::from_micros(
+            Self::new(date, self.time())
+                .0
+                .checked_add(interval.micros)?,
+        )
+    }
+
+    pub fn checked_sub(self, interval: Interval) -> Option<Self> {
+        self.checked_add(interval.checked_neg()?)
+    }
+
+    /// The interval from `other` to the timestamp, in days and time.
+    pub fn diff(self, other: Self) -> Interval {
+        // Timestamps are less than 10000 years apart, so neither part overflows.
+        let micros = self.0 - other.0;
+        Interval::new(0, (micros / MICROS_PER_DAY) as i32, micros % MICROS_PER_DAY)
+    }
+
+    pub fn extract(self, field: DateField) -> Numeric {
+        let date = self.date();
+        let (year, month, day) = date.ymd();
+        let value = match field {
+            DateField::Day => day,
+            DateField::Week => date.iso_week(),
+            DateField::Month => month,
+            DateField::Quarter => (month - 1) / 3 + 1,
+            DateField::Year => year,
+            DateField::DayOfWeek => date.weekday(),
+            DateField::DayOfYear => date.day_of_year(),
+            DateField::Epoch => return fixed(self.0, 6),
+            field => return field.of_clock(self.time().0).unwrap(),
+        };
+        fixed(value, 0)
+    }
+
+    /// The start of the unit the timestamp is in, e.g. the first day of its month for
+    /// [`DateField::Month`]. `None` for fields that aren't units, see
+    /// [`DateField::can_truncate`].
+    pub fn truncate(self, field: DateField) -> Option<Self> {
+        let (date, time) = (self.date(), self.time().0);
+        let (year, month, _) = date.ymd();
+        let start_of = |unit| Some(Self::new(date, Time(time - time % unit)));
+        let date = match field {
+            DateField::Microseconds => return Some(self),
+            DateField::Milliseconds => return start_of(1000),
+            DateField::Second => return start_of(MICROS_PER_SECOND),
+            DateField::Minute => return start_of(MICROS_PER_MINUTE),
+            DateField::Hour => return start_of(MICROS_PER_HOUR),
+            DateField::Day => date,
+            // 0001-01-01 was a Monday, so the week starts in range.
+            DateField::Week => date.checked_add_days(-((date.weekday() + 6) % 7))?,
+            DateField::Month => Date::from_ymd(year, month, 1)?,
+            DateField::Quarter => Date::from_ymd(year, month - (month - 1) % 3, 1)?,
+            DateField::Year => Date::from_ymd(year, 1, 1)?,
+            DateField::DayOfWeek | DateField::DayOfYear | DateField::Epoch => return None,
+        };
+        Some(date.at_midnight())
+    }
+
+    pub fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        Self::from_micros(reader.read_i64()?).ok_or_else(|| invalid_data("timestamp"))
+    }
+
+    pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_i64(self.0)
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.date())?;
+        write_clock(f, self.time().0 as u64)
+    }
+}
+
+/// Parses `yyyy-mm-dd[ hh:mm[:ss[.ffffff]]]`, with a `T` or a space between the date and
+/// the time.
+impl FromStr for Timestamp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (date, time) = match s.split_once(['T', ' ']) {
+            Some((date, time)) => (date, time.parse()?),
+            None => (s, Time(0)),
+        };
+        Ok(Self::new(date.parse()?, time))
+    }
+}
+
+/// Length of time in months, days and microseconds. They are kept apart as months and days
+/// vary in length, so adding `1 mon` to a timestamp gives the same day of the next month.
+#[derive(Debug, Clone, Copy)]
+pub struct Interval {
+    months: i32,
+    days: i32,
+    micros: i64,
+}
+
+impl Interval {
+    pub fn new(months: i32, days: i32, micros: i64) -> Self {
+        Self {
+            months,
+            days,
+            micros,
+        }
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        Some(Self::new(
+            self.months.checked_add(other.months)?,
+            self.days.checked_add(other.days)?,
+            self.micros.checked_add(other.micros)?,
+        ))
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        Some(Self::new(
+            self.months.checked_neg()?,
+            self.days.checked_neg()?,
+            self.micros.checked_neg()?,
+        ))
+    }
+
+    pub fn micros(self) -> i64 {
+        self.micros
+    }
+
+    /// Length in microseconds, with months of 30 days and days of 24 hours.
+    fn span(self) -> i128 {
+        let days = i128::from(self.months) * i128::from(DAYS_PER_MONTH) + i128::from(self.days);
+        days * i128::from(MICROS_PER_DAY) + i128::from(self.micros)
+    }
+
+    /// The interval with whole days of its time carried into days and whole months of its
+    /// days into months, so that equal intervals have the same parts.
+    pub fn justified(self) -> Self {
+        let month = i128::from(DAYS_PER_MONTH * MICROS_PER_DAY);
+        let span = self.span();
+        // Only intervals of more than 178 million years don't fit.
+        Self::new(
+            (span / month) as i32,
+            (span % month / i128::from(MICROS_PER_DAY)) as i32,
+            (span % i128::from(MICROS_PER_DAY)) as i64,
+        )
+    }
+
+    /// The field of the interval, `None` for the fields that only dates have.
+    pub fn extract(self, field: DateField) -> Option<Numeric> {
+        let (years, months) = (i64::from(self.months / 12), i64::from(self.months % 12));
+        let value = match field {
+            DateField::Day => self.days.into(),
+            DateField::Month => months,
+            DateField::Quarter => months / 3 + 1,
+            DateField::Year => years,
+            // Years of 365.25 days, and months of 30 days.
+            DateField::Epoch => {
+                let days = months * DAYS_PER_MONTH + i64::from(self.days);
+                let micros = i128::from(years) * 36_525 * i128::from(MICROS_PER_DAY) / 100
+                    + i128::from(days) * i128::from(MICROS_PER_DAY)
+                    + i128::from(self.micros);
+                return Numeric::new(micros, 6);
+            }
+            DateField::Week | DateField::DayOfWeek | DateField::DayOfYear => return None,
+            field => return field.of_clock(self.micros),
+        };
+        Some(fixed(value, 0))
+    }
+
+    /// Parses ISO 8601 durations, `P[nY][nM][nW][nD][T[nH][nM][nS]]`.
+    fn parse_iso(s: &str) -> Option<Self> {
+        let (date, time) = s.split_once('T').unwrap_or((s, ""));
+        if date.is_empty() && time.is_empty() {
+            return None;
+        }
+        let date_units = [('Y', "year"), ('M', "month"), ('W', "week"), ('D', "day")];
+        let time_units = [('H', "hour"), ('M', "minute"), ('S', "second")];
+        let mut interval = Self::new(0, 0, 0);
+        for (mut rest, units) in [(date, &date_units[..]), (time, &time_units[..])] {
+            let mut units = units.iter();
+            while !rest.is_empty() {
+                let end = rest.find(|c: char| c.is_ascii_alphabetic())?;
+                let (number, designator) = (&rest[..end], rest[end..].chars().next()?);
+                rest = &rest[end + 1..];
+                // Units are in order and used once.
+                let (_, unit) = units.find(|(u, _)| *u == designator)?;
+                interval = interval.checked_add(Self::parse_quantity(number, unit)?)?;
+            }
+        }
+        Some(interval)
+    }
+
+    /// The interval of `number` units, e.g. `3 days`. Only units of time may have a
+    /// fraction.
+    fn parse_quantity(number: &str, unit: &str) -> Option<Self> {
+        let (negative, unsigned) = match number.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, number.strip_prefix('+').unwrap_or(number)),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let integer = parse_digits(integer, 1, 18)?;
+        let unit = unit.to_lowercase();
+        let unit = unit.strip_suffix('s').unwrap_or(&unit);
+        let micros_per_unit = match unit {
+            "microsecond" => 1,
+            "millisecond" => 1000,
+            "second" | "sec" => MICROS_PER_SECOND,
+            "minute" | "min" => MICROS_PER_MINUTE,
+            "hour" => MICROS_PER_HOUR,
+            _ if !fraction.is_empty() => return None,
+            "day" => return Self::new(0, integer.try_into().ok()?, 0).signed(negative),
+            "week" => {
+                let days = integer.checked_mul(7)?.try_into().ok()?;
+                return Self::new(0, days, 0).signed(negative);
+            }
+            "month" | "mon" => return Self::new(integer.try_into().ok()?, 0, 0).signed(negative),
+            "year" => {
+                let months = integer.checked_mul(12)?.try_into().ok()?;
+                return Self::new(months, 0, 0).signed(negative);
+            }
+            _ => return None,
+        };
+        let fraction = match fraction {
+            "" => 0,
+            fraction => parse_fraction(&fraction[..fraction.len().min(6)])?,
+        };
+        let micros = integer
+            .checked_mul(micros_per_unit)?
+            .checked_add(fraction * micros_per_unit / MICROS_PER_SECOND)?;
+        Self::new(0, 0, micros).signed(negative)
+    }
+
+    fn signed(self, negative: bool) -> Option<Self> {
+        if negative {
+            self.checked_neg()
+        } else {
+            Some(self)
+        }
+    }
+
+    pub fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        Ok(Self::new(
+            reader.read_i32()?,
+            reader.read_i32()?,
+            reader.read_i64()?,
+        ))
+    }
+
+    pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_i32(self.months)?;
+        writer.write_i32(self.days)?;
+        writer.write_i64(self.micros)
+    }
+}
+
+/// Intervals compare by their length, with months of 30 days, so `1 mon = 30 days`.
+impl Ord for Interval {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.span().cmp(&other.span())
+    }
+}
+
+impl PartialOrd for Interval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Interval {}
+
+/// Writes e.g. `1 year 2 mons 3 days 04:05:06`.
+impl Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        let (years, months) = (self.months / 12, self.months % 12);
+        for (count, unit) in [(years, "year"), (months, "mon"), (self.days, "day")] {
+            match count {
+                0 => {}
+                1 => parts.push(format!("1 {}", unit)),
+                count => parts.push(format!("{} {}s", count, unit)),
+            }
+        }
+        write!(f, "{}", parts.join(" "))?;
+        if self.micros == 0 && !parts.is_empty() {
+            return Ok(());
+        }
+        let separator = if parts.is_empty() { "" } else { " " };
+        let sign = if self.micros < 0 { "-" } else { "" };
+        write!(f, "{}{}", separator, sign)?;
+        write_clock(f, self.micros.unsigned_abs())
+    }
+}
+
+/// Parses ISO 8601 durations, e.g. `P1Y2M3DT4H`, and lists of quantities and a time like
+/// the ones intervals are displayed as, e.g. `1 year 2 mons 3 days -04:05:06`.
+impl FromStr for Interval {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(duration) = s.strip_prefix('P') {
+            return Self::parse_iso(duration).ok_or(());
+        }
+        let mut interval = Self::new(0, 0, 0);
+        let mut words = s.split_whitespace().peekable();
+        if words.peek().is_none() {
+            return Err(());
+        }
+        while let Some(word) = words.next() {
+            let part = if word.contains(':') {
+                let (negative, clock) = match word.strip_prefix('-') {
+                    Some(clock) => (true, clock),
+                    None => (false, word.strip_prefix('+').unwrap_or(word)),
+                };
+                let micros = parse_clock(clock, 999_999_999).ok_or(())?;
+                Self::new(0, 0, micros).signed(negative)
+            } else {
+                Self::parse_quantity(word, words.next().ok_or(())?)
+            };
+            interval = part.and_then(|part| interval.checked_add(part)).ok_or(())?;
+        }
+        Ok(interval)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Date, DateField, Interval, Time, Timestamp};
+
+    fn timestamp(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn interval(s: &str) -> Interval {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in ["2024-02-29", "1970-01-01", "0001-01-01", "9999-12-31"] {
+            assert_eq!(s.parse::<Date>().unwrap().to_string(), s);
+        }
+        assert_eq!(
+            "2024-1-5".parse::<Date>().unwrap().to_string(),
+            "2024-01-05"
+        );
+        for s in [
+            "2023-02-29",
+            "2024-13-01",
+            "24-01-01",
+            "2024-01-01x",
+            "",
+            "10000-01-01",
+        ] {
+            assert!(s.parse::<Date>().is_err(), "{}", s);
+        }
+        assert_eq!(
+            "23:59:59.5".parse::<Time>().unwrap().to_string(),
+            "23:59:59.5"
+        );
+        assert_eq!("07:30".parse::<Time>().unwrap().to_string(), "07:30:00");
+        for s in ["24:00", "12:60", "12:00:00.1234567", "12"] {
+            assert!(s.parse::<Time>().is_err(), "{}", s);
+        }
+        assert_eq!(
+            timestamp("2024-03-10T08:15:00.000250").to_string(),
+            "2024-03-10 08:15:00.00025"
+        );
+        assert_eq!(timestamp("1969-12-31").to_string(), "1969-12-31 00:00:00");
+        assert!("2024-03-10 25:00".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn test_intervals() {
+        assert_eq!(
+            interval("1 year 14 mons 3 days 04:05:06.5").to_string(),
+            "2 years 2 mons 3 days 04:05:06.5"
+        );
+        assert_eq!(interval("-1 day +02:00").to_string(), "-1 days 02:00:00");
+        assert_eq!(interval("90 minutes").to_string(), "01:30:00");
+        assert_eq!(interval("1.5 hours"), interval("01:30"));
+        assert_eq!(interval("2 weeks").to_string(), "14 days");
+        assert_eq!(interval("0 days").to_string(), "00:00:00");
+        assert_eq!(interval("-00:00:01").to_string(), "-00:00:01");
+        assert_eq!(
+            interval("P1Y2M10DT2H30M15.5S").to_string(),
+            "1 year 2 mons 10 days 02:30:15.5"
+        );
+        assert_eq!(interval("PT36H").to_string(), "36:00:00");
+        assert_eq!(interval("P1W").to_string(), "7 days");
+        for s in [
+            "",
+            "1",
+            "1 fortnight",
+            "1.5 days",
+            "P",
+            "PT",
+            "P1H",
+            "P1D2Y",
+            "PT1H1H",
+        ] {
+            assert!(s.parse::<Interval>().is_err(), "{}", s);
+        }
+        // Months are 30 days long when comparing.
+        assert_eq!(interval("1 mon"), interval("30 days"));
+        assert_eq!(interval("1 day"), interval("24 hours"));
+        assert!(interval("1 mon 1 second") > interval("30 days"));
+        assert_eq!(
+            interval("25 hours").justified().to_string(),
+            "1 day 01:00:00"
+        );
+        assert_eq!(
+            interval("-2 mons 59 days").justified().to_string(),
+            "-1 days"
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let add = |t: &str, i: &str| timestamp(t).checked_add(interval(i)).unwrap().to_string();
+        assert_eq!(add("2024-01-31", "1 mon"), "2024-02-29 00:00:00");
+        assert_eq!(
+            add("2024-01-31 12:00", "1 mon 1 day 12:00"),
+            "2024-03-02 00:00:00"
+        );
+        assert_eq!(add("2024-03-01", "-1 day"), "2024-02-29 00:00:00");
+        assert_eq!(add("2023-12-31 23:30", "1 hour"), "2024-01-01 00:30:00");
+        assert_eq!(
+            timestamp("2024-01-01 00:00")
+                .checked_sub(interval("1 year"))
+                .unwrap()
+                .to_string(),
+            "2023-01-01 00:00:00"
+        );
+        assert_eq!(timestamp("9999-12-31").checked_add(interval("1 day")), None);
+        assert_eq!(
+            timestamp("2024-03-02 06:00")
+                .diff(timestamp("2024-02-28 12:00"))
+                .to_string(),
+            "2 days 18:00:00"
+        );
+        assert_eq!(
+            timestamp("2024-01-01")
+                .diff(timestamp("2024-01-01 01:30"))
+                .to_string(),
+            "-01:30:00"
+        );
+        let time: Time = "23:30".parse().unwrap();
+        assert_eq!(
+            time.wrapping_add(interval("1 hour").micros()).to_string(),
+            "00:30:00"
+        );
+        assert_eq!(time.wrapping_add(interval("-24 hours").micros()), time);
+    }
+
+    #[test]
+    fn test_extract_and_truncate() {
+        let t = timestamp("2024-12-30 13:45:30.25");
+        let extract = |field| t.extract(field).to_string();
+        assert_eq!(extract(DateField::Year), "2024");
+        assert_eq!(extract(DateField::Quarter), "4");
+        assert_eq!(extract(DateField::Day), "30");
+        assert_eq!(extract(DateField::Hour), "13");
+        assert_eq!(extract(DateField::Second), "30.250000");
+        assert_eq!(extract(DateField::Milliseconds), "30250.000");
+        // 2024-12-30 is a Monday in the first week of 2025.
+        assert_eq!(extract(DateField::DayOfWeek), "1");
+        assert_eq!(extract(DateField::Week), "1");
+        assert_eq!(extract(DateField::DayOfYear), "365");
+        assert_eq!(
+            timestamp("1970-01-02")
+                .extract(DateField::Epoch)
+                .to_string(),
+            "86400.000000"
+        );
+        let date: Date = "2021-01-03".parse().unwrap();
+        assert_eq!(date.extract(DateField::Week).unwrap().to_string(), "53");
+        assert_eq!(date.extract(DateField::Hour), None);
+        let i = interval("1 year 14 mons 3 days 26:00:01");
+        assert_eq!(i.extract(DateField::Year).unwrap().to_string(), "2");
+        assert_eq!(i.extract(DateField::Month).unwrap().to_string(), "2");
+        assert_eq!(i.extract(DateField::Hour).unwrap().to_string(), "26");
+        assert_eq!(
+            interval("1 year 1 day")
+                .extract(DateField::Epoch)
+                .unwrap()
+                .to_string(),
+            "31644000.000000"
+        );
+        assert_eq!(i.extract(DateField::DayOfWeek), None);
+
+        let truncate = |field| t.truncate(field).unwrap().to_string();
+        assert_eq!(truncate(DateField::Year), "2024-01-01 00:00:00");
+        assert_eq!(truncate(DateField::Quarter), "2024-10-01 00:00:00");
+        assert_eq!(truncate(DateField::Month), "2024-12-01 00:00:00");
+        assert_eq!(truncate(DateField::Week), "2024-12-30 00:00:00");
+        assert_eq!(truncate(DateField::Hour), "2024-12-30 13:00:00");
+        assert_eq!(truncate(DateField::Second), "2024-12-30 13:45:30");
+        assert_eq!(
+            timestamp("2025-01-05 10:00")
+                .truncate(DateField::Week)
+                .unwrap()
+                .to_string(),
+            "2024-12-30 00:00:00"
+        );
+        assert_eq!(t.truncate(DateField::Epoch), None);
+    }
+}
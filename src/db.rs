@@ -1,26 +1,45 @@
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{self, BufReader, BufWriter},
+    io::{self, Cursor},
     path::PathBuf,
 };
 
 use crate::{
+    buffer_pool::{BufferPoolStats, EvictionPolicy},
+    datetime::Timestamp,
     errors::{self, DBError},
     operations::{
-        execute_create_table, execute_delete, execute_drop_table, execute_insert, execute_select,
-        execute_update,
+        execute_alter_table, execute_create_index, execute_create_sequence, execute_create_table,
+        execute_delete, execute_drop_index, execute_drop_sequence, execute_drop_table,
+        execute_insert, execute_select, execute_update, execute_vacuum,
     },
+    pager::Pager,
+    sequence::Sequences,
     serializer::{BinaryReader, BinaryWriter},
     sql::Query,
     table::Table,
     tuple::Tuple,
 };
 
+/// Lazily evaluated result rows of a query.
+pub type Rows<'a> = Box<dyn Iterator<Item = errors::Result<Tuple>> + 'a>;
+
+/// Either the result rows (with column names) or a number of rows affected.
+pub type QueryResult<'a> = (Option<(Vec<String>, Rows<'a>)>, Option<usize>);
+
+/// Default memory limit of a single sort or hash table, see [`DB::set_work_mem`].
+const DEFAULT_WORK_MEM: usize = 4 << 20;
+
 pub struct DB {
     pub(crate) storage_dir: PathBuf,
     pub(crate) tables: HashMap<String, Table>,
     pub(crate) next_table_id: u32,
+    pub(crate) sequences: Sequences,
+    pub(crate) work_mem: usize,
+    pub(crate) pager: Pager,
+    /// Time the current transaction started, or the current statement outside of explicit
+    /// transactions, which `now()` returns for the whole transaction.
+    pub(crate) now: Timestamp,
 }
 
 impl DB {
@@ -33,11 +52,18 @@ impl DB {
         }
         std::fs::create_dir_all(&storage_dir)?;
         let res = Self {
-            storage_dir,
             tables: HashMap::new(),
             next_table_id: 0,
+            sequences: Sequences::new(storage_dir.clone()),
+            work_mem: DEFAULT_WORK_MEM,
+            pager: Pager::new(storage_dir.join("wal")),
+            now: Timestamp::now(),
+            storage_dir,
         };
         res.save_metadata()?;
+        res.pager
+            .commit_statement()
+            .map_err(|err| io::Error::other(err.to_string()))?;
         Ok(res)
     }
 
@@ -48,84 +74,2705 @@ impl DB {
                 "Storage directory does not exist",
             ));
         }
-        let file = File::open(storage_dir.join("metadata"))?;
-        let mut reader = BinaryReader::new(BufReader::new(file));
-        let next_table_id = reader.read_u32()?;
-        let table_count = reader.read_u32()? as usize;
-        let mut tables = HashMap::with_capacity(table_count);
-        for _ in 0..table_count {
-            let table = Table::from_reader(&mut reader)?;
-            tables.insert(table.name().to_owned(), table);
-        }
-        Ok(Self {
+        let mut res = Self {
+            tables: HashMap::new(),
+            next_table_id: 0,
+            sequences: Sequences::new(storage_dir.clone()),
+            work_mem: DEFAULT_WORK_MEM,
+            pager: Pager::new(storage_dir.join("wal")),
+            now: Timestamp::now(),
             storage_dir,
-            tables,
-            next_table_id,
-        })
+        };
+        // Finishes the commits interrupted by a crash.
+        res.pager.recover()?;
+        res.load_metadata()?;
+        // Spill files left over from queries interrupted by a crash.
+        if res.temp_dir().exists() {
+            std::fs::remove_dir_all(res.temp_dir())?;
+        }
+        Ok(res)
+    }
+
+    /// Sets how many bytes a single sort may use before spilling to temp files.
+    pub fn set_work_mem(&mut self, bytes: usize) {
+        self.work_mem = bytes;
+    }
+
+    /// Sets how many pages the buffer pool keeps in memory, writing out the pages that no
+    /// longer fit.
+    pub fn set_buffer_pool_capacity(&mut self, pages: usize) -> errors::Result<()> {
+        self.pager.pool().set_capacity(pages)
+    }
+
+    pub fn set_eviction_policy(&mut self, policy: EvictionPolicy) {
+        self.pager.pool().set_policy(policy);
+    }
+
+    pub fn buffer_pool_stats(&self) -> BufferPoolStats {
+        self.pager.pool().stats()
+    }
+
+    /// Directory for the temp files of queries that don't fit in memory.
+    pub(crate) fn temp_dir(&self) -> PathBuf {
+        self.storage_dir.join("tmp")
     }
 
+    fn metadata_path(&self) -> PathBuf {
+        self.storage_dir.join("metadata")
+    }
+
+    /// Writes the catalog. Like pages, it's written to disk when the statement commits.
     pub(crate) fn save_metadata(&self) -> io::Result<()> {
-        let file = File::create(self.storage_dir.join("metadata"))?;
-        let mut writer = BinaryWriter::new(BufWriter::new(file));
+        let mut data = vec![];
+        let mut writer = BinaryWriter::new(&mut data);
         writer.write_u32(self.next_table_id)?;
+        self.sequences.write(&mut writer)?;
         writer.write_u32(self.tables.len() as u32)?;
         for table in self.tables.values() {
             table.write_metadata(&mut writer)?;
         }
+        self.pager.write_file(&self.metadata_path(), data);
         Ok(())
     }
 
+    /// Reads the catalog, including the changes of the current transaction.
+    fn load_metadata(&mut self) -> io::Result<()> {
+        let data = self.pager.read_file(&self.metadata_path())?;
+        let mut reader = BinaryReader::new(Cursor::new(data));
+        self.next_table_id = reader.read_u32()?;
+        self.sequences.read(&mut reader)?;
+        let table_count = reader.read_u32()? as usize;
+        self.tables = HashMap::with_capacity(table_count);
+        for _ in 0..table_count {
+            let table = Table::from_reader(&mut reader)?;
+            self.tables.insert(table.name().to_owned(), table);
+        }
+        Ok(())
+    }
+
+    /// Commits the changes of a statement that succeeded and undoes the changes of
+    /// a statement that failed, so that either all or none of them are visible.
+    fn finish_statement<T>(&mut self, result: errors::Result<T>) -> errors::Result<T> {
+        let result = result.and_then(|value| {
+            self.pager.commit_statement()?;
+            Ok(value)
+        });
+        if result.is_err() {
+            self.pager.rollback_statement();
+            // The statement may have changed the catalog.
+            self.load_metadata()?;
+        }
+        result
+    }
+
     pub(crate) fn get_table(&self, name: &str) -> errors::Result<&Table> {
         self.tables
             .get(name)
             .ok_or(DBError::Execution(format!("Table {} does not exist", name)))
     }
 
+    /// Returns the table with the id, e.g. the table referenced by a foreign key.
+    pub(crate) fn get_table_by_id(&self, id: u32) -> errors::Result<&Table> {
+        self.tables
+            .values()
+            .find(|table| table.id() == id)
+            .ok_or_else(|| DBError::Integrity(format!("Table with id {} does not exist", id)))
+    }
+
     /// Returns either an iterator over the results (with column names) or a number of rows affected.
-    pub fn execute(
-        &mut self,
-        query_string: &str,
-    ) -> errors::Result<(
-        Option<(Vec<String>, impl Iterator<Item = errors::Result<Tuple>>)>,
-        Option<usize>,
-    )> {
+    pub fn execute(&mut self, query_string: &str) -> errors::Result<QueryResult<'_>> {
+        self.pager.check_poisoned()?;
+        if !self.pager.in_transaction() {
+            self.now = Timestamp::now();
+        }
         let query = Query::compile(self, query_string)?;
         match query {
+            Query::Begin { db } => db.pager.begin().map(|_| (None, None)),
+            Query::Commit { db } => db.pager.commit().map(|_| (None, None)),
+            Query::Rollback { db } => {
+                db.pager.rollback()?;
+                db.load_metadata()?;
+                Ok((None, None))
+            }
             Query::CreateTable {
                 db,
                 table_name,
                 column_decls: columns,
+                keys,
+                foreign_keys,
+                serials,
             } => {
-                execute_create_table(db, table_name.clone(), columns.clone()).map(|_| (None, None))
+                let result =
+                    execute_create_table(db, table_name, columns, keys, foreign_keys, serials);
+                db.finish_statement(result).map(|_| (None, None))
             }
             Query::DropTable { db, table_name } => {
-                execute_drop_table(db, table_name).map(|_| (None, None))
+                let result = execute_drop_table(db, table_name);
+                db.finish_statement(result).map(|_| (None, None))
             }
-            Query::Insert {
+            Query::AlterTable {
                 db,
                 table_name,
-                values,
-            } => execute_insert(db, table_name, values).map(|count| (None, Some(count))),
-            Query::Select {
+                change,
+            } => {
+                let result = execute_alter_table(db, table_name, change);
+                db.finish_statement(result).map(|_| (None, None))
+            }
+            Query::CreateIndex {
                 db,
+                index_name,
                 table_name,
-                where_clause,
                 columns,
-            } => execute_select(db, table_name, where_clause, columns).map(|res| (Some(res), None)),
+            } => {
+                let result = execute_create_index(db, index_name, table_name, columns);
+                db.finish_statement(result).map(|_| (None, None))
+            }
+            Query::DropIndex { db, index_name } => {
+                let result = execute_drop_index(db, index_name);
+                db.finish_statement(result).map(|_| (None, None))
+            }
+            Query::Insert {
+                db,
+                table_name,
+                values,
+                checks,
+            } => {
+                let result = execute_insert(db, table_name, values, checks);
+                db.finish_statement(result).map(|count| (None, Some(count)))
+            }
+            Query::Select { db, select } => {
+                execute_select(db, select).map(|(header, rows)| (Some((header, rows)), None))
+            }
             Query::Update {
                 db,
                 table_name,
                 where_clause,
                 update_clauses,
-            } => execute_update(db, table_name, update_clauses, where_clause)
-                .map(|affected| (None, Some(affected))),
+                checks,
+            } => {
+                let result = execute_update(db, table_name, update_clauses, where_clause, checks);
+                db.finish_statement(result)
+                    .map(|affected| (None, Some(affected)))
+            }
             Query::Delete {
                 db,
                 table_name,
                 where_clause,
             } => {
-                execute_delete(db, table_name, where_clause).map(|affected| (None, Some(affected)))
+                let result = execute_delete(db, table_name, where_clause);
+                db.finish_statement(result)
+                    .map(|affected| (None, Some(affected)))
+            }
+            Query::CreateSequence { db, name, sequence } => {
+                let result = execute_create_sequence(db, name, sequence);
+                db.finish_statement(result).map(|_| (None, None))
+            }
+            Query::DropSequence { db, name } => {
+                let result = execute_drop_sequence(db, name);
+                db.finish_statement(result).map(|_| (None, None))
+            }
+            Query::Vacuum {
+                db,
+                table_name,
+                full,
+            } => {
+                let result = execute_vacuum(db, table_name, full);
+                db.finish_statement(result).map(|_| (None, None))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use temp_dir::TempDir;
+
+    use crate::{EvictionPolicy, errors::DBError, page::PAGE_SIZE, utils, values::DBValue};
+
+    use super::DB;
+
+    pub fn temp_db() -> (TempDir, DB) {
+        let storage_dir = TempDir::new().unwrap();
+        let db = DB::init(storage_dir.path().join("db")).unwrap();
+        (storage_dir, db)
+    }
+
+    pub fn select(db: &mut DB, query: &str) -> (Vec<String>, Vec<Vec<DBValue>>) {
+        let (Some((header, rows)), None) = db.execute(query).unwrap() else {
+            panic!("Expected rows from {}", query);
+        };
+        let rows = rows
+            .map(|row| row.unwrap().into_values())
+            .collect::<Vec<_>>();
+        (header, rows)
+    }
+
+    pub fn affected(db: &mut DB, query: &str) -> usize {
+        let (None, Some(affected)) = db.execute(query).unwrap() else {
+            panic!("Expected affected row count from {}", query);
+        };
+        affected
+    }
+
+    pub fn create_sample_table(db: &mut DB) {
+        db.execute("create table meow (id int, name string, height double, is_fox bool)")
+            .unwrap();
+        affected(db, "insert into meow values (1, 'vanutp', 182.5, true)");
+        affected(db, "insert into meow values (2, 'meow, meow', 50., false)");
+        affected(db, "insert into meow values (3, 'it''s', 120., true)");
+    }
+
+    #[test]
+    fn test_basic_queries() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+
+        let (header, rows) = select(&mut db, "select * from meow where name = 'vanutp'");
+        assert_eq!(header, vec!["id", "name", "height", "is_fox"]);
+        assert_eq!(
+            rows,
+            vec![vec![
+                DBValue::Int(1),
+                DBValue::String("vanutp".to_owned()),
+                DBValue::Double(182.5),
+                DBValue::Bool(true),
+            ]]
+        );
+
+        assert_eq!(
+            affected(
+                &mut db,
+                "update meow set height = -1. where name = 'vanutp'"
+            ),
+            1
+        );
+        assert_eq!(
+            affected(&mut db, "delete from meow where is_fox = false"),
+            1
+        );
+        let (_, rows) = select(&mut db, "select id, height from meow");
+        assert_eq!(
+            rows,
+            vec![
+                vec![DBValue::Int(1), DBValue::Double(-1.)],
+                vec![DBValue::Int(3), DBValue::Double(120.)],
+            ]
+        );
+
+        db.execute("drop table meow").unwrap();
+        assert!(db.execute("select * from meow").is_err());
+    }
+
+    #[test]
+    fn test_query_syntax() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+
+        let (header, rows) = select(
+            &mut db,
+            "SELECT name\n  FROM meow /* all foxes */\n  WHERE height   >= 100. ; -- done",
+        );
+        assert_eq!(header, vec!["name"]);
+        assert_eq!(
+            rows,
+            vec![
+                vec![DBValue::String("vanutp".to_owned())],
+                vec![DBValue::String("it's".to_owned())],
+            ]
+        );
+        let (_, rows) = select(&mut db, "select id from meow where name = 'meow, meow'");
+        assert_eq!(rows, vec![vec![DBValue::Int(2)]]);
+    }
+
+    #[test]
+    fn test_boolean_where() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        affected(&mut db, "insert into meow values (4, 'fox', 90., false)");
+
+        let ids = |db: &mut DB, query: &str| {
+            select(db, query)
+                .1
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id from meow where id > 1 and (height > 100. or not is_fox)"
+            ),
+            vec![DBValue::Int(2), DBValue::Int(3), DBValue::Int(4)]
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id from meow where not (id = 1 or id = 2) and is_fox"
+            ),
+            vec![DBValue::Int(3)]
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow where 100. < height or false"),
+            vec![DBValue::Int(1), DBValue::Int(3)]
+        );
+        assert_eq!(
+            affected(&mut db, "update meow set is_fox = true where not is_fox"),
+            2
+        );
+        assert_eq!(
+            affected(&mut db, "delete from meow where is_fox and id >= 3"),
+            2
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow"),
+            vec![DBValue::Int(1), DBValue::Int(2)]
+        );
+
+        let Err(DBError::Execution(msg)) = db.execute("select id from meow where id or true")
+        else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(
+            msg,
+            "Type mismatch: id is not of type bool at line 1, column 27"
+        );
+    }
+
+    #[test]
+    fn test_expressions() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+
+        let (header, rows) = select(
+            &mut db,
+            "select id, height * 2, name || '!' as shout, id % 2 = 1 odd from meow where height > id * 20 and id != 3",
+        );
+        assert_eq!(header, vec!["id", "height * 2", "shout", "odd"]);
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    DBValue::Int(1),
+                    DBValue::Double(365.),
+                    DBValue::String("vanutp!".to_owned()),
+                    DBValue::Bool(true),
+                ],
+                vec![
+                    DBValue::Int(2),
+                    DBValue::Double(100.),
+                    DBValue::String("meow, meow!".to_owned()),
+                    DBValue::Bool(false),
+                ],
+            ]
+        );
+
+        assert_eq!(
+            affected(
+                &mut db,
+                "update meow set height = height + 1, id = id * 10 where id * 100 < height * 3"
+            ),
+            2
+        );
+        let (_, rows) = select(&mut db, "select id, height from meow where height > 100");
+        assert_eq!(
+            rows,
+            vec![
+                vec![DBValue::Int(10), DBValue::Double(183.5)],
+                vec![DBValue::Int(30), DBValue::Double(121.)],
+            ]
+        );
+        // Assignments see the old values of other columns.
+        affected(&mut db, "update meow set id = 7, height = id where id = 30");
+        let (_, rows) = select(&mut db, "select id, height from meow where id = 7");
+        assert_eq!(rows, vec![vec![DBValue::Int(7), DBValue::Double(30.)]]);
+
+        affected(
+            &mut db,
+            "insert into meow values (2 + 2, 'a' || 'b', 40, 1 < 2)",
+        );
+        let (_, rows) = select(&mut db, "select * from meow where id = 4");
+        assert_eq!(
+            rows,
+            vec![vec![
+                DBValue::Int(4),
+                DBValue::String("ab".to_owned()),
+                DBValue::Double(40.),
+                DBValue::Bool(true),
+            ]]
+        );
+
+        let (_, mut rows) = db.execute("select id / 0 from meow").unwrap().0.unwrap();
+        let Some(Err(DBError::Execution(msg))) = rows.next() else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(msg, "Division by zero");
+        drop(rows);
+        let Err(DBError::Execution(msg)) = db.execute("select name - 1 from meow") else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(
+            msg,
+            "Operator - is not defined for text and int at line 1, column 8"
+        );
+        let Err(DBError::Execution(msg)) = db.execute("update meow set id = height") else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(
+            msg,
+            "Type mismatch: height is not of type int at line 1, column 22"
+        );
+    }
+
+    #[test]
+    fn test_nulls() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        affected(&mut db, "insert into meow values (4, null, null, null)");
+        affected(&mut db, "update meow set height = null where id = 2");
+
+        let (_, rows) = select(&mut db, "select * from meow where id = 4");
+        assert_eq!(
+            rows,
+            vec![vec![
+                DBValue::Int(4),
+                DBValue::Null,
+                DBValue::Null,
+                DBValue::Null
+            ]]
+        );
+        let ids = |db: &mut DB, query: &str| {
+            select(db, query)
+                .1
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(&mut db, "select id from meow where height is null"),
+            vec![DBValue::Int(2), DBValue::Int(4)]
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id from meow where name is not null and height is null"
+            ),
+            vec![DBValue::Int(2)]
+        );
+        // Comparisons with NULL are unknown, so neither the predicate nor its negation match.
+        assert_eq!(
+            ids(&mut db, "select id from meow where height > 100."),
+            vec![DBValue::Int(1), DBValue::Int(3)]
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow where not height > 100."),
+            vec![]
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow where name = null"),
+            vec![]
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow where is_fox or id = 4"),
+            vec![DBValue::Int(1), DBValue::Int(3), DBValue::Int(4)]
+        );
+        let (_, rows) = select(
+            &mut db,
+            "select height + 1, name || '!' from meow where id = 4",
+        );
+        assert_eq!(rows, vec![vec![DBValue::Null, DBValue::Null]]);
+
+        assert_eq!(
+            affected(&mut db, "delete from meow where is_fox is null"),
+            1
+        );
+        assert_eq!(ids(&mut db, "select id from meow where id = 4"), vec![]);
+    }
+
+    #[test]
+    fn test_order_by() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        affected(&mut db, "insert into meow values (4, 'fox', null, true)");
+
+        let ids = |db: &mut DB, query: &str| {
+            select(db, query)
+                .1
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+        let int_ids = |ids: &[i32]| ids.iter().map(|id| DBValue::Int(*id)).collect::<Vec<_>>();
+        assert_eq!(
+            ids(&mut db, "select id from meow order by height"),
+            int_ids(&[2, 3, 1, 4])
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow order by height desc"),
+            int_ids(&[4, 1, 3, 2])
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id from meow order by height desc nulls last"
+            ),
+            int_ids(&[1, 3, 2, 4])
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id, height h from meow where id > 1 order by is_fox desc, h asc nulls first"
+            ),
+            int_ids(&[4, 3, 2])
+        );
+        assert_eq!(
+            ids(&mut db, "select id, name from meow order by 2"),
+            int_ids(&[4, 3, 2, 1])
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow order by id % 2, -id"),
+            int_ids(&[4, 2, 3, 1])
+        );
+
+        let Err(DBError::Execution(msg)) = db.execute("select id from meow order by 2") else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(
+            msg,
+            "ORDER BY position 2 is not in select list at line 1, column 30"
+        );
+    }
+
+    #[test]
+    fn test_order_by_spills_to_disk() {
+        let (_dir, mut db) = temp_db();
+        db.set_work_mem(4096);
+        db.execute("create table t (id int, k int)").unwrap();
+        for i in 0..500 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, {})", i, (i * 37) % 100),
+            );
+        }
+        let (_, rows) = select(&mut db, "select k, id from t order by k desc, id");
+        let mut expected = (0..500).map(|i| ((i * 37) % 100, i)).collect::<Vec<_>>();
+        expected.sort_by_key(|(k, id)| (-k, *id));
+        let expected = expected
+            .into_iter()
+            .map(|(k, id)| vec![DBValue::Int(k), DBValue::Int(id)])
+            .collect::<Vec<_>>();
+        assert_eq!(rows, expected);
+        assert_eq!(db.temp_dir().read_dir().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_limit_offset() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+
+        let ids = |db: &mut DB, query: &str| {
+            select(db, query)
+                .1
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(&mut db, "select id from meow limit 2"),
+            vec![DBValue::Int(1), DBValue::Int(2)]
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow limit 1 offset 1"),
+            vec![DBValue::Int(2)]
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow order by id desc offset 1"),
+            vec![DBValue::Int(2), DBValue::Int(1)]
+        );
+        assert_eq!(ids(&mut db, "select id from meow limit 0"), vec![]);
+        assert_eq!(ids(&mut db, "select id from meow offset 10"), vec![]);
+    }
+
+    #[test]
+    fn test_limit_stops_reading_pages() {
+        let (_dir, mut db) = temp_db();
+        db.execute("create table t (id int, padding string)")
+            .unwrap();
+        let padding = "x".repeat(1000);
+        for i in 0..100 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, '{}')", i, padding),
+            );
+        }
+        // Only the file is truncated below, so the pages must be written to it and not be
+        // in the buffer pool.
+        db.set_buffer_pool_capacity(1).unwrap();
+        db.pager.checkpoint().unwrap();
+        let table_file = db
+            .get_table("t")
+            .unwrap()
+            .get_table_file_path(&db.storage_dir);
+        assert!(std::fs::metadata(&table_file).unwrap().len() > 10 * PAGE_SIZE as u64);
+
+        let (_, rows) = db
+            .execute("select id from t limit 3 offset 2")
+            .unwrap()
+            .0
+            .unwrap();
+        // Reading anything past the first page would now fail.
+        std::fs::File::options()
+            .write(true)
+            .open(&table_file)
+            .unwrap()
+            .set_len(PAGE_SIZE as u64)
+            .unwrap();
+        let rows = rows
+            .map(|row| row.unwrap().into_values())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                vec![DBValue::Int(2)],
+                vec![DBValue::Int(3)],
+                vec![DBValue::Int(4)]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        affected(&mut db, "insert into meow values (4, 'fox', null, true)");
+        affected(&mut db, "insert into meow values (5, 'fox', 30., null)");
+
+        let (header, rows) = select(
+            &mut db,
+            "select count(*), count(height), count(distinct name), sum(id), avg(height), min(name), max(height) from meow",
+        );
+        assert_eq!(
+            header,
+            vec![
+                "count(*)",
+                "count(height)",
+                "count(distinct name)",
+                "sum(id)",
+                "avg(height)",
+                "min(name)",
+                "max(height)"
+            ]
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                DBValue::Int(5),
+                DBValue::Int(4),
+                DBValue::Int(4),
+                DBValue::BigInt(15),
+                DBValue::Double(95.625),
+                DBValue::String("fox".to_owned()),
+                DBValue::Double(182.5),
+            ]]
+        );
+
+        let (_, rows) = select(
+            &mut db,
+            "select is_fox, count(*) n, sum(height) from meow group by is_fox order by n desc, is_fox",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![DBValue::Bool(true), DBValue::Int(3), DBValue::Double(302.5)],
+                vec![DBValue::Bool(false), DBValue::Int(1), DBValue::Double(50.)],
+                vec![DBValue::Null, DBValue::Int(1), DBValue::Double(30.)],
+            ]
+        );
+        let (_, rows) = select(
+            &mut db,
+            "select name, id % 2 = 0 as even, max(id) * 10 from meow where id > 1 group by name, id % 2 = 0 having min(id) >= 3 order by 1, even",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    DBValue::String("fox".to_owned()),
+                    DBValue::Bool(false),
+                    DBValue::Int(50)
+                ],
+                vec![
+                    DBValue::String("fox".to_owned()),
+                    DBValue::Bool(true),
+                    DBValue::Int(40)
+                ],
+                vec![
+                    DBValue::String("it's".to_owned()),
+                    DBValue::Bool(false),
+                    DBValue::Int(30)
+                ],
+            ]
+        );
+        let (_, rows) = select(&mut db, "select count(*), max(id) from meow where id > 10");
+        assert_eq!(rows, vec![vec![DBValue::Int(0), DBValue::Null]]);
+        let (_, rows) = select(&mut db, "select id from meow where id > 10 group by id");
+        assert_eq!(rows, Vec::<Vec<DBValue>>::new());
+
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        assert_eq!(
+            error(&mut db, "select name, count(*) from meow"),
+            "Column name must appear in the GROUP BY clause or be used in an aggregate function at line 1, column 8"
+        );
+        assert_eq!(
+            error(&mut db, "select * from meow group by name"),
+            "Column id must appear in the GROUP BY clause or be used in an aggregate function"
+        );
+        assert_eq!(
+            error(&mut db, "select id from meow where count(*) > 1"),
+            "Aggregate function count is not allowed here at line 1, column 27"
+        );
+        assert_eq!(
+            error(&mut db, "select sum(name) from meow"),
+            "Function sum is not defined for text at line 1, column 8"
+        );
+        assert_eq!(
+            error(&mut db, "select max(count(*)) from meow"),
+            "Aggregate function calls cannot be nested at line 1, column 8"
+        );
+        assert_eq!(
+            error(&mut db, "select sum(*) from meow"),
+            "Function sum(*) does not exist at line 1, column 8"
+        );
+        assert_eq!(
+            error(&mut db, "select meow(id) from meow"),
+            "Function meow does not exist at line 1, column 8"
+        );
+    }
+
+    #[test]
+    fn test_group_by_spills_to_disk() {
+        let (_dir, mut db) = temp_db();
+        db.set_work_mem(4096);
+        db.execute("create table t (id int, k int)").unwrap();
+        for i in 0..1000 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, {})", i, i % 300),
+            );
+        }
+        let (_, rows) = select(
+            &mut db,
+            "select k, count(*), sum(id) from t group by k order by k",
+        );
+        let expected = (0..300)
+            .map(|k| {
+                let ids = (k..1000).step_by(300);
+                vec![
+                    DBValue::Int(k),
+                    DBValue::Int(ids.clone().count() as i32),
+                    DBValue::BigInt(ids.sum::<i32>() as i64),
+                ]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rows, expected);
+        assert_eq!(db.temp_dir().read_dir().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_joins() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        db.execute("create table food (meow_id int, food string)")
+            .unwrap();
+        for (meow_id, food) in [("1", "fish"), ("1", "cake"), ("3", "mice"), ("4", "grass")] {
+            affected(
+                &mut db,
+                &format!("insert into food values ({}, '{}')", meow_id, food),
+            );
+        }
+        affected(&mut db, "insert into food values (null, 'air')");
+        let string = |s: &str| DBValue::String(s.to_owned());
+        let pairs = |db: &mut DB, query: &str| {
+            select(db, query)
+                .1
+                .into_iter()
+                .map(|row| (row[0].to_raw_string(), row[1].to_raw_string()))
+                .collect::<Vec<_>>()
+        };
+        let expected = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(a, b)| (a.to_string(), b.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            pairs(
+                &mut db,
+                "select m.name, f.food from meow m join food f on m.id = f.meow_id order by m.id, f.food"
+            ),
+            expected(&[("vanutp", "cake"), ("vanutp", "fish"), ("it's", "mice")])
+        );
+        assert_eq!(
+            pairs(
+                &mut db,
+                "select name, food from meow left join food on id = meow_id order by id, food"
+            ),
+            expected(&[
+                ("vanutp", "cake"),
+                ("vanutp", "fish"),
+                ("meow, meow", "NULL"),
+                ("it's", "mice")
+            ])
+        );
+        assert_eq!(
+            pairs(
+                &mut db,
+                "select meow.id, food from meow right outer join food on meow.id = food.meow_id order by food"
+            ),
+            expected(&[
+                ("NULL", "air"),
+                ("1", "cake"),
+                ("1", "fish"),
+                ("NULL", "grass"),
+                ("3", "mice")
+            ])
+        );
+        // Conditions without equalities are joined with a nested loop.
+        assert_eq!(
+            pairs(
+                &mut db,
+                "select m.id, f.food from meow m left join food f on f.meow_id > m.id order by 1, 2"
+            ),
+            expected(&[
+                ("1", "grass"),
+                ("1", "mice"),
+                ("2", "grass"),
+                ("2", "mice"),
+                ("3", "grass")
+            ])
+        );
+        assert_eq!(
+            pairs(
+                &mut db,
+                "select m.id, f.food from meow m right join food f on f.meow_id < m.id order by 2, 1"
+            ),
+            expected(&[
+                ("NULL", "air"),
+                ("2", "cake"),
+                ("3", "cake"),
+                ("2", "fish"),
+                ("3", "fish"),
+                ("NULL", "grass"),
+                ("NULL", "mice")
+            ])
+        );
+        assert_eq!(
+            pairs(
+                &mut db,
+                "select a.id, b.id from meow a cross join meow b where a.id < b.id order by 1, 2"
+            ),
+            expected(&[("1", "2"), ("1", "3"), ("2", "3")])
+        );
+        assert_eq!(
+            select(&mut db, "select count(*) from meow, food").1,
+            vec![vec![DBValue::Int(15)]]
+        );
+        assert_eq!(
+            pairs(
+                &mut db,
+                "select a.id, c.food from meow a join meow b on a.id = b.id \
+                 join food c on c.meow_id = b.id order by 2"
+            ),
+            expected(&[("1", "cake"), ("1", "fish"), ("3", "mice")])
+        );
+        assert_eq!(
+            pairs(
+                &mut db,
+                "select m.name, count(f.food) from meow m left join food f on m.id = f.meow_id \
+                 group by m.name order by 1"
+            ),
+            expected(&[("it's", "1"), ("meow, meow", "0"), ("vanutp", "2")])
+        );
+
+        let (header, rows) = select(
+            &mut db,
+            "select * from meow m join food f on m.id = f.meow_id where f.food = 'mice'",
+        );
+        assert_eq!(
+            header,
+            vec!["id", "name", "height", "is_fox", "meow_id", "food"]
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                DBValue::Int(3),
+                string("it's"),
+                DBValue::Double(120.),
+                DBValue::Bool(true),
+                DBValue::Int(3),
+                string("mice"),
+            ]]
+        );
+
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        assert_eq!(
+            error(&mut db, "select id from meow, meow"),
+            "Table name meow specified more than once at line 1, column 22"
+        );
+        assert_eq!(
+            error(&mut db, "select id from meow m join meow n on m.id = n.id"),
+            "Column reference id is ambiguous at line 1, column 8"
+        );
+        assert_eq!(
+            error(&mut db, "select meow.id from meow m"),
+            "Table meow is not in the FROM clause at line 1, column 8"
+        );
+        assert_eq!(
+            error(&mut db, "select m.nope from meow m"),
+            "Column m.nope does not exist at line 1, column 10"
+        );
+        assert_eq!(
+            error(&mut db, "select * from meow join food on food.food"),
+            "Type mismatch: food.food is not of type bool at line 1, column 33"
+        );
+        assert_eq!(
+            error(
+                &mut db,
+                "select * from meow m join food f on f.meow_id = n.id"
+            ),
+            "Table n is not in the FROM clause at line 1, column 49"
+        );
+    }
+
+    #[test]
+    fn test_hash_join_spills_to_disk() {
+        let (_dir, mut db) = temp_db();
+        db.set_work_mem(4096);
+        db.execute("create table a (id int)").unwrap();
+        db.execute("create table b (id int, v int)").unwrap();
+        for i in 0..600 {
+            affected(&mut db, &format!("insert into a values ({})", i));
+        }
+        for i in 0..1000 {
+            affected(
+                &mut db,
+                &format!("insert into b values ({}, {})", i % 500, i),
+            );
+        }
+        let (_, rows) = select(
+            &mut db,
+            "select count(*), count(b.v), sum(b.v) from a left join b on a.id = b.id",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                DBValue::Int(1100),
+                DBValue::Int(1000),
+                DBValue::BigInt(499500)
+            ]]
+        );
+        assert_eq!(db.temp_dir().read_dir().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_indexes() {
+        let (dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        affected(&mut db, "insert into meow values (4, null, 120., false)");
+        db.execute("create index meow_id on meow (id)").unwrap();
+        db.execute("create index meow_height_name on meow (height, name)")
+            .unwrap();
+        let ids = |db: &mut DB, query: &str| {
+            select(db, query)
+                .1
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+        let int_ids = |ids: &[i32]| ids.iter().map(|&id| DBValue::Int(id)).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(&mut db, "select id from meow where id = 2"),
+            int_ids(&[2])
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow where 2 <= id and id < 4"),
+            int_ids(&[2, 3])
+        );
+        // Only the rows matching the whole condition are returned.
+        assert_eq!(
+            ids(&mut db, "select id from meow where id > 1 and is_fox"),
+            int_ids(&[3])
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id from meow where height = 120. and name = 'it''s'"
+            ),
+            int_ids(&[3])
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id from meow where height = 120. order by id"
+            ),
+            int_ids(&[3, 4])
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id from meow where height < 150. order by id"
+            ),
+            int_ids(&[2, 3, 4])
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow where id = null"),
+            int_ids(&[])
+        );
+
+        // Updates and deletes keep the indexes up to date.
+        assert_eq!(affected(&mut db, "update meow set id = 10 where id = 2"), 1);
+        assert_eq!(
+            ids(&mut db, "select id from meow where id = 2"),
+            int_ids(&[])
+        );
+        assert_eq!(
+            ids(&mut db, "select id from meow where id = 10"),
+            int_ids(&[10])
+        );
+        assert_eq!(
+            affected(
+                &mut db,
+                "update meow set name = 'x' || name where height = 120."
+            ),
+            2
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "select id from meow where height = 120. and name = 'xit''s'"
+            ),
+            int_ids(&[3])
+        );
+        assert_eq!(affected(&mut db, "delete from meow where id >= 3"), 3);
+        assert_eq!(
+            ids(&mut db, "select id from meow where id > 0"),
+            int_ids(&[1])
+        );
+        affected(&mut db, "insert into meow values (5, 'new', 1., true)");
+        assert_eq!(
+            ids(&mut db, "select id from meow where id = 5"),
+            int_ids(&[5])
+        );
+
+        // Indexes are persisted.
+        let mut db = DB::load(dir.path().join("db")).unwrap();
+        assert_eq!(
+            ids(&mut db, "select id from meow where height < 100."),
+            int_ids(&[5])
+        );
+
+        let index_files = |db: &DB| {
+            std::fs::read_dir(&db.storage_dir)
+                .unwrap()
+                .filter(|entry| {
+                    entry.as_ref().unwrap().path().extension() == Some(std::ffi::OsStr::new("idx"))
+                })
+                .count()
+        };
+        assert_eq!(index_files(&db), 2);
+        db.execute("drop index meow_id").unwrap();
+        assert_eq!(index_files(&db), 1);
+        assert_eq!(
+            ids(&mut db, "select id from meow where id = 5"),
+            int_ids(&[5])
+        );
+        db.execute("drop table meow").unwrap();
+        assert_eq!(index_files(&db), 0);
+    }
+
+    #[test]
+    fn test_index_lookup_reads_few_pages() {
+        let (_dir, mut db) = temp_db();
+        db.execute("create table t (id int, padding string)")
+            .unwrap();
+        let padding = "x".repeat(1000);
+        for i in 0..200 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, '{}')", i, padding),
+            );
+        }
+        db.execute("create index t_id on t (id)").unwrap();
+
+        crate::page_table::PAGE_READS.with(|reads| reads.set(0));
+        let (_, rows) = select(&mut db, "select id from t where id = 150");
+        assert_eq!(rows, vec![vec![DBValue::Int(150)]]);
+        assert_eq!(crate::page_table::PAGE_READS.with(|reads| reads.get()), 1);
+
+        crate::page_table::PAGE_READS.with(|reads| reads.set(0));
+        let (_, rows) = select(&mut db, "select id from t where id < 0");
+        assert!(rows.is_empty());
+        assert_eq!(crate::page_table::PAGE_READS.with(|reads| reads.get()), 0);
+    }
+
+    #[test]
+    fn test_index_errors() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        db.execute("create index meow_id on meow (id)").unwrap();
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+
+        assert_eq!(
+            error(&mut db, "create index meow_id on meow (name)"),
+            "Index meow_id already exists"
+        );
+        assert_eq!(
+            error(&mut db, "create index i on meow (nope)"),
+            "Column nope does not exist at line 1, column 25"
+        );
+        assert_eq!(
+            error(&mut db, "create index i on nope (id)"),
+            "Table nope does not exist"
+        );
+        assert_eq!(
+            error(&mut db, "drop index nope"),
+            "Index nope does not exist"
+        );
+        affected(
+            &mut db,
+            &format!(
+                "insert into meow values (4, '{}', 1., true)",
+                "x".repeat(3000)
+            ),
+        );
+        assert_eq!(
+            error(&mut db, "create index meow_name on meow (name)"),
+            "Index row size 3011 exceeds maximum 2048 for index meow_name"
+        );
+        // The failed index is not created, so inserts don't check it.
+        affected(
+            &mut db,
+            &format!(
+                "insert into meow values (5, '{}', 1., true)",
+                "x".repeat(3000)
+            ),
+        );
+    }
+
+    #[test]
+    fn test_transactions() {
+        let (dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        db.execute("create table food (meow_id int, food string)")
+            .unwrap();
+        let count = |db: &mut DB, table: &str| {
+            select(db, &format!("select count(*) from {}", table)).1[0][0].clone()
+        };
+
+        db.execute("begin").unwrap();
+        affected(&mut db, "insert into food values (1, 'fish')");
+        assert_eq!(affected(&mut db, "delete from meow where id > 1"), 2);
+        // The changes are visible inside the transaction, but not to other connections.
+        assert_eq!(count(&mut db, "food"), DBValue::Int(1));
+        assert_eq!(count(&mut db, "meow"), DBValue::Int(1));
+        let mut other = DB::load(dir.path().join("db")).unwrap();
+        assert_eq!(count(&mut other, "food"), DBValue::Int(0));
+        assert_eq!(count(&mut other, "meow"), DBValue::Int(3));
+        db.execute("rollback").unwrap();
+        assert_eq!(count(&mut db, "food"), DBValue::Int(0));
+        assert_eq!(count(&mut db, "meow"), DBValue::Int(3));
+
+        db.execute("begin transaction").unwrap();
+        affected(&mut db, "insert into food values (1, 'fish')");
+        affected(&mut db, "update meow set height = height + 1. where id = 1");
+        // A failed statement is undone without aborting the transaction.
+        assert!(db.execute("insert into food values (1)").is_err());
+        db.execute("commit").unwrap();
+        let mut other = DB::load(dir.path().join("db")).unwrap();
+        assert_eq!(count(&mut other, "food"), DBValue::Int(1));
+        assert_eq!(
+            select(&mut other, "select height from meow where id = 1").1,
+            vec![vec![DBValue::Double(183.5)]]
+        );
+
+        // Tables created in a rolled back transaction disappear with their files.
+        let files = |db: &DB| std::fs::read_dir(&db.storage_dir).unwrap().count();
+        let file_count = files(&db);
+        db.execute("begin").unwrap();
+        db.execute("create table t (id int)").unwrap();
+        db.execute("create index t_id on t (id)").unwrap();
+        affected(&mut db, "insert into t values (1)");
+        db.execute("drop table food").unwrap();
+        assert!(db.execute("select * from food").is_err());
+        assert_eq!(select(&mut db, "select id from t where id = 1").1.len(), 1);
+        db.execute("rollback").unwrap();
+        assert!(db.execute("select * from t").is_err());
+        assert_eq!(count(&mut db, "food"), DBValue::Int(1));
+        assert_eq!(files(&db), file_count);
+
+        let Err(DBError::Execution(msg)) = db.execute("commit") else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(msg, "There is no transaction in progress");
+        db.execute("begin").unwrap();
+        let Err(DBError::Execution(msg)) = db.execute("begin") else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(msg, "There is already a transaction in progress");
+    }
+
+    #[test]
+    fn test_failed_statement_changes_nothing() {
+        let (dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        db.execute("create index meow_name on meow (name)").unwrap();
+        // The first row still fits in the index, the second one doesn't.
+        let query = format!("update meow set name = name || '{}'", "x".repeat(2030));
+        let Err(DBError::Execution(msg)) = db.execute(&query) else {
+            panic!("Expected execution error");
+        };
+        assert!(msg.starts_with("Index row size"), "{}", msg);
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            let names = select(db, "select name from meow where name > '' order by id").1;
+            assert_eq!(
+                names,
+                vec![
+                    vec![DBValue::String("vanutp".to_owned())],
+                    vec![DBValue::String("meow, meow".to_owned())],
+                    vec![DBValue::String("it's".to_owned())],
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_recovery_replays_log() {
+        let (dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        let storage_dir = dir.path().join("db");
+        let wal_path = storage_dir.join("wal");
+        // Runs the statements, crashing on the last one after it's logged.
+        let crash = |mut db: DB, queries: &[&str]| {
+            let (last, queries) = queries.split_last().unwrap();
+            for query in queries {
+                db.execute(query).unwrap();
             }
+            crate::pager::CRASH_AFTER_LOGGING.with(|crash| crash.set(true));
+            // The commit is logged, so it's done even though it isn't applied.
+            db.execute(last).unwrap();
+            crate::pager::CRASH_AFTER_LOGGING.with(|crash| crash.set(false));
+            let Err(DBError::Execution(msg)) = db.execute("select * from meow") else {
+                panic!("Expected execution error");
+            };
+            assert_eq!(
+                msg,
+                "The last commit is logged but couldn't be applied (IO Error: Simulated crash), \
+                 the database has to be loaded again"
+            );
+            // A crashed process doesn't write out its buffer pool.
+            std::mem::forget(db);
+            assert!(std::fs::metadata(&wal_path).unwrap().len() > 0);
+            DB::load(storage_dir.clone()).unwrap()
+        };
+
+        db = crash(db, &["insert into meow values (4, 'fox', 1., true)"]);
+        assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+        assert_eq!(select(&mut db, "select * from meow").1.len(), 4);
+        db = crash(
+            db,
+            &[
+                "begin",
+                "create table food (food string)",
+                "insert into food values ('fish')",
+                "delete from meow where id = 1",
+                "update meow set name = 'cat' where id = 2",
+                "commit",
+            ],
+        );
+        assert_eq!(
+            select(&mut db, "select id, name from meow order by id").1,
+            vec![
+                vec![DBValue::Int(2), DBValue::String("cat".to_owned())],
+                vec![DBValue::Int(3), DBValue::String("it's".to_owned())],
+                vec![DBValue::Int(4), DBValue::String("fox".to_owned())],
+            ]
+        );
+        assert_eq!(select(&mut db, "select * from food").1.len(), 1);
+
+        // A commit cut short while writing the log didn't happen.
+        drop(db);
+        let mut wal = std::fs::File::options()
+            .append(true)
+            .open(&wal_path)
+            .unwrap();
+        std::io::Write::write_all(&mut wal, &[0, 0, 1, 0, 0, 0, 0, 0, 1, 2, 3]).unwrap();
+        let mut db = DB::load(storage_dir.clone()).unwrap();
+        assert_eq!(select(&mut db, "select * from meow").1.len(), 3);
+        assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_buffer_pool() {
+        let (dir, mut db) = temp_db();
+        db.execute("create table t (id int, padding string)")
+            .unwrap();
+        let padding = "x".repeat(1000);
+        for i in 0..100 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, '{}')", i, padding),
+            );
+        }
+        let sum = |db: &mut DB| select(db, "select sum(id) from t").1[0][0].clone();
+
+        // Everything fits in the default pool, so nothing is read twice.
+        let before = db.buffer_pool_stats();
+        assert_eq!(sum(&mut db), DBValue::BigInt(4950));
+        let after = db.buffer_pool_stats();
+        assert_eq!(after.misses, before.misses);
+        assert!(after.hits > before.hits);
+        assert!(after.dirty_pages > 10);
+
+        for policy in [EvictionPolicy::Lru, EvictionPolicy::Clock] {
+            db.set_eviction_policy(policy);
+            db.set_buffer_pool_capacity(4).unwrap();
+            let before = db.buffer_pool_stats();
+            assert_eq!(sum(&mut db), DBValue::BigInt(4950));
+            assert_eq!(
+                affected(&mut db, "update t set id = id + 1000 where id % 10 = 0"),
+                10
+            );
+            assert_eq!(
+                affected(&mut db, "update t set id = id - 1000 where id >= 1000"),
+                10
+            );
+            let after = db.buffer_pool_stats();
+            assert!(after.misses > before.misses + 10);
+            assert!(after.evictions > before.evictions + 10);
+            assert!(after.pages <= 4);
+        }
+        assert_eq!(sum(&mut db), DBValue::BigInt(4950));
+
+        drop(db);
+        let mut db = DB::load(dir.path().join("db")).unwrap();
+        assert_eq!(sum(&mut db), DBValue::BigInt(4950));
+    }
+
+    #[test]
+    fn test_vacuum() {
+        let (dir, mut db) = temp_db();
+        db.execute("create table t (id int, padding string)")
+            .unwrap();
+        db.execute("create index t_id on t (id)").unwrap();
+        let padding = "x".repeat(1000);
+        for i in 0..100 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, '{}')", i, padding),
+            );
+        }
+        let page_count = |db: &DB| {
+            let table = db.get_table("t").unwrap();
+            db.pager
+                .page_count(&table.get_table_file_path(&db.storage_dir))
+                .unwrap()
+        };
+        let ids = |db: &mut DB, query: &str| {
+            select(db, query)
+                .1
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+        let pages = page_count(&db);
+        assert!(pages > 10);
+        assert_eq!(affected(&mut db, "delete from t where id % 4 != 0"), 75);
+
+        // The freed space is used by new rows instead of new pages.
+        db.execute("vacuum t").unwrap();
+        for i in 100..150 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, '{}')", i, padding),
+            );
+        }
+        assert_eq!(page_count(&db), pages);
+        assert_eq!(
+            ids(&mut db, "select id from t where id = 40"),
+            vec![DBValue::Int(40)]
+        );
+        assert_eq!(
+            ids(&mut db, "select id from t where id > 140"),
+            (141..150).map(DBValue::Int).collect::<Vec<_>>()
+        );
+
+        assert_eq!(affected(&mut db, "delete from t where id < 140"), 65);
+        db.execute("vacuum full").unwrap();
+        // 10 rows fit in 2 pages, and the file is cut after them.
+        assert_eq!(page_count(&db), 2);
+        db.pager.checkpoint().unwrap();
+        let table_file = db
+            .get_table("t")
+            .unwrap()
+            .get_table_file_path(&db.storage_dir);
+        assert_eq!(
+            std::fs::metadata(&table_file).unwrap().len(),
+            2 * PAGE_SIZE as u64
+        );
+        assert_eq!(
+            ids(&mut db, "select id from t where id >= 145"),
+            (145..150).map(DBValue::Int).collect::<Vec<_>>()
+        );
+
+        // Rolled back with the transaction, like any other change.
+        db.execute("begin").unwrap();
+        affected(&mut db, "delete from t where id < 145");
+        db.execute("vacuum full t").unwrap();
+        assert_eq!(page_count(&db), 1);
+        db.execute("rollback").unwrap();
+        assert_eq!(page_count(&db), 2);
+
+        drop(db);
+        let mut db = DB::load(dir.path().join("db")).unwrap();
+        assert_eq!(
+            ids(&mut db, "select id from t"),
+            (140..150).map(DBValue::Int).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            ids(&mut db, "select id from t where id = 147"),
+            vec![DBValue::Int(147)]
+        );
+        let Err(DBError::Execution(msg)) = db.execute("vacuum nope") else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(msg, "Table nope does not exist");
+    }
+
+    #[test]
+    fn test_long_values() {
+        use rand::Rng;
+
+        let (dir, mut db) = temp_db();
+        db.execute("create table docs (id int, title text, body text)")
+            .unwrap();
+        db.execute("create index docs_id on docs (id)").unwrap();
+        let repetitive = "meow ".repeat(40000);
+        let random = rand::rng()
+            .sample_iter(&rand::distr::Alphanumeric)
+            .take(30000)
+            .map(char::from)
+            .collect::<String>();
+        affected(
+            &mut db,
+            &format!("insert into docs values (1, 'cats', '{}')", repetitive),
+        );
+        affected(
+            &mut db,
+            &format!("insert into docs values (2, '{}', '{}')", random, random),
+        );
+        let row = |db: &mut DB, id: i32| {
+            select(
+                db,
+                &format!("select title, body from docs where id = {}", id),
+            )
+            .1
+        };
+        let text = |value: &str| DBValue::String(value.to_owned());
+        assert_eq!(row(&mut db, 1), vec![vec![text("cats"), text(&repetitive)]]);
+        assert_eq!(row(&mut db, 2), vec![vec![text(&random), text(&random)]]);
+
+        let table = db.get_table("docs").unwrap();
+        let toast_path = table.get_toast_file_path(&db.storage_dir);
+        let table_path = table.get_table_file_path(&db.storage_dir);
+        assert_eq!(db.pager.page_count(&table_path).unwrap(), 1);
+        // The repetitive value is compressed to a single page.
+        assert_eq!(db.pager.page_count(&toast_path).unwrap(), 1 + 1 + 4 + 4);
+
+        // Space of replaced and deleted values is used again.
+        let update = |db: &mut DB, body: &str| {
+            affected(
+                db,
+                &format!("update docs set body = '{}' where id = 1", body),
+            )
+        };
+        update(&mut db, &random);
+        let toast_pages = db.pager.page_count(&toast_path).unwrap();
+        for _ in 0..3 {
+            update(&mut db, &repetitive);
+            update(&mut db, &random);
+        }
+        update(&mut db, &repetitive);
+        affected(&mut db, "delete from docs where id = 2");
+        affected(
+            &mut db,
+            &format!("insert into docs values (3, 'fox', '{}')", random),
+        );
+        assert_eq!(db.pager.page_count(&toast_path).unwrap(), toast_pages);
+
+        db.execute("vacuum full").unwrap();
+        drop(db);
+        let mut db = DB::load(dir.path().join("db")).unwrap();
+        assert_eq!(row(&mut db, 1), vec![vec![text("cats"), text(&repetitive)]]);
+        assert_eq!(row(&mut db, 3), vec![vec![text("fox"), text(&random)]]);
+        let (_, rows) = select(
+            &mut db,
+            "select id from docs where body = 'nope' or title = 'fox'",
+        );
+        assert_eq!(rows, vec![vec![DBValue::Int(3)]]);
+
+        db.execute("drop table docs").unwrap();
+        db.pager.checkpoint().unwrap();
+        assert!(!toast_path.exists());
+    }
+
+    #[test]
+    fn test_alter_table() {
+        let (dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+        db.execute("create index meow_id on meow (id)").unwrap();
+        db.execute("create index meow_name on meow (name)").unwrap();
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+
+        db.execute("alter table meow add column age int default 2 + 3")
+            .unwrap();
+        db.execute("alter table meow add nickname string").unwrap();
+        affected(
+            &mut db,
+            "insert into meow values (4, 'fox', 40., true, 1, 'foxy')",
+        );
+        affected(&mut db, "update meow set age = 7 where id = 2");
+        assert_eq!(
+            select(&mut db, "select id, age, nickname from meow order by id").1,
+            vec![
+                vec![DBValue::Int(1), DBValue::Int(5), DBValue::Null],
+                vec![DBValue::Int(2), DBValue::Int(7), DBValue::Null],
+                vec![DBValue::Int(3), DBValue::Int(5), DBValue::Null],
+                vec![
+                    DBValue::Int(4),
+                    DBValue::Int(1),
+                    DBValue::String("foxy".to_owned())
+                ],
+            ]
+        );
+
+        // The indexes on a dropped column go away, the others follow the columns.
+        db.execute("alter table meow drop column name").unwrap();
+        assert_eq!(
+            error(&mut db, "drop index meow_name"),
+            "Index meow_name does not exist"
+        );
+        db.execute("alter table meow rename column id to key")
+            .unwrap();
+        db.execute("alter table meow rename to cat").unwrap();
+        assert_eq!(
+            error(&mut db, "select * from meow"),
+            "Table meow does not exist"
+        );
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            let (header, rows) = select(db, "select * from cat where key = 3");
+            assert_eq!(header, ["key", "height", "is_fox", "age", "nickname"]);
+            assert_eq!(
+                rows,
+                vec![vec![
+                    DBValue::Int(3),
+                    DBValue::Double(120.),
+                    DBValue::Bool(true),
+                    DBValue::Int(5),
+                    DBValue::Null,
+                ]]
+            );
+        }
+
+        assert_eq!(
+            error(&mut db, "alter table cat add column age int"),
+            "Column age of table cat already exists"
+        );
+        assert_eq!(
+            error(&mut db, "alter table cat rename key to height"),
+            "Column height of table cat already exists"
+        );
+        assert_eq!(
+            error(&mut db, "alter table cat drop column name"),
+            "Column name does not exist at line 1, column 29"
+        );
+        assert_eq!(
+            error(&mut db, "alter table cat add column alive bool default 1"),
+            "Type mismatch: 1 is not of type bool at line 1, column 47"
+        );
+        db.execute("create table dog (id int)").unwrap();
+        assert_eq!(
+            error(&mut db, "alter table cat rename to dog"),
+            "Table dog already exists"
+        );
+        assert_eq!(
+            error(&mut db, "alter table dog drop id"),
+            "Cannot drop column id, the only column of table dog"
+        );
+
+        // Schema changes are undone with the transaction.
+        db.execute("begin").unwrap();
+        db.execute("alter table cat drop column height").unwrap();
+        db.execute("alter table cat rename to meow").unwrap();
+        db.execute("rollback").unwrap();
+        assert_eq!(
+            select(&mut db, "select height from cat where key = 1").1,
+            vec![vec![DBValue::Double(182.5)]]
+        );
+    }
+
+    #[test]
+    fn test_alter_table_with_long_values() {
+        let (_dir, mut db) = temp_db();
+        db.execute("create table t (id int, text string)").unwrap();
+        let long = "meow".repeat(5000);
+        for i in 0..10 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, '{}{}')", i, i, long),
+            );
+        }
+        let toast_pages = |db: &DB| {
+            let table = db.get_table("t").unwrap();
+            db.pager
+                .page_count(&table.get_toast_file_path(&db.storage_dir))
+                .unwrap()
+        };
+        let page_count = toast_pages(&db);
+        db.execute("alter table t add column extra string default 'x'")
+            .unwrap();
+        assert_eq!(
+            select(&mut db, "select text, extra from t where id = 3").1,
+            vec![vec![
+                DBValue::String(format!("3{}", long)),
+                DBValue::String("x".to_owned())
+            ]]
+        );
+        // The values of a dropped column are freed once their tuples are deleted.
+        db.execute("alter table t drop column text").unwrap();
+        assert_eq!(affected(&mut db, "delete from t where id < 5"), 5);
+        for i in 10..15 {
+            affected(
+                &mut db,
+                &format!("insert into t values ({}, '{}')", i, long),
+            );
         }
+        assert_eq!(toast_pages(&db), page_count);
+        assert_eq!(
+            select(&mut db, "select count(*) from t where extra = 'x'").1,
+            vec![vec![DBValue::Int(5)]]
+        );
+    }
+
+    #[test]
+    fn test_key_constraints() {
+        let (dir, mut db) = temp_db();
+        db.execute("create table users (id int primary key, email text unique, name text)")
+            .unwrap();
+        affected(&mut db, "insert into users values (1, 'a@fox', 'a')");
+        affected(&mut db, "insert into users values (2, 'b@fox', 'b')");
+        let integrity_error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Integrity(msg)) => msg,
+            _ => panic!("Expected integrity error from {}", query),
+        };
+
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            assert_eq!(
+                integrity_error(db, "insert into users values (1, 'c@fox', 'c')"),
+                "Duplicate key (id) = (1) violates unique constraint users_pkey"
+            );
+            assert_eq!(
+                integrity_error(db, "insert into users values (3, 'a@fox', 'c')"),
+                "Duplicate key (email) = ('a@fox') violates unique constraint users_email_key"
+            );
+            assert_eq!(
+                integrity_error(db, "insert into users values (null, 'c@fox', 'c')"),
+                "NULL value in column id violates primary key users_pkey"
+            );
+            assert_eq!(
+                integrity_error(db, "update users set email = 'b@fox' where id = 1"),
+                "Duplicate key (email) = ('b@fox') violates unique constraint users_email_key"
+            );
+        }
+        // Keys with NULLs are all different, and a tuple doesn't conflict with itself.
+        affected(&mut db, "insert into users values (3, null, 'c')");
+        affected(&mut db, "insert into users values (4, null, 'd')");
+        assert_eq!(
+            affected(&mut db, "update users set name = 'aa' where id = 1"),
+            1
+        );
+        assert_eq!(
+            affected(&mut db, "update users set id = id + 10 where id > 2"),
+            2
+        );
+        affected(&mut db, "delete from users where id = 2");
+        affected(&mut db, "insert into users values (2, 'b@fox', 'b')");
+        assert_eq!(
+            select(&mut db, "select id from users order by id").1,
+            [1, 2, 13, 14].map(|id| vec![DBValue::Int(id)])
+        );
+
+        db.execute(
+            "create table pairs (a int, b int, constraint pairs_ab primary key (a, b), unique (b))",
+        )
+        .unwrap();
+        affected(&mut db, "insert into pairs values (1, 1)");
+        affected(&mut db, "insert into pairs values (1, 2)");
+        assert_eq!(
+            integrity_error(&mut db, "insert into pairs values (1, 1)"),
+            "Duplicate key (a, b) = (1, 1) violates unique constraint pairs_ab"
+        );
+        assert_eq!(
+            integrity_error(&mut db, "insert into pairs values (2, 1)"),
+            "Duplicate key (b) = (1) violates unique constraint pairs_b_key"
+        );
+
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        assert_eq!(
+            error(&mut db, "drop index users_pkey"),
+            "Index users_pkey enforces a constraint of table users and can't be dropped"
+        );
+        assert_eq!(
+            error(
+                &mut db,
+                "create table t (a int primary key, b int, primary key (b))"
+            ),
+            "Multiple primary keys for table t are not allowed at line 1, column 56"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a int, unique (a, c))"),
+            "Column c named in key does not exist at line 1, column 35"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a int constraint pairs_ab unique)"),
+            "Index pairs_ab already exists"
+        );
+        assert!(db.execute("select * from t").is_err());
+    }
+
+    #[test]
+    fn test_column_constraints() {
+        let (dir, mut db) = temp_db();
+        db.execute(
+            "create table items (id int primary key, name text not null, \
+             qty int default 1 check (qty >= 0), \
+             price double default 2 * 1.5 check (price > 0) check (price < 1000), \
+             note text default 'none')",
+        )
+        .unwrap();
+        affected(&mut db, "insert into items (id, name) values (1, 'a')");
+        affected(
+            &mut db,
+            "insert into items values (2, 'b', default, 5, null)",
+        );
+        affected(
+            &mut db,
+            "insert into items (name, id, qty) values ('c', 3, null)",
+        );
+        let string = |s: &str| DBValue::String(s.to_owned());
+        assert_eq!(
+            select(&mut db, "select * from items order by id").1,
+            vec![
+                vec![
+                    DBValue::Int(1),
+                    string("a"),
+                    DBValue::Int(1),
+                    DBValue::Double(3.),
+                    string("none")
+                ],
+                vec![
+                    DBValue::Int(2),
+                    string("b"),
+                    DBValue::Int(1),
+                    DBValue::Double(5.),
+                    DBValue::Null
+                ],
+                vec![
+                    DBValue::Int(3),
+                    string("c"),
+                    DBValue::Null,
+                    DBValue::Double(3.),
+                    string("none")
+                ],
+            ]
+        );
+
+        let integrity_error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Integrity(msg)) => msg,
+            _ => panic!("Expected integrity error from {}", query),
+        };
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            assert_eq!(
+                integrity_error(db, "insert into items (id, qty) values (4, 1)"),
+                "Column name violates NOT NULL"
+            );
+            assert_eq!(
+                integrity_error(db, "insert into items (id, name, qty) values (4, 'd', -1)"),
+                "Column qty violates CHECK (qty >= 0)"
+            );
+            assert_eq!(
+                integrity_error(db, "insert into items values (4, 'd', 1, 1000, null)"),
+                "Column price violates CHECK (price < 1000)"
+            );
+            assert_eq!(
+                integrity_error(db, "update items set qty = qty - 5 where id = 2"),
+                "Column qty violates CHECK (qty >= 0)"
+            );
+            assert_eq!(
+                integrity_error(db, "update items set name = null"),
+                "Column name violates NOT NULL"
+            );
+        }
+        assert_eq!(
+            affected(
+                &mut db,
+                "update items set qty = default, note = default where id > 1"
+            ),
+            2
+        );
+        assert_eq!(
+            select(&mut db, "select qty, note from items where id = 2").1,
+            vec![vec![DBValue::Int(1), string("none")]]
+        );
+
+        // Stored checks follow the renamed columns and go away with the dropped ones.
+        db.execute("alter table items rename column qty to amount")
+            .unwrap();
+        assert_eq!(
+            integrity_error(&mut db, "update items set amount = -1"),
+            "Column amount violates CHECK (amount >= 0)"
+        );
+        db.execute("create table ranges (lo int, hi int check (hi >= lo))")
+            .unwrap();
+        assert_eq!(
+            integrity_error(&mut db, "insert into ranges values (2, 1)"),
+            "Column hi violates CHECK (hi >= lo)"
+        );
+        db.execute("alter table ranges drop column lo").unwrap();
+        affected(&mut db, "insert into ranges values (1)");
+
+        assert_eq!(
+            integrity_error(&mut db, "alter table items add column color text not null"),
+            "Column color violates NOT NULL"
+        );
+        db.execute("alter table items add column sold bool not null default false")
+            .unwrap();
+        assert_eq!(
+            select(&mut db, "select count(*) from items where not sold").1,
+            vec![vec![DBValue::Int(3)]]
+        );
+        assert_eq!(
+            integrity_error(&mut db, "update items set sold = null"),
+            "Column sold violates NOT NULL"
+        );
+
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        assert_eq!(
+            error(&mut db, "create table t (a int default 'x')"),
+            "Type mismatch: 'x' is not of type int at line 1, column 31"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a int check (a + 1))"),
+            "Type mismatch: a + 1 is not of type bool at line 1, column 30"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a int check (b > 0))"),
+            "Column b does not exist at line 1, column 30"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a int check (t.a > 0))"),
+            "Columns in CHECK can't be qualified with a table at line 1, column 30"
+        );
+        assert_eq!(
+            error(&mut db, "insert into items (id, id) values (1, 1)"),
+            "Column id is specified more than once at line 1, column 24"
+        );
+        assert_eq!(
+            error(&mut db, "insert into items (id, name) values (1)"),
+            "Insert values count (1) does not match columns count (2)"
+        );
+        assert_eq!(
+            error(&mut db, "select default from items"),
+            "DEFAULT is not allowed here at line 1, column 8"
+        );
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let (dir, mut db) = temp_db();
+        db.execute("create table meow (id int primary key, name text unique)")
+            .unwrap();
+        db.execute(
+            "create table food (id int primary key, \
+             meow_id int references meow on delete cascade on update cascade)",
+        )
+        .unwrap();
+        db.execute(
+            "create table toys (id int, owner text, \
+             foreign key (owner) references meow (name) on delete set null)",
+        )
+        .unwrap();
+        affected(&mut db, "insert into meow values (1, 'a')");
+        affected(&mut db, "insert into meow values (2, 'b')");
+        affected(&mut db, "insert into food values (1, 1)");
+        affected(&mut db, "insert into food values (2, 1)");
+        affected(&mut db, "insert into food values (3, 2)");
+        affected(&mut db, "insert into food values (4, null)");
+        affected(&mut db, "insert into toys values (1, 'a')");
+        affected(&mut db, "insert into toys values (2, 'b')");
+        let integrity_error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Integrity(msg)) => msg,
+            _ => panic!("Expected integrity error from {}", query),
+        };
+
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            assert_eq!(
+                integrity_error(db, "insert into food values (5, 3)"),
+                "Key (meow_id) = (3) is not present in table meow, violates foreign key \
+                 food_meow_id_fkey"
+            );
+            assert_eq!(
+                integrity_error(db, "update toys set owner = 'c' where id = 1"),
+                "Key (owner) = ('c') is not present in table meow, violates foreign key \
+                 toys_owner_fkey"
+            );
+            assert_eq!(
+                integrity_error(db, "update meow set name = 'c' where id = 1"),
+                "Key (name) = ('a') is still referenced from table toys, violates foreign key \
+                 toys_owner_fkey"
+            );
+        }
+        // Once nothing references it, the key may change.
+        affected(&mut db, "update toys set owner = null where id = 1");
+        assert_eq!(
+            affected(&mut db, "update meow set name = 'c' where id = 1"),
+            1
+        );
+        affected(&mut db, "update toys set owner = 'c' where id = 1");
+
+        assert_eq!(affected(&mut db, "update meow set id = 10 where id = 1"), 1);
+        assert_eq!(
+            select(&mut db, "select id, meow_id from food order by id").1,
+            [(1, Some(10)), (2, Some(10)), (3, Some(2)), (4, None)].map(|(id, meow_id)| vec![
+                DBValue::Int(id),
+                meow_id.map_or(DBValue::Null, DBValue::Int)
+            ])
+        );
+        assert_eq!(affected(&mut db, "delete from meow where id = 2"), 1);
+        assert_eq!(
+            select(&mut db, "select id from food order by id").1,
+            [1, 2, 4].map(|id| vec![DBValue::Int(id)])
+        );
+        assert_eq!(
+            select(&mut db, "select id, owner from toys order by id").1,
+            [
+                vec![DBValue::Int(1), DBValue::String("c".to_owned())],
+                vec![DBValue::Int(2), DBValue::Null]
+            ]
+        );
+
+        // Actions cascade through tables referencing themselves.
+        db.execute(
+            "create table staff (id int primary key, \
+             boss int references staff (id) on delete cascade)",
+        )
+        .unwrap();
+        affected(&mut db, "insert into staff values (1, null)");
+        affected(&mut db, "insert into staff values (2, 1)");
+        affected(&mut db, "insert into staff values (3, 2)");
+        affected(&mut db, "insert into staff values (4, 4)");
+        assert_eq!(affected(&mut db, "delete from staff where id = 1"), 1);
+        assert_eq!(
+            select(&mut db, "select id from staff").1,
+            [vec![DBValue::Int(4)]]
+        );
+
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        assert_eq!(
+            error(&mut db, "drop table meow"),
+            "Table meow is referenced by foreign key food_meow_id_fkey of table food"
+        );
+        assert_eq!(
+            error(&mut db, "alter table meow drop column name"),
+            "Column name of table meow is referenced by foreign key toys_owner_fkey of table toys"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a int references toys)"),
+            "Table toys has no primary key to reference at line 1, column 34"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a int references toys (id))"),
+            "No PRIMARY KEY or UNIQUE constraint of table toys has the referenced columns at \
+             line 1, column 34"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a text references meow)"),
+            "Column a of type text can't reference column id of type int at line 1, column 17"
+        );
+        assert_eq!(
+            error(
+                &mut db,
+                "create table t (a int, foreign key (a) references meow (id, name))"
+            ),
+            "Foreign key has 1 columns but references 2 at line 1, column 51"
+        );
+
+        // The foreign key goes away with its column, and then the table with its key.
+        db.execute("alter table toys drop column owner").unwrap();
+        db.execute("drop table food").unwrap();
+        db.execute("drop table meow").unwrap();
+    }
+
+    #[test]
+    fn test_numeric_widths() {
+        let (dir, mut db) = temp_db();
+        db.execute("create table reading (id bigserial, sensor smallint, at bigint, value real)")
+            .unwrap();
+        db.execute("create index reading_at on reading (at)")
+            .unwrap();
+        affected(
+            &mut db,
+            "insert into reading (sensor, at, value) values (1, 1700000000000, 20.5)",
+        );
+        affected(
+            &mut db,
+            "insert into reading (sensor, at, value) values (2, 1700000060000, -1)",
+        );
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        assert_eq!(
+            error(
+                &mut db,
+                "insert into reading (sensor, at) values (40000, 1)"
+            ),
+            "Smallint out of range"
+        );
+        assert_eq!(
+            error(&mut db, "update reading set at = at * 10000000"),
+            "Bigint out of range"
+        );
+
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            assert_eq!(
+                select(db, "select * from reading order by id").1,
+                [
+                    vec![
+                        DBValue::BigInt(1),
+                        DBValue::SmallInt(1),
+                        DBValue::BigInt(1_700_000_000_000),
+                        DBValue::Real(20.5)
+                    ],
+                    vec![
+                        DBValue::BigInt(2),
+                        DBValue::SmallInt(2),
+                        DBValue::BigInt(1_700_000_060_000),
+                        DBValue::Real(-1.)
+                    ]
+                ]
+            );
+            // Numbers of different widths compare and compute in the wider type.
+            assert_eq!(
+                select(
+                    db,
+                    "select sensor + 1, at / 1000, value * 2, value + 0.25 from reading \
+                     where at > 1700000000000 and sensor = 2 and value < 0"
+                )
+                .1,
+                [vec![
+                    DBValue::Int(3),
+                    DBValue::BigInt(1_700_000_060),
+                    DBValue::Real(-2.),
+                    DBValue::Real(-0.75)
+                ]]
+            );
+            assert_eq!(
+                select(db, "select id from reading where id = 1").1,
+                [vec![DBValue::BigInt(1)]]
+            );
+            assert_eq!(
+                select(
+                    db,
+                    "select a.id, b.sensor from reading a join reading b on a.id = b.sensor"
+                )
+                .1,
+                [
+                    vec![DBValue::BigInt(1), DBValue::SmallInt(1)],
+                    vec![DBValue::BigInt(2), DBValue::SmallInt(2)]
+                ]
+            );
+            assert_eq!(
+                select(db, "select sum(sensor), max(at), avg(value) from reading").1,
+                [vec![
+                    DBValue::BigInt(3),
+                    DBValue::BigInt(1_700_000_060_000),
+                    DBValue::Double(9.75)
+                ]]
+            );
+        }
+        assert_eq!(
+            error(&mut db, "update reading set sensor = 1.5"),
+            "Type mismatch: 1.5 is not of type smallint at line 1, column 29"
+        );
+        // Integers fit any numeric column, if they are in its range.
+        assert_eq!(
+            affected(&mut db, "update reading set value = at, sensor = id + 10"),
+            2
+        );
+        assert_eq!(
+            select(&mut db, "select sensor, value from reading where id = 1").1,
+            [vec![DBValue::SmallInt(11), DBValue::Real(1.7e12)]]
+        );
+        // Sums are added up in a wider type, so they don't overflow.
+        affected(
+            &mut db,
+            "insert into reading (sensor, at, value) values (32767, 9223372036854775807, 0.5)",
+        );
+        assert_eq!(
+            select(
+                &mut db,
+                "select sum(sensor), sum(at), sum(value) from reading where sensor > 100"
+            )
+            .1,
+            [vec![
+                DBValue::BigInt(32767),
+                DBValue::Numeric("9223372036854775807".parse().unwrap()),
+                DBValue::Double(0.5)
+            ]]
+        );
+        assert_eq!(
+            select(&mut db, "select sum(sensor), sum(at) from reading").1,
+            [vec![
+                DBValue::BigInt(11 + 12 + 32767),
+                DBValue::Numeric("9223375436854835807".parse().unwrap())
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_numeric() {
+        let (dir, mut db) = temp_db();
+        db.execute(
+            "create table account (id int primary key, balance numeric(12, 2), rate numeric)",
+        )
+        .unwrap();
+        db.execute("create index account_balance on account (balance)")
+            .unwrap();
+        affected(&mut db, "insert into account values (1, 10.005, 0.1)");
+        affected(&mut db, "insert into account values (2, 0.1, 0.2)");
+        for _ in 0..10 {
+            affected(
+                &mut db,
+                "update account set balance = balance + rate where id = 2",
+            );
+        }
+        let numeric = |s: &str| DBValue::Numeric(s.parse().unwrap());
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        // Decimal literals are stored exactly.
+        db.execute("create table ledger (amount numeric(20, 2))")
+            .unwrap();
+        affected(&mut db, "insert into ledger values (12345678901234567.89)");
+        assert_eq!(
+            select(&mut db, "select amount from ledger").1,
+            [vec![numeric("12345678901234567.89")]]
+        );
+        assert_eq!(
+            error(&mut db, "insert into account values (3, 12345678901, 0)"),
+            "Numeric field overflow, 12345678901 doesn't fit in numeric(12,2)"
+        );
+        assert_eq!(
+            error(&mut db, "update account set balance = 'a'"),
+            "Type mismatch: 'a' is not of type numeric(12,2) at line 1, column 30"
+        );
+        assert_eq!(
+            error(
+                &mut db,
+                "update account set balance = balance / (rate - rate)"
+            ),
+            "Division by zero"
+        );
+        // Numbers of different scales are equal groups and distinct values.
+        db.execute("create table measure (n numeric)").unwrap();
+        for n in ["1.0", "1.00", "1", "2.50", "2.5"] {
+            affected(&mut db, &format!("insert into measure values ({})", n));
+        }
+        let (_, rows) = select(
+            &mut db,
+            "select n, count(*), count(distinct n) from measure group by n order by n",
+        );
+        assert_eq!(
+            rows.iter()
+                .map(|row| format!("{} {} {}", row[0], row[1], row[2]))
+                .collect::<Vec<_>>(),
+            ["1.0 3 1", "2.50 2 1"]
+        );
+        assert_eq!(
+            select(&mut db, "select count(distinct n) from measure").1,
+            [vec![DBValue::Int(2)]]
+        );
+
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            let (_, rows) = select(db, "select balance, rate from account order by id");
+            assert_eq!(
+                rows.iter()
+                    .map(|row| format!("{} {}", row[0], row[1]))
+                    .collect::<Vec<_>>(),
+                ["10.01 0.1", "2.10 0.2"]
+            );
+            // Sums and averages are exact, unlike with doubles.
+            assert_eq!(
+                select(
+                    db,
+                    "select sum(balance), sum(rate), avg(balance), avg(rate) from account"
+                )
+                .1,
+                [vec![
+                    numeric("12.11"),
+                    numeric("0.3"),
+                    numeric("6.055"),
+                    numeric("0.15")
+                ]]
+            );
+            assert_eq!(
+                select(db, "select avg(amount + 0.01) from ledger").1,
+                [vec![numeric("12345678901234567.9")]]
+            );
+            assert_eq!(
+                select(
+                    db,
+                    "select balance * 3, balance / 3, balance % 3, -rate + 1, balance + 0.5 \
+                     from account where id = 1"
+                )
+                .1,
+                [vec![
+                    numeric("30.03"),
+                    numeric("3.3366666666666667"),
+                    numeric("1.01"),
+                    numeric("0.9"),
+                    numeric("10.51")
+                ]]
+            );
+            assert_eq!(
+                select(
+                    db,
+                    "select id from account where balance = 2.1 and rate < 1"
+                )
+                .1,
+                [vec![DBValue::Int(2)]]
+            );
+            assert_eq!(
+                select(
+                    db,
+                    "select a.id, b.id from account a join account b on a.balance = b.rate * 21"
+                )
+                .1,
+                [vec![DBValue::Int(2), DBValue::Int(1)]]
+            );
+        }
+    }
+
+    #[test]
+    fn test_temporal() {
+        let (dir, mut db) = temp_db();
+        db.execute(
+            "create table event (id int primary key, day date, at timestamp, starts time, \
+             length interval)",
+        )
+        .unwrap();
+        db.execute("create index event_at on event (at)").unwrap();
+        affected(
+            &mut db,
+            "insert into event values (1, '2024-01-15', '2024-01-15 10:30', '09:00', '90 minutes')",
+        );
+        affected(
+            &mut db,
+            "insert into event values (2, '2024-02-29', '2024-02-29T23:59:59.5', '23:30', \
+             'P1M2D')",
+        );
+        affected(
+            &mut db,
+            "insert into event values (3, date '2023-12-31', timestamp '2024-01-01', \
+             time '00:00', interval '-1 day')",
+        );
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        assert_eq!(
+            error(
+                &mut db,
+                "insert into event (id, day) values (4, '2023-02-29')"
+            ),
+            "Invalid date `2023-02-29`"
+        );
+        assert_eq!(
+            error(&mut db, "select id from event where at < 'soon'"),
+            "Invalid timestamp `soon` at line 1, column 33"
+        );
+        assert_eq!(
+            error(&mut db, "select extract(year from id) from event"),
+            "Function extract is not defined for int at line 1, column 8"
+        );
+        assert_eq!(
+            error(&mut db, "select date_trunc('dow', at) from event"),
+            "Unit dow is not supported by date_trunc at line 1, column 19"
+        );
+        assert_eq!(
+            error(&mut db, "select at + 1 from event"),
+            "Operator + is not defined for timestamp and int at line 1, column 8"
+        );
+        assert!(matches!(
+            db.execute("select date '2024-13-01'"),
+            Err(DBError::Parse(_))
+        ));
+
+        let strings = |rows: Vec<Vec<DBValue>>| {
+            rows.iter()
+                .map(|row| {
+                    row.iter()
+                        .map(DBValue::to_string)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+        };
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            let (_, rows) = select(db, "select day, at, starts, length from event order by at");
+            assert_eq!(
+                strings(rows),
+                [
+                    "2023-12-31 2024-01-01 00:00:00 00:00:00 -1 days",
+                    "2024-01-15 2024-01-15 10:30:00 09:00:00 01:30:00",
+                    "2024-02-29 2024-02-29 23:59:59.5 23:30:00 1 mon 2 days",
+                ]
+            );
+            // Range scans of the index on `at`, dates compare as their midnights.
+            let ids = |db: &mut DB, condition: &str| {
+                let query = format!("select id from event where {} order by id", condition);
+                strings(select(db, &query).1)
+            };
+            assert_eq!(
+                ids(db, "at >= '2024-01-01' and at < '2024-02-01'"),
+                ["1", "3"]
+            );
+            assert_eq!(ids(db, "at > date '2024-01-15'"), ["1", "2"]);
+            assert_eq!(ids(db, "day < at"), ["1", "2", "3"]);
+            assert_eq!(
+                ids(db, "length > '1 day' or starts <= '09:00'"),
+                ["1", "2", "3"]
+            );
+            assert_eq!(ids(db, "length = interval '-24 hours'"), ["3"]);
+
+            let (_, rows) = select(
+                db,
+                "select at + length, at - interval '1 mon', at - timestamp '2024-01-01', \
+                 day - date '2024-01-01', day + 1, starts + length from event order by id",
+            );
+            assert_eq!(
+                strings(rows),
+                [
+                    "2024-01-15 12:00:00 2023-12-15 10:30:00 14 days 10:30:00 14 2024-01-16 10:30:00",
+                    "2024-03-31 23:59:59.5 2024-01-29 23:59:59.5 59 days 23:59:59.5 59 2024-03-01 23:30:00",
+                    "2023-12-31 00:00:00 2023-12-01 00:00:00 00:00:00 -1 2024-01-01 00:00:00",
+                ]
+            );
+            let (_, rows) = select(
+                db,
+                "select extract(year from day), extract(dow from day), extract(second from at), \
+                 extract(hour from starts), extract(epoch from length) from event order by id",
+            );
+            assert_eq!(
+                strings(rows),
+                [
+                    "2024 1 0.000000 9 5400.000000",
+                    "2024 4 59.500000 23 2764800.000000",
+                    "2023 0 0.000000 0 -86400.000000",
+                ]
+            );
+            let (_, rows) = select(
+                db,
+                "select date_trunc('month', at), count(*) from event \
+                 group by date_trunc('month', at) order by date_trunc('month', at)",
+            );
+            assert_eq!(
+                strings(rows),
+                ["2024-01-01 00:00:00 2", "2024-02-01 00:00:00 1"]
+            );
+            let (_, mut rows) = db
+                .execute("select extract(hour from day) from event")
+                .unwrap()
+                .0
+                .unwrap();
+            let Some(Err(DBError::Execution(msg))) = rows.next() else {
+                panic!("Expected execution error");
+            };
+            assert_eq!(msg, "Field hour is not supported for type date");
+        }
+        assert_eq!(
+            select(
+                &mut db,
+                "select now() > timestamp '2024-01-01', current_date <= current_timestamp, \
+                 current_date = date_trunc('day', now())"
+            )
+            .1,
+            [vec![DBValue::Bool(true); 3]]
+        );
+        // Intervals of the same length are equal groups and distinct values.
+        db.execute("create table shift (length interval)").unwrap();
+        for length in ["1 day", "24 hours", "P1D", "36 hours"] {
+            affected(&mut db, &format!("insert into shift values ('{}')", length));
+        }
+        assert_eq!(
+            select(
+                &mut db,
+                "select count(*), count(distinct length) from shift group by length \
+                 order by count(*)"
+            )
+            .1,
+            [
+                vec![DBValue::Int(1), DBValue::Int(1)],
+                vec![DBValue::Int(3), DBValue::Int(1)]
+            ]
+        );
+        assert_eq!(
+            select(&mut db, "select count(distinct length) from shift").1,
+            [vec![DBValue::Int(2)]]
+        );
+        let now = |db: &mut DB| select(db, "select now(), current_timestamp").1;
+        let outside = now(&mut db);
+        assert_eq!(outside[0][0], outside[0][1]);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        db.execute("begin").unwrap();
+        let started = now(&mut db);
+        assert!(started[0][0] > outside[0][0]);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(now(&mut db), started);
+        db.execute("commit").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(now(&mut db)[0][0] > started[0][0]);
+    }
+
+    #[test]
+    fn test_bytea() {
+        use rand::Rng;
+
+        let (dir, mut db) = temp_db();
+        db.execute("create table blob (id int, hash bytea, data blob)")
+            .unwrap();
+        db.execute("create index blob_hash on blob (hash)").unwrap();
+        let large = rand::rng().random_iter().take(30000).collect::<Vec<u8>>();
+        affected(
+            &mut db,
+            &format!(
+                "insert into blob values (1, x'DEADbeef', x'{}')",
+                utils::hex(&large)
+            ),
+        );
+        affected(&mut db, "insert into blob values (2, x'00', x'')");
+        affected(&mut db, "insert into blob values (3, x'deadbe', NULL)");
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Parse(msg)) => msg,
+            _ => panic!("Expected parse error from {}", query),
+        };
+        assert_eq!(
+            error(&mut db, "insert into blob values (4, x'0g', NULL)"),
+            "Invalid hex literal, expected pairs of hex digits at line 1, column 29"
+        );
+        assert!(
+            db.execute("insert into blob values (4, 'text', NULL)")
+                .is_err()
+        );
+
+        for db in [&mut db, &mut DB::load(dir.path().join("db")).unwrap()] {
+            let (_, rows) = select(db, "select data from blob where id = 1");
+            assert_eq!(rows, [vec![DBValue::Bytes(large.clone())]]);
+            let (_, rows) = select(
+                db,
+                "select id, hash, data from blob where id > 1 order by id",
+            );
+            let rows = rows
+                .iter()
+                .map(|row| row.iter().map(DBValue::to_string).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            assert_eq!(rows, [["2", "x'00'", "x''"], ["3", "x'deadbe'", "NULL"]]);
+            // Range scan of the index on `hash`, ordered byte by byte.
+            let (_, rows) = select(
+                db,
+                "select id from blob where hash > x'deadbe' or hash = x'' order by hash",
+            );
+            assert_eq!(rows, [vec![DBValue::Int(1)]]);
+            let (_, rows) = select(db, "select id from blob where hash || x'ef' = x'deadbeef'");
+            assert_eq!(rows, [vec![DBValue::Int(3)]]);
+        }
+    }
+
+    #[test]
+    fn test_sequences() {
+        let (dir, mut db) = temp_db();
+        db.execute("create table note (id serial primary key, body text)")
+            .unwrap();
+        let error = |db: &mut DB, query: &str| match db.execute(query) {
+            Err(DBError::Execution(msg)) => msg,
+            _ => panic!("Expected execution error from {}", query),
+        };
+        let (_, mut rows) = db
+            .execute("select currval('note_id_seq')")
+            .unwrap()
+            .0
+            .unwrap();
+        let Some(Err(DBError::Execution(msg))) = rows.next() else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(
+            msg,
+            "currval of sequence note_id_seq is not yet defined in this session"
+        );
+        drop(rows);
+        for body in ["a", "b", "c"] {
+            affected(
+                &mut db,
+                &format!("insert into note (body) values ('{}')", body),
+            );
+        }
+        assert_eq!(
+            select(&mut db, "select id from note order by id").1,
+            [1, 2, 3].map(|id| vec![DBValue::Int(id)])
+        );
+
+        db.execute("create sequence ids start with 100 increment by 10")
+            .unwrap();
+        assert_eq!(
+            select(&mut db, "select nextval('ids'), nextval('ids')").1,
+            [vec![DBValue::BigInt(100), DBValue::BigInt(110)]]
+        );
+        assert_eq!(
+            select(&mut db, "select currval('ids')").1,
+            [vec![DBValue::BigInt(110)]]
+        );
+        // Values taken by a failed statement aren't given back, as another connection may
+        // have taken the next ones.
+        assert!(matches!(
+            db.execute("insert into note values (nextval('ids') - 119, 'x')"),
+            Err(DBError::Integrity(_))
+        ));
+        db.execute("begin").unwrap();
+        assert_eq!(
+            select(&mut db, "select nextval('ids')").1,
+            [vec![DBValue::BigInt(130)]]
+        );
+        db.execute("rollback").unwrap();
+        // Connections sharing the storage directory never take the same value.
+        let mut other = DB::load(dir.path().join("db")).unwrap();
+        let next = |db: &mut DB, name: &str| {
+            select(db, &format!("select nextval('{}')", name)).1[0][0].clone()
+        };
+        assert_eq!(next(&mut other, "ids"), DBValue::BigInt(140));
+        assert_eq!(next(&mut db, "ids"), DBValue::BigInt(150));
+        assert_eq!(next(&mut other, "ids"), DBValue::BigInt(160));
+        assert_eq!(next(&mut other, "note_id_seq"), DBValue::BigInt(4));
+        assert_eq!(next(&mut db, "note_id_seq"), DBValue::BigInt(5));
+        drop(other);
+        drop(db);
+        let mut db = DB::load(dir.path().join("db")).unwrap();
+        assert_eq!(
+            select(&mut db, "select nextval('ids')").1,
+            [vec![DBValue::BigInt(170)]]
+        );
+        affected(&mut db, "insert into note (body) values ('d')");
+        assert_eq!(
+            select(
+                &mut db,
+                "select id, currval('note_id_seq') from note where body = 'd'"
+            )
+            .1,
+            [vec![DBValue::Int(6), DBValue::BigInt(6)]]
+        );
+
+        db.execute("create sequence down increment by -2").unwrap();
+        assert_eq!(
+            select(&mut db, "select nextval('down'), nextval('down')").1,
+            [vec![DBValue::BigInt(-1), DBValue::BigInt(-3)]]
+        );
+
+        assert_eq!(
+            error(&mut db, "create sequence ids"),
+            "Sequence ids already exists"
+        );
+        assert_eq!(
+            error(&mut db, "create sequence zero increment by 0"),
+            "INCREMENT of sequence zero must not be zero"
+        );
+        assert_eq!(
+            error(&mut db, "select nextval('nothing')"),
+            "Sequence nothing does not exist at line 1, column 16"
+        );
+        assert_eq!(
+            error(&mut db, "drop sequence note_id_seq"),
+            "Sequence note_id_seq is used by column id of table note and can't be dropped"
+        );
+        assert_eq!(
+            error(
+                &mut db,
+                "create table t (a int default 1 check (a < nextval('ids')))"
+            ),
+            "Function nextval is not allowed here at line 1, column 44"
+        );
+        assert_eq!(
+            error(&mut db, "create table t (a serial default 1)"),
+            "Multiple default values specified for column a at line 1, column 17"
+        );
+
+        db.execute("drop sequence ids").unwrap();
+        db.execute("drop table note").unwrap();
+        assert_eq!(
+            error(&mut db, "drop sequence note_id_seq"),
+            "Sequence note_id_seq does not exist"
+        );
+    }
+
+    #[test]
+    fn test_errors_point_at_token() {
+        let (_dir, mut db) = temp_db();
+        create_sample_table(&mut db);
+
+        let Err(DBError::Parse(msg)) = db.execute("select id, from meow") else {
+            panic!("Expected parse error");
+        };
+        assert_eq!(msg, "Expected expression, found FROM at line 1, column 12");
+        let Err(DBError::Execution(msg)) = db.execute("select id, nme from meow") else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(msg, "Column nme does not exist at line 1, column 12");
+        let Err(DBError::Execution(msg)) = db.execute("delete from meow where id = 'one'") else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(
+            msg,
+            "Type mismatch: 'one' is not of type int at line 1, column 29"
+        );
     }
 }
@@ -0,0 +1,744 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use crate::{
+    datetime::{Date, DateField, Interval},
+    errors::{self, DBError},
+    numeric::Numeric,
+    sequence::Sequences,
+    tuple::Tuple,
+    values::{DBType, DBValue},
+};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub(crate) enum CompareOp {
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl CompareOp {
+    /// The operator with the operands swapped, `a < b` is `b > a`.
+    pub fn flipped(self) -> Self {
+        match self {
+            CompareOp::Eq | CompareOp::Neq => self,
+            CompareOp::Lt => CompareOp::Gt,
+            CompareOp::Lte => CompareOp::Gte,
+            CompareOp::Gt => CompareOp::Lt,
+            CompareOp::Gte => CompareOp::Lte,
+        }
+    }
+
+    fn matches(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Neq => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Lte => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Gte => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompareOp::Eq => write!(f, "="),
+            CompareOp::Neq => write!(f, "!="),
+            CompareOp::Lt => write!(f, "<"),
+            CompareOp::Lte => write!(f, "<="),
+            CompareOp::Gt => write!(f, ">"),
+            CompareOp::Gte => write!(f, ">="),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub(crate) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Concat,
+}
+
+impl BinaryOp {
+    /// Result type of the operator applied to the given operand types, if it is defined.
+    /// `None` operands are NULL literals, which take the type of the other operand.
+    pub fn result_type(self, left: Option<DBType>, right: Option<DBType>) -> Option<DBType> {
+        let default = match self {
+            BinaryOp::Concat => DBType::String,
+            _ => DBType::Int,
+        };
+        let left = left.or(right).unwrap_or(default);
+        let right = right.unwrap_or(left);
+        match (self, left, right) {
+            (BinaryOp::Concat, DBType::Bytes, DBType::Bytes) => Some(DBType::Bytes),
+            (BinaryOp::Concat, DBType::String, _) | (BinaryOp::Concat, _, DBType::String) => {
+                Some(DBType::String)
+            }
+            (BinaryOp::Concat, _, _) => None,
+            (op, left, right) if left.is_temporal() || right.is_temporal() => {
+                op.temporal_result_type(left, right)
+            }
+            (_, left, right) => left.common_numeric(right),
+        }
+    }
+
+    /// Result type of date and time arithmetic: intervals added to or subtracted from
+    /// dates, times, timestamps and intervals, differences of dates, times and timestamps,
+    /// and days added to or subtracted from dates.
+    fn temporal_result_type(self, left: DBType, right: DBType) -> Option<DBType> {
+        match (self, left, right) {
+            (BinaryOp::Add | BinaryOp::Sub, DBType::Interval, DBType::Interval) => {
+                Some(DBType::Interval)
+            }
+            (BinaryOp::Add, DBType::Date | DBType::Timestamp, DBType::Interval)
+            | (BinaryOp::Add, DBType::Interval, DBType::Date | DBType::Timestamp)
+            | (BinaryOp::Sub, DBType::Date | DBType::Timestamp, DBType::Interval) => {
+                Some(DBType::Timestamp)
+            }
+            (BinaryOp::Add, DBType::Time, DBType::Interval)
+            | (BinaryOp::Add, DBType::Interval, DBType::Time)
+            | (BinaryOp::Sub, DBType::Time, DBType::Interval) => Some(DBType::Time),
+            (BinaryOp::Sub, DBType::Date, DBType::Date) => Some(DBType::Int),
+            (BinaryOp::Sub, DBType::Date | DBType::Timestamp, DBType::Date | DBType::Timestamp)
+            | (BinaryOp::Sub, DBType::Time, DBType::Time) => Some(DBType::Interval),
+            (BinaryOp::Add, DBType::Date, days) | (BinaryOp::Add, days, DBType::Date)
+                if days.is_integer() =>
+            {
+                Some(DBType::Date)
+            }
+            (BinaryOp::Sub, DBType::Date, days) if days.is_integer() => Some(DBType::Date),
+            _ => None,
+        }
+    }
+
+    /// Applies the operator to dates, times and intervals, see
+    /// [`BinaryOp::temporal_result_type`].
+    fn apply_temporal(self, left: DBValue, right: DBValue) -> errors::Result<DBValue> {
+        let out_of_range = |dtype: DBType| move || dtype.out_of_range();
+        let add_interval = |value: DBValue, interval: Option<Interval>| {
+            let interval = interval.ok_or_else(out_of_range(DBType::Interval))?;
+            match value {
+                // Times wrap around midnight, and only the time of the interval is added.
+                DBValue::Time(time) => Ok(DBValue::Time(time.wrapping_add(interval.micros()))),
+                value => value
+                    .as_timestamp()
+                    .and_then(|timestamp| timestamp.checked_add(interval))
+                    .map(DBValue::Timestamp)
+                    .ok_or_else(out_of_range(DBType::Timestamp)),
+            }
+        };
+        let add_days = |date: Date, days: Option<i64>| {
+            days.and_then(|days| date.checked_add_days(days))
+                .map(DBValue::Date)
+                .ok_or_else(out_of_range(DBType::Date))
+        };
+        match (self, left, right) {
+            (BinaryOp::Add, DBValue::Interval(a), DBValue::Interval(b)) => a
+                .checked_add(b)
+                .map(DBValue::Interval)
+                .ok_or_else(out_of_range(DBType::Interval)),
+            (BinaryOp::Sub, DBValue::Interval(a), DBValue::Interval(b)) => a
+                .checked_sub(b)
+                .map(DBValue::Interval)
+                .ok_or_else(out_of_range(DBType::Interval)),
+            (BinaryOp::Add, value, DBValue::Interval(interval))
+            | (BinaryOp::Add, DBValue::Interval(interval), value) => {
+                add_interval(value, Some(interval))
+            }
+            (BinaryOp::Sub, value, DBValue::Interval(interval)) => {
+                add_interval(value, interval.checked_neg())
+            }
+            (BinaryOp::Sub, DBValue::Date(a), DBValue::Date(b)) => {
+                Ok(DBValue::Int(a.days() - b.days()))
+            }
+            (BinaryOp::Sub, DBValue::Time(a), DBValue::Time(b)) => Ok(DBValue::Interval(
+                Interval::new(0, 0, a.micros() - b.micros()),
+            )),
+            (BinaryOp::Sub, a, b) if a.as_timestamp().is_some() && b.as_timestamp().is_some() => {
+                let (a, b) = (a.as_timestamp().unwrap(), b.as_timestamp().unwrap());
+                Ok(DBValue::Interval(a.diff(b)))
+            }
+            (BinaryOp::Add, DBValue::Date(date), days)
+            | (BinaryOp::Add, days, DBValue::Date(date))
+                if days.as_i64().is_some() =>
+            {
+                add_days(date, days.as_i64())
+            }
+            (BinaryOp::Sub, DBValue::Date(date), days) if days.as_i64().is_some() => {
+                add_days(date, days.as_i64().unwrap().checked_neg())
+            }
+            (op, left, right) => Err(DBError::Execution(format!(
+                "Operator {} is not defined for {} and {}",
+                op,
+                left.type_name(),
+                right.type_name()
+            ))),
+        }
+    }
+
+    /// Applies the operator, computing numbers in the wider of the operand types.
+    pub fn apply(self, left: DBValue, right: DBValue) -> errors::Result<DBValue> {
+        match (self, left, right) {
+            (_, DBValue::Null, _) | (_, _, DBValue::Null) => Ok(DBValue::Null),
+            (BinaryOp::Concat, DBValue::Bytes(mut left), DBValue::Bytes(right)) => {
+                left.extend(right);
+                Ok(DBValue::Bytes(left))
+            }
+            (BinaryOp::Concat, left, right) => Ok(DBValue::String(format!(
+                "{}{}",
+                left.to_raw_string(),
+                right.to_raw_string()
+            ))),
+            (op, left, right)
+                if left.dtype().is_some_and(DBType::is_temporal)
+                    || right.dtype().is_some_and(DBType::is_temporal) =>
+            {
+                op.apply_temporal(left, right)
+            }
+            (op, left, right) => {
+                let dtype = left
+                    .dtype()
+                    .zip(right.dtype())
+                    .and_then(|(a, b)| a.common_numeric(b))
+                    .ok_or_else(|| {
+                        DBError::Execution(format!(
+                            "Operator {} is not defined for {} and {}",
+                            op,
+                            left.type_name(),
+                            right.type_name()
+                        ))
+                    })?;
+                let division = matches!(op, BinaryOp::Div | BinaryOp::Mod);
+                let division_by_zero = || DBError::Execution("Division by zero".to_owned());
+                if let (Some(a), Some(b)) = (left.as_i64(), right.as_i64()) {
+                    if division && b == 0 {
+                        return Err(division_by_zero());
+                    }
+                    let value = match op {
+                        BinaryOp::Add => a.checked_add(b),
+                        BinaryOp::Sub => a.checked_sub(b),
+                        BinaryOp::Mul => a.checked_mul(b),
+                        BinaryOp::Div => a.checked_div(b),
+                        BinaryOp::Mod => a.checked_rem(b),
+                        BinaryOp::Concat => unreachable!(),
+                    };
+                    return DBValue::from_i64(value.ok_or_else(|| dtype.out_of_range())?, dtype);
+                }
+                if dtype == DBType::Numeric(None) {
+                    let (a, b) = (left.as_numeric().unwrap(), right.as_numeric().unwrap());
+                    if division && b == Numeric::from_i64(0) {
+                        return Err(division_by_zero());
+                    }
+                    let value = match op {
+                        BinaryOp::Add => a.checked_add(b),
+                        BinaryOp::Sub => a.checked_sub(b),
+                        BinaryOp::Mul => a.checked_mul(b),
+                        BinaryOp::Div => a.checked_div(b),
+                        BinaryOp::Mod => a.checked_rem(b),
+                        BinaryOp::Concat => unreachable!(),
+                    };
+                    return Ok(DBValue::Numeric(value.ok_or_else(|| dtype.out_of_range())?));
+                }
+                let (a, b) = (left.as_f64().unwrap(), right.as_f64().unwrap());
+                if division && b == 0. {
+                    return Err(division_by_zero());
+                }
+                // Rounding the exact `f64` result gives the `f32` one for `real`.
+                let value = match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Mod => a % b,
+                    BinaryOp::Concat => unreachable!(),
+                };
+                DBValue::from_f64(value, dtype)
+            }
+        }
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "+"),
+            BinaryOp::Sub => write!(f, "-"),
+            BinaryOp::Mul => write!(f, "*"),
+            BinaryOp::Div => write!(f, "/"),
+            BinaryOp::Mod => write!(f, "%"),
+            BinaryOp::Concat => write!(f, "||"),
+        }
+    }
+}
+
+/// A type-checked scalar expression evaluated against a row, e.g. a table tuple or the
+/// concatenated tuples of joined tables.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum Expr {
+    Column(usize),
+    Literal(DBValue),
+    Negate(Box<Expr>),
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Compare {
+        op: CompareOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// `operand IS NULL`, or `IS NOT NULL` if `negated`.
+    IsNull {
+        operand: Box<Expr>,
+        negated: bool,
+    },
+    /// `extract(field from operand)`, the field of a date, time or interval as a numeric.
+    Extract {
+        field: DateField,
+        operand: Box<Expr>,
+    },
+    /// `date_trunc('field', operand)`, the start of the unit a timestamp is in.
+    DateTrunc {
+        field: DateField,
+        operand: Box<Expr>,
+    },
+    /// `nextval('name')`, takes the next value of the sequence.
+    NextVal {
+        sequences: Sequences,
+        name: String,
+    },
+    /// `currval('name')`, the value nextval of the sequence last returned in this session.
+    CurrVal {
+        sequences: Sequences,
+        name: String,
+    },
+}
+
+impl Expr {
+    pub fn eval(&self, tuple: &Tuple) -> errors::Result<DBValue> {
+        match self {
+            Expr::Column(index) => Ok(tuple.values[*index].clone()),
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Negate(operand) => match operand.eval(tuple)? {
+                DBValue::Null => Ok(DBValue::Null),
+                DBValue::SmallInt(v) => v
+                    .checked_neg()
+                    .map(DBValue::SmallInt)
+                    .ok_or_else(|| DBType::SmallInt.out_of_range()),
+                DBValue::Int(v) => v
+                    .checked_neg()
+                    .map(DBValue::Int)
+                    .ok_or_else(|| DBType::Int.out_of_range()),
+                DBValue::BigInt(v) => v
+                    .checked_neg()
+                    .map(DBValue::BigInt)
+                    .ok_or_else(|| DBType::BigInt.out_of_range()),
+                DBValue::Numeric(v) => Ok(DBValue::Numeric(-v)),
+                DBValue::Real(v) => Ok(DBValue::Real(-v)),
+                DBValue::Double(v) => Ok(DBValue::Double(-v)),
+                DBValue::Interval(v) => v
+                    .checked_neg()
+                    .map(DBValue::Interval)
+                    .ok_or_else(|| DBType::Interval.out_of_range()),
+                v => Err(DBError::Execution(format!(
+                    "Cannot negate a value of type {}",
+                    v.type_name()
+                ))),
+            },
+            Expr::Binary { op, left, right } => op.apply(left.eval(tuple)?, right.eval(tuple)?),
+            Expr::Compare { op, left, right } => {
+                let left = left.eval(tuple)?;
+                let right = right.eval(tuple)?;
+                if left.is_null() || right.is_null() {
+                    return Ok(DBValue::Null);
+                }
+                let ordering = left.partial_cmp(&right).ok_or_else(|| {
+                    DBError::Execution(format!(
+                        "Cannot compare {} with {}",
+                        left.type_name(),
+                        right.type_name()
+                    ))
+                })?;
+                Ok(DBValue::Bool(op.matches(ordering)))
+            }
+            // Kleene logic: FALSE AND NULL is FALSE, TRUE OR NULL is TRUE.
+            Expr::And(left, right) => Ok(match left.eval_bool(tuple)? {
+                Some(false) => DBValue::Bool(false),
+                left => match (left, right.eval_bool(tuple)?) {
+                    (_, Some(false)) => DBValue::Bool(false),
+                    (Some(true), Some(true)) => DBValue::Bool(true),
+                    _ => DBValue::Null,
+                },
+            }),
+            Expr::Or(left, right) => Ok(match left.eval_bool(tuple)? {
+                Some(true) => DBValue::Bool(true),
+                left => match (left, right.eval_bool(tuple)?) {
+                    (_, Some(true)) => DBValue::Bool(true),
+                    (Some(false), Some(false)) => DBValue::Bool(false),
+                    _ => DBValue::Null,
+                },
+            }),
+            Expr::Not(operand) => Ok(match operand.eval_bool(tuple)? {
+                Some(v) => DBValue::Bool(!v),
+                None => DBValue::Null,
+            }),
+            Expr::IsNull { operand, negated } => {
+                Ok(DBValue::Bool(operand.eval(tuple)?.is_null() != *negated))
+            }
+            Expr::Extract { field, operand } => {
+                let value = operand.eval(tuple)?;
+                let number = match &value {
+                    DBValue::Null => return Ok(DBValue::Null),
+                    DBValue::Date(v) => v.extract(*field),
+                    DBValue::Time(v) => v.extract(*field),
+                    DBValue::Timestamp(v) => Some(v.extract(*field)),
+                    DBValue::Interval(v) => v.extract(*field),
+                    _ => None,
+                };
+                number.map(DBValue::Numeric).ok_or_else(|| {
+                    DBError::Execution(format!(
+                        "Field {} is not supported for type {}",
+                        field,
+                        value.type_name()
+                    ))
+                })
+            }
+            Expr::DateTrunc { field, operand } => {
+                let value = operand.eval(tuple)?;
+                if value.is_null() {
+                    return Ok(DBValue::Null);
+                }
+                value
+                    .as_timestamp()
+                    .and_then(|timestamp| timestamp.truncate(*field))
+                    .map(DBValue::Timestamp)
+                    .ok_or_else(|| {
+                        DBError::Execution(format!(
+                            "Unit {} is not supported by date_trunc for type {}",
+                            field,
+                            value.type_name()
+                        ))
+                    })
+            }
+            Expr::NextVal { sequences, name } => Ok(DBValue::BigInt(sequences.next(name)?)),
+            Expr::CurrVal { sequences, name } => Ok(DBValue::BigInt(sequences.current(name)?)),
+        }
+    }
+
+    fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Column(_) | Expr::Literal(_) | Expr::NextVal { .. } | Expr::CurrVal { .. } => {
+                vec![]
+            }
+            Expr::Negate(operand)
+            | Expr::Not(operand)
+            | Expr::IsNull { operand, .. }
+            | Expr::Extract { operand, .. }
+            | Expr::DateTrunc { operand, .. } => vec![operand],
+            Expr::Binary { left, right, .. }
+            | Expr::Compare { left, right, .. }
+            | Expr::And(left, right)
+            | Expr::Or(left, right) => vec![left, right],
+        }
+    }
+
+    /// Indexes of all columns used by the expression.
+    pub fn columns(&self) -> Vec<usize> {
+        match self {
+            Expr::Column(index) => vec![*index],
+            _ => self
+                .children()
+                .into_iter()
+                .flat_map(|child| child.columns())
+                .collect(),
+        }
+    }
+
+    /// Copy of the expression with each column index replaced by `f(index)`.
+    pub fn map_columns(&self, f: &impl Fn(usize) -> usize) -> Expr {
+        let map = |expr: &Expr| Box::new(expr.map_columns(f));
+        match self {
+            Expr::Column(index) => Expr::Column(f(*index)),
+            Expr::Literal(value) => Expr::Literal(value.clone()),
+            Expr::Negate(operand) => Expr::Negate(map(operand)),
+            Expr::Binary { op, left, right } => Expr::Binary {
+                op: *op,
+                left: map(left),
+                right: map(right),
+            },
+            Expr::Compare { op, left, right } => Expr::Compare {
+                op: *op,
+                left: map(left),
+                right: map(right),
+            },
+            Expr::And(left, right) => Expr::And(map(left), map(right)),
+            Expr::Or(left, right) => Expr::Or(map(left), map(right)),
+            Expr::Not(operand) => Expr::Not(map(operand)),
+            Expr::IsNull { operand, negated } => Expr::IsNull {
+                operand: map(operand),
+                negated: *negated,
+            },
+            Expr::Extract { field, operand } => Expr::Extract {
+                field: *field,
+                operand: map(operand),
+            },
+            Expr::DateTrunc { field, operand } => Expr::DateTrunc {
+                field: *field,
+                operand: map(operand),
+            },
+            Expr::NextVal { .. } | Expr::CurrVal { .. } => self.clone(),
+        }
+    }
+
+    /// Splits the expression into the operands of its top-level ANDs.
+    pub fn conjuncts(&self) -> Vec<&Expr> {
+        match self {
+            Expr::And(left, right) => {
+                let mut conjuncts = left.conjuncts();
+                conjuncts.extend(right.conjuncts());
+                conjuncts
+            }
+            expr => vec![expr],
+        }
+    }
+
+    /// Evaluates a boolean expression, `None` meaning NULL (unknown).
+    pub fn eval_bool(&self, tuple: &Tuple) -> errors::Result<Option<bool>> {
+        match self.eval(tuple)? {
+            DBValue::Bool(v) => Ok(Some(v)),
+            DBValue::Null => Ok(None),
+            v => Err(DBError::Execution(format!(
+                "Expected a value of type bool, got {}",
+                v.type_name()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        errors::{self, DBError},
+        tuple::Tuple,
+        values::{DBType, DBValue},
+    };
+
+    use super::{BinaryOp, CompareOp, Expr};
+
+    fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        Expr::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let tuple = Tuple::new(vec![DBValue::Int(7), DBValue::Double(1.5)]);
+        let eval = |expr: Expr| expr.eval(&tuple).unwrap();
+        assert_eq!(
+            eval(binary(
+                BinaryOp::Div,
+                Expr::Column(0),
+                Expr::Literal(DBValue::Int(2))
+            )),
+            DBValue::Int(3)
+        );
+        assert_eq!(
+            eval(binary(
+                BinaryOp::Mod,
+                Expr::Column(0),
+                Expr::Literal(DBValue::Int(4))
+            )),
+            DBValue::Int(3)
+        );
+        assert_eq!(
+            eval(binary(BinaryOp::Mul, Expr::Column(0), Expr::Column(1))),
+            DBValue::Double(10.5)
+        );
+        assert_eq!(
+            eval(Expr::Negate(Box::new(Expr::Column(1)))),
+            DBValue::Double(-1.5)
+        );
+        assert_eq!(
+            eval(binary(
+                BinaryOp::Concat,
+                Expr::Literal(DBValue::String("id: ".to_owned())),
+                Expr::Column(0)
+            )),
+            DBValue::String("id: 7".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_widens() {
+        let tuple = Tuple::new(vec![
+            DBValue::SmallInt(i16::MAX),
+            DBValue::Int(i32::MAX),
+            DBValue::BigInt(1 << 20),
+            DBValue::Real(0.5),
+        ]);
+        let add = |left, right| {
+            binary(BinaryOp::Add, Expr::Column(left), Expr::Column(right)).eval(&tuple)
+        };
+        let error = |result: errors::Result<DBValue>| match result {
+            Err(DBError::Execution(msg)) => msg,
+            other => panic!("Expected execution error, got {:?}", other),
+        };
+        assert_eq!(add(0, 2).unwrap(), DBValue::BigInt(1_081_343));
+        assert_eq!(add(2, 3).unwrap(), DBValue::Real(1_048_576.5));
+        assert_eq!(error(add(0, 0)), "Smallint out of range");
+        assert_eq!(error(add(0, 1)), "Integer out of range");
+        assert_eq!(
+            binary(
+                BinaryOp::Div,
+                Expr::Column(3),
+                Expr::Literal(DBValue::Double(4.))
+            )
+            .eval(&tuple)
+            .unwrap(),
+            DBValue::Double(0.125)
+        );
+        assert_eq!(
+            error(Expr::Negate(Box::new(Expr::Literal(DBValue::BigInt(i64::MIN)))).eval(&tuple)),
+            "Bigint out of range"
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_errors() {
+        let tuple = Tuple::new(vec![DBValue::Int(i32::MAX), DBValue::Int(0)]);
+        let DBError::Execution(msg) = binary(BinaryOp::Add, Expr::Column(0), Expr::Column(0))
+            .eval(&tuple)
+            .unwrap_err()
+        else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(msg, "Integer out of range");
+        let DBError::Execution(msg) = binary(BinaryOp::Div, Expr::Column(0), Expr::Column(1))
+            .eval(&tuple)
+            .unwrap_err()
+        else {
+            panic!("Expected execution error");
+        };
+        assert_eq!(msg, "Division by zero");
+    }
+
+    #[test]
+    fn test_compare_across_numeric_types() {
+        let tuple = Tuple::new(vec![DBValue::Int(2), DBValue::Double(2.)]);
+        let compare = |op| Expr::Compare {
+            op,
+            left: Box::new(Expr::Column(0)),
+            right: Box::new(Expr::Column(1)),
+        };
+        assert_eq!(
+            compare(CompareOp::Eq).eval_bool(&tuple).unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            compare(CompareOp::Lte).eval_bool(&tuple).unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            compare(CompareOp::Neq).eval_bool(&tuple).unwrap(),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        let tuple = Tuple::new(vec![DBValue::Null, DBValue::Int(1)]);
+        let null = || Box::new(Expr::Literal(DBValue::Null));
+        let bool = |v| Box::new(Expr::Literal(DBValue::Bool(v)));
+        let eval = |expr: Expr| expr.eval_bool(&tuple).unwrap();
+        assert_eq!(eval(Expr::And(null(), bool(false))), Some(false));
+        assert_eq!(eval(Expr::And(null(), bool(true))), None);
+        assert_eq!(eval(Expr::Or(null(), bool(true))), Some(true));
+        assert_eq!(eval(Expr::Or(bool(false), null())), None);
+        assert_eq!(eval(Expr::Not(null())), None);
+        let compare = Expr::Compare {
+            op: CompareOp::Eq,
+            left: Box::new(Expr::Column(0)),
+            right: Box::new(Expr::Column(0)),
+        };
+        assert_eq!(eval(compare), None);
+        let is_null = |column, negated| Expr::IsNull {
+            operand: Box::new(Expr::Column(column)),
+            negated,
+        };
+        assert_eq!(eval(is_null(0, false)), Some(true));
+        assert_eq!(eval(is_null(1, false)), Some(false));
+        assert_eq!(eval(is_null(1, true)), Some(true));
+        assert_eq!(
+            binary(BinaryOp::Add, Expr::Column(0), Expr::Column(1))
+                .eval(&tuple)
+                .unwrap(),
+            DBValue::Null
+        );
+    }
+
+    #[test]
+    fn test_result_type() {
+        assert_eq!(
+            BinaryOp::Add.result_type(Some(DBType::Int), Some(DBType::Int)),
+            Some(DBType::Int)
+        );
+        assert_eq!(
+            BinaryOp::Add.result_type(Some(DBType::Int), Some(DBType::Double)),
+            Some(DBType::Double)
+        );
+        assert_eq!(
+            BinaryOp::Sub.result_type(Some(DBType::SmallInt), Some(DBType::BigInt)),
+            Some(DBType::BigInt)
+        );
+        assert_eq!(
+            BinaryOp::Mul.result_type(Some(DBType::BigInt), Some(DBType::Real)),
+            Some(DBType::Real)
+        );
+        assert_eq!(
+            BinaryOp::Add.result_type(Some(DBType::String), Some(DBType::Int)),
+            None
+        );
+        assert_eq!(
+            BinaryOp::Mul.result_type(None, Some(DBType::Double)),
+            Some(DBType::Double)
+        );
+        assert_eq!(
+            BinaryOp::Concat.result_type(Some(DBType::String), Some(DBType::Bool)),
+            Some(DBType::String)
+        );
+        assert_eq!(
+            BinaryOp::Concat.result_type(Some(DBType::Bytes), None),
+            Some(DBType::Bytes)
+        );
+        assert_eq!(
+            BinaryOp::Add.result_type(Some(DBType::Interval), Some(DBType::Date)),
+            Some(DBType::Timestamp)
+        );
+        assert_eq!(
+            BinaryOp::Sub.result_type(Some(DBType::Timestamp), Some(DBType::Date)),
+            Some(DBType::Interval)
+        );
+        assert_eq!(
+            BinaryOp::Sub.result_type(Some(DBType::Date), Some(DBType::SmallInt)),
+            Some(DBType::Date)
+        );
+        assert_eq!(
+            BinaryOp::Sub.result_type(Some(DBType::Interval), Some(DBType::Timestamp)),
+            None
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::ops::Bound;
+
+use crate::{
+    DB,
+    btree::BTree,
+    errors::{self, DBError},
+    expr::{CompareOp, Expr},
+    operations::{delete_tuples, referencing_tables, scan_tuples, tuple_matches, update_tuples},
+    sql::Query,
+    table::{ReferentialAction, Table},
+    tuple::Tuple,
+    values::DBValue,
+};
+
+/// Values of the columns of the tuple, or `None` if one of them is NULL, as a key with
+/// a NULL doesn't reference anything.
+fn key_values(tuple: &Tuple, columns: &[usize]) -> Option<Vec<DBValue>> {
+    let key = columns
+        .iter()
+        .map(|&column| tuple.values[column].clone())
+        .collect::<Vec<_>>();
+    (!key.iter().any(DBValue::is_null)).then_some(key)
+}
+
+/// `(columns) = (values)` for error messages.
+fn describe_key(table: &Table, columns: &[usize], key: &[DBValue]) -> String {
+    let columns = columns
+        .iter()
+        .map(|&column| table.columns()[column].name())
+        .collect::<Vec<_>>();
+    let values = key.iter().map(DBValue::to_string).collect::<Vec<_>>();
+    format!("({}) = ({})", columns.join(", "), values.join(", "))
+}
+
+/// Whether the table has a row with the key, looked up in the index of the PRIMARY KEY or
+/// UNIQUE constraint on the columns.
+fn key_exists(db: &DB, table: &Table, columns: &[usize], key: &[DBValue]) -> errors::Result<bool> {
+    let index = table
+        .indexes()
+        .iter()
+        .find(|index| {
+            index.constraint().is_some()
+                && index.columns().len() == columns.len()
+                && columns
+                    .iter()
+                    .all(|column| index.columns().contains(column))
+        })
+        .ok_or_else(|| {
+            DBError::Integrity(format!(
+                "Table {} has no unique index on the referenced columns",
+                table.name()
+            ))
+        })?;
+    // The key values in the order of the index columns.
+    let key = index
+        .columns()
+        .iter()
+        .map(|column| key[columns.iter().position(|c| c == column).unwrap()].clone())
+        .collect::<Vec<_>>();
+    let tree = BTree::open(&db.pager, index.get_file_path(&db.storage_dir))?;
+    let mut range = tree.range(Bound::Included(key.clone()), Bound::Included(key))?;
+    Ok(range.next().transpose()?.is_some())
+}
+
+/// Fails if the tuple references a key that doesn't exist. With the `old` tuple of an
+/// update, only the foreign keys whose values changed are checked.
+pub(crate) fn check_references(
+    db: &DB,
+    table: &Table,
+    tuple: &Tuple,
+    old: Option<&Tuple>,
+) -> errors::Result<()> {
+    for foreign_key in table.foreign_keys() {
+        let columns = table.column_positions(foreign_key.columns());
+        let Some(key) = key_values(tuple, &columns) else {
+            continue;
+        };
+        if let Some(old) = old
+            && key_values(old, &columns).as_ref() == Some(&key)
+        {
+            continue;
+        }
+        let parent = db.get_table_by_id(foreign_key.table_id())?;
+        let referenced = parent.column_positions(foreign_key.referenced());
+        if !key_exists(db, parent, &referenced, &key)? {
+            return Err(DBError::Integrity(format!(
+                "Key {} is not present in table {}, violates foreign key {}",
+                describe_key(table, &columns, &key),
+                parent.name(),
+                foreign_key.name()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Rows of the table with the key in the columns. They are looked up in an index on the
+/// columns if there is one, and read from the whole table otherwise.
+fn rows_with_key(
+    db: &DB,
+    table: &Table,
+    columns: &[usize],
+    key: &[DBValue],
+) -> errors::Result<Vec<(u32, u16, Tuple)>> {
+    let where_clause = columns
+        .iter()
+        .zip(key)
+        .map(|(&column, value)| Expr::Compare {
+            op: CompareOp::Eq,
+            left: Box::new(Expr::Column(column)),
+            right: Box::new(Expr::Literal(value.clone())),
+        })
+        .reduce(|left, right| Expr::And(Box::new(left), Box::new(right)));
+    let mut rows = vec![];
+    for row in scan_tuples(db, table, where_clause.as_ref())? {
+        let row = row?;
+        if tuple_matches(&row.2, &where_clause)? {
+            rows.push(row);
+        }
+    }
+    Ok(rows)
+}
+
+/// Applies the actions of the foreign keys referencing the keys of a deleted tuple, or of
+/// an updated one whose keys changed. `new` is the updated tuple, `None` for a delete.
+///
+/// Runs after the statement wrote all its tuples, so a key that another tuple has now is
+/// still referenced, and the rows the statement deleted don't reference anything.
+pub(crate) fn apply_actions(
+    db: &DB,
+    table: &Table,
+    old: &Tuple,
+    new: Option<&Tuple>,
+) -> errors::Result<()> {
+    for (child, foreign_key) in referencing_tables(db, table) {
+        let referenced = table.column_positions(foreign_key.referenced());
+        let Some(key) = key_values(old, &referenced) else {
+            continue;
+        };
+        let new_key = new.map(|new| {
+            referenced
+                .iter()
+                .map(|&column| new.values[column].clone())
+                .collect::<Vec<_>>()
+        });
+        if new_key.as_ref() == Some(&key) || key_exists(db, table, &referenced, &key)? {
+            continue;
+        }
+        let columns = child.column_positions(foreign_key.columns());
+        let rows = rows_with_key(db, child, &columns, &key)?;
+        if rows.is_empty() {
+            continue;
+        }
+        let action = match new {
+            Some(_) => foreign_key.on_update(),
+            None => foreign_key.on_delete(),
+        };
+        let values = match (action, new_key) {
+            (ReferentialAction::Restrict, _) => {
+                return Err(DBError::Integrity(format!(
+                    "Key {} is still referenced from table {}, violates foreign key {}",
+                    describe_key(table, &referenced, &key),
+                    child.name(),
+                    foreign_key.name()
+                )));
+            }
+            (ReferentialAction::Cascade, None) => {
+                delete_tuples(db, child, rows)?;
+                continue;
+            }
+            (ReferentialAction::Cascade, Some(new_key)) => new_key,
+            (ReferentialAction::SetNull, _) => vec![DBValue::Null; columns.len()],
+        };
+        let updates = rows
+            .into_iter()
+            .map(|(page_id, slot, tuple)| {
+                let mut updated = tuple.clone();
+                for (&column, value) in columns.iter().zip(&values) {
+                    updated.values[column] = value.clone();
+                }
+                (page_id, slot, tuple, updated)
+            })
+            .collect();
+        update_tuples(db, child, updates, &Query::column_checks(child, db.now)?)?;
+    }
+    Ok(())
+}
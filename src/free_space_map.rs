@@ -0,0 +1,135 @@
+use std::{
+    io::{self, Cursor},
+    path::PathBuf,
+};
+
+use crate::{
+    errors,
+    page::PAGE_SIZE,
+    pager::Pager,
+    serializer::{BinaryReader, BinaryWriter},
+};
+
+/// Number of `u16` entries in a page of the map.
+const ENTRIES_PER_PAGE: usize = PAGE_SIZE / 2;
+const ROOT_PAGE_ID: u32 = 0;
+
+/// Free space of every page of a table, stored in a file next to the table file so that
+/// inserts can find a page with enough room without reading the table.
+///
+/// The pages after the first one are leaves with the free bytes of [`ENTRIES_PER_PAGE`]
+/// table pages each. The first page has the largest entry of every leaf, so a search reads
+/// only the root and one leaf. Pages of the map that were never written have no free space,
+/// and so do table pages that are not in the map yet.
+pub(crate) struct FreeSpaceMap<'a> {
+    pager: &'a Pager,
+    file_path: PathBuf,
+}
+
+impl<'a> FreeSpaceMap<'a> {
+    pub fn new(pager: &'a Pager, file_path: PathBuf) -> Self {
+        Self { pager, file_path }
+    }
+
+    fn page_count(&self) -> io::Result<u32> {
+        match self.pager.page_count(&self.file_path) {
+            Ok(page_count) => Ok(page_count),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn read_entries(&self, fsm_page_id: u32) -> errors::Result<Vec<u16>> {
+        if fsm_page_id >= self.page_count()? {
+            return Ok(vec![0; ENTRIES_PER_PAGE]);
+        }
+        let data = self.pager.read_page(&self.file_path, fsm_page_id)?;
+        let mut reader = BinaryReader::new(Cursor::new(&*data));
+        Ok((0..ENTRIES_PER_PAGE)
+            .map(|_| reader.read_u16())
+            .collect::<io::Result<_>>()?)
+    }
+
+    fn write_entries(&self, fsm_page_id: u32, entries: &[u16]) -> errors::Result<()> {
+        // The file has no holes, so the pages before this one are written first.
+        for page_id in self.page_count()?..fsm_page_id {
+            self.pager.write_page(&self.file_path, page_id, vec![]);
+        }
+        let mut data = Vec::with_capacity(PAGE_SIZE);
+        let mut writer = BinaryWriter::new(&mut data);
+        for &entry in entries {
+            writer.write_u16(entry)?;
+        }
+        self.pager.write_page(&self.file_path, fsm_page_id, data);
+        Ok(())
+    }
+
+    /// Records the free space of a table page.
+    pub fn set(&self, page_id: u32, free_space: usize) -> errors::Result<()> {
+        let free_space = free_space as u16;
+        let leaf = page_id as usize / ENTRIES_PER_PAGE;
+        let leaf_page_id = leaf as u32 + 1;
+        let mut entries = self.read_entries(leaf_page_id)?;
+        let entry = &mut entries[page_id as usize % ENTRIES_PER_PAGE];
+        if *entry == free_space {
+            return Ok(());
+        }
+        *entry = free_space;
+        self.write_entries(leaf_page_id, &entries)?;
+
+        let leaf_max = entries.iter().copied().max().unwrap();
+        let mut root = self.read_entries(ROOT_PAGE_ID)?;
+        if root[leaf] != leaf_max {
+            root[leaf] = leaf_max;
+            self.write_entries(ROOT_PAGE_ID, &root)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the first table page with at least `needed` free bytes.
+    pub fn find(&self, needed: usize) -> errors::Result<Option<u32>> {
+        let root = self.read_entries(ROOT_PAGE_ID)?;
+        let Some(leaf) = root.iter().position(|&max| max as usize >= needed) else {
+            return Ok(None);
+        };
+        let entries = self.read_entries(leaf as u32 + 1)?;
+        let position = entries.iter().position(|&free| free as usize >= needed);
+        Ok(position.map(|position| (leaf * ENTRIES_PER_PAGE + position) as u32))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use temp_dir::TempDir;
+
+    use crate::pager::Pager;
+
+    use super::{ENTRIES_PER_PAGE, FreeSpaceMap};
+
+    #[test]
+    fn test_free_space_map() {
+        let dir = TempDir::new().unwrap();
+        let pager = Pager::new(dir.path().join("wal"));
+        let fsm = FreeSpaceMap::new(&pager, dir.path().join("test.fsm"));
+        assert_eq!(fsm.find(1).unwrap(), None);
+
+        let far = ENTRIES_PER_PAGE as u32 * 2 + 5;
+        fsm.set(far, 100).unwrap();
+        fsm.set(3, 200).unwrap();
+        assert_eq!(fsm.find(50).unwrap(), Some(3));
+        assert_eq!(fsm.find(150).unwrap(), Some(3));
+        assert_eq!(fsm.find(201).unwrap(), None);
+        fsm.set(3, 10).unwrap();
+        assert_eq!(fsm.find(50).unwrap(), Some(far));
+        // The largest entry of the leaf goes down too.
+        fsm.set(far, 0).unwrap();
+        assert_eq!(fsm.find(50).unwrap(), None);
+
+        pager.commit_statement().unwrap();
+        fsm.set(far + 1, 8000).unwrap();
+        assert_eq!(fsm.find(8000).unwrap(), Some(far + 1));
+        pager.rollback_statement();
+        assert_eq!(fsm.find(8000).unwrap(), None);
+        assert_eq!(fsm.find(5).unwrap(), Some(3));
+    }
+}
@@ -0,0 +1,231 @@
+use std::{ops::Bound, path::Path};
+
+use crate::{
+    btree::{BTree, BTreeRange},
+    errors,
+    expr::{CompareOp, Expr},
+    page_table::PageTable,
+    pager::Pager,
+    table::{IndexDef, Table},
+    tuple::Tuple,
+    values::DBValue,
+};
+
+/// `column op value` comparison of a WHERE clause conjunct.
+fn column_comparison(expr: &Expr) -> Option<(usize, CompareOp, &DBValue)> {
+    let Expr::Compare { op, left, right } = expr else {
+        return None;
+    };
+    match (left.as_ref(), right.as_ref()) {
+        // Comparisons with NULL are never true, the index can't help with them.
+        (Expr::Column(column), Expr::Literal(value)) if !value.is_null() => {
+            Some((*column, *op, value))
+        }
+        (Expr::Literal(value), Expr::Column(column)) if !value.is_null() => {
+            Some((*column, op.flipped(), value))
+        }
+        _ => None,
+    }
+}
+
+/// Range of an index containing all tuples that may match a WHERE clause. The tuples still
+/// have to be checked against the whole clause.
+#[derive(PartialEq, Debug)]
+pub(crate) struct IndexScan {
+    pub index: IndexDef,
+    pub lower: Bound<Vec<DBValue>>,
+    pub upper: Bound<Vec<DBValue>>,
+}
+
+impl IndexScan {
+    /// Chooses the index with the most columns restricted by the WHERE clause: equalities on
+    /// a prefix of the index columns, optionally followed by a range of the next column.
+    /// Returns `None` if no index restricts its first column.
+    pub fn choose(table: &Table, where_clause: Option<&Expr>) -> Option<Self> {
+        let comparisons = where_clause?
+            .conjuncts()
+            .into_iter()
+            .filter_map(column_comparison)
+            .collect::<Vec<_>>();
+        table
+            .indexes()
+            .iter()
+            .filter_map(|index| Self::for_index(index, &comparisons))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, scan)| scan)
+    }
+
+    /// Returns the scan of the index with its score, the number of columns with an
+    /// equality and whether there is a range.
+    fn for_index(
+        index: &IndexDef,
+        comparisons: &[(usize, CompareOp, &DBValue)],
+    ) -> Option<((usize, bool), Self)> {
+        let mut prefix = vec![];
+        for column in index.columns() {
+            let equal = comparisons
+                .iter()
+                .find(|(other, op, _)| other == column && *op == CompareOp::Eq);
+            match equal {
+                Some((_, _, value)) => prefix.push((*value).clone()),
+                None => break,
+            }
+        }
+        let mut lower = None;
+        let mut upper = None;
+        if let Some(column) = index.columns().get(prefix.len()) {
+            for (_, op, value) in comparisons.iter().filter(|(other, ..)| other == column) {
+                match op {
+                    CompareOp::Gt => lower.get_or_insert(Bound::Excluded(*value)),
+                    CompareOp::Gte => lower.get_or_insert(Bound::Included(*value)),
+                    CompareOp::Lt => upper.get_or_insert(Bound::Excluded(*value)),
+                    CompareOp::Lte => upper.get_or_insert(Bound::Included(*value)),
+                    _ => continue,
+                };
+            }
+        }
+        if prefix.is_empty() && lower.is_none() && upper.is_none() {
+            return None;
+        }
+        let score = (prefix.len(), lower.is_some() || upper.is_some());
+        let key_bound = |bound: Option<Bound<&DBValue>>| {
+            let with_prefix = |value: &DBValue| {
+                let mut key = prefix.clone();
+                key.push(value.clone());
+                key
+            };
+            match bound {
+                Some(Bound::Included(value)) => Bound::Included(with_prefix(value)),
+                Some(Bound::Excluded(value)) => Bound::Excluded(with_prefix(value)),
+                _ if prefix.is_empty() => Bound::Unbounded,
+                _ => Bound::Included(prefix.clone()),
+            }
+        };
+        let scan = Self {
+            index: index.clone(),
+            lower: key_bound(lower),
+            upper: key_bound(upper),
+        };
+        Some((score, scan))
+    }
+
+    /// Returns the tuples in the range in the index order.
+    pub fn rows<'a>(
+        self,
+        storage_dir: &Path,
+        pager: &'a Pager,
+        page_table: PageTable<'a>,
+    ) -> errors::Result<IndexRows<'a>> {
+        let tree = BTree::open(pager, self.index.get_file_path(storage_dir))?;
+        Ok(IndexRows {
+            tuple_ids: tree.range(self.lower, self.upper)?,
+            page_table,
+        })
+    }
+}
+
+pub(crate) struct IndexRows<'a> {
+    tuple_ids: BTreeRange<'a>,
+    page_table: PageTable<'a>,
+}
+
+impl Iterator for IndexRows<'_> {
+    type Item = errors::Result<(u32, u16, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (page_id, slot) = match self.tuple_ids.next()? {
+            Ok(tuple_id) => tuple_id,
+            Err(err) => return Some(Err(err)),
+        };
+        let tuple = self.page_table.get_tuple(page_id, slot);
+        Some(tuple.map(|tuple| (page_id, slot, tuple)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Bound;
+
+    use crate::{
+        expr::{CompareOp, Expr},
+        table::{IndexDef, Table, test::sample_table},
+        values::DBValue,
+    };
+
+    use super::IndexScan;
+
+    fn compare(column: usize, op: CompareOp, value: DBValue) -> Expr {
+        Expr::Compare {
+            op,
+            left: Box::new(Expr::Column(column)),
+            right: Box::new(Expr::Literal(value)),
+        }
+    }
+
+    fn and(left: Expr, right: Expr) -> Expr {
+        Expr::And(Box::new(left), Box::new(right))
+    }
+
+    #[test]
+    fn test_choose_index() {
+        let mut table: Table = sample_table();
+        table.add_index(IndexDef::new(10, "by_id".to_owned(), vec![0]));
+        table.add_index(IndexDef::new(11, "by_name_height".to_owned(), vec![1, 2]));
+        let choose = |expr: Expr| {
+            IndexScan::choose(&table, Some(&expr))
+                .map(|scan| (scan.index.name().to_owned(), scan.lower, scan.upper))
+        };
+        let name = || DBValue::String("meow".to_owned());
+
+        assert_eq!(
+            choose(compare(0, CompareOp::Eq, DBValue::Int(5))),
+            Some((
+                "by_id".to_owned(),
+                Bound::Included(vec![DBValue::Int(5)]),
+                Bound::Included(vec![DBValue::Int(5)])
+            ))
+        );
+        // The literal may be on the left.
+        let flipped = Expr::Compare {
+            op: CompareOp::Lt,
+            left: Box::new(Expr::Literal(DBValue::Int(3))),
+            right: Box::new(Expr::Column(0)),
+        };
+        assert_eq!(
+            choose(and(flipped, compare(3, CompareOp::Eq, DBValue::Bool(true)))),
+            Some((
+                "by_id".to_owned(),
+                Bound::Excluded(vec![DBValue::Int(3)]),
+                Bound::Unbounded
+            ))
+        );
+        // The equality on the first column of the composite index and the range on the
+        // second one beat the equality on id.
+        let expr = and(
+            and(
+                compare(0, CompareOp::Eq, DBValue::Int(5)),
+                compare(2, CompareOp::Lte, DBValue::Double(100.)),
+            ),
+            compare(1, CompareOp::Eq, name()),
+        );
+        assert_eq!(
+            choose(expr),
+            Some((
+                "by_name_height".to_owned(),
+                Bound::Included(vec![name()]),
+                Bound::Included(vec![name(), DBValue::Double(100.)])
+            ))
+        );
+        // Only the second column of the composite index, OR and NULL can't be used.
+        assert_eq!(choose(compare(2, CompareOp::Gt, DBValue::Double(1.))), None);
+        assert_eq!(
+            choose(Expr::Or(
+                Box::new(compare(0, CompareOp::Eq, DBValue::Int(1))),
+                Box::new(compare(0, CompareOp::Eq, DBValue::Int(2)))
+            )),
+            None
+        );
+        assert_eq!(choose(compare(0, CompareOp::Eq, DBValue::Null)), None);
+        assert_eq!(choose(compare(0, CompareOp::Neq, DBValue::Int(1))), None);
+    }
+}
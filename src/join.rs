@@ -0,0 +1,562 @@
+use std::{
+    collections::{HashMap, HashSet, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    iter, mem,
+    path::PathBuf,
+    vec,
+};
+
+use crate::{
+    Rows, errors,
+    expr::{CompareOp, Expr},
+    operations::tuple_matches,
+    page_table::{PageTable, TableIterator},
+    serializer::BinaryWriter,
+    spill::{SpillFile, row_memory_size},
+    tuple::Tuple,
+    values::DBValue,
+};
+
+/// Number of partitions the inputs are split into when the right side doesn't fit in memory.
+const SPILL_PARTITIONS: u64 = 16;
+/// Partitions are split again at most this many times, after that they are joined in memory.
+const MAX_SPILL_DEPTH: u32 = 4;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub(crate) enum JoinKind {
+    Inner,
+    /// Left outer join, left rows without a match are joined with NULLs.
+    Left,
+    /// Right outer join, right tuples without a match are joined with NULLs.
+    Right,
+    Cross,
+}
+
+/// Join of the rows of `left_width` columns with the tuples of a table of `right_width` columns.
+pub(crate) struct JoinSpec {
+    pub kind: JoinKind,
+    /// Condition evaluated on the joined rows, `None` matching all of them.
+    pub on: Option<Expr>,
+    pub left_width: usize,
+    pub right_width: usize,
+}
+
+impl JoinSpec {
+    /// Concatenates the rows, a missing side is filled with NULLs.
+    fn join_rows(&self, left: Option<&Tuple>, right: Option<&Tuple>) -> Tuple {
+        let side = |row: Option<&Tuple>, width| match row {
+            Some(row) => row.values.clone(),
+            None => vec![DBValue::Null; width],
+        };
+        let mut values = side(left, self.left_width);
+        values.extend(side(right, self.right_width));
+        Tuple::new(values)
+    }
+
+    /// Finds the `left = right` conditions of the join where each side only uses the columns
+    /// of one input. Returns the key expressions evaluated on the left rows and on the right
+    /// tuples, empty if there are none.
+    pub fn equi_keys(&self) -> (Vec<Expr>, Vec<Expr>) {
+        let mut left_keys = vec![];
+        let mut right_keys = vec![];
+        let Some(on) = &self.on else {
+            return (left_keys, right_keys);
+        };
+        // Whether the expression only uses left columns, `None` if it uses both sides or none.
+        let is_left = |expr: &Expr| {
+            let columns = expr.columns();
+            if columns.is_empty() {
+                None
+            } else if columns.iter().all(|&i| i < self.left_width) {
+                Some(true)
+            } else if columns.iter().all(|&i| i >= self.left_width) {
+                Some(false)
+            } else {
+                None
+            }
+        };
+        for conjunct in on.conjuncts() {
+            let Expr::Compare {
+                op: CompareOp::Eq,
+                left,
+                right,
+            } = conjunct
+            else {
+                continue;
+            };
+            let (left, right) = match (is_left(left), is_left(right)) {
+                (Some(true), Some(false)) => (left, right),
+                (Some(false), Some(true)) => (right, left),
+                _ => continue,
+            };
+            left_keys.push((**left).clone());
+            right_keys.push(right.map_columns(&|i| i - self.left_width));
+        }
+        (left_keys, right_keys)
+    }
+}
+
+/// Joins each left row with the right tuples by scanning the right table again for every left
+/// row. Works with any join condition.
+pub(crate) struct NestedLoopJoin<'a> {
+    spec: JoinSpec,
+    left: Rows<'a>,
+    left_done: bool,
+    right: PageTable<'a>,
+    /// Left row being joined, the scan of the right table for it and whether it matched.
+    current: Option<(Tuple, TableIterator<'a, PageTable<'a>>, bool)>,
+    /// Right tuples that matched a left row, only tracked for a right join.
+    matched: HashSet<(u32, u16)>,
+    /// Scan for the right tuples without a match, started after the left rows for a right join.
+    unmatched: Option<TableIterator<'a, PageTable<'a>>>,
+}
+
+impl<'a> NestedLoopJoin<'a> {
+    pub fn new(spec: JoinSpec, left: Rows<'a>, right: PageTable<'a>) -> Self {
+        Self {
+            spec,
+            left,
+            left_done: false,
+            right,
+            current: None,
+            matched: HashSet::new(),
+            unmatched: None,
+        }
+    }
+}
+
+impl Iterator for NestedLoopJoin<'_> {
+    type Item = errors::Result<Tuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((left, scan, found)) = &mut self.current {
+                match scan.next() {
+                    Some(Ok((page_id, offset, right))) => {
+                        let row = self.spec.join_rows(Some(left), Some(&right));
+                        match tuple_matches(&row, &self.spec.on) {
+                            Ok(true) => {
+                                *found = true;
+                                if self.spec.kind == JoinKind::Right {
+                                    self.matched.insert((page_id, offset));
+                                }
+                                return Some(Ok(row));
+                            }
+                            Ok(false) => continue,
+                            Err(err) => return Some(Err(err)),
+                        }
+                    }
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => {
+                        let (left, _, found) = self.current.take().unwrap();
+                        if !found && self.spec.kind == JoinKind::Left {
+                            return Some(Ok(self.spec.join_rows(Some(&left), None)));
+                        }
+                        continue;
+                    }
+                }
+            }
+            if self.left_done {
+                break;
+            }
+            match self.left.next() {
+                Some(Ok(left)) => {
+                    self.current = Some((left, self.right.clone().into_iter(), false));
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.left_done = true;
+                    if self.spec.kind == JoinKind::Right {
+                        self.unmatched = Some(self.right.clone().into_iter());
+                    }
+                }
+            }
+        }
+        let unmatched = self.unmatched.as_mut()?;
+        loop {
+            match unmatched.next()? {
+                Ok((page_id, offset, _)) if self.matched.contains(&(page_id, offset)) => {}
+                Ok((_, _, right)) => return Some(Ok(self.spec.join_rows(None, Some(&right)))),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Encodes the key of a row for hashing, `None` if any of the key values is NULL, since NULL
+/// doesn't equal anything.
+fn encode_key(keys: &[Expr], row: &Tuple) -> errors::Result<Option<Vec<u8>>> {
+    let mut writer = BinaryWriter::new(vec![]);
+    for key in keys {
+        let value = key.eval(row)?;
+        if value.is_null() {
+            return Ok(None);
+        }
+        // Writing to a vector can't fail.
+        value.write_key(&mut writer).unwrap();
+    }
+    Ok(Some(writer.into_inner()))
+}
+
+/// Hash join on the equality conditions of the join. The right tuples are loaded into a hash
+/// table by their key values and each left row is joined with the tuples having the same key,
+/// checking the whole join condition on the joined rows.
+///
+/// If the right tuples exceed `memory_limit` bytes, both inputs are written to temp files
+/// partitioned by the key hash and the pairs of partitions are joined one by one.
+pub(crate) struct HashJoin {
+    spec: JoinSpec,
+    left_keys: Vec<Expr>,
+    right_keys: Vec<Expr>,
+    temp_dir: PathBuf,
+    memory_limit: usize,
+}
+
+impl HashJoin {
+    /// `left_keys` and `right_keys` are the keys returned by [`JoinSpec::equi_keys`].
+    pub fn new(
+        spec: JoinSpec,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+        temp_dir: PathBuf,
+        memory_limit: usize,
+    ) -> Self {
+        Self {
+            spec,
+            left_keys,
+            right_keys,
+            temp_dir,
+            memory_limit,
+        }
+    }
+
+    pub fn execute<'a>(self, left: Rows<'a>, right: Rows<'a>) -> errors::Result<HashJoinRows<'a>> {
+        let mut rows = HashJoinRows {
+            join: self,
+            build: None,
+            probe: Box::new(iter::empty()),
+            probe_done: false,
+            current: None,
+            unmatched: 0,
+            partitions: vec![],
+        };
+        rows.start(left, right, 0)?;
+        Ok(rows)
+    }
+
+    fn partition(&self, key: &[u8], depth: u32) -> usize {
+        let mut hasher = DefaultHasher::new();
+        depth.hash(&mut hasher);
+        key.hash(&mut hasher);
+        (hasher.finish() % SPILL_PARTITIONS) as usize
+    }
+
+    /// Writes the rows to temp files by the hash of their key. Rows with NULL keys don't match
+    /// anything and all go to the first partition.
+    fn write_partitions(
+        &self,
+        keys: &[Expr],
+        rows: impl Iterator<Item = errors::Result<Tuple>>,
+        depth: u32,
+    ) -> errors::Result<Vec<Option<SpillFile>>> {
+        let mut partitions: Vec<Option<SpillFile>> = vec![];
+        partitions.resize_with(SPILL_PARTITIONS as usize, || None);
+        for row in rows {
+            let row = row?;
+            let partition = match encode_key(keys, &row)? {
+                Some(key) => self.partition(&key, depth),
+                None => 0,
+            };
+            let file = match &mut partitions[partition] {
+                Some(file) => file,
+                file => file.insert(SpillFile::create(&self.temp_dir)?),
+            };
+            file.write_row(&row)?;
+        }
+        Ok(partitions)
+    }
+}
+
+/// Right tuples of the partition being joined.
+struct Build {
+    rows: Vec<Tuple>,
+    /// Indexes of the rows by their encoded key. Rows with NULL keys are not in the index.
+    index: HashMap<Vec<u8>, Vec<usize>>,
+    /// Whether each row matched a left row, only used for a right join.
+    matched: Vec<bool>,
+}
+
+pub(crate) struct HashJoinRows<'a> {
+    join: HashJoin,
+    build: Option<Build>,
+    probe: Rows<'a>,
+    probe_done: bool,
+    /// Left row being joined, the right rows with its key and whether any of them matched.
+    current: Option<(Tuple, vec::IntoIter<usize>, bool)>,
+    /// Position of the search for the right rows without a match, for a right join.
+    unmatched: usize,
+    /// Spilled pairs of left and right partitions with their depth. A partition is `None` if
+    /// no rows were written to it.
+    partitions: Vec<(Option<SpillFile>, Option<SpillFile>, u32)>,
+}
+
+impl<'a> HashJoinRows<'a> {
+    /// Loads the right rows into memory to join the left rows with them, or partitions both
+    /// inputs if the right rows don't fit.
+    fn start(&mut self, left: Rows<'a>, mut right: Rows<'a>, depth: u32) -> errors::Result<()> {
+        let join = &self.join;
+        let mut build = Build {
+            rows: vec![],
+            index: HashMap::new(),
+            matched: vec![],
+        };
+        let mut memory_used = 0;
+        while let Some(row) = right.next() {
+            let row = row?;
+            memory_used += row_memory_size(&row);
+            if let Some(key) = encode_key(&join.right_keys, &row)? {
+                memory_used += key.len();
+                build.index.entry(key).or_default().push(build.rows.len());
+            }
+            build.rows.push(row);
+            if memory_used > join.memory_limit && depth < MAX_SPILL_DEPTH {
+                let right_rows = mem::take(&mut build.rows).into_iter().map(Ok).chain(right);
+                let right_partitions =
+                    join.write_partitions(&join.right_keys, right_rows, depth)?;
+                let left_partitions = join.write_partitions(&join.left_keys, left, depth)?;
+                let kind = join.spec.kind;
+                // Skip the pairs that can't produce any rows.
+                let pairs = left_partitions
+                    .into_iter()
+                    .zip(right_partitions)
+                    .filter(|(left, right)| match kind {
+                        JoinKind::Inner | JoinKind::Cross => left.is_some() && right.is_some(),
+                        JoinKind::Left => left.is_some(),
+                        JoinKind::Right => right.is_some(),
+                    })
+                    .map(|(left, right)| (left, right, depth + 1));
+                self.partitions.extend(pairs);
+                return Ok(());
+            }
+        }
+        build.matched = vec![false; build.rows.len()];
+        self.build = Some(build);
+        self.probe = left;
+        self.probe_done = false;
+        self.current = None;
+        self.unmatched = 0;
+        Ok(())
+    }
+
+    fn next_partition(&mut self) -> errors::Result<bool> {
+        let Some((left, right, depth)) = self.partitions.pop() else {
+            return Ok(false);
+        };
+        let rows = |file: Option<SpillFile>| -> errors::Result<Rows<'a>> {
+            Ok(match file {
+                Some(file) => Box::new(file.into_rows()?),
+                None => Box::new(iter::empty()),
+            })
+        };
+        self.start(rows(left)?, rows(right)?, depth)?;
+        Ok(true)
+    }
+
+    /// Returns the next joined row of the partition in memory.
+    fn next_in_partition(&mut self) -> Option<errors::Result<Tuple>> {
+        let spec = &self.join.spec;
+        let build = self.build.as_mut()?;
+        loop {
+            if let Some((left, matches, found)) = &mut self.current {
+                for index in matches.by_ref() {
+                    let row = spec.join_rows(Some(left), Some(&build.rows[index]));
+                    match tuple_matches(&row, &spec.on) {
+                        Ok(true) => {
+                            *found = true;
+                            build.matched[index] = true;
+                            return Some(Ok(row));
+                        }
+                        Ok(false) => {}
+                        Err(err) => return Some(Err(err)),
+                    }
+                }
+                let (left, _, found) = self.current.take().unwrap();
+                if !found && spec.kind == JoinKind::Left {
+                    return Some(Ok(spec.join_rows(Some(&left), None)));
+                }
+            }
+            if self.probe_done {
+                break;
+            }
+            match self.probe.next() {
+                Some(Ok(left)) => {
+                    let matches = match encode_key(&self.join.left_keys, &left) {
+                        Ok(Some(key)) => build.index.get(&key).cloned().unwrap_or_default(),
+                        Ok(None) => vec![],
+                        Err(err) => return Some(Err(err)),
+                    };
+                    self.current = Some((left, matches.into_iter(), false));
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None => self.probe_done = true,
+            }
+        }
+        if spec.kind == JoinKind::Right {
+            while self.unmatched < build.rows.len() {
+                let index = self.unmatched;
+                self.unmatched += 1;
+                if !build.matched[index] {
+                    return Some(Ok(spec.join_rows(None, Some(&build.rows[index]))));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for HashJoinRows<'_> {
+    type Item = errors::Result<Tuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.next_in_partition() {
+                if row.is_err() {
+                    self.build = None;
+                    self.partitions.clear();
+                }
+                return Some(row);
+            }
+            self.build = None;
+            match self.next_partition() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(err) => {
+                    self.partitions.clear();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use temp_dir::TempDir;
+
+    use crate::{
+        Rows,
+        expr::{CompareOp, Expr},
+        tuple::Tuple,
+        values::DBValue,
+    };
+
+    use super::{HashJoin, JoinKind, JoinSpec};
+
+    fn int_rows(values: &[Option<i32>]) -> Rows<'static> {
+        let rows = values
+            .iter()
+            .map(|value| Ok(Tuple::new(vec![value.map_or(DBValue::Null, DBValue::Int)])))
+            .collect::<Vec<_>>();
+        Box::new(rows.into_iter())
+    }
+
+    fn hash_join(
+        kind: JoinKind,
+        left: &[Option<i32>],
+        right: &[Option<i32>],
+        memory_limit: usize,
+    ) -> Vec<(Option<i32>, Option<i32>)> {
+        let dir = TempDir::new().unwrap();
+        let spec = JoinSpec {
+            kind,
+            on: Some(Expr::Compare {
+                op: CompareOp::Eq,
+                left: Box::new(Expr::Column(1)),
+                right: Box::new(Expr::Column(0)),
+            }),
+            left_width: 1,
+            right_width: 1,
+        };
+        let (left_keys, right_keys) = spec.equi_keys();
+        assert_eq!(left_keys, [Expr::Column(0)]);
+        assert_eq!(right_keys, [Expr::Column(0)]);
+        let join = HashJoin::new(
+            spec,
+            left_keys,
+            right_keys,
+            dir.path().to_owned(),
+            memory_limit,
+        );
+        let int = |value: &DBValue| match value {
+            DBValue::Int(v) => Some(*v),
+            _ => None,
+        };
+        let mut rows = join
+            .execute(int_rows(left), int_rows(right))
+            .unwrap()
+            .map(|row| {
+                let row = row.unwrap();
+                (int(&row.values[0]), int(&row.values[1]))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(dir.path().read_dir().unwrap().count(), 0);
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn test_hash_join_kinds() {
+        let left = [Some(1), Some(2), Some(2), None];
+        let right = [Some(2), Some(3), None];
+        assert_eq!(
+            hash_join(JoinKind::Inner, &left, &right, 1 << 20),
+            [(Some(2), Some(2)), (Some(2), Some(2))]
+        );
+        assert_eq!(
+            hash_join(JoinKind::Left, &left, &right, 1 << 20),
+            [
+                (None, None),
+                (Some(1), None),
+                (Some(2), Some(2)),
+                (Some(2), Some(2))
+            ]
+        );
+        assert_eq!(
+            hash_join(JoinKind::Right, &left, &right, 1 << 20),
+            [
+                (None, None),
+                (None, Some(3)),
+                (Some(2), Some(2)),
+                (Some(2), Some(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash_join_spills_partitions() {
+        let left = (0..2000).map(|i| Some(i % 700)).collect::<Vec<_>>();
+        let right = (0..1000)
+            .map(|i| (i % 10 != 0).then_some(i))
+            .collect::<Vec<_>>();
+        let mut expected = vec![];
+        for l in &left {
+            let r = right.iter().find(|r| *r == l);
+            expected.push((*l, r.copied().flatten()));
+        }
+        for r in &right {
+            if !left.contains(r) {
+                expected.push((None, *r));
+            }
+        }
+        expected.sort();
+        // Small enough to split the right side more than once.
+        let mut full = hash_join(JoinKind::Left, &left, &right, 500);
+        full.extend(
+            hash_join(JoinKind::Right, &left, &right, 500)
+                .into_iter()
+                .filter(|(l, _)| l.is_none()),
+        );
+        full.sort();
+        assert_eq!(full, expected);
+    }
+}
@@ -1,14 +1,31 @@
+mod aggregate;
+mod btree;
+mod buffer_pool;
+mod datetime;
 mod db;
 pub mod errors;
+mod expr;
+mod foreign_key;
+mod free_space_map;
+mod index;
+mod join;
+mod numeric;
 mod operations;
 mod page;
 mod page_table;
+mod pager;
+mod sequence;
 mod serializer;
+mod sort;
+mod spill;
 mod sql;
 mod table;
+mod toast;
 mod tuple;
 mod utils;
 mod values;
+mod wal;
 
-pub use db::DB;
-pub use tuple::Tuple;
\ No newline at end of file
+pub use buffer_pool::{BufferPoolStats, EvictionPolicy};
+pub use db::{DB, QueryResult, Rows};
+pub use tuple::Tuple;
@@ -0,0 +1,354 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    io::{self, Read, Write},
+    ops::Neg,
+    str::FromStr,
+};
+
+use crate::serializer::{BinaryReader, BinaryWriter};
+
+/// Maximum number of digits of a numeric, and of digits after its decimal point.
+pub const MAX_PRECISION: u8 = 38;
+
+/// Minimum number of digits after the decimal point of a quotient.
+const DIVISION_SCALE: u8 = 16;
+
+/// Numbers have fewer than [`MAX_PRECISION`] digits, so their magnitude is below this.
+const LIMIT: i128 = 10_i128.pow(MAX_PRECISION as u32);
+
+fn power_of_ten(exponent: u8) -> Option<i128> {
+    10_i128.checked_pow(exponent as u32)
+}
+
+/// `n / d` rounded half away from zero.
+fn div_round(n: i128, d: i128) -> i128 {
+    let (quotient, remainder) = (n / d, n % d);
+    // `2 * |remainder| >= |d|` without overflowing.
+    if remainder.unsigned_abs() >= d.unsigned_abs() - remainder.unsigned_abs() {
+        quotient + n.signum() * d.signum()
+    } else {
+        quotient
+    }
+}
+
+/// Exact decimal number for `numeric` values: an integer of at most [`MAX_PRECISION`]
+/// digits, of which the last `scale` are after the decimal point.
+#[derive(Debug, Clone, Copy)]
+pub struct Numeric {
+    value: i128,
+    scale: u8,
+}
+
+impl Numeric {
+    /// `None` if the number has too many digits.
+    pub fn new(value: i128, scale: u8) -> Option<Self> {
+        (value.abs() < LIMIT && scale <= MAX_PRECISION).then_some(Self { value, scale })
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        Self {
+            value: value as i128,
+            scale: 0,
+        }
+    }
+
+    /// The shortest decimal that converts back to the float, `None` for infinities, NaN and
+    /// numbers out of range.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        // Floats are displayed without an exponent.
+        value.to_string().parse().ok()
+    }
+
+    pub fn to_f64(self) -> f64 {
+        // Parsing the decimal rounds it correctly.
+        self.to_string().parse().unwrap()
+    }
+
+    /// The number if it's an integer that fits in `i64`.
+    pub fn to_i64(self) -> Option<i64> {
+        let unit = power_of_ten(self.scale)?;
+        (self.value % unit == 0)
+            .then(|| i64::try_from(self.value / unit).ok())
+            .flatten()
+    }
+
+    pub fn scale(self) -> u8 {
+        self.scale
+    }
+
+    /// The number rounded half away from zero, or padded with zeros, to `scale` digits after
+    /// the decimal point. `None` if it gets too many digits.
+    pub fn with_scale(self, scale: u8) -> Option<Self> {
+        if scale >= self.scale {
+            Self::new(self.scaled_value(scale)?, scale)
+        } else {
+            let unit = power_of_ten(self.scale - scale)?;
+            Self::new(div_round(self.value, unit), scale)
+        }
+    }
+
+    /// Whether the number has at most `precision` digits.
+    pub fn fits_precision(self, precision: u8) -> bool {
+        power_of_ten(precision).is_none_or(|limit| self.value.abs() < limit)
+    }
+
+    /// The value as an integer of `scale` digits after the decimal point, which must not be
+    /// less than the number's scale.
+    fn scaled_value(self, scale: u8) -> Option<i128> {
+        self.value.checked_mul(power_of_ten(scale - self.scale)?)
+    }
+
+    /// The values of both numbers with the larger of their scales.
+    fn aligned(self, other: Self) -> Option<(i128, i128, u8)> {
+        let scale = self.scale.max(other.scale);
+        Some((self.scaled_value(scale)?, other.scaled_value(scale)?, scale))
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        Self::new(a.checked_add(b)?, scale)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        Self::new(a.checked_sub(b)?, scale)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        let product = Self {
+            value: self.value.checked_mul(other.value)?,
+            scale: self.scale + other.scale,
+        };
+        product.with_scale(product.scale.min(MAX_PRECISION))
+    }
+
+    /// The quotient with at least [`DIVISION_SCALE`] digits after the decimal point, fewer
+    /// if the integer part leaves no room for them. `None` when dividing by zero.
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.value == 0 {
+            return None;
+        }
+        let min_scale = self.scale.max(other.scale);
+        (min_scale..=min_scale.max(DIVISION_SCALE))
+            .rev()
+            .find_map(|scale| {
+                // `self * 10^(scale + other.scale) / other` has `scale` digits after the point.
+                let dividend = self.scaled_value(scale + other.scale)?;
+                Self::new(div_round(dividend, other.value), scale)
+            })
+    }
+
+    /// The remainder with the sign of `self`, `None` when dividing by zero.
+    pub fn checked_rem(self, other: Self) -> Option<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        Self::new(a.checked_rem(b)?, scale)
+    }
+
+    /// The same number without trailing zeros after the decimal point.
+    pub fn normalized(self) -> Self {
+        let mut number = self;
+        while number.scale > 0 && number.value % 10 == 0 {
+            number.value /= 10;
+            number.scale -= 1;
+        }
+        number
+    }
+
+    pub fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        let scale = reader.read_u8()?;
+        let mut buffer = [0; 16];
+        reader.read_exact(&mut buffer)?;
+        Self::new(i128::from_be_bytes(buffer), scale)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid numeric"))
+    }
+
+    pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_u8(self.scale)?;
+        writer.write_all(&self.value.to_be_bytes())
+    }
+}
+
+impl Neg for Numeric {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            value: -self.value,
+            scale: self.scale,
+        }
+    }
+}
+
+impl Ord for Numeric {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.aligned(*other) {
+            Some((a, b, _)) => a.cmp(&b),
+            // Only the number with the larger magnitude can overflow.
+            None if self.scale < other.scale => self.value.signum().cmp(&0),
+            None => 0.cmp(&other.value.signum()),
+        }
+    }
+}
+
+impl PartialOrd for Numeric {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Numbers are equal whatever their scales, `1.50 = 1.5`.
+impl PartialEq for Numeric {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Numeric {}
+
+impl Display for Numeric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scale = self.scale as usize;
+        let digits = format!("{:0>1$}", self.value.unsigned_abs(), scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        let sign = if self.value < 0 { "-" } else { "" };
+        match fraction {
+            "" => write!(f, "{}{}", sign, integer),
+            fraction => write!(f, "{}{}.{}", sign, integer, fraction),
+        }
+    }
+}
+
+/// Parses `[-]digits[.digits]`. Digits after the decimal point beyond [`MAX_PRECISION`] are
+/// rounded.
+impl FromStr for Numeric {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let is_digits = |part: &str| part.bytes().all(|c| c.is_ascii_digit());
+        if integer.len() + fraction.len() == 0 || !is_digits(integer) || !is_digits(fraction) {
+            return Err(());
+        }
+        let (fraction, rest) = fraction.split_at(fraction.len().min(MAX_PRECISION as usize));
+        let mut value = format!("0{}{}", integer, fraction)
+            .parse::<i128>()
+            .map_err(|_| ())?;
+        if rest.starts_with(['5', '6', '7', '8', '9']) {
+            value += 1;
+        }
+        let value = if negative { -value } else { value };
+        Self::new(value, fraction.len() as u8).ok_or(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+
+    use super::Numeric;
+
+    fn numeric(s: &str) -> Numeric {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in [
+            "0",
+            "12.50",
+            "-0.05",
+            "99999999999999999999999999999999999999",
+        ] {
+            assert_eq!(numeric(s).to_string(), s);
+        }
+        assert_eq!(numeric(".5").to_string(), "0.5");
+        assert_eq!(numeric("-007.0").to_string(), "-7.0");
+        assert_eq!(Numeric::from_f64(19.99).unwrap().to_string(), "19.99");
+        assert_eq!(Numeric::from_f64(1e-40).unwrap().scale(), 38);
+        for s in [
+            "",
+            ".",
+            "1.2.3",
+            "1e5",
+            "--1",
+            "100000000000000000000000000000000000000",
+        ] {
+            assert!(s.parse::<Numeric>().is_err(), "{}", s);
+        }
+        assert_eq!(Numeric::from_f64(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(
+            numeric("0.1").checked_add(numeric("0.2")),
+            Some(numeric("0.3"))
+        );
+        assert_eq!(
+            numeric("10.25")
+                .checked_sub(numeric("0.255"))
+                .unwrap()
+                .to_string(),
+            "9.995"
+        );
+        assert_eq!(
+            numeric("1.5")
+                .checked_mul(numeric("-0.20"))
+                .unwrap()
+                .to_string(),
+            "-0.300"
+        );
+        assert_eq!(
+            numeric("1").checked_div(numeric("3")).unwrap().to_string(),
+            "0.3333333333333333"
+        );
+        assert_eq!(
+            numeric("2.00")
+                .checked_div(numeric("3"))
+                .unwrap()
+                .to_string(),
+            "0.6666666666666667"
+        );
+        // Large quotients get fewer digits after the decimal point.
+        assert_eq!(
+            numeric("1000000000000000000000000000").checked_div(numeric("3")),
+            Some(numeric("333333333333333333333333333.33333333333"))
+        );
+        assert_eq!(numeric("1").checked_div(numeric("0.00")), None);
+        assert_eq!(
+            numeric("-7.5")
+                .checked_rem(numeric("2"))
+                .unwrap()
+                .to_string(),
+            "-1.5"
+        );
+        let max = numeric("99999999999999999999999999999999999999");
+        assert_eq!(max.checked_add(numeric("1")), None);
+        assert_eq!(max.checked_mul(numeric("1.5")), None);
+    }
+
+    #[test]
+    fn test_scale_and_compare() {
+        assert_eq!(numeric("2.345").with_scale(2).unwrap().to_string(), "2.35");
+        assert_eq!(numeric("-2.345").with_scale(0).unwrap().to_string(), "-2");
+        assert_eq!(numeric("2.5").with_scale(3).unwrap().to_string(), "2.500");
+        assert!(numeric("999.99").fits_precision(5));
+        assert!(!numeric("1000.00").fits_precision(5));
+        assert_eq!(numeric("1.50"), numeric("1.5"));
+        assert!(numeric("-0.001") < numeric("0"));
+        // Comparing needs more digits than a numeric has.
+        let large = numeric("99999999999999999999999999999999999999");
+        let small = numeric("0.00000000000000000000000000000000000001");
+        assert_eq!(large.cmp(&small), Ordering::Greater);
+        assert_eq!(small.cmp(&large), Ordering::Less);
+        assert_eq!((-large).cmp(&small), Ordering::Less);
+        assert_eq!(numeric("12.300").normalized().to_string(), "12.3");
+        assert_eq!(numeric("5.00").to_i64(), Some(5));
+        assert_eq!(numeric("5.01").to_i64(), None);
+    }
+}
@@ -1,21 +1,29 @@
 use crate::{
-    DB,
+    DB, Rows,
+    aggregate::HashAggregate,
+    btree::{BTree, check_entry_size},
     errors::{self, DBError},
-    page_table::{PageTable, TableIterator},
-    sql::WhereClause,
-    table::{ColumnDef, Table},
+    expr::Expr,
+    foreign_key::{apply_actions, check_references},
+    index::IndexScan,
+    join::{HashJoin, JoinSpec, NestedLoopJoin},
+    page_table::PageTable,
+    sequence::Sequence,
+    sort::Sorter,
+    sql::{
+        Aggregation, ColumnCheck, FromPlan, JoinPlan, KeyDef, Select, TableChange,
+        rename_column_references, uses_column,
+    },
+    table::{ColumnDef, ForeignKey, IndexDef, Table},
     tuple::Tuple,
     values::DBValue,
 };
 
-fn tuple_matches(tuple: &Tuple, where_clause: &WhereClause) -> bool {
+pub(crate) fn tuple_matches(tuple: &Tuple, where_clause: &Option<Expr>) -> errors::Result<bool> {
     match where_clause {
-        WhereClause::Eq(col_index, value) => &tuple.values[*col_index] == value,
-        WhereClause::Neq(col_index, value) => &tuple.values[*col_index] != value,
-        WhereClause::Lt(col_index, value) => &tuple.values[*col_index] < value,
-        WhereClause::Lte(col_index, value) => &tuple.values[*col_index] <= value,
-        WhereClause::Gt(col_index, value) => &tuple.values[*col_index] > value,
-        WhereClause::Gte(col_index, value) => &tuple.values[*col_index] >= value,
+        // Only TRUE matches, both FALSE and NULL (unknown) filter the tuple out.
+        Some(expr) => Ok(expr.eval_bool(tuple)? == Some(true)),
+        None => Ok(true),
     }
 }
 
@@ -23,6 +31,9 @@ pub(crate) fn execute_create_table(
     db: &mut DB,
     table_name: String,
     columns: Vec<ColumnDef>,
+    keys: Vec<KeyDef>,
+    foreign_keys: Vec<ForeignKey>,
+    serials: Vec<(String, usize)>,
 ) -> errors::Result<()> {
     if db.tables.contains_key(&table_name) {
         return Err(DBError::Execution(format!(
@@ -30,74 +41,559 @@ pub(crate) fn execute_create_table(
             table_name
         )));
     }
-    let table = Table::new(db.next_table_id, table_name.clone(), columns);
-    let table_file_path = table.get_table_file_path(&db.storage_dir);
-    PageTable::init(&table, &table_file_path)?;
-    db.tables.insert(table_name, table);
+    let mut table = Table::new(db.next_table_id, table_name.clone(), columns);
     db.next_table_id += 1;
+    for foreign_key in foreign_keys {
+        table.add_foreign_key(foreign_key);
+    }
+    for (name, position) in serials {
+        let sequence = Sequence::new(1, 1)
+            .with_id(db.next_table_id)
+            .with_owner(table.id(), table.columns()[position].id());
+        db.sequences.insert(name, sequence)?;
+        db.next_table_id += 1;
+    }
+    let table_file_path = table.get_table_file_path(&db.storage_dir);
+    PageTable::init(&db.pager, &table, &table_file_path)?;
+    db.tables.insert(table_name.clone(), table);
+    for key in keys {
+        let index =
+            IndexDef::new(db.next_table_id, key.name, key.columns).with_constraint(key.constraint);
+        add_index(db, &table_name, index)?;
+    }
     db.save_metadata()?;
     Ok(())
 }
 
+/// Foreign keys referencing the table, with their tables, in the order of the table names.
+pub(crate) fn referencing_tables<'a>(
+    db: &'a DB,
+    table: &Table,
+) -> Vec<(&'a Table, &'a ForeignKey)> {
+    let mut tables = db.tables.values().collect::<Vec<_>>();
+    tables.sort_by_key(|table| table.name());
+    tables
+        .into_iter()
+        .flat_map(|child| {
+            child
+                .foreign_keys()
+                .iter()
+                .filter(|foreign_key| foreign_key.table_id() == table.id())
+                .map(move |foreign_key| (child, foreign_key))
+        })
+        .collect()
+}
+
 pub(crate) fn execute_drop_table(db: &mut DB, table_name: String) -> errors::Result<()> {
-    db.get_table(&table_name)?
-        .get_page_table(&db.storage_dir)?
-        .delete()?;
+    let table = db.get_table(&table_name)?;
+    if let Some((child, foreign_key)) = referencing_tables(db, table)
+        .into_iter()
+        .find(|(child, _)| child.id() != table.id())
+    {
+        return Err(DBError::Execution(format!(
+            "Table {} is referenced by foreign key {} of table {}",
+            table_name,
+            foreign_key.name(),
+            child.name()
+        )));
+    }
+    table.get_page_table(&db.storage_dir, &db.pager)?.delete()?;
+    for sequence in db.sequences.owned_by(table.id(), None) {
+        remove_sequence(db, &sequence);
+    }
     db.tables.remove(&table_name).unwrap();
     db.save_metadata()?;
     Ok(())
 }
 
+/// Changes the table's schema. Only the catalog changes, the stored tuples are read in the
+/// new schema, see [`crate::table::SchemaVersion`].
+pub(crate) fn execute_alter_table(
+    db: &mut DB,
+    table_name: String,
+    change: TableChange,
+) -> errors::Result<()> {
+    let table = db.get_table(&table_name)?;
+    let new_column_name = match &change {
+        TableChange::AddColumn(column) => Some(column.name()),
+        TableChange::RenameColumn(_, name) => Some(name.as_str()),
+        _ => None,
+    };
+    if let Some(name) = new_column_name
+        && table.columns().iter().any(|column| column.name() == name)
+    {
+        return Err(DBError::Execution(format!(
+            "Column {} of table {} already exists",
+            name, table_name
+        )));
+    }
+    match change {
+        TableChange::AddColumn(column) => {
+            if column.not_null()
+                && column.missing().is_null()
+                && let Some(row) = table
+                    .get_page_table(&db.storage_dir, &db.pager)?
+                    .iter()
+                    .next()
+            {
+                row?;
+                return Err(DBError::Integrity(format!(
+                    "Column {} violates NOT NULL",
+                    column.name()
+                )));
+            }
+            db.tables.get_mut(&table_name).unwrap().add_column(column)?;
+        }
+        TableChange::DropColumn(position) => {
+            if table.columns().len() == 1 {
+                return Err(DBError::Execution(format!(
+                    "Cannot drop column {}, the only column of table {}",
+                    table.columns()[position].name(),
+                    table_name
+                )));
+            }
+            let dropped_id = table.columns()[position].id();
+            if let Some((child, foreign_key)) = referencing_tables(db, table)
+                .into_iter()
+                .find(|(_, foreign_key)| foreign_key.referenced().contains(&dropped_id))
+            {
+                return Err(DBError::Execution(format!(
+                    "Column {} of table {} is referenced by foreign key {} of table {}",
+                    table.columns()[position].name(),
+                    table_name,
+                    foreign_key.name(),
+                    child.name()
+                )));
+            }
+            // Like the indexes and foreign keys, the checks that use the column go away with
+            // it.
+            let dropped_name = table.columns()[position].name().to_owned();
+            let mut checks = vec![];
+            for column in table.columns() {
+                let mut kept = vec![];
+                for check in column.checks() {
+                    if !uses_column(check, &dropped_name)? {
+                        kept.push(check.clone());
+                    }
+                }
+                checks.push(kept);
+            }
+            for sequence in db.sequences.owned_by(table.id(), Some(dropped_id)) {
+                remove_sequence(db, &sequence);
+            }
+            let table = db.tables.get_mut(&table_name).unwrap();
+            for (i, checks) in checks.into_iter().enumerate() {
+                table.set_checks(i, checks);
+            }
+            let dropped = table.drop_column(position)?;
+            for index in dropped {
+                db.pager.remove_file(&index.get_file_path(&db.storage_dir));
+            }
+        }
+        TableChange::RenameColumn(position, name) => {
+            let old_name = table.columns()[position].name().to_owned();
+            let checks = table
+                .columns()
+                .iter()
+                .map(|column| {
+                    column
+                        .checks()
+                        .iter()
+                        .map(|check| rename_column_references(check, &old_name, &name))
+                        .collect::<errors::Result<Vec<_>>>()
+                })
+                .collect::<errors::Result<Vec<_>>>()?;
+            let table = db.tables.get_mut(&table_name).unwrap();
+            for (i, checks) in checks.into_iter().enumerate() {
+                table.set_checks(i, checks);
+            }
+            table.rename_column(position, name);
+        }
+        TableChange::RenameTable(name) => {
+            if db.tables.contains_key(&name) {
+                return Err(DBError::Execution(format!("Table {} already exists", name)));
+            }
+            let mut table = db.tables.remove(&table_name).unwrap();
+            table.set_name(name.clone());
+            db.tables.insert(name, table);
+        }
+    }
+    db.save_metadata()?;
+    Ok(())
+}
+
+pub(crate) fn execute_create_sequence(
+    db: &mut DB,
+    name: String,
+    sequence: Sequence,
+) -> errors::Result<()> {
+    if db.sequences.contains(&name) {
+        return Err(DBError::Execution(format!(
+            "Sequence {} already exists",
+            name
+        )));
+    }
+    db.sequences
+        .insert(name, sequence.with_id(db.next_table_id))?;
+    db.next_table_id += 1;
+    db.save_metadata()?;
+    Ok(())
+}
+
+/// Removes the sequence from the catalog, and its file when the statement commits.
+fn remove_sequence(db: &DB, name: &str) {
+    if let Some(sequence) = db.sequences.remove(name) {
+        db.pager
+            .remove_file(&sequence.get_file_path(&db.storage_dir));
+    }
+}
+
+/// Drops a sequence, unless it belongs to a `serial` column, which it's dropped with.
+pub(crate) fn execute_drop_sequence(db: &mut DB, name: String) -> errors::Result<()> {
+    let sequence = db
+        .sequences
+        .get(&name)
+        .ok_or_else(|| DBError::Execution(format!("Sequence {} does not exist", name)))?;
+    if let Some((table_id, column_id)) = sequence.owner() {
+        let table = db.get_table_by_id(table_id)?;
+        let column = table.column_positions(&[column_id])[0];
+        return Err(DBError::Execution(format!(
+            "Sequence {} is used by column {} of table {} and can't be dropped",
+            name,
+            table.columns()[column].name(),
+            table.name()
+        )));
+    }
+    remove_sequence(db, &name);
+    db.save_metadata()?;
+    Ok(())
+}
+
+pub(crate) fn execute_create_index(
+    db: &mut DB,
+    index_name: String,
+    table_name: String,
+    columns: Vec<usize>,
+) -> errors::Result<()> {
+    // Tables and indexes share the id sequence, so index files don't need the table id.
+    let index = IndexDef::new(db.next_table_id, index_name, columns);
+    add_index(db, &table_name, index)?;
+    db.save_metadata()?;
+    Ok(())
+}
+
+/// Builds the index from the tuples of the table and adds it to the table.
+fn add_index(db: &mut DB, table_name: &str, index: IndexDef) -> errors::Result<()> {
+    if db.tables.values().any(|table| {
+        table
+            .indexes()
+            .iter()
+            .any(|other| other.name() == index.name())
+    }) {
+        return Err(DBError::Execution(format!(
+            "Index {} already exists",
+            index.name()
+        )));
+    }
+    let table = db.get_table(table_name)?;
+    let index_path = index.get_file_path(&db.storage_dir);
+    let mut tree = BTree::create(&db.pager, &index_path, index.columns().len())?;
+    for row in table.get_page_table(&db.storage_dir, &db.pager)?.iter() {
+        let (page_id, slot, tuple) = row?;
+        check_entry_size(index.name(), &index.key(&tuple))?;
+        tree.insert(index.entry(&tuple, (page_id, slot)))?;
+    }
+    db.tables.get_mut(table_name).unwrap().add_index(index);
+    db.next_table_id += 1;
+    Ok(())
+}
+
+pub(crate) fn execute_drop_index(db: &mut DB, index_name: String) -> errors::Result<()> {
+    let table = db
+        .tables
+        .values_mut()
+        .find(|table| {
+            table
+                .indexes()
+                .iter()
+                .any(|index| index.name() == index_name)
+        })
+        .ok_or_else(|| DBError::Execution(format!("Index {} does not exist", index_name)))?;
+    if table
+        .indexes()
+        .iter()
+        .any(|index| index.name() == index_name && index.constraint().is_some())
+    {
+        return Err(DBError::Execution(format!(
+            "Index {} enforces a constraint of table {} and can't be dropped",
+            index_name,
+            table.name()
+        )));
+    }
+    let index = table.remove_index(&index_name).unwrap();
+    db.pager.remove_file(&index.get_file_path(&db.storage_dir));
+    db.save_metadata()?;
+    Ok(())
+}
+
+/// Compacts the pages of the table, or of all tables, and with `full` also gives the
+/// trailing empty pages back.
+pub(crate) fn execute_vacuum(
+    db: &DB,
+    table_name: Option<String>,
+    full: bool,
+) -> errors::Result<()> {
+    let tables = match &table_name {
+        Some(table_name) => vec![db.get_table(table_name)?],
+        None => db.tables.values().collect(),
+    };
+    for table in tables {
+        let mut page_table = table.get_page_table(&db.storage_dir, &db.pager)?;
+        if full {
+            page_table.vacuum_full()?;
+        } else {
+            page_table.vacuum()?;
+        }
+    }
+    Ok(())
+}
+
+/// Fails if the tuple violates a NOT NULL or CHECK constraint of its table. Like in SQL, a
+/// CHECK that evaluates to NULL holds.
+fn check_tuple(table: &Table, checks: &[ColumnCheck], tuple: &Tuple) -> errors::Result<()> {
+    for check in checks {
+        let holds = match &check.expr {
+            Some(expr) => expr.eval_bool(tuple)? != Some(false),
+            None => !tuple.values[check.column].is_null(),
+        };
+        if !holds {
+            return Err(DBError::Integrity(format!(
+                "Column {} violates {}",
+                table.columns()[check.column].name(),
+                check.rule
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Adds a row with a value for every column of the table.
 pub(crate) fn execute_insert(
     db: &DB,
     table_name: String,
     values: Vec<DBValue>,
+    checks: Vec<ColumnCheck>,
 ) -> errors::Result<usize> {
     let table = db.get_table(&table_name)?;
-    if values.len() != table.columns().len() {
-        return Err(DBError::Execution(format!(
-            "Insert values count ({}) does not match table columns count ({})",
-            values.len(),
-            table.columns().len()
-        )));
-    }
+    let values = values
+        .into_iter()
+        .zip(table.columns())
+        .map(|(value, column)| value.coerce_to(column.dtype()))
+        .collect::<errors::Result<Vec<_>>>()?;
     for (i, value) in values.iter().enumerate() {
-        if value.dtype() != table.columns()[i].dtype() {
+        if let Some(dtype) = value.dtype()
+            && dtype != table.columns()[i].dtype().unconstrained()
+        {
             return Err(DBError::Execution(format!(
                 "Value type mismatch for column {}: expected {:?}, got {:?}",
                 table.columns()[i].name(),
                 table.columns()[i].dtype(),
-                value.dtype()
+                dtype
             )));
         }
     }
 
-    let mut page_table = table.get_page_table(&db.storage_dir)?;
     let tuple = Tuple::new(values);
+    check_tuple(table, &checks, &tuple)?;
+    let mut page_table = table.get_page_table(&db.storage_dir, &db.pager)?;
     page_table.insert_tuple(&tuple)?;
+    // After the insert, so that a row may reference itself.
+    check_references(db, table, &tuple, None)?;
     Ok(1)
 }
 
-pub(crate) fn execute_select(
-    db: &DB,
-    table_name: String,
-    where_clause: Option<WhereClause>,
-    columns: Vec<(String, usize)>,
-) -> errors::Result<(Vec<String>, SelectIterator)> {
-    let page_table = db.get_table(&table_name)?.get_page_table(&db.storage_dir)?;
-    let column_names = columns.iter().map(|(name, _)| name.clone()).collect();
-    let iterator = SelectIterator {
-        table_iter: page_table.into_iter(),
+type TupleScan<'a> = Box<dyn Iterator<Item = errors::Result<(u32, u16, Tuple)>> + 'a>;
+
+/// Returns the tuples of the table with their ids. If an index matches the WHERE clause, only
+/// the tuples in its range are read, so the clause still has to be checked.
+pub(crate) fn scan_tuples<'a>(
+    db: &'a DB,
+    table: &'a Table,
+    where_clause: Option<&Expr>,
+) -> errors::Result<TupleScan<'a>> {
+    let page_table = table.get_page_table(&db.storage_dir, &db.pager)?;
+    match IndexScan::choose(table, where_clause) {
+        Some(scan) => Ok(Box::new(scan.rows(
+            &db.storage_dir,
+            &db.pager,
+            page_table,
+        )?)),
+        None => Ok(Box::new(page_table.into_iter())),
+    }
+}
+
+fn scan_table<'a>(
+    db: &'a DB,
+    table_name: &str,
+    where_clause: Option<&Expr>,
+) -> errors::Result<Rows<'a>> {
+    let table = db.get_table(table_name)?;
+    let tuples = scan_tuples(db, table, where_clause)?;
+    Ok(Box::new(tuples.map(|row| row.map(|(_, _, tuple)| tuple))))
+}
+
+/// Returns the rows of the FROM clause. Tables are joined with a hash join if the join
+/// condition has equalities between the two sides, and with a nested loop join otherwise.
+///
+/// The first table starts the joined rows, so the columns of the WHERE clause that are
+/// in the first table have the same indexes and may be looked up in its indexes.
+fn scan_from<'a>(
+    db: &'a DB,
+    from: FromPlan,
+    where_clause: Option<&Expr>,
+) -> errors::Result<Rows<'a>> {
+    let join = match from {
+        FromPlan::SingleRow => return Ok(Box::new(std::iter::once(Ok(Tuple::new(vec![]))))),
+        FromPlan::Table(table_name) => return scan_table(db, &table_name, where_clause),
+        FromPlan::Join(join) => *join,
+    };
+    let JoinPlan {
+        left,
+        right,
+        kind,
+        on,
+        left_width,
+    } = join;
+    let left = scan_from(db, left, where_clause)?;
+    let table = db.get_table(&right)?;
+    let spec = JoinSpec {
+        kind,
+        on,
+        left_width,
+        right_width: table.columns().len(),
+    };
+    let (left_keys, right_keys) = spec.equi_keys();
+    if left_keys.is_empty() {
+        let page_table = table.get_page_table(&db.storage_dir, &db.pager)?;
+        return Ok(Box::new(NestedLoopJoin::new(spec, left, page_table)));
+    }
+    let join = HashJoin::new(spec, left_keys, right_keys, db.temp_dir(), db.work_mem);
+    Ok(Box::new(join.execute(left, scan_table(db, &right, None)?)?))
+}
+
+pub(crate) fn execute_select(db: &DB, select: Select) -> errors::Result<(Vec<String>, Rows<'_>)> {
+    let Select {
+        from,
         where_clause,
+        aggregation,
         columns,
+        order_by,
+        limit,
+        offset,
+    } = select;
+    let scan = scan_from(db, from, where_clause.as_ref())?;
+    let (column_names, mut columns): (_, Vec<_>) = columns.into_iter().unzip();
+    let (sort_exprs, sort_keys): (Vec<_>, Vec<_>) = order_by.into_iter().unzip();
+    // Sort key values are computed along with the output columns and stripped after sorting.
+    columns.extend(sort_exprs);
+    let iterator = match aggregation {
+        None => SelectIterator {
+            rows: scan,
+            where_clause,
+            columns,
+        },
+        Some(Aggregation {
+            group_by,
+            aggregates,
+            having,
+        }) => {
+            let key_count = group_by.len();
+            let (args, functions) = aggregates
+                .into_iter()
+                .map(|aggregate| (aggregate.arg, (aggregate.func, aggregate.distinct)))
+                .unzip::<_, _, Vec<_>, _>();
+            let input = SelectIterator {
+                rows: scan,
+                where_clause,
+                columns: group_by.into_iter().chain(args).collect(),
+            };
+            let groups = HashAggregate::new(key_count, functions, db.temp_dir(), db.work_mem)
+                .execute(input)?;
+            SelectIterator {
+                rows: Box::new(groups),
+                where_clause: having,
+                columns,
+            }
+        }
+    };
+    let rows: Rows = if sort_keys.is_empty() {
+        Box::new(iterator)
+    } else {
+        let mut sorter = Sorter::new(sort_keys, db.temp_dir(), db.work_mem);
+        for row in iterator {
+            sorter.push(row?)?;
+        }
+        Box::new(sorter.finish()?)
     };
-    Ok((column_names, iterator))
+    if limit.is_none() && offset == 0 {
+        return Ok((column_names, rows));
+    }
+    let rows = LimitIterator {
+        rows,
+        offset,
+        limit,
+    };
+    Ok((column_names, Box::new(rows)))
+}
+
+/// Skips `offset` rows and stops after `limit` more. Rows are pulled lazily, so once the limit
+/// is reached no more pages are read.
+struct LimitIterator<'a> {
+    rows: Rows<'a>,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Iterator for LimitIterator<'_> {
+    type Item = errors::Result<Tuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.limit == Some(0) {
+            return None;
+        }
+        loop {
+            let row = self.rows.next()?;
+            if row.is_ok() && self.offset > 0 {
+                self.offset -= 1;
+                continue;
+            }
+            if let Some(limit) = &mut self.limit {
+                *limit -= 1;
+            }
+            return Some(row);
+        }
+    }
 }
 
+/// Filters the rows and computes the given columns from them.
 pub(crate) struct SelectIterator<'a> {
-    table_iter: TableIterator<'a, PageTable<'a>>,
-    where_clause: Option<WhereClause>,
-    columns: Vec<(String, usize)>,
+    rows: Rows<'a>,
+    where_clause: Option<Expr>,
+    columns: Vec<Expr>,
+}
+
+impl SelectIterator<'_> {
+    fn project(&self, tuple: &Tuple) -> errors::Result<Option<Tuple>> {
+        if !tuple_matches(tuple, &self.where_clause)? {
+            return Ok(None);
+        }
+        let values = self
+            .columns
+            .iter()
+            .map(|expr| expr.eval(tuple))
+            .collect::<errors::Result<Vec<_>>>()?;
+        Ok(Some(Tuple::new(values)))
+    }
 }
 
 impl Iterator for SelectIterator<'_> {
@@ -105,22 +601,12 @@ impl Iterator for SelectIterator<'_> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            match self.table_iter.next() {
-                Some(Ok((_, _, tuple))) => {
-                    let matches = self
-                        .where_clause
-                        .as_ref()
-                        .map(|clause| tuple_matches(&tuple, clause))
-                        .unwrap_or(true);
-                    if matches {
-                        let filtered_values: Vec<DBValue> = self
-                            .columns
-                            .iter()
-                            .map(|&(_, index)| tuple.values[index].clone())
-                            .collect();
-                        return Some(Ok(Tuple::new(filtered_values)));
-                    }
-                }
+            match self.rows.next() {
+                Some(Ok(tuple)) => match self.project(&tuple) {
+                    Ok(Some(row)) => return Some(Ok(row)),
+                    Ok(None) => continue,
+                    Err(err) => return Some(Err(err)),
+                },
                 Some(Err(err)) => return Some(Err(err)),
                 None => return None,
             }
@@ -131,53 +617,86 @@ impl Iterator for SelectIterator<'_> {
 pub(crate) fn execute_update(
     db: &DB,
     table_name: String,
-    update_clauses: Vec<(usize, DBValue)>,
-    where_clause: Option<WhereClause>,
+    update_clauses: Vec<(usize, Expr)>,
+    where_clause: Option<Expr>,
+    checks: Vec<ColumnCheck>,
 ) -> errors::Result<usize> {
-    let mut page_table = db.get_table(&table_name)?.get_page_table(&db.storage_dir)?;
+    let table = db.get_table(&table_name)?;
 
     let mut update_queue = vec![];
-    for tuple in page_table.iter() {
-        let (page_id, offset, mut tup_data) = tuple?;
-        if let Some(ref clause) = where_clause {
-            if !tuple_matches(&tup_data, clause) {
-                continue;
-            }
+    for tuple in scan_tuples(db, table, where_clause.as_ref())? {
+        let (page_id, slot, tup_data) = tuple?;
+        if !tuple_matches(&tup_data, &where_clause)? {
+            continue;
         }
-        for (col_index, value) in &update_clauses {
-            tup_data.values[*col_index] = value.clone();
+        // All expressions see the values from before the update.
+        let mut new_data = tup_data.clone();
+        for (col_index, expr) in &update_clauses {
+            let dtype = table.columns()[*col_index].dtype();
+            new_data.values[*col_index] = expr.eval(&tup_data)?.coerce_to(dtype)?;
         }
-        update_queue.push((page_id, offset, tup_data));
+        update_queue.push((page_id, slot, tup_data, new_data));
     }
     let update_count = update_queue.len();
-    for (page_id, offset, tuple) in update_queue {
-        page_table.overwrite_tuple(page_id, offset, &tuple)?;
-    }
-
+    update_tuples(db, table, update_queue, &checks)?;
     Ok(update_count)
 }
 
 pub(crate) fn execute_delete(
     db: &DB,
     table_name: String,
-    where_clause: Option<WhereClause>,
+    where_clause: Option<Expr>,
 ) -> errors::Result<usize> {
-    let mut page_table = db.get_table(&table_name)?.get_page_table(&db.storage_dir)?;
+    let table = db.get_table(&table_name)?;
 
     let mut delete_queue = vec![];
-    for tuple in page_table.iter() {
-        let (page_id, offset, tup_data) = tuple?;
-        if let Some(ref clause) = where_clause {
-            if !tuple_matches(&tup_data, clause) {
-                continue;
-            }
+    for tuple in scan_tuples(db, table, where_clause.as_ref())? {
+        let (page_id, slot, tup_data) = tuple?;
+        if !tuple_matches(&tup_data, &where_clause)? {
+            continue;
         }
-        delete_queue.push((page_id, offset));
+        delete_queue.push((page_id, slot, tup_data));
     }
     let delete_count = delete_queue.len();
-    for (page_id, offset) in delete_queue {
-        page_table.delete_tuple(page_id, offset)?;
+    delete_tuples(db, table, delete_queue)?;
+    Ok(delete_count)
+}
+
+/// Replaces the tuples with their updated versions, given with the old ones. The foreign
+/// keys are checked and their actions applied once all tuples are written.
+pub(crate) fn update_tuples(
+    db: &DB,
+    table: &Table,
+    updates: Vec<(u32, u16, Tuple, Tuple)>,
+    checks: &[ColumnCheck],
+) -> errors::Result<()> {
+    for (_, _, _, new) in &updates {
+        check_tuple(table, checks, new)?;
     }
+    let mut page_table = table.get_page_table(&db.storage_dir, &db.pager)?;
+    for (page_id, slot, _, new) in &updates {
+        page_table.overwrite_tuple(*page_id, *slot, new)?;
+    }
+    for (_, _, old, new) in &updates {
+        check_references(db, table, new, Some(old))?;
+        apply_actions(db, table, old, Some(new))?;
+    }
+    Ok(())
+}
 
-    Ok(delete_count)
+/// Deletes the tuples, given with their values, then applies the ON DELETE actions of the
+/// foreign keys referencing them.
+pub(crate) fn delete_tuples(
+    db: &DB,
+    table: &Table,
+    rows: Vec<(u32, u16, Tuple)>,
+) -> errors::Result<()> {
+    let mut page_table = table.get_page_table(&db.storage_dir, &db.pager)?;
+    for (page_id, slot, _) in &rows {
+        page_table.delete_tuple(*page_id, *slot)?;
+    }
+    for (_, _, tuple) in &rows {
+        apply_actions(db, table, tuple, None)?;
+    }
+    Ok(())
 }
@@ -7,43 +7,48 @@ use crate::{
     errors::{self, DBError},
     serializer::{BinaryReader, BinaryWriter},
     table::Table,
+    toast::Toast,
     tuple::Tuple,
 };
 
 pub(crate) const PAGE_SIZE: usize = 8192;
-pub(crate) const PAGE_HEADER_SIZE: usize = 4 + 2 * 2;
+pub(crate) const PAGE_HEADER_SIZE: usize = 4 + 3 * 2;
 pub(crate) const PAGE_DATA_SIZE: usize = PAGE_SIZE - PAGE_HEADER_SIZE;
 
-#[derive(Debug)]
-struct TupleHeader {
-    alive: bool,
+/// Entry of the slot array, pointing to the data of a tuple.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// Start of the tuple data, or 0 if the slot is free. The slot array is at the start of
+    /// the data section, so tuple data never starts there.
+    offset: usize,
     len: usize,
 }
 
-impl TupleHeader {
-    fn size() -> usize {
-        1 + 2
-    }
+impl Slot {
+    const FREE: Self = Self { offset: 0, len: 0 };
 
-    fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
-        let alive = reader.read_bool()?;
-        let len = reader.read_u16()? as usize;
-        Ok(Self { alive, len })
+    fn size() -> usize {
+        2 + 2
     }
 
-    fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
-        writer.write_bool(self.alive)?;
-        writer.write_u16(self.len as u16)?;
-        Ok(())
+    fn is_free(&self) -> bool {
+        self.offset == 0
     }
 }
 
+/// Page of a table file. The slot array grows from the start of the data section and the
+/// tuple data from its end, with the free space in between. Tuples are identified by their
+/// slot, which stays the same when a tuple grows or when the page is compacted.
+///
+/// Long strings of tuples are stored in the [`Toast`] of the page, if it has one.
 #[derive(PartialEq, Debug)]
 pub(crate) struct Page<'a> {
     table: &'a Table,
+    toast: Option<Toast<'a>>,
     id: u32,
-    free_space_end: usize, // u16, starting from data section
-    dead_space: usize,     // u16
+    slot_count: usize, // u16
+    data_start: usize, // u16, start of the tuple data
+    dead_space: usize, // u16, tuple data no slot points to
     data: [u8; PAGE_DATA_SIZE],
 }
 
@@ -51,8 +56,10 @@ impl<'a> Page<'a> {
     pub fn new(table: &'a Table, id: u32) -> Self {
         Self {
             table,
+            toast: None,
             id,
-            free_space_end: 0,
+            slot_count: 0,
+            data_start: PAGE_DATA_SIZE,
             dead_space: 0,
             data: [0; PAGE_DATA_SIZE],
         }
@@ -60,14 +67,17 @@ impl<'a> Page<'a> {
 
     pub fn read(table: &'a Table, reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
         let id = reader.read_u32()?;
-        let free_space_end = reader.read_u16()? as usize;
+        let slot_count = reader.read_u16()? as usize;
+        let data_start = reader.read_u16()? as usize;
         let dead_space = reader.read_u16()? as usize;
         let mut data = [0; PAGE_DATA_SIZE];
         reader.read_exact(&mut data)?;
         Ok(Self {
             table,
+            toast: None,
             id,
-            free_space_end,
+            slot_count,
+            data_start,
             dead_space,
             data,
         })
@@ -75,94 +85,246 @@ impl<'a> Page<'a> {
 
     pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
         writer.write_u32(self.id)?;
-        writer.write_u16(self.free_space_end as u16)?;
+        writer.write_u16(self.slot_count as u16)?;
+        writer.write_u16(self.data_start as u16)?;
         writer.write_u16(self.dead_space as u16)?;
         writer.write_all(&self.data)?;
         Ok(())
     }
 
+    pub fn with_toast(mut self, toast: Toast<'a>) -> Self {
+        self.toast = Some(toast);
+        self
+    }
+
     pub fn id(&self) -> u32 {
         self.id
     }
 
+    fn read_slot(&self, slot: usize) -> Slot {
+        let entry = &self.data[slot * Slot::size()..];
+        Slot {
+            offset: u16::from_be_bytes([entry[0], entry[1]]) as usize,
+            len: u16::from_be_bytes([entry[2], entry[3]]) as usize,
+        }
+    }
+
+    fn write_slot(&mut self, slot: usize, value: Slot) {
+        let entry = &mut self.data[slot * Slot::size()..];
+        entry[0..2].copy_from_slice(&(value.offset as u16).to_be_bytes());
+        entry[2..4].copy_from_slice(&(value.len as u16).to_be_bytes());
+    }
+
+    /// Returns the slot of a live tuple.
+    fn live_slot(&self, slot: u16) -> errors::Result<Slot> {
+        if slot as usize >= self.slot_count {
+            return Err(DBError::Integrity(format!("Slot out of bounds: {}", slot)));
+        }
+        let value = self.read_slot(slot as usize);
+        if value.is_free() {
+            return Err(DBError::Integrity(format!("Tuple is dead: {}", slot)));
+        }
+        Ok(value)
+    }
+
+    fn free_slot(&self) -> Option<usize> {
+        (0..self.slot_count).find(|&slot| self.read_slot(slot).is_free())
+    }
+
+    /// Free space between the slot array and the tuple data.
+    fn gap(&self) -> usize {
+        self.data_start - self.slot_count * Slot::size()
+    }
+
+    /// Bytes available for new tuples, including the space of dead tuples, which is reclaimed
+    /// by compacting the page when it's needed.
     pub fn free_space(&self) -> usize {
-        PAGE_DATA_SIZE - self.free_space_end
+        self.gap() + self.dead_space
+    }
+
+    pub fn dead_space(&self) -> usize {
+        self.dead_space
+    }
+
+    /// Bytes of free space taken by adding the tuple to a page with a [`Toast`] and no free
+    /// slots.
+    pub fn required_space(tuple: &Tuple) -> usize {
+        Slot::size() + tuple.stored_size()
+    }
+
+    /// Size of the tuple data in this page.
+    fn tuple_len(&self, tuple: &Tuple) -> usize {
+        match self.toast {
+            Some(_) => tuple.stored_size(),
+            None => tuple.size(),
+        }
+    }
+
+    fn space_needed(&self, len: usize) -> usize {
+        match self.free_slot() {
+            Some(_) => len,
+            None => Slot::size() + len,
+        }
     }
 
     pub fn can_fit_tuple(&self, tuple: &Tuple) -> bool {
-        self.free_space() >= TupleHeader::size() + tuple.size()
+        self.can_fit_tuple_data(self.tuple_len(tuple))
+    }
+
+    pub fn can_fit_tuple_data(&self, len: usize) -> bool {
+        self.free_space() >= self.space_needed(len)
+    }
+
+    /// Serializes the tuple, storing its long strings in the toast.
+    fn encode(&self, tuple: &Tuple) -> errors::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.tuple_len(tuple));
+        tuple.write(
+            self.table,
+            &mut BinaryWriter::new(&mut data),
+            self.toast.as_ref(),
+        )?;
+        Ok(data)
+    }
+
+    /// Writes tuple data below the data of the other tuples, compacting the page first if the
+    /// gap is too small, and returns its offset. The caller checks that the data fits.
+    fn write_tuple_data(&mut self, data: &[u8]) -> usize {
+        if self.gap() < data.len() {
+            self.compact();
+        }
+        self.data_start -= data.len();
+        self.data[self.data_start..self.data_start + data.len()].copy_from_slice(data);
+        self.data_start
     }
 
     pub fn insert_tuple(&mut self, tuple: &Tuple) -> errors::Result<u16> {
-        if !self.can_fit_tuple(tuple) {
+        self.check_fits(self.tuple_len(tuple))?;
+        let data = self.encode(tuple)?;
+        self.insert_tuple_data(&data)
+    }
+
+    fn check_fits(&self, len: usize) -> errors::Result<()> {
+        if !self.can_fit_tuple_data(len) {
             return Err(DBError::Integrity(format!(
                 "Not enough space to add tuple: {} bytes needed, {} bytes available",
-                TupleHeader::size() + tuple.size(),
+                self.space_needed(len),
                 self.free_space(),
             )));
         }
+        Ok(())
+    }
 
-        let header = TupleHeader {
-            alive: true,
-            len: tuple.size(),
+    /// Adds a tuple in its stored form, e.g. from [`Page::tuple_data`] of another page of
+    /// the same table.
+    pub fn insert_tuple_data(&mut self, data: &[u8]) -> errors::Result<u16> {
+        self.check_fits(data.len())?;
+        let slot = match self.free_slot() {
+            Some(slot) => slot,
+            None => {
+                if self.gap() < Slot::size() {
+                    self.compact();
+                }
+                self.slot_count += 1;
+                self.write_slot(self.slot_count - 1, Slot::FREE);
+                self.slot_count - 1
+            }
         };
-        let header_cursor = Cursor::new(&mut self.data[self.free_space_end..]);
-        header.write(&mut BinaryWriter::new(header_cursor))?;
-
-        let data_cursor = Cursor::new(&mut self.data[self.free_space_end + TupleHeader::size()..]);
-        tuple.write(self.table, &mut BinaryWriter::new(data_cursor))?;
-
-        let tuple_offset = self.free_space_end as u16;
-        self.free_space_end += TupleHeader::size() + header.len;
-        Ok(tuple_offset)
+        let offset = self.write_tuple_data(data);
+        let len = data.len();
+        self.write_slot(slot, Slot { offset, len });
+        Ok(slot as u16)
     }
 
-    pub fn mark_tuple_dead(&mut self, tuple_offset: u16) -> errors::Result<()> {
-        if tuple_offset >= PAGE_DATA_SIZE as u16 {
-            return Err(DBError::Integrity(format!(
-                "Tuple offset out of bounds: {}",
-                tuple_offset
-            )));
-        }
-
-        let cursor = Cursor::new(&self.data[tuple_offset as usize..]);
-        let mut header = TupleHeader::from_reader(&mut BinaryReader::new(cursor))?;
+    /// Stored form of the tuple, with pointers to the values in the toast.
+    pub fn tuple_data(&self, slot: u16) -> errors::Result<&[u8]> {
+        let Slot { offset, len } = self.live_slot(slot)?;
+        Ok(&self.data[offset..offset + len])
+    }
 
-        header.alive = false;
-        self.dead_space += TupleHeader::size() + header.len;
+    pub fn live_slots(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.slot_count)
+            .filter(|&slot| !self.read_slot(slot).is_free())
+            .map(|slot| slot as u16)
+    }
 
-        let cursor = Cursor::new(&mut self.data[tuple_offset as usize..]);
-        header.write(&mut BinaryWriter::new(cursor))?;
+    pub fn get_tuple(&self, slot: u16) -> errors::Result<Tuple> {
+        let cursor = Cursor::new(self.tuple_data(slot)?);
+        Tuple::read(
+            self.table,
+            &mut BinaryReader::new(cursor),
+            self.toast.as_ref(),
+        )
+    }
 
+    /// Frees the values of the tuple stored in the toast.
+    fn free_external_values(&self, slot: u16) -> errors::Result<()> {
+        let Some(toast) = &self.toast else {
+            return Ok(());
+        };
+        let mut reader = BinaryReader::new(Cursor::new(self.tuple_data(slot)?));
+        for pointer in Tuple::external_values(self.table, &mut reader)? {
+            toast.free(&pointer)?;
+        }
         Ok(())
     }
 
-    pub fn overwrite_tuple(&mut self, tuple_offset: u16, tuple: &Tuple) -> errors::Result<bool> {
-        if tuple_offset >= PAGE_DATA_SIZE as u16 {
-            return Err(DBError::Integrity(format!(
-                "Tuple offset out of bounds: {}",
-                tuple_offset
-            )));
+    pub fn mark_tuple_dead(&mut self, slot: u16) -> errors::Result<()> {
+        let value = self.live_slot(slot)?;
+        self.free_external_values(slot)?;
+        self.dead_space += value.len;
+        self.write_slot(slot as usize, Slot::FREE);
+        // Free slots at the end of the array are given back to the free space.
+        while self.slot_count > 0 && self.read_slot(self.slot_count - 1).is_free() {
+            self.slot_count -= 1;
         }
-        let header_cursor = Cursor::new(&mut self.data[tuple_offset as usize..]);
-        let header = TupleHeader::from_reader(&mut BinaryReader::new(header_cursor))?;
-        if !header.alive {
-            return Err(DBError::Integrity(format!(
-                "Tuple is dead: {}",
-                tuple_offset
-            )));
-        }
-        if tuple.size() > header.len {
+        Ok(())
+    }
+
+    /// Replaces the tuple, keeping its slot. Returns `false` if the page has no room for it.
+    pub fn overwrite_tuple(&mut self, slot: u16, tuple: &Tuple) -> errors::Result<bool> {
+        let old = self.live_slot(slot)?;
+        let len = self.tuple_len(tuple);
+        if len > old.len && self.free_space() + old.len < len {
             return Ok(false);
         }
-
-        let data_cursor =
-            Cursor::new(&mut self.data[tuple_offset as usize + TupleHeader::size()..]);
-        tuple.write(self.table, &mut BinaryWriter::new(data_cursor))?;
-
+        self.free_external_values(slot)?;
+        let data = self.encode(tuple)?;
+        let offset = if len <= old.len {
+            self.data[old.offset..old.offset + len].copy_from_slice(&data);
+            self.dead_space += old.len - len;
+            old.offset
+        } else {
+            // The old data is only dead space once the slot points elsewhere.
+            self.write_slot(slot as usize, Slot::FREE);
+            self.dead_space += old.len;
+            self.write_tuple_data(&data)
+        };
+        self.write_slot(slot as usize, Slot { offset, len });
         Ok(true)
     }
 
+    /// Slides the tuple data to the end of the page, so the space of dead tuples is part of
+    /// the gap again. Tuples keep their slots.
+    pub fn compact(&mut self) {
+        let slots = (0..self.slot_count)
+            .map(|slot| self.read_slot(slot))
+            .collect::<Vec<_>>();
+        let old_data = std::mem::replace(&mut self.data, [0; PAGE_DATA_SIZE]);
+        let mut data_start = PAGE_DATA_SIZE;
+        for (slot, mut value) in slots.into_iter().enumerate() {
+            if !value.is_free() {
+                data_start -= value.len;
+                self.data[data_start..data_start + value.len]
+                    .copy_from_slice(&old_data[value.offset..value.offset + value.len]);
+                value.offset = data_start;
+            }
+            self.write_slot(slot, value);
+        }
+        self.data_start = data_start;
+        self.dead_space = 0;
+    }
+
+    #[cfg(test)]
     pub fn iter(&self) -> PageIterator<&Self> {
         self.into_iter()
     }
@@ -189,12 +351,12 @@ impl<'a, 'b> IntoIterator for &'b Page<'a> {
 #[derive(Debug)]
 pub(crate) struct PageIterator<P> {
     page: P,
-    offset: usize,
+    slot: usize,
 }
 
 impl<'a, P: Borrow<Page<'a>>> PageIterator<P> {
     pub fn new(page: P) -> Self {
-        Self { page, offset: 0 }
+        Self { page, slot: 0 }
     }
 }
 
@@ -203,20 +365,10 @@ impl<'a, P: Borrow<Page<'a>>> Iterator for PageIterator<P> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let page = self.page.borrow();
-
-        if self.offset >= page.free_space_end {
-            return None;
-        }
-        let mut cursor = Cursor::new(&page.data[self.offset..]);
-        let header = TupleHeader::from_reader(&mut BinaryReader::new(cursor.clone())).expect("Failed to read tuple header");
-        if !header.alive {
-            self.offset += TupleHeader::size() + header.len;
-            return self.next();
-        }
-        cursor.set_position(TupleHeader::size() as u64);
-        let tuple_offset = self.offset as u16;
-        self.offset += TupleHeader::size() + header.len;
-        Some(Tuple::read(page.table, &mut BinaryReader::new(cursor)).map(|t| (tuple_offset, t)))
+        let slot = (self.slot..page.slot_count).find(|&slot| !page.read_slot(slot).is_free())?;
+        self.slot = slot + 1;
+        let slot = slot as u16;
+        Some(page.get_tuple(slot).map(|tuple| (slot, tuple)))
     }
 }
 
@@ -241,7 +393,7 @@ pub(crate) mod test {
         values::DBValue,
     };
 
-    use super::{Page, TupleHeader};
+    use super::Page;
 
     pub fn create_tuple(id: i32, name: &str, height: f64, is_fox: bool) -> Tuple {
         Tuple::new(vec![
@@ -313,31 +465,31 @@ pub(crate) mod test {
                 .unwrap()
         );
         validate_tuples(&page, &tuples_map);
-        // cannot grow more than the original size
+        // can grow past the original size, keeping the slot
         tuples_map.get_mut(&1).unwrap().1.values[1] =
             DBValue::String("very_very_very_long".to_string());
         assert!(
-            !page
-                .overwrite_tuple(tuples_map[&1].0, &tuples_map[&1].1.clone())
+            page.overwrite_tuple(tuples_map[&1].0, &tuples_map[&1].1.clone())
                 .unwrap()
         );
-        tuples_map.get_mut(&1).unwrap().1.values[1] = DBValue::String("test_test_test".to_string());
+        validate_tuples(&page, &tuples_map);
+        // but not past the free space of the page
+        let huge = create_tuple(1, &"a".repeat(PAGE_SIZE), 1., true);
+        assert!(!page.overwrite_tuple(tuples_map[&1].0, &huge).unwrap());
+        validate_tuples(&page, &tuples_map);
 
         // test mark dead
         page.mark_tuple_dead(tuples_map[&3].0).unwrap();
         tuples_map.remove(&3);
         validate_tuples(&page, &tuples_map);
+        let DBError::Integrity(_) = page.get_tuple(2).unwrap_err() else {
+            panic!("Expected IntegrityError");
+        };
 
-        // test relocate
-        tuples_map.get_mut(&1).unwrap().1.values[1] =
-            DBValue::String("very_very_very_long".to_string());
-        assert!(
-            !page
-                .overwrite_tuple(tuples_map[&1].0, &tuples_map[&1].1.clone())
-                .unwrap()
-        );
-        page.mark_tuple_dead(tuples_map[&1].0).unwrap();
-        tuples_map.get_mut(&1).unwrap().0 = page.insert_tuple(&tuples_map[&1].1).unwrap();
+        // the slot of a dead tuple is reused
+        let tuple = create_tuple(5, "test5", 5., false);
+        assert_eq!(page.insert_tuple(&tuple).unwrap(), 2);
+        tuples_map.insert(5, (2, tuple));
         validate_tuples(&page, &tuples_map);
 
         // test read/write
@@ -351,6 +503,53 @@ pub(crate) mod test {
         assert_eq!(read_page, page);
     }
 
+    #[test]
+    fn test_compact() {
+        let table = sample_table();
+        let mut page = Page::new(&table, 0);
+        let mut tuples_map = HashMap::new();
+        for id in 0..5 {
+            let tuple = create_tuple(id, &"a".repeat(id as usize * 10), 1., true);
+            tuples_map.insert(id, (page.insert_tuple(&tuple).unwrap(), tuple));
+        }
+        let free_space = page.free_space();
+        for id in [0, 2] {
+            page.mark_tuple_dead(tuples_map.remove(&id).unwrap().0)
+                .unwrap();
+        }
+        let dead_space = page.dead_space();
+        assert!(dead_space > 0);
+        assert_eq!(page.free_space(), free_space + dead_space);
+
+        // Tuples keep their slots.
+        page.compact();
+        validate_tuples(&page, &tuples_map);
+        assert_eq!(page.dead_space(), 0);
+        assert_eq!(page.free_space(), free_space + dead_space);
+    }
+
+    #[test]
+    fn test_dead_space_is_reused() {
+        let table = sample_table();
+        let mut page = Page::new(&table, 0);
+        let tuple = create_tuple(1, &"a".repeat(2000), 1., true);
+        let slots = (0..4)
+            .map(|_| page.insert_tuple(&tuple).unwrap())
+            .collect::<Vec<_>>();
+        assert!(!page.can_fit_tuple(&tuple));
+        page.mark_tuple_dead(slots[1]).unwrap();
+        page.mark_tuple_dead(slots[2]).unwrap();
+
+        // Needs the space of both dead tuples, so the page is compacted.
+        let big = create_tuple(2, &"b".repeat(3500), 2., false);
+        let slot = page.insert_tuple(&big).unwrap();
+        assert_eq!(page.get_tuple(slot).unwrap(), big);
+        for slot in [slots[0], slots[3]] {
+            assert_eq!(page.get_tuple(slot).unwrap(), tuple);
+        }
+        assert_eq!(page.iter().count(), 3);
+    }
+
     #[test]
     fn stress_test() {
         let table = sample_table();
@@ -366,9 +565,9 @@ pub(crate) mod test {
                 }
                 let id = *tuples_map.keys().choose(&mut rand::rng()).unwrap();
                 for tuple in page.iter() {
-                    let (offset, tuple) = tuple.unwrap();
+                    let (slot, tuple) = tuple.unwrap();
                     if tuple.values[0] == DBValue::Int(id) {
-                        page.mark_tuple_dead(offset).unwrap();
+                        page.mark_tuple_dead(slot).unwrap();
                         break;
                     }
                 }
@@ -376,7 +575,7 @@ pub(crate) mod test {
             } else {
                 let tuple = create_random_tuple(i);
 
-                if page.free_space() < TupleHeader::size() + tuple.size() {
+                if !page.can_fit_tuple(&tuple) {
                     // page is full, validate that we can't add more tuples
                     let DBError::Integrity(_) = page.insert_tuple(&tuple).unwrap_err() else {
                         panic!("Expected IntegrityError");
@@ -412,7 +611,8 @@ pub(crate) mod test {
         let mut reader = BinaryReader::new(BufReader::new(file));
         let read_page = Page::read(&table, &mut reader).unwrap();
         assert_eq!(read_page.id(), page.id());
-        assert_eq!(read_page.free_space_end, page.free_space_end);
+        assert_eq!(read_page.slot_count, page.slot_count);
+        assert_eq!(read_page.data_start, page.data_start);
         assert_eq!(read_page.dead_space, page.dead_space);
         assert_eq!(read_page.data, page.data);
     }
@@ -1,66 +1,107 @@
 use std::{
     borrow::Borrow,
-    fs::{self, File},
-    io::{self, BufWriter, Cursor, Read, Seek, SeekFrom},
+    io::{self, Cursor},
+    ops::Bound,
     path::{Path, PathBuf},
 };
 
 use crate::{
+    btree::{BTree, check_entry_size},
     errors::{self, DBError},
+    free_space_map::FreeSpaceMap,
     page::{PAGE_SIZE, Page, PageIterator},
+    pager::{Pager, PinnedPage},
     serializer::{BinaryReader, BinaryWriter},
-    table::Table,
+    table::{KeyConstraint, Table},
+    toast::Toast,
     tuple::Tuple,
+    values::DBValue,
 };
 
+#[cfg(test)]
+thread_local! {
+    /// Number of pages read by the current thread, for tests that check what a query reads.
+    pub(crate) static PAGE_READS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[derive(Clone)]
 pub(crate) struct PageTable<'a> {
+    pager: &'a Pager,
     table: &'a Table,
     file_path: PathBuf,
     page_count: u32,
 }
 
 impl<'a> PageTable<'a> {
-    pub fn load(table: &'a Table, file_path: PathBuf) -> io::Result<Self> {
-        let metadata = fs::metadata(&file_path)?;
-        if metadata.len() % PAGE_SIZE as u64 != 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Page file size is not a multiple of page size",
-            ));
-        }
-        let page_count = (metadata.len() / PAGE_SIZE as u64) as u32;
+    pub fn load(pager: &'a Pager, table: &'a Table, file_path: PathBuf) -> io::Result<Self> {
+        let page_count = pager.page_count(&file_path)?;
         Ok(Self {
+            pager,
             table,
             file_path,
             page_count,
         })
     }
 
+    /// Removes the table file, its free space map, its toast and the files of its indexes.
     pub fn delete(&mut self) -> errors::Result<()> {
-        fs::remove_file(&self.file_path)?;
+        for index in self.table.indexes() {
+            self.pager
+                .remove_file(&index.get_file_path(self.storage_dir()));
+        }
+        self.pager
+            .remove_file(&self.table.get_fsm_file_path(self.storage_dir()));
+        self.pager
+            .remove_file(&self.table.get_toast_file_path(self.storage_dir()));
+        self.pager.remove_file(&self.file_path);
         self.page_count = 0;
         Ok(())
     }
 
-    pub fn init(table: &'a Table, file_path: &Path) -> errors::Result<Self> {
-        if fs::exists(file_path)? {
+    pub fn init(pager: &'a Pager, table: &'a Table, file_path: &Path) -> errors::Result<Self> {
+        if pager.exists(file_path)? {
             return Err(DBError::Integrity(format!(
                 "Table {} is already initialized",
                 table.name(),
             )));
         }
-        let file = File::create_new(file_path)?;
-        let empty_page = Page::new(table, 0);
-        let mut writer = BinaryWriter::new(BufWriter::new(file));
-        empty_page.write(&mut writer)?;
-        Ok(Self {
+        let mut page_table = Self {
+            pager,
             table,
             file_path: file_path.to_owned(),
-            page_count: 1,
-        })
+            page_count: 0,
+        };
+        page_table.save_page(&page_table.new_page(0))?;
+        Ok(page_table)
+    }
+
+    fn storage_dir(&self) -> &Path {
+        self.file_path.parent().unwrap()
+    }
+
+    fn toast(&self) -> Toast<'a> {
+        Toast::new(
+            self.pager,
+            self.table.get_toast_file_path(self.storage_dir()),
+        )
+    }
+
+    /// Returns an empty page with the table's toast.
+    fn new_page(&self, page_id: u32) -> Page<'a> {
+        Page::new(self.table, page_id).with_toast(self.toast())
+    }
+
+    fn free_space_map(&self) -> FreeSpaceMap<'a> {
+        FreeSpaceMap::new(self.pager, self.table.get_fsm_file_path(self.storage_dir()))
     }
 
     fn get_page(&self, page_id: u32) -> errors::Result<Page<'a>> {
+        Ok(self.get_pinned_page(page_id)?.0)
+    }
+
+    /// Returns the page with the pin of its buffer pool page, which keeps the page in the
+    /// pool while the page is used.
+    fn get_pinned_page(&self, page_id: u32) -> errors::Result<(Page<'a>, PinnedPage<'a>)> {
         let page_count = self.page_count;
         if page_id >= page_count {
             return Err(DBError::Integrity(format!(
@@ -68,37 +109,127 @@ impl<'a> PageTable<'a> {
                 page_id, page_count,
             )));
         }
-        let mut file = File::open(&self.file_path)?;
-        file.seek(SeekFrom::Start(PAGE_SIZE as u64 * page_id as u64))?;
-        let mut buf = vec![0; PAGE_SIZE];
-        file.read_exact(&mut buf)?;
-        let mut reader = BinaryReader::new(Cursor::new(&buf));
-        Ok(Page::read(self.table, &mut reader)?)
+        #[cfg(test)]
+        PAGE_READS.with(|reads| reads.set(reads.get() + 1));
+        let buf = self.pager.read_page(&self.file_path, page_id)?;
+        let mut reader = BinaryReader::new(Cursor::new(&*buf));
+        let page = Page::read(self.table, &mut reader)?.with_toast(self.toast());
+        Ok((page, buf))
     }
 
     fn save_page(&mut self, page: &Page) -> errors::Result<()> {
-        let mut file = File::options().write(true).open(&self.file_path)?;
-        file.seek(SeekFrom::Start(PAGE_SIZE as u64 * page.id() as u64))?;
-        let mut writer = BinaryWriter::new(BufWriter::new(file));
-        page.write(&mut writer)?;
-        drop(writer);
+        let mut data = Vec::with_capacity(PAGE_SIZE);
+        page.write(&mut BinaryWriter::new(&mut data))?;
+        self.pager.write_page(&self.file_path, page.id(), data);
         if page.id() >= self.page_count {
             self.page_count = page.id() + 1;
         }
-        Ok(())
+        self.free_space_map().set(page.id(), page.free_space())
     }
 
     pub fn iter(&self) -> TableIterator<'a, &PageTable<'a>> {
         self.into_iter()
     }
 
+    pub fn get_tuple(&self, page_id: u32, slot: u16) -> errors::Result<Tuple> {
+        self.get_page(page_id)?.get_tuple(slot)
+    }
+
+    /// Fails if the tuple can't be added to the indexes or violates a key constraint, before
+    /// anything is written. `replaced` is the id of the tuple it replaces, if any.
+    fn check_index_entries(
+        &self,
+        tuple: &Tuple,
+        replaced: Option<(u32, u16)>,
+    ) -> errors::Result<()> {
+        for index in self.table.indexes() {
+            let key = index.key(tuple);
+            check_entry_size(index.name(), &key)?;
+            let Some(constraint) = index.constraint() else {
+                continue;
+            };
+            let column_name = |i: usize| self.table.columns()[index.columns()[i]].name();
+            if let Some(i) = key.iter().position(DBValue::is_null) {
+                if constraint == KeyConstraint::PrimaryKey {
+                    return Err(DBError::Integrity(format!(
+                        "NULL value in column {} violates primary key {}",
+                        column_name(i),
+                        index.name()
+                    )));
+                }
+                continue;
+            }
+            let tree = BTree::open(self.pager, index.get_file_path(self.storage_dir()))?;
+            for tuple_id in
+                tree.range(Bound::Included(key.clone()), Bound::Included(key.clone()))?
+            {
+                if Some(tuple_id?) == replaced {
+                    continue;
+                }
+                let columns = (0..key.len()).map(column_name).collect::<Vec<_>>();
+                let values = key.iter().map(DBValue::to_string).collect::<Vec<_>>();
+                return Err(DBError::Integrity(format!(
+                    "Duplicate key ({}) = ({}) violates unique constraint {}",
+                    columns.join(", "),
+                    values.join(", "),
+                    index.name()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the index entries of the `old` tuple with the entries of the `new` one.
+    fn update_indexes(
+        &self,
+        old: Option<(&Tuple, (u32, u16))>,
+        new: Option<(&Tuple, (u32, u16))>,
+    ) -> errors::Result<()> {
+        for index in self.table.indexes() {
+            let old = old.map(|(tuple, tuple_id)| index.entry(tuple, tuple_id));
+            let new = new.map(|(tuple, tuple_id)| index.entry(tuple, tuple_id));
+            if old == new {
+                continue;
+            }
+            let mut tree = BTree::open(self.pager, index.get_file_path(self.storage_dir()))?;
+            if let Some(old) = old
+                && !tree.delete(&old)?
+            {
+                return Err(DBError::Integrity(format!(
+                    "Index {} has no entry for tuple {:?}",
+                    index.name(),
+                    old.tuple_id
+                )));
+            }
+            if let Some(new) = new {
+                tree.insert(new)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn insert_tuple(&mut self, tuple: &Tuple) -> errors::Result<(u32, u16)> {
+        self.check_index_entries(tuple, None)?;
+        let tuple_id = self.insert_into_pages(tuple)?;
+        self.update_indexes(None, Some((tuple, tuple_id)))?;
+        Ok(tuple_id)
+    }
+
+    /// Adds the tuple to the first page with enough room, or to a new page at the end.
+    fn insert_into_pages(&mut self, tuple: &Tuple) -> errors::Result<(u32, u16)> {
         let page_count = self.page_count;
-        let mut page = self.get_page(page_count - 1)?;
+        // Tables created before the free space map have no entries, but may still have room
+        // in the last page.
+        let candidate = self
+            .free_space_map()
+            .find(Page::required_space(tuple))?
+            .filter(|&page_id| page_id < page_count)
+            .unwrap_or(page_count - 1);
+        let mut page = self.get_page(candidate)?;
         if !page.can_fit_tuple(tuple) {
-            page = Page::new(self.table, self.page_count);
+            page = self.new_page(self.page_count);
         }
-        let res = page.insert_tuple(tuple).map(|offset| (page.id(), offset))?;
+        let res = page.insert_tuple(tuple).map(|slot| (page.id(), slot))?;
         self.save_page(&page)?;
         Ok(res)
     }
@@ -106,28 +237,84 @@ impl<'a> PageTable<'a> {
     pub fn overwrite_tuple(
         &mut self,
         page_id: u32,
-        offset: u16,
+        slot: u16,
         tuple: &Tuple,
     ) -> errors::Result<(u32, u16)> {
+        self.check_index_entries(tuple, Some((page_id, slot)))?;
         let mut page = self.get_page(page_id)?;
-        let mut res = (page_id, offset);
-        if !page.overwrite_tuple(offset, tuple)? {
-            page.mark_tuple_dead(offset)?;
-            if page.can_fit_tuple(tuple) {
-                res.1 = page.insert_tuple(tuple)?;
-            } else {
-                res = self.insert_tuple(tuple)?;
-            }
+        let old = page.get_tuple(slot)?;
+        let mut res = (page_id, slot);
+        if page.overwrite_tuple(slot, tuple)? {
+            self.save_page(&page)?;
+        } else {
+            page.mark_tuple_dead(slot)?;
+            // Saved first, as the tuple may be moved to any page with enough room.
+            self.save_page(&page)?;
+            res = self.insert_into_pages(tuple)?;
         }
-        // TODO: transactions
-        self.save_page(&page)?;
+        self.update_indexes(Some((&old, (page_id, slot))), Some((tuple, res)))?;
         Ok(res)
     }
 
-    pub fn delete_tuple(&mut self, page_id: u32, offset: u16) -> errors::Result<()> {
+    /// Compacts the pages with dead tuples. Tuples keep their slots, so the indexes stay
+    /// valid.
+    pub fn vacuum(&mut self) -> errors::Result<()> {
+        for page_id in 0..self.page_count {
+            let mut page = self.get_page(page_id)?;
+            if page.dead_space() > 0 {
+                page.compact();
+                self.save_page(&page)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrites the table with the live tuples packed into as few pages as possible, cuts
+    /// the file after them and rebuilds the indexes for the new tuple addresses.
+    pub fn vacuum_full(&mut self) -> errors::Result<()> {
+        let mut trees = vec![];
+        for index in self.table.indexes() {
+            let mut tree = BTree::open(self.pager, index.get_file_path(self.storage_dir()))?;
+            tree.clear()?;
+            trees.push((index, tree));
+        }
+        let fsm_path = self.table.get_fsm_file_path(self.storage_dir());
+        if self.pager.exists(&fsm_path)? {
+            self.pager.truncate(&fsm_path, 0)?;
+        }
+
+        // Tuples are packed in order, so a page is never written before it was read. They
+        // are moved in their stored form, so values in the toast stay where they are.
+        let mut new_page = self.new_page(0);
+        for page_id in 0..self.page_count {
+            let page = self.get_page(page_id)?;
+            for slot in page.live_slots() {
+                let data = page.tuple_data(slot)?;
+                if !new_page.can_fit_tuple_data(data.len()) {
+                    self.save_page(&new_page)?;
+                    new_page = self.new_page(new_page.id() + 1);
+                }
+                let tuple_id = (new_page.id(), new_page.insert_tuple_data(data)?);
+                if !trees.is_empty() {
+                    let tuple = page.get_tuple(slot)?;
+                    for (index, tree) in &mut trees {
+                        tree.insert(index.entry(&tuple, tuple_id))?;
+                    }
+                }
+            }
+        }
+        self.save_page(&new_page)?;
+        self.page_count = new_page.id() + 1;
+        self.pager.truncate(&self.file_path, self.page_count)?;
+        Ok(())
+    }
+
+    pub fn delete_tuple(&mut self, page_id: u32, slot: u16) -> errors::Result<()> {
         let mut page = self.get_page(page_id)?;
-        page.mark_tuple_dead(offset)?;
+        let old = page.get_tuple(slot)?;
+        page.mark_tuple_dead(slot)?;
         self.save_page(&page)?;
+        self.update_indexes(Some((&old, (page_id, slot))), None)?;
         Ok(())
     }
 }
@@ -154,6 +341,8 @@ pub(crate) struct TableIterator<'a, PT> {
     page_table: PT,
     page_id: u32,
     page_iterator: Option<PageIterator<Page<'a>>>,
+    /// The current page stays in the buffer pool until the iterator moves to the next one.
+    pin: Option<PinnedPage<'a>>,
     errored: bool,
 }
 
@@ -163,6 +352,7 @@ impl<'a, PT: Borrow<PageTable<'a>>> TableIterator<'a, PT> {
             page_table,
             page_id: 0,
             page_iterator: None,
+            pin: None,
             errored: false,
         }
     }
@@ -179,7 +369,7 @@ impl<'a, PT: Borrow<PageTable<'a>>> Iterator for TableIterator<'a, PT> {
         }
         loop {
             match self.page_iterator.as_mut().and_then(|it| it.next()) {
-                Some(Ok((offset, tuple))) => return Some(Ok((self.page_id, offset, tuple))),
+                Some(Ok((slot, tuple))) => return Some(Ok((self.page_id, slot, tuple))),
                 Some(Err(err)) => {
                     self.errored = true;
                     return Some(Err(err));
@@ -193,9 +383,11 @@ impl<'a, PT: Borrow<PageTable<'a>>> Iterator for TableIterator<'a, PT> {
                     if self.page_id >= page_table.page_count {
                         return None;
                     }
-                    match page_table.get_page(self.page_id) {
-                        Ok(page) => {
+                    self.pin = None;
+                    match page_table.get_pinned_page(self.page_id) {
+                        Ok((page, pin)) => {
                             self.page_iterator = Some(page.into_iter());
+                            self.pin = Some(pin);
                         }
                         Err(err) => {
                             self.errored = true;
@@ -216,7 +408,11 @@ mod test {
     use temp_dir::TempDir;
 
     use crate::{
-        page::test::create_random_tuple, table::test::sample_table, tuple::Tuple, values::DBValue,
+        page::test::{create_random_tuple, create_tuple},
+        pager::Pager,
+        table::test::sample_table,
+        tuple::Tuple,
+        values::DBValue,
     };
 
     use super::PageTable;
@@ -244,7 +440,8 @@ mod test {
         let storage_dir = TempDir::new().unwrap();
         let table = sample_table();
         let table_file_path = table.get_table_file_path(storage_dir.path());
-        let mut page_table = PageTable::init(&table, &table_file_path).unwrap();
+        let pager = Pager::new(storage_dir.path().join("wal"));
+        let mut page_table = PageTable::init(&pager, &table, &table_file_path).unwrap();
         let mut tuples_map = HashMap::new();
         let mut id = 0;
 
@@ -259,24 +456,72 @@ mod test {
                 }
                 let id = *tuples_map.keys().choose(&mut rand::rng()).unwrap();
                 for tuple in page_table.iter() {
-                    let (page_id, offset, tuple) = tuple.unwrap();
+                    let (page_id, slot, tuple) = tuple.unwrap();
                     if tuple.values[0] == DBValue::Int(id) {
-                        page_table.delete_tuple(page_id, offset).unwrap();
+                        page_table.delete_tuple(page_id, slot).unwrap();
                         break;
                     }
                 }
                 tuples_map.remove(&id);
             } else {
                 let tuple = create_random_tuple(id);
-                let (page_id, offset) = page_table.insert_tuple(&tuple).unwrap();
-                tuples_map.insert(id, (page_id, offset, tuple));
+                let (page_id, slot) = page_table.insert_tuple(&tuple).unwrap();
+                tuples_map.insert(id, (page_id, slot, tuple));
                 id += 1;
             }
             if i % 1000 == 0 {
                 validate_tuples(&page_table, &tuples_map);
+                pager.commit_statement().unwrap();
             }
         }
 
         assert!(page_table.page_count > 10);
     }
+
+    #[test]
+    fn test_inserts_reuse_free_space() {
+        let storage_dir = TempDir::new().unwrap();
+        let table = sample_table();
+        let table_file_path = table.get_table_file_path(storage_dir.path());
+        let pager = Pager::new(storage_dir.path().join("wal"));
+        let mut page_table = PageTable::init(&pager, &table, &table_file_path).unwrap();
+        // Four of these fill a page, leaving 494 bytes.
+        let big_name = "f".repeat(1900);
+        for id in 0..12 {
+            let tuple = create_tuple(id, &big_name, 1.0, true);
+            assert_eq!(page_table.insert_tuple(&tuple).unwrap().0, id as u32 / 4);
+        }
+        pager.commit_statement().unwrap();
+        pager.checkpoint().unwrap();
+        drop(page_table);
+
+        // The map is read back from disk, and small tuples go to the first pages.
+        let pager = Pager::new(storage_dir.path().join("wal2"));
+        let mut page_table = table.get_page_table(storage_dir.path(), &pager).unwrap();
+        let small = create_tuple(20, &"m".repeat(200), 1.0, true);
+        let first = page_table.insert_tuple(&small).unwrap();
+        assert_eq!(first.0, 0);
+        assert_eq!(page_table.insert_tuple(&small).unwrap().0, 0);
+        let third = page_table.insert_tuple(&small).unwrap();
+        assert_eq!(third.0, 1);
+
+        // A tuple that grows keeps its slot if its page has room.
+        let grown = create_tuple(20, &"m".repeat(400), 1.0, true);
+        assert_eq!(
+            page_table
+                .overwrite_tuple(third.0, third.1, &grown)
+                .unwrap(),
+            third
+        );
+        assert_eq!(page_table.get_tuple(third.0, third.1).unwrap(), grown);
+
+        // Otherwise it moves to a page with room.
+        let grown = create_tuple(20, &"m".repeat(300), 1.0, true);
+        let moved = page_table
+            .overwrite_tuple(first.0, first.1, &grown)
+            .unwrap();
+        assert_eq!(moved.0, 2);
+        assert_eq!(page_table.get_tuple(moved.0, moved.1).unwrap(), grown);
+        assert_eq!(page_table.page_count, 3);
+    }
 }
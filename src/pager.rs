@@ -0,0 +1,673 @@
+use std::{
+    cell::{RefCell, RefMut},
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    buffer_pool::{BufferPool, DEFAULT_CAPACITY, EvictionPolicy},
+    errors::{self, DBError},
+    page::PAGE_SIZE,
+    serializer::{BinaryReader, BinaryWriter},
+    wal::{Wal, sync_dir},
+};
+
+/// Size of the log after which the dirty pages are written to the files and the log is
+/// emptied.
+const CHECKPOINT_WAL_SIZE: u64 = 16 << 20;
+
+#[cfg(test)]
+thread_local! {
+    /// Makes applying the commits of the current thread fail after writing the log, as if
+    /// the files couldn't be written or the process crashed before they were.
+    pub(crate) static CRASH_AFTER_LOGGING: std::cell::Cell<bool> =
+        const { std::cell::Cell::new(false) };
+}
+
+/// Pages and files changed by a statement or a transaction that are not on disk yet.
+#[derive(Default)]
+struct WriteSet {
+    pages: HashMap<PathBuf, BTreeMap<u32, Vec<u8>>>,
+    /// Whole files that are replaced, e.g. the catalog.
+    files: HashMap<PathBuf, Vec<u8>>,
+    removed: HashSet<PathBuf>,
+    /// Files cut to a number of pages. They are cut before the pages are written.
+    truncated: HashMap<PathBuf, u32>,
+}
+
+impl WriteSet {
+    fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+            && self.files.is_empty()
+            && self.removed.is_empty()
+            && self.truncated.is_empty()
+    }
+
+    fn remove(&mut self, path: PathBuf) {
+        self.pages.remove(&path);
+        self.files.remove(&path);
+        self.truncated.remove(&path);
+        self.removed.insert(path);
+    }
+
+    fn truncate(&mut self, path: PathBuf, page_count: u32) {
+        if let Some(pages) = self.pages.get_mut(&path) {
+            pages.retain(|&page_id, _| page_id < page_count);
+        }
+        let truncated = self.truncated.entry(path).or_insert(page_count);
+        *truncated = (*truncated).min(page_count);
+    }
+
+    /// Adds the changes of a later write set.
+    fn merge(&mut self, other: WriteSet) {
+        for path in other.removed {
+            self.remove(path);
+        }
+        for (path, page_count) in other.truncated {
+            self.truncate(path, page_count);
+        }
+        for (path, pages) in other.pages {
+            self.pages.entry(path).or_default().extend(pages);
+        }
+        self.files.extend(other.files);
+    }
+
+    /// Writes the changes in the format of a log batch. Paths are stored relative to the
+    /// log directory, as all the files are in the storage directory.
+    fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        let file_name = |path: &Path| path.file_name().unwrap().to_string_lossy().into_owned();
+        writer.write_u32(self.pages.values().map(|pages| pages.len() as u32).sum())?;
+        for (path, pages) in &self.pages {
+            for (&page_id, data) in pages {
+                writer.write_string(&file_name(path))?;
+                writer.write_u32(page_id)?;
+                writer.write_all(data)?;
+            }
+        }
+        writer.write_u32(self.files.len() as u32)?;
+        for (path, data) in &self.files {
+            writer.write_string(&file_name(path))?;
+            writer.write_u32(data.len() as u32)?;
+            writer.write_all(data)?;
+        }
+        writer.write_u32(self.removed.len() as u32)?;
+        for path in &self.removed {
+            writer.write_string(&file_name(path))?;
+        }
+        writer.write_u32(self.truncated.len() as u32)?;
+        for (path, &page_count) in &self.truncated {
+            writer.write_string(&file_name(path))?;
+            writer.write_u32(page_count)?;
+        }
+        Ok(())
+    }
+
+    fn read(reader: &mut BinaryReader<impl Read>, dir: &Path) -> io::Result<Self> {
+        let mut changes = Self::default();
+        for _ in 0..reader.read_u32()? {
+            let path = dir.join(reader.read_string()?);
+            let page_id = reader.read_u32()?;
+            let mut data = vec![0; PAGE_SIZE];
+            reader.read_exact(&mut data)?;
+            changes.pages.entry(path).or_default().insert(page_id, data);
+        }
+        for _ in 0..reader.read_u32()? {
+            let path = dir.join(reader.read_string()?);
+            let mut data = vec![0; reader.read_u32()? as usize];
+            reader.read_exact(&mut data)?;
+            changes.files.insert(path, data);
+        }
+        for _ in 0..reader.read_u32()? {
+            changes.removed.insert(dir.join(reader.read_string()?));
+        }
+        for _ in 0..reader.read_u32()? {
+            let path = dir.join(reader.read_string()?);
+            changes.truncated.insert(path, reader.read_u32()?);
+        }
+        Ok(changes)
+    }
+
+    /// Writes the changes to the files in `dir`, bypassing the buffer pool, and syncs them.
+    /// Applying the same changes again gives the same result, so a log batch may be replayed
+    /// after a crash in the middle of this. Files are removed first, as anything written to
+    /// a removed path was written after the removal.
+    fn apply(self, dir: &Path) -> io::Result<()> {
+        replace_files(self.files, self.removed, dir)?;
+        for (path, &page_count) in &self.truncated {
+            truncate_file(path, page_count)?;
+        }
+        for (path, pages) in self.pages {
+            let mut file = File::options()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)?;
+            for (page_id, data) in pages {
+                file.seek(SeekFrom::Start(PAGE_SIZE as u64 * page_id as u64))?;
+                file.write_all(&data)?;
+            }
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Cuts the file to at most `page_count` pages and syncs it.
+fn truncate_file(path: &Path, page_count: u32) -> io::Result<()> {
+    let file = match File::options().write(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let len = PAGE_SIZE as u64 * page_count as u64;
+    if file.metadata()?.len() > len {
+        file.set_len(len)?;
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Removes files and then writes whole files in `dir`, and syncs them. Files are replaced
+/// by renaming a new file over them, so they are never left truncated.
+fn replace_files(
+    files: HashMap<PathBuf, Vec<u8>>,
+    removed: HashSet<PathBuf>,
+    dir: &Path,
+) -> io::Result<()> {
+    if files.is_empty() && removed.is_empty() {
+        return Ok(());
+    }
+    for path in removed {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    for (path, data) in files {
+        let temp_path = path.with_extension("new");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(temp_path, path)?;
+    }
+    sync_dir(dir)
+}
+
+#[derive(Default)]
+struct PagerState {
+    statement: WriteSet,
+    transaction: Option<WriteSet>,
+    /// Error of applying a commit that was logged. The files and the buffer pool may miss
+    /// the commit until the log is replayed on the next load, so the pager is not used
+    /// anymore and the log is not emptied.
+    poisoned: Option<String>,
+}
+
+impl PagerState {
+    /// Write sets from the newest to the oldest.
+    fn layers(&self) -> impl Iterator<Item = &WriteSet> {
+        [Some(&self.statement), self.transaction.as_ref()]
+            .into_iter()
+            .flatten()
+    }
+}
+
+/// Reads and writes the pages of table and index files. Changes are kept in memory until
+/// the statement or the explicit transaction making them commits, so a failed statement or
+/// a rolled back transaction never leaves anything on disk.
+///
+/// Changes of a statement inside a transaction are kept separately from the changes of the
+/// earlier statements, so a failed statement is undone without aborting the transaction.
+///
+/// Committed changes are written to the [`Wal`] and then to the [`BufferPool`], which
+/// writes them to the files later. The log is emptied by [`Pager::checkpoint`] once all the
+/// pages are written, and is replayed by [`Pager::recover`] after a crash.
+pub(crate) struct Pager {
+    state: RefCell<PagerState>,
+    pool: RefCell<BufferPool>,
+    wal: Wal,
+}
+
+/// Page read through the [`Pager`]. A page of the buffer pool stays pinned, so it can't be
+/// evicted, until this is dropped.
+pub(crate) struct PinnedPage<'a> {
+    pager: &'a Pager,
+    pinned: Option<(PathBuf, u32)>,
+    data: Rc<Vec<u8>>,
+}
+
+impl Deref for PinnedPage<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Drop for PinnedPage<'_> {
+    fn drop(&mut self) {
+        if let Some((path, page_id)) = &self.pinned {
+            self.pager
+                .pool
+                .borrow_mut()
+                .unpin(path, *page_id, &self.data);
+        }
+    }
+}
+
+impl Pager {
+    pub fn new(wal_path: PathBuf) -> Self {
+        Self {
+            state: RefCell::default(),
+            pool: RefCell::new(BufferPool::new(DEFAULT_CAPACITY, EvictionPolicy::Lru)),
+            wal: Wal::new(wal_path),
+        }
+    }
+
+    pub fn pool(&self) -> RefMut<'_, BufferPool> {
+        self.pool.borrow_mut()
+    }
+
+    /// Applies the changes of the commits that were logged but may not have been written to
+    /// the files because of a crash.
+    pub fn recover(&self) -> io::Result<()> {
+        for batch in self.wal.read_batches()? {
+            let changes = WriteSet::read(&mut BinaryReader::new(batch.as_slice()), self.wal.dir())?;
+            changes.apply(self.wal.dir())?;
+        }
+        self.wal.truncate()
+    }
+
+    /// Writes all dirty pages of the buffer pool to the files and empties the log.
+    pub fn checkpoint(&self) -> io::Result<()> {
+        if self.state.borrow().poisoned.is_some() {
+            return Err(io::Error::other("The log has a commit that wasn't applied"));
+        }
+        self.pool.borrow_mut().flush()?;
+        sync_dir(self.wal.dir())?;
+        self.wal.truncate()
+    }
+
+    /// Fails if a logged commit couldn't be applied, so the database has to be loaded again.
+    pub fn check_poisoned(&self) -> errors::Result<()> {
+        match &self.state.borrow().poisoned {
+            Some(msg) => Err(DBError::Execution(format!(
+                "The last commit is logged but couldn't be applied ({}), the database has to \
+                 be loaded again",
+                msg
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Logs the changes and applies them. The commit is durable once it's logged, as the log
+    /// is replayed on the next load, so errors after that don't fail it: a failed checkpoint
+    /// is retried after the next commit, and a failure to apply the changes poisons the pager.
+    fn write_to_disk(&self, changes: WriteSet) -> errors::Result<()> {
+        let mut batch = vec![];
+        changes.write(&mut BinaryWriter::new(&mut batch))?;
+        self.wal.append(&batch)?;
+        if let Err(err) = self.apply_logged(changes) {
+            self.state.borrow_mut().poisoned = Some(err.to_string());
+            return Ok(());
+        }
+        if self.wal.size().is_ok_and(|size| size > CHECKPOINT_WAL_SIZE) {
+            let _ = self.checkpoint();
+        }
+        Ok(())
+    }
+
+    /// Writes logged changes to the buffer pool, and the whole files to disk.
+    fn apply_logged(&self, changes: WriteSet) -> errors::Result<()> {
+        #[cfg(test)]
+        if CRASH_AFTER_LOGGING.with(|crash| crash.get()) {
+            return Err(io::Error::other("Simulated crash").into());
+        }
+        let mut pool = self.pool.borrow_mut();
+        for path in &changes.removed {
+            pool.remove_file(path);
+        }
+        replace_files(changes.files, changes.removed, self.wal.dir())?;
+        for (path, &page_count) in &changes.truncated {
+            pool.truncate_file(path, page_count);
+            truncate_file(path, page_count)?;
+        }
+        for (path, pages) in changes.pages {
+            for (page_id, data) in pages {
+                pool.insert(&path, page_id, data, true)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_page(&self, path: &Path, page_id: u32) -> errors::Result<PinnedPage<'_>> {
+        let state = self.state.borrow();
+        for layer in state.layers() {
+            if let Some(data) = layer.pages.get(path).and_then(|pages| pages.get(&page_id)) {
+                return Ok(PinnedPage {
+                    pager: self,
+                    pinned: None,
+                    data: Rc::new(data.clone()),
+                });
+            }
+            if layer.removed.contains(path)
+                || layer
+                    .truncated
+                    .get(path)
+                    .is_some_and(|&page_count| page_id >= page_count)
+            {
+                return Err(io::Error::from(io::ErrorKind::NotFound).into());
+            }
+        }
+        let mut pool = self.pool.borrow_mut();
+        let frame = match pool.find(path, page_id) {
+            Some(frame) => frame,
+            None => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(PAGE_SIZE as u64 * page_id as u64))?;
+                let mut data = vec![0; PAGE_SIZE];
+                file.read_exact(&mut data)?;
+                pool.insert(path, page_id, data, false)?
+            }
+        };
+        pool.pin(frame);
+        Ok(PinnedPage {
+            pager: self,
+            pinned: Some((path.to_owned(), page_id)),
+            data: pool.data(frame),
+        })
+    }
+
+    /// Writes a page, padding it to [`PAGE_SIZE`]. The file is created if it doesn't exist.
+    pub fn write_page(&self, path: &Path, page_id: u32, mut data: Vec<u8>) {
+        data.resize(PAGE_SIZE, 0);
+        let mut state = self.state.borrow_mut();
+        state
+            .statement
+            .pages
+            .entry(path.to_owned())
+            .or_default()
+            .insert(page_id, data);
+    }
+
+    /// Returns the number of pages in the file, including the pages written but not
+    /// committed yet. Fails if the file doesn't exist.
+    pub fn page_count(&self, path: &Path) -> io::Result<u32> {
+        let state = self.state.borrow();
+        let mut page_count = None;
+        for layer in state.layers() {
+            if let Some((&last, _)) = layer
+                .pages
+                .get(path)
+                .and_then(|pages| pages.last_key_value())
+            {
+                page_count = page_count.max(Some(last + 1));
+            }
+            if layer.removed.contains(path) {
+                return page_count.ok_or(io::ErrorKind::NotFound.into());
+            }
+            if let Some(&truncated) = layer.truncated.get(path) {
+                return Ok(page_count.unwrap_or(0).max(truncated));
+            }
+        }
+        // Pages added by commits may be only in the buffer pool.
+        page_count = page_count.max(self.pool.borrow().page_count(path));
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && page_count.is_some() => {
+                return Ok(page_count.unwrap());
+            }
+            Err(err) => return Err(err),
+        };
+        if metadata.len() % PAGE_SIZE as u64 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Page file size is not a multiple of page size",
+            ));
+        }
+        let file_page_count = (metadata.len() / PAGE_SIZE as u64) as u32;
+        Ok(page_count.unwrap_or(0).max(file_page_count))
+    }
+
+    pub fn exists(&self, path: &Path) -> io::Result<bool> {
+        match self.page_count(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let state = self.state.borrow();
+        for layer in state.layers() {
+            if let Some(data) = layer.files.get(path) {
+                return Ok(data.clone());
+            }
+            if layer.removed.contains(path) {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+        }
+        fs::read(path)
+    }
+
+    /// Replaces the whole file.
+    pub fn write_file(&self, path: &Path, data: Vec<u8>) {
+        let mut state = self.state.borrow_mut();
+        state.statement.files.insert(path.to_owned(), data);
+    }
+
+    pub fn remove_file(&self, path: &Path) {
+        self.state.borrow_mut().statement.remove(path.to_owned());
+    }
+
+    /// Removes the pages of the file from `page_count` on. Fails if the file doesn't exist.
+    pub fn truncate(&self, path: &Path, page_count: u32) -> io::Result<()> {
+        if page_count < self.page_count(path)? {
+            let mut state = self.state.borrow_mut();
+            state.statement.truncate(path.to_owned(), page_count);
+        }
+        Ok(())
+    }
+
+    /// Makes the changes of the current statement part of the transaction, or writes them
+    /// to disk if there is no explicit transaction.
+    pub fn commit_statement(&self) -> errors::Result<()> {
+        let mut state = self.state.borrow_mut();
+        let statement = std::mem::take(&mut state.statement);
+        if let Some(transaction) = &mut state.transaction {
+            transaction.merge(statement);
+            return Ok(());
+        }
+        drop(state);
+        if statement.is_empty() {
+            return Ok(());
+        }
+        self.write_to_disk(statement)
+    }
+
+    /// Discards the changes of the current statement.
+    pub fn rollback_statement(&self) {
+        self.state.borrow_mut().statement = WriteSet::default();
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.state.borrow().transaction.is_some()
+    }
+
+    pub fn begin(&self) -> errors::Result<()> {
+        let mut state = self.state.borrow_mut();
+        if state.transaction.is_some() {
+            return Err(DBError::Execution(
+                "There is already a transaction in progress".to_owned(),
+            ));
+        }
+        state.transaction = Some(WriteSet::default());
+        Ok(())
+    }
+
+    pub fn commit(&self) -> errors::Result<()> {
+        let transaction = self.take_transaction()?;
+        if !transaction.is_empty() {
+            self.write_to_disk(transaction)?;
+        }
+        Ok(())
+    }
+
+    pub fn rollback(&self) -> errors::Result<()> {
+        self.take_transaction()?;
+        Ok(())
+    }
+
+    fn take_transaction(&self) -> errors::Result<WriteSet> {
+        let mut state = self.state.borrow_mut();
+        debug_assert!(state.statement.is_empty());
+        state
+            .transaction
+            .take()
+            .ok_or_else(|| DBError::Execution("There is no transaction in progress".to_owned()))
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        // The log is replayed on the next load if this fails.
+        let _ = self.checkpoint();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use temp_dir::TempDir;
+
+    use crate::page::PAGE_SIZE;
+
+    use super::{CRASH_AFTER_LOGGING, Pager};
+
+    #[test]
+    fn test_pager() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test");
+        let page = |byte: u8| vec![byte; PAGE_SIZE];
+        let pager = Pager::new(dir.path().join("wal"));
+
+        assert!(!pager.exists(&path).unwrap());
+        pager.write_page(&path, 1, vec![1]);
+        assert_eq!(pager.page_count(&path).unwrap(), 2);
+        assert!(!path.exists());
+        pager.commit_statement().unwrap();
+        // Committed pages are in the buffer pool until a checkpoint.
+        assert!(!path.exists());
+        assert_eq!(pager.page_count(&path).unwrap(), 2);
+        pager.checkpoint().unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            2 * PAGE_SIZE as u64
+        );
+
+        // A failed statement inside a transaction doesn't abort it.
+        pager.begin().unwrap();
+        pager.write_page(&path, 0, page(2));
+        pager.commit_statement().unwrap();
+        pager.write_page(&path, 0, page(3));
+        pager.write_page(&path, 2, page(3));
+        assert_eq!(*pager.read_page(&path, 0).unwrap(), page(3));
+        assert_eq!(pager.page_count(&path).unwrap(), 3);
+        pager.rollback_statement();
+        assert_eq!(*pager.read_page(&path, 0).unwrap(), page(2));
+        assert_eq!(pager.page_count(&path).unwrap(), 2);
+        assert_eq!(std::fs::read(&path).unwrap()[0], 0);
+        pager.commit().unwrap();
+        pager.checkpoint().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap()[0], 2);
+
+        pager.begin().unwrap();
+        pager.remove_file(&path);
+        pager.commit_statement().unwrap();
+        assert!(!pager.exists(&path).unwrap());
+        assert!(pager.read_page(&path, 0).is_err());
+        pager.rollback().unwrap();
+        assert_eq!(pager.read_page(&path, 1).unwrap()[0], 1);
+        assert!(pager.rollback().is_err());
+    }
+
+    #[test]
+    fn test_rewrite_removed() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test");
+        let page = |byte: u8| vec![byte; PAGE_SIZE];
+        let pager = Pager::new(dir.path().join("wal"));
+        pager.write_page(&path, 0, page(1));
+        pager.write_page(&path, 1, page(1));
+        pager.commit_statement().unwrap();
+        pager.checkpoint().unwrap();
+
+        // The file is removed before the pages written after the removal.
+        pager.begin().unwrap();
+        pager.remove_file(&path);
+        pager.commit_statement().unwrap();
+        pager.write_page(&path, 0, page(2));
+        pager.commit_statement().unwrap();
+        pager.commit().unwrap();
+        assert_eq!(pager.page_count(&path).unwrap(), 1);
+        assert_eq!(*pager.read_page(&path, 0).unwrap(), page(2));
+        pager.checkpoint().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), page(2));
+
+        // Also when the log is replayed.
+        pager.remove_file(&path);
+        pager.write_page(&path, 0, page(3));
+        CRASH_AFTER_LOGGING.with(|crash| crash.set(true));
+        pager.commit_statement().unwrap();
+        CRASH_AFTER_LOGGING.with(|crash| crash.set(false));
+        assert!(pager.check_poisoned().is_err());
+        // The log isn't emptied before it's replayed.
+        assert!(pager.checkpoint().is_err());
+        drop(pager);
+        Pager::new(dir.path().join("wal")).recover().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), page(3));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test");
+        let page = |byte: u8| vec![byte; PAGE_SIZE];
+        let pager = Pager::new(dir.path().join("wal"));
+        for page_id in 0..4 {
+            pager.write_page(&path, page_id, page(page_id as u8));
+        }
+        pager.commit_statement().unwrap();
+        pager.checkpoint().unwrap();
+
+        pager.truncate(&path, 1).unwrap();
+        assert_eq!(pager.page_count(&path).unwrap(), 1);
+        assert!(pager.read_page(&path, 2).is_err());
+        // Pages written after the cut are kept.
+        pager.write_page(&path, 1, page(5));
+        assert_eq!(pager.page_count(&path).unwrap(), 2);
+        pager.commit_statement().unwrap();
+        assert_eq!(pager.page_count(&path).unwrap(), 2);
+        assert_eq!(*pager.read_page(&path, 1).unwrap(), page(5));
+        pager.checkpoint().unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            2 * PAGE_SIZE as u64
+        );
+
+        // Cut pages are gone from the buffer pool too.
+        pager.write_page(&path, 2, page(6));
+        pager.commit_statement().unwrap();
+        pager.truncate(&path, 2).unwrap();
+        pager.commit_statement().unwrap();
+        assert_eq!(pager.page_count(&path).unwrap(), 2);
+        pager.checkpoint().unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            2 * PAGE_SIZE as u64
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    errors::{self, DBError},
+    serializer::{BinaryReader, BinaryWriter},
+};
+
+/// Generator of integers, e.g. for the ids of a table, created with CREATE SEQUENCE or for
+/// a `serial` column.
+///
+/// The catalog has the definition of the sequence, and the last value taken is in a file of
+/// its own, see [`Sequences::next`].
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct Sequence {
+    /// Number of the sequence's file, unique like the ids of tables and indexes.
+    id: u32,
+    start: i64,
+    increment: i64,
+    /// Table and column ids of the `serial` column the sequence was created for, which it's
+    /// dropped with.
+    owner: Option<(u32, u32)>,
+}
+
+impl Sequence {
+    pub fn new(start: i64, increment: i64) -> Self {
+        Self {
+            id: 0,
+            start,
+            increment,
+            owner: None,
+        }
+    }
+
+    pub fn with_id(self, id: u32) -> Self {
+        Self { id, ..self }
+    }
+
+    pub fn with_owner(self, table_id: u32, column_id: u32) -> Self {
+        Self {
+            owner: Some((table_id, column_id)),
+            ..self
+        }
+    }
+
+    pub fn owner(&self) -> Option<(u32, u32)> {
+        self.owner
+    }
+
+    /// File with the last value taken, empty before the first one.
+    pub fn get_file_path(&self, storage_dir: &Path) -> PathBuf {
+        storage_dir.join(format!("{}.seq", self.id))
+    }
+
+    fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        let id = reader.read_u32()?;
+        let start = reader.read_i64()?;
+        let increment = reader.read_i64()?;
+        let owner = match reader.read_bool()? {
+            true => Some((reader.read_u32()?, reader.read_u32()?)),
+            false => None,
+        };
+        Ok(Self {
+            id,
+            start,
+            increment,
+            owner,
+        })
+    }
+
+    fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_u32(self.id)?;
+        writer.write_i64(self.start)?;
+        writer.write_i64(self.increment)?;
+        writer.write_bool(self.owner.is_some())?;
+        if let Some((table_id, column_id)) = self.owner {
+            writer.write_u32(table_id)?;
+            writer.write_u32(column_id)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct SequenceSet {
+    storage_dir: PathBuf,
+    sequences: BTreeMap<String, Sequence>,
+    /// Values of the last nextval of the sequences in this session, returned by currval.
+    session: HashMap<String, i64>,
+}
+
+/// The sequences of the catalog, shared with the compiled expressions that take values from
+/// them.
+///
+/// Other connections to the storage directory, possibly in other processes, take values
+/// from the same sequences, so values are taken outside of transactions: each one is saved
+/// before it's returned, and isn't given back if the statement or its transaction is rolled
+/// back. No value is taken twice, even after a crash.
+#[derive(Debug, Clone)]
+pub(crate) struct Sequences(Rc<RefCell<SequenceSet>>);
+
+impl PartialEq for Sequences {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Sequences {
+    pub fn new(storage_dir: PathBuf) -> Self {
+        Self(Rc::new(RefCell::new(SequenceSet {
+            storage_dir,
+            sequences: BTreeMap::new(),
+            session: HashMap::new(),
+        })))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.borrow().sequences.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Sequence> {
+        self.0.borrow().sequences.get(name).cloned()
+    }
+
+    /// Adds a sequence, with an empty file as no value is taken yet. The file is created
+    /// right away, so it's left behind if the statement is rolled back, but it's emptied again
+    /// if a later sequence gets the same id.
+    pub fn insert(&self, name: String, sequence: Sequence) -> io::Result<()> {
+        let mut set = self.0.borrow_mut();
+        File::create(sequence.get_file_path(&set.storage_dir))?.sync_all()?;
+        set.sequences.insert(name, sequence);
+        Ok(())
+    }
+
+    /// Removes the sequence from the catalog. Its file is removed by the caller when the
+    /// statement commits.
+    pub fn remove(&self, name: &str) -> Option<Sequence> {
+        let mut set = self.0.borrow_mut();
+        set.session.remove(name);
+        set.sequences.remove(name)
+    }
+
+    /// Names of the sequences of the `serial` columns of the table, or of one of its columns.
+    pub fn owned_by(&self, table_id: u32, column_id: Option<u32>) -> Vec<String> {
+        self.0
+            .borrow()
+            .sequences
+            .iter()
+            .filter(|(_, sequence)| {
+                sequence.owner.is_some_and(|(owner_table, owner_column)| {
+                    owner_table == table_id && column_id.is_none_or(|id| id == owner_column)
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Takes the next value of the sequence. The last value is read from the sequence's file
+    /// and the new one written there while the file is locked, so connections sharing the
+    /// file never take the same value. The value is synced before it's returned, and as it's
+    /// a single small write, a crash doesn't leave it torn.
+    pub fn next(&self, name: &str) -> errors::Result<i64> {
+        let mut set = self.0.borrow_mut();
+        let does_not_exist = || DBError::Execution(format!("Sequence {} does not exist", name));
+        let sequence = set.sequences.get(name).ok_or_else(does_not_exist)?;
+        let mut file = match File::options()
+            .read(true)
+            .write(true)
+            .open(sequence.get_file_path(&set.storage_dir))
+        {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(does_not_exist()),
+            file => file?,
+        };
+        // Unlocked when the file is closed.
+        file.lock()?;
+        let mut last = vec![];
+        file.read_to_end(&mut last)?;
+        let value = match <[u8; 8]>::try_from(last) {
+            Err(_) => sequence.start,
+            Ok(last) => i64::from_be_bytes(last)
+                .checked_add(sequence.increment)
+                .ok_or_else(|| {
+                    DBError::Execution(format!("Sequence {} reached its limit", name))
+                })?,
+        };
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&value.to_be_bytes())?;
+        file.sync_data()?;
+        set.session.insert(name.to_owned(), value);
+        Ok(value)
+    }
+
+    /// The value of the last nextval of the sequence in this session.
+    pub fn current(&self, name: &str) -> errors::Result<i64> {
+        self.0.borrow().session.get(name).copied().ok_or_else(|| {
+            DBError::Execution(format!(
+                "currval of sequence {} is not yet defined in this session",
+                name
+            ))
+        })
+    }
+
+    /// Replaces the sequences with the ones of the catalog. The values of this session stay.
+    pub fn read(&self, reader: &mut BinaryReader<impl Read>) -> io::Result<()> {
+        let count = reader.read_u32()? as usize;
+        let mut sequences = BTreeMap::new();
+        for _ in 0..count {
+            let name = reader.read_string()?;
+            sequences.insert(name, Sequence::from_reader(reader)?);
+        }
+        self.0.borrow_mut().sequences = sequences;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        let set = self.0.borrow();
+        writer.write_u32(set.sequences.len() as u32)?;
+        for (name, sequence) in &set.sequences {
+            writer.write_string(name)?;
+            sequence.write(writer)?;
+        }
+        Ok(())
+    }
+}
@@ -31,11 +31,22 @@ impl<R: Read> BinaryReader<R> {
     }
 
     pub fn read_string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.read_bytes()?)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8"))
+    }
+
+    /// Reads bytes preceded by their length.
+    pub fn read_bytes(&mut self) -> io::Result<Vec<u8>> {
         let len = self.read_u32()? as usize;
         let mut buffer = vec![0; len];
         self.reader.read_exact(&mut buffer)?;
-        String::from_utf8(buffer)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8"))
+        Ok(buffer)
+    }
+
+    pub fn read_f32(&mut self) -> io::Result<f32> {
+        let mut buffer = [0; 4];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(f32::from_be_bytes(buffer))
     }
 
     pub fn read_f64(&mut self) -> io::Result<f64> {
@@ -49,11 +60,23 @@ impl<R: Read> BinaryReader<R> {
         Ok(byte != 0)
     }
 
+    pub fn read_i16(&mut self) -> io::Result<i16> {
+        let mut buffer = [0; 2];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(i16::from_be_bytes(buffer))
+    }
+
     pub fn read_i32(&mut self) -> io::Result<i32> {
         let mut buffer = [0; 4];
         self.reader.read_exact(&mut buffer)?;
         Ok(i32::from_be_bytes(buffer))
     }
+
+    pub fn read_i64(&mut self) -> io::Result<i64> {
+        let mut buffer = [0; 8];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(i64::from_be_bytes(buffer))
+    }
 }
 
 impl<R: Read> Read for BinaryReader<R> {
@@ -71,6 +94,10 @@ impl<W: Write> BinaryWriter<W> {
         Self { writer }
     }
 
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
     pub fn write_u8(&mut self, value: u8) -> io::Result<()> {
         self.writer.write_all(slice::from_ref(&value))
     }
@@ -84,9 +111,16 @@ impl<W: Write> BinaryWriter<W> {
     }
 
     pub fn write_string(&mut self, value: &str) -> io::Result<()> {
-        let bytes = value.as_bytes();
-        self.write_u32(bytes.len() as u32)?;
-        self.writer.write_all(bytes)
+        self.write_bytes(value.as_bytes())
+    }
+
+    pub fn write_bytes(&mut self, value: &[u8]) -> io::Result<()> {
+        self.write_u32(value.len() as u32)?;
+        self.writer.write_all(value)
+    }
+
+    pub fn write_f32(&mut self, value: f32) -> io::Result<()> {
+        self.writer.write_all(&value.to_be_bytes())
     }
 
     pub fn write_f64(&mut self, value: f64) -> io::Result<()> {
@@ -98,9 +132,17 @@ impl<W: Write> BinaryWriter<W> {
         self.write_u8(byte)
     }
 
+    pub fn write_i16(&mut self, value: i16) -> io::Result<()> {
+        self.writer.write_all(&value.to_be_bytes())
+    }
+
     pub fn write_i32(&mut self, value: i32) -> io::Result<()> {
         self.writer.write_all(&value.to_be_bytes())
     }
+
+    pub fn write_i64(&mut self, value: i64) -> io::Result<()> {
+        self.writer.write_all(&value.to_be_bytes())
+    }
 }
 
 impl<W: Write> Write for BinaryWriter<W> {
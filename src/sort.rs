@@ -0,0 +1,274 @@
+use std::{cmp::Ordering, mem, path::PathBuf, vec};
+
+use crate::{
+    errors,
+    spill::{SpillFile, SpillRows, row_memory_size},
+    tuple::Tuple,
+    values::DBValue,
+};
+
+/// Maximum number of runs merged at once, bounding the number of open files.
+const MERGE_FAN_IN: usize = 64;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub(crate) struct SortKey {
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl SortKey {
+    pub fn compare(&self, a: &DBValue, b: &DBValue) -> Ordering {
+        // NULL placement doesn't depend on the direction.
+        match (a.is_null(), b.is_null()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) if self.nulls_first => return Ordering::Less,
+            (true, false) => return Ordering::Greater,
+            (false, true) if self.nulls_first => return Ordering::Greater,
+            (false, true) => return Ordering::Less,
+            (false, false) => {}
+        }
+        let ordering = match (a, b) {
+            (DBValue::Real(_) | DBValue::Double(_), DBValue::Real(_) | DBValue::Double(_)) => {
+                a.as_f64().unwrap().total_cmp(&b.as_f64().unwrap())
+            }
+            _ => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Compares rows by their sort key values, which are stored after the output columns.
+fn compare_rows(keys: &[SortKey], a: &Tuple, b: &Tuple) -> Ordering {
+    let a = &a.values[a.values.len() - keys.len()..];
+    let b = &b.values[b.values.len() - keys.len()..];
+    keys.iter()
+        .zip(a.iter().zip(b))
+        .map(|(key, (a, b))| key.compare(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Stable sort of rows that have their sort key values appended. Rows are sorted in memory
+/// until they exceed `memory_limit` bytes, then written to temp files as sorted runs that
+/// are merged when reading the result.
+pub(crate) struct Sorter {
+    keys: Vec<SortKey>,
+    temp_dir: PathBuf,
+    memory_limit: usize,
+    rows: Vec<Tuple>,
+    memory_used: usize,
+    runs: Vec<SpillFile>,
+}
+
+impl Sorter {
+    pub fn new(keys: Vec<SortKey>, temp_dir: PathBuf, memory_limit: usize) -> Self {
+        Self {
+            keys,
+            temp_dir,
+            memory_limit,
+            rows: vec![],
+            memory_used: 0,
+            runs: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Tuple) -> errors::Result<()> {
+        self.memory_used += row_memory_size(&row);
+        self.rows.push(row);
+        if self.memory_used > self.memory_limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> errors::Result<()> {
+        let mut rows = mem::take(&mut self.rows);
+        self.memory_used = 0;
+        rows.sort_by(|a, b| compare_rows(&self.keys, a, b));
+        let mut run = SpillFile::create(&self.temp_dir)?;
+        for row in &rows {
+            run.write_row(row)?;
+        }
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Returns the sorted rows with the sort key values removed.
+    pub fn finish(mut self) -> errors::Result<SortedRows> {
+        let key_count = self.keys.len();
+        if self.runs.is_empty() {
+            self.rows.sort_by(|a, b| compare_rows(&self.keys, a, b));
+            return Ok(SortedRows {
+                source: SortedSource::Memory(self.rows.into_iter()),
+                key_count,
+            });
+        }
+        if !self.rows.is_empty() {
+            self.spill()?;
+        }
+        let mut runs = self.runs;
+        while runs.len() > MERGE_FAN_IN {
+            let mut merged_runs = vec![];
+            let mut runs_iter = runs.into_iter();
+            loop {
+                let chunk = runs_iter.by_ref().take(MERGE_FAN_IN).collect::<Vec<_>>();
+                if chunk.is_empty() {
+                    break;
+                }
+                let mut merged = SpillFile::create(&self.temp_dir)?;
+                for row in Merge::new(self.keys.clone(), chunk)? {
+                    merged.write_row(&row?)?;
+                }
+                merged_runs.push(merged);
+            }
+            runs = merged_runs;
+        }
+        Ok(SortedRows {
+            source: SortedSource::Merge(Merge::new(self.keys, runs)?),
+            key_count,
+        })
+    }
+}
+
+/// K-way merge of sorted runs. Ties are resolved in favor of earlier runs to keep the sort stable.
+struct Merge {
+    keys: Vec<SortKey>,
+    inputs: Vec<SpillRows>,
+    heads: Vec<Option<Tuple>>,
+}
+
+impl Merge {
+    fn new(keys: Vec<SortKey>, runs: Vec<SpillFile>) -> errors::Result<Self> {
+        let mut inputs = runs
+            .into_iter()
+            .map(SpillFile::into_rows)
+            .collect::<Result<Vec<_>, _>>()?;
+        let heads = inputs
+            .iter_mut()
+            .map(|input| input.next().transpose())
+            .collect::<errors::Result<Vec<_>>>()?;
+        Ok(Self {
+            keys,
+            inputs,
+            heads,
+        })
+    }
+}
+
+impl Iterator for Merge {
+    type Item = errors::Result<Tuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut min: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some(head) = head else { continue };
+            if let Some(min_index) = min
+                && compare_rows(&self.keys, head, self.heads[min_index].as_ref().unwrap())
+                    != Ordering::Less
+            {
+                continue;
+            }
+            min = Some(i);
+        }
+        let min = min?;
+        let next_head = match self.inputs[min].next().transpose() {
+            Ok(row) => row,
+            Err(err) => return Some(Err(err)),
+        };
+        mem::replace(&mut self.heads[min], next_head).map(Ok)
+    }
+}
+
+enum SortedSource {
+    Memory(vec::IntoIter<Tuple>),
+    Merge(Merge),
+}
+
+pub(crate) struct SortedRows {
+    source: SortedSource,
+    key_count: usize,
+}
+
+impl Iterator for SortedRows {
+    type Item = errors::Result<Tuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = match &mut self.source {
+            SortedSource::Memory(rows) => rows.next().map(Ok),
+            SortedSource::Merge(merge) => merge.next(),
+        };
+        row.map(|row| {
+            row.map(|mut row| {
+                row.values.truncate(row.values.len() - self.key_count);
+                row
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use temp_dir::TempDir;
+
+    use crate::{tuple::Tuple, values::DBValue};
+
+    use super::{SortKey, Sorter};
+
+    fn sort(rows: &[(i32, Option<i32>)], keys: Vec<SortKey>, memory_limit: usize) -> Vec<i32> {
+        let dir = TempDir::new().unwrap();
+        let mut sorter = Sorter::new(keys, dir.path().to_owned(), memory_limit);
+        for (id, key) in rows {
+            let key = key.map_or(DBValue::Null, DBValue::Int);
+            sorter
+                .push(Tuple::new(vec![DBValue::Int(*id), key]))
+                .unwrap();
+        }
+        let sorted = sorter
+            .finish()
+            .unwrap()
+            .map(|row| {
+                let values = row.unwrap().into_values();
+                assert_eq!(values.len(), 1);
+                let DBValue::Int(id) = values[0] else {
+                    panic!("Expected int");
+                };
+                id
+            })
+            .collect();
+        assert_eq!(dir.path().read_dir().unwrap().count(), 0);
+        sorted
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let rows = [(1, Some(3)), (2, None), (3, Some(1)), (4, Some(3))];
+        let key = |descending, nulls_first| SortKey {
+            descending,
+            nulls_first,
+        };
+        assert_eq!(sort(&rows, vec![key(false, false)], 1 << 20), [3, 1, 4, 2]);
+        assert_eq!(sort(&rows, vec![key(true, true)], 1 << 20), [2, 1, 4, 3]);
+        assert_eq!(sort(&rows, vec![key(true, false)], 1 << 20), [1, 4, 3, 2]);
+        assert_eq!(sort(&rows, vec![key(false, true)], 1 << 20), [2, 3, 1, 4]);
+    }
+
+    #[test]
+    fn test_external_sort() {
+        let rows = (0..5000)
+            .map(|i| (i, Some((i * 7919) % 1000)))
+            .collect::<Vec<_>>();
+        let key = SortKey {
+            descending: false,
+            nulls_first: false,
+        };
+        let mut expected = rows.clone();
+        expected.sort_by_key(|(_, key)| *key);
+        let expected = expected.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        // Small enough to produce more runs than can be merged in one pass.
+        assert_eq!(sort(&rows, vec![key], 2000), expected);
+    }
+}
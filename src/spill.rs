@@ -0,0 +1,134 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    errors,
+    serializer::{BinaryReader, BinaryWriter},
+    tuple::Tuple,
+    values::DBValue,
+};
+
+static NEXT_SPILL_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Temporary file for rows that don't fit in memory. The file is removed when dropped.
+///
+/// Rows are stored with tagged values (see [`DBValue::write_tagged`]), so they don't need
+/// a table schema and may contain NULLs and computed values.
+pub(crate) struct SpillFile {
+    writer: BinaryWriter<BufWriter<File>>,
+    row_count: usize,
+    path: PathBuf,
+}
+
+impl SpillFile {
+    pub fn create(temp_dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(temp_dir)?;
+        let id = NEXT_SPILL_ID.fetch_add(1, Ordering::Relaxed);
+        let path = temp_dir.join(format!("{}-{}.spill", std::process::id(), id));
+        let file = File::create_new(&path)?;
+        Ok(Self {
+            writer: BinaryWriter::new(BufWriter::new(file)),
+            row_count: 0,
+            path,
+        })
+    }
+
+    pub fn write_row(&mut self, row: &Tuple) -> io::Result<()> {
+        self.writer.write_u32(row.values.len() as u32)?;
+        for value in &row.values {
+            value.write_tagged(&mut self.writer)?;
+        }
+        self.row_count += 1;
+        Ok(())
+    }
+
+    /// Finishes writing and returns an iterator over the rows in the order they were written.
+    pub fn into_rows(mut self) -> io::Result<SpillRows> {
+        self.writer.flush()?;
+        let file = File::open(&self.path)?;
+        Ok(SpillRows {
+            reader: BinaryReader::new(BufReader::new(file)),
+            remaining: self.row_count,
+            _file: self,
+        })
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub(crate) struct SpillRows {
+    reader: BinaryReader<BufReader<File>>,
+    remaining: usize,
+    // Declared last so the reader is closed before the file is removed.
+    _file: SpillFile,
+}
+
+impl SpillRows {
+    fn read_row(&mut self) -> io::Result<Tuple> {
+        let len = self.reader.read_u32()? as usize;
+        let values = (0..len)
+            .map(|_| DBValue::read_tagged(&mut self.reader))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Tuple::new(values))
+    }
+}
+
+impl Iterator for SpillRows {
+    type Item = errors::Result<Tuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(self.read_row().map_err(Into::into))
+    }
+}
+
+/// Rough number of bytes a row takes in memory, used to decide when to spill.
+pub(crate) fn row_memory_size(row: &Tuple) -> usize {
+    size_of::<Tuple>() + row.values.len() * size_of::<DBValue>() + row.size()
+}
+
+#[cfg(test)]
+mod test {
+    use temp_dir::TempDir;
+
+    use crate::{tuple::Tuple, values::DBValue};
+
+    use super::SpillFile;
+
+    #[test]
+    fn test_spill_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let rows = vec![
+            Tuple::new(vec![DBValue::Int(1), DBValue::Null]),
+            Tuple::new(vec![
+                DBValue::String("meow".to_owned()),
+                DBValue::Double(2.5),
+                DBValue::Bool(true),
+            ]),
+            Tuple::new(vec![]),
+        ];
+        let mut file = SpillFile::create(dir.path()).unwrap();
+        for row in &rows {
+            file.write_row(row).unwrap();
+        }
+        let path = file.path.clone();
+        let read = file
+            .into_rows()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, rows);
+        assert!(!path.exists());
+    }
+}
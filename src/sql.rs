@@ -1,312 +1,1229 @@
-use std::{collections::HashMap, iter};
-
-use once_cell::sync::Lazy;
-use regex::{Captures, Regex};
+mod ast;
+mod lexer;
+mod parser;
+mod scope;
 
 use crate::{
+    aggregate::Aggregate,
+    datetime::{DateField, Timestamp},
     db::DB,
     errors::{self, DBError},
-    table::{ColumnDef, Table},
+    expr::Expr,
+    join::JoinKind,
+    sequence::Sequence,
+    sort::SortKey,
+    sql::{
+        ast::{
+            AlterAction, Assignment, ColumnConstraintKind, ColumnDecl, ExprKind, FromClause, Ident,
+            JoinClause, OrderByItem, References, SelectItem, SelectStatement, Statement,
+            TableConstraint, TableConstraintKind,
+        },
+        parser::Parser,
+        scope::{AggregateScope, Scope, TableScope, is_aggregate_call, is_sequence_call},
+    },
+    table::{ColumnDef, ForeignKey, KeyConstraint, Table},
+    tuple::Tuple,
     values::{DBType, DBValue},
 };
 
-pub(crate) enum WhereClause {
-    Eq(usize, DBValue),
-    Neq(usize, DBValue),
-    Lt(usize, DBValue),
-    Lte(usize, DBValue),
-    Gt(usize, DBValue),
-    Gte(usize, DBValue),
+/// GROUP BY and aggregates of a SELECT query.
+pub(crate) struct Aggregation {
+    pub group_by: Vec<Expr>,
+    pub aggregates: Vec<Aggregate>,
+    pub having: Option<Expr>,
 }
 
-impl WhereClause {
-    pub fn compile(
-        expression: &str,
-        name_to_col: &HashMap<String, (usize, ColumnDef)>,
-    ) -> errors::Result<Self> {
-        // TODO: sane parser
-        static RGX: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"^(\w+)\s*(=|!=|<|<=|>|>=)\s*(.+)$").unwrap());
-        let caps = RGX
-            .captures(expression)
-            .ok_or_else(|| DBError::Parse(format!("Error parsing where clause: {}", expression)))?;
-        let col_name = caps.get(1).unwrap().as_str();
-        let op = caps.get(2).unwrap().as_str();
-        let value_str = caps.get(3).unwrap().as_str();
-        let value = value_str
-            .parse::<DBValue>()
-            .map_err(|_| DBError::Parse(format!("Invalid value in where clause: {}", value_str)))?;
-        let (col_index, column_def) = name_to_col
-            .get(col_name)
-            .ok_or_else(|| DBError::Execution(format!("Column {} does not exist", col_name)))?;
-        if value.dtype() != column_def.dtype() {
-            return Err(DBError::Execution(format!(
-                "Type mismatch: {} is not of type {}",
-                value_str,
-                column_def.dtype()
-            )));
+/// Source of the rows of a SELECT query.
+pub(crate) enum FromPlan {
+    /// A single row without columns, for a SELECT without FROM.
+    SingleRow,
+    Table(String),
+    Join(Box<JoinPlan>),
+}
+
+/// Rows of `left` joined with the tuples of the `right` table. The joined rows are the
+/// left row followed by the right tuple, with NULLs in place of a missing side.
+pub(crate) struct JoinPlan {
+    pub left: FromPlan,
+    pub right: String,
+    pub kind: JoinKind,
+    pub on: Option<Expr>,
+    /// Number of columns in the rows of `left`.
+    pub left_width: usize,
+}
+
+/// A SELECT query with names resolved against the catalog.
+pub(crate) struct Select {
+    pub from: FromPlan,
+    pub where_clause: Option<Expr>,
+    /// If set, the output columns, HAVING and ORDER BY are evaluated on rows
+    /// of the group key values followed by the aggregate results.
+    pub aggregation: Option<Aggregation>,
+    /// Output column names and the expressions computing them.
+    pub columns: Vec<(String, Expr)>,
+    pub order_by: Vec<(Expr, SortKey)>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// PRIMARY KEY or UNIQUE constraint of a new table, with the columns resolved.
+pub(crate) struct KeyDef {
+    pub name: String,
+    pub constraint: KeyConstraint,
+    pub columns: Vec<usize>,
+}
+
+/// NOT NULL or CHECK constraint of a column, bound to the rows of its table.
+pub(crate) struct ColumnCheck {
+    pub column: usize,
+    /// The constraint as declared, e.g. `CHECK (age >= 0)`.
+    pub rule: String,
+    /// The CHECK expression, `None` for NOT NULL.
+    pub expr: Option<Expr>,
+}
+
+/// Change made by ALTER TABLE, with the columns resolved.
+pub(crate) enum TableChange {
+    AddColumn(ColumnDef),
+    /// Position of the column.
+    DropColumn(usize),
+    RenameColumn(usize, String),
+    RenameTable(String),
+}
+
+/// Columns used by an expression stored in the catalog, e.g. a CHECK.
+fn column_references(source: &str) -> errors::Result<Vec<Ident>> {
+    fn collect(expr: &ast::Expr, names: &mut Vec<Ident>) {
+        if let ExprKind::Column { name, .. } = &expr.kind {
+            names.push(name.clone());
         }
-        match op {
-            "=" => Ok(WhereClause::Eq(*col_index, value)),
-            "!=" => Ok(WhereClause::Neq(*col_index, value)),
-            "<" => Ok(WhereClause::Lt(*col_index, value)),
-            "<=" => Ok(WhereClause::Lte(*col_index, value)),
-            ">" => Ok(WhereClause::Gt(*col_index, value)),
-            ">=" => Ok(WhereClause::Gte(*col_index, value)),
-            _ => Err(DBError::Parse(format!(
-                "Invalid operator in where clause: {}",
-                op
-            ))),
+        for child in expr.children() {
+            collect(child, names);
+        }
+    }
+    let mut names = vec![];
+    collect(&Parser::parse_expression(source)?, &mut names);
+    names.sort_by_key(|name| name.span.start);
+    Ok(names)
+}
+
+pub(crate) fn uses_column(source: &str, column: &str) -> errors::Result<bool> {
+    Ok(column_references(source)?
+        .iter()
+        .any(|name| name.name == column))
+}
+
+/// Replaces the uses of a renamed column in an expression stored in the catalog.
+pub(crate) fn rename_column_references(
+    source: &str,
+    old: &str,
+    new: &str,
+) -> errors::Result<String> {
+    let mut renamed = source.to_owned();
+    // From the end, so that the spans before stay valid.
+    for name in column_references(source)?.iter().rev() {
+        if name.name == old {
+            renamed.replace_range(name.span.start..name.span.end, new);
         }
     }
+    Ok(renamed)
 }
 
 pub(crate) enum Query<'a> {
+    Begin {
+        db: &'a mut DB,
+    },
+    Commit {
+        db: &'a mut DB,
+    },
+    Rollback {
+        db: &'a mut DB,
+    },
     CreateTable {
         db: &'a mut DB,
         table_name: String,
         column_decls: Vec<ColumnDef>,
+        keys: Vec<KeyDef>,
+        foreign_keys: Vec<ForeignKey>,
+        /// Names of the sequences of the `serial` columns, with the column positions.
+        serials: Vec<(String, usize)>,
     },
     DropTable {
         db: &'a mut DB,
         table_name: String,
     },
+    AlterTable {
+        db: &'a mut DB,
+        table_name: String,
+        change: TableChange,
+    },
+    CreateIndex {
+        db: &'a mut DB,
+        index_name: String,
+        table_name: String,
+        columns: Vec<usize>,
+    },
+    DropIndex {
+        db: &'a mut DB,
+        index_name: String,
+    },
     Insert {
-        db: &'a DB,
+        db: &'a mut DB,
         table_name: String,
         values: Vec<DBValue>,
+        checks: Vec<ColumnCheck>,
     },
     Select {
-        db: &'a DB,
-        table_name: String,
-        where_clause: Option<WhereClause>,
-        columns: Vec<(String, usize)>,
+        db: &'a mut DB,
+        select: Select,
     },
     Update {
-        db: &'a DB,
+        db: &'a mut DB,
         table_name: String,
-        where_clause: Option<WhereClause>,
-        update_clauses: Vec<(usize, DBValue)>,
+        where_clause: Option<Expr>,
+        update_clauses: Vec<(usize, Expr)>,
+        checks: Vec<ColumnCheck>,
     },
     Delete {
-        db: &'a DB,
+        db: &'a mut DB,
         table_name: String,
-        where_clause: Option<WhereClause>,
+        where_clause: Option<Expr>,
+    },
+    CreateSequence {
+        db: &'a mut DB,
+        name: String,
+        sequence: Sequence,
+    },
+    DropSequence {
+        db: &'a mut DB,
+        name: String,
+    },
+    Vacuum {
+        db: &'a mut DB,
+        /// All tables if not given.
+        table_name: Option<String>,
+        full: bool,
     },
 }
 
 impl<'a> Query<'a> {
-    fn parse_where_clause(
-        caps: &Captures,
-        column_mapping: &HashMap<String, (usize, ColumnDef)>,
-    ) -> errors::Result<Option<WhereClause>> {
-        caps.name("where_clause")
-            .map(|w| w.as_str())
-            .map(|w| WhereClause::compile(w, column_mapping))
+    fn compile_where_clause(
+        source: &str,
+        where_clause: Option<ast::Expr>,
+        scope: &dyn Scope,
+    ) -> errors::Result<Option<Expr>> {
+        where_clause
+            .map(|w| Query::compile_bool_expr(source, w, scope))
             .transpose()
     }
 
-    fn get_column_mapping(table: &Table) -> HashMap<String, (usize, ColumnDef)> {
-        table
-            .columns()
-            .iter()
-            .enumerate()
-            .map(|(i, col)| (col.name().to_owned(), (i, col.clone())))
-            .collect()
+    fn type_mismatch(source: &str, expr: &ast::Expr, expected: DBType) -> DBError {
+        DBError::Execution(format!(
+            "Type mismatch: {} is not of type {} at {}",
+            expr.text(source),
+            expected,
+            expr.span.position(source)
+        ))
     }
 
-    pub fn compile_create_table(db: &'a mut DB, query: &str) -> errors::Result<Self> {
-        static RGX: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"^create table\s+(?P<table>\w+)\s+\(\s*(?P<decls>(?:\w+\s+\w+,\s*)*)(?P<last_decl>\w+\s+\w+\s*)\)$").unwrap()
-        });
-        let caps = RGX
-            .captures(query)
-            .ok_or_else(|| DBError::Parse("Error parsing create table statement".to_owned()))?;
-
-        let table_name = caps.name("table").unwrap().as_str().to_owned();
-        let column_decls = caps
-            .name("decls")
-            .unwrap()
-            .as_str()
-            .split(',')
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .chain(iter::once(caps.name("last_decl").unwrap().as_str()))
-            .map(|s| {
-                let (name, dtype_str) = s.split_once(char::is_whitespace).unwrap();
-                dtype_str
-                    .parse::<DBType>()
-                    .map(|dtype| ColumnDef::new(name.to_owned(), dtype))
+    fn type_name(dtype: Option<DBType>) -> String {
+        match dtype {
+            Some(dtype) => dtype.to_string(),
+            None => "null".to_owned(),
+        }
+    }
+
+    /// Resolves names in `scope` and type-checks the expression, returning it with its type.
+    /// The type is `None` for expressions that are always NULL, e.g. the `NULL` literal.
+    fn compile_expr(
+        source: &str,
+        expr: ast::Expr,
+        scope: &dyn Scope,
+    ) -> errors::Result<(Expr, Option<DBType>)> {
+        if let Some(resolved) = scope.resolve(source, &expr)? {
+            return Ok(resolved);
+        }
+        let compile = |expr: Box<ast::Expr>| Query::compile_expr(source, *expr, scope);
+        let compile_bool =
+            |expr: Box<ast::Expr>| Query::compile_bool_expr(source, *expr, scope).map(Box::new);
+        let position = expr.span.position(source);
+        match expr.kind {
+            ExprKind::Column { .. } => Err(DBError::Execution(format!(
+                "Column {} does not exist at {}",
+                expr.text(source),
+                position
+            ))),
+            ExprKind::Function { ref name, .. } if is_sequence_call(&expr) => {
+                Err(DBError::Execution(format!(
+                    "Function {} is not allowed here at {}",
+                    name.name, position
+                )))
+            }
+            ExprKind::Function { ref name, .. } if is_aggregate_call(&expr) => {
+                Err(DBError::Execution(format!(
+                    "Aggregate function {} is not allowed here at {}",
+                    name.name, position
+                )))
+            }
+            ExprKind::Function { .. } => Query::compile_function(source, expr, scope),
+            ExprKind::Extract { field, operand } => {
+                let field = Query::date_field(source, &field)?;
+                let (operand, dtype) = compile(operand)?;
+                if !dtype.is_none_or(DBType::is_temporal) {
+                    return Err(DBError::Execution(format!(
+                        "Function extract is not defined for {} at {}",
+                        Query::type_name(dtype),
+                        position
+                    )));
+                }
+                let expr = Expr::Extract {
+                    field,
+                    operand: Box::new(operand),
+                };
+                Ok((expr, Some(DBType::Numeric(None))))
+            }
+            ExprKind::Default => Err(DBError::Execution(format!(
+                "DEFAULT is not allowed here at {}",
+                position
+            ))),
+            ExprKind::Literal(value) => {
+                let dtype = value.dtype();
+                Ok((Expr::Literal(value), dtype))
+            }
+            ExprKind::Negate(operand) => {
+                let (operand, dtype) = compile(operand)?;
+                if !dtype.is_none_or(|dtype| dtype.is_numeric() || dtype == DBType::Interval) {
+                    return Err(DBError::Execution(format!(
+                        "Operator - is not defined for {} at {}",
+                        Query::type_name(dtype),
+                        position
+                    )));
+                }
+                Ok((Expr::Negate(Box::new(operand)), dtype))
+            }
+            ExprKind::Binary { left, op, right } => {
+                let (left, left_type) = compile(left)?;
+                let (right, right_type) = compile(right)?;
+                let dtype = op.result_type(left_type, right_type).ok_or_else(|| {
+                    DBError::Execution(format!(
+                        "Operator {} is not defined for {} and {} at {}",
+                        op,
+                        Query::type_name(left_type),
+                        Query::type_name(right_type),
+                        position
+                    ))
+                })?;
+                let expr = Expr::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                };
+                Ok((expr, Some(dtype)))
+            }
+            ExprKind::Compare { left, op, right } => {
+                let (left_ast, right_ast) = ((*left).clone(), (*right).clone());
+                let (left, left_type) = compile(left)?;
+                let (right, right_type) = compile(right)?;
+                let (left, left_type) =
+                    Query::temporal_literal(source, &left_ast, (left, left_type), right_type)?;
+                let (right, right_type) =
+                    Query::temporal_literal(source, &right_ast, (right, right_type), left_type)?;
+                if let (Some(left_type), Some(right_type)) = (left_type, right_type)
+                    && !left_type.is_comparable_with(right_type)
+                {
+                    return Err(Query::type_mismatch(source, &right_ast, left_type));
+                }
+                let expr = Expr::Compare {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                };
+                Ok((expr, Some(DBType::Bool)))
+            }
+            ExprKind::And(left, right) => Ok((
+                Expr::And(compile_bool(left)?, compile_bool(right)?),
+                Some(DBType::Bool),
+            )),
+            ExprKind::Or(left, right) => Ok((
+                Expr::Or(compile_bool(left)?, compile_bool(right)?),
+                Some(DBType::Bool),
+            )),
+            ExprKind::Not(operand) => Ok((Expr::Not(compile_bool(operand)?), Some(DBType::Bool))),
+            ExprKind::IsNull { operand, negated } => {
+                let (operand, _) = compile(operand)?;
+                let expr = Expr::IsNull {
+                    operand: Box::new(operand),
+                    negated,
+                };
+                Ok((expr, Some(DBType::Bool)))
+            }
+        }
+    }
+
+    /// Reads a string literal compared with a date, time or interval as a value of that
+    /// type, e.g. in `created_at >= '2024-01-01'`. Other expressions are returned unchanged.
+    fn temporal_literal(
+        source: &str,
+        expr: &ast::Expr,
+        compiled: (Expr, Option<DBType>),
+        other_type: Option<DBType>,
+    ) -> errors::Result<(Expr, Option<DBType>)> {
+        match (compiled, other_type) {
+            ((Expr::Literal(DBValue::String(text)), _), Some(dtype)) if dtype.is_temporal() => {
+                let value = DBValue::parse_temporal(&text, dtype).ok_or_else(|| {
+                    DBError::Execution(format!(
+                        "Invalid {} `{}` at {}",
+                        dtype,
+                        text,
+                        expr.span.position(source)
+                    ))
+                })?;
+                Ok((Expr::Literal(value), Some(dtype)))
+            }
+            (compiled, _) => Ok(compiled),
+        }
+    }
+
+    fn date_field(source: &str, name: &Ident) -> errors::Result<DateField> {
+        name.name.parse().map_err(|_| {
+            DBError::Execution(format!(
+                "Unit {} is not recognized at {}",
+                name.name,
+                name.span.position(source)
+            ))
+        })
+    }
+
+    /// Binds a call of a scalar function: `now()` and `current_timestamp`, `current_date`,
+    /// which are the time of the statement's transaction, and `date_trunc('unit', timestamp)`.
+    fn compile_function(
+        source: &str,
+        expr: ast::Expr,
+        scope: &dyn Scope,
+    ) -> errors::Result<(Expr, Option<DBType>)> {
+        let position = expr.span.position(source);
+        let ExprKind::Function {
+            name,
+            mut args,
+            distinct,
+            star,
+        } = expr.kind
+        else {
+            unreachable!();
+        };
+        let function = name.name.to_lowercase();
+        let takes_arguments = |count: usize, description: &str| {
+            if args.len() == count && !distinct && !star {
+                return Ok(());
+            }
+            Err(DBError::Execution(format!(
+                "Function {} takes {} at {}",
+                name.name, description, position
+            )))
+        };
+        match function.as_str() {
+            "now" | "current_timestamp" => {
+                takes_arguments(0, "no arguments")?;
+                let now = DBValue::Timestamp(scope.now());
+                Ok((Expr::Literal(now), Some(DBType::Timestamp)))
+            }
+            "current_date" => {
+                takes_arguments(0, "no arguments")?;
+                Ok((
+                    Expr::Literal(DBValue::Date(scope.now().date())),
+                    Some(DBType::Date),
+                ))
+            }
+            "date_trunc" => {
+                takes_arguments(2, "a unit as a string and a timestamp")?;
+                let operand = args.pop().unwrap();
+                let unit = match args.pop().unwrap() {
+                    ast::Expr {
+                        kind: ExprKind::Literal(DBValue::String(unit)),
+                        span,
+                    } => Ident { name: unit, span },
+                    _ => {
+                        return Err(DBError::Execution(format!(
+                            "Function date_trunc takes a unit as a string at {}",
+                            position
+                        )));
+                    }
+                };
+                let field = Query::date_field(source, &unit)?;
+                if !field.can_truncate() {
+                    return Err(DBError::Execution(format!(
+                        "Unit {} is not supported by date_trunc at {}",
+                        field,
+                        unit.span.position(source)
+                    )));
+                }
+                let (operand, dtype) = Query::compile_expr(source, operand, scope)?;
+                if !dtype.is_none_or(|dtype| dtype.is_comparable_with(DBType::Timestamp)) {
+                    return Err(DBError::Execution(format!(
+                        "Function date_trunc is not defined for {} at {}",
+                        Query::type_name(dtype),
+                        position
+                    )));
+                }
+                let expr = Expr::DateTrunc {
+                    field,
+                    operand: Box::new(operand),
+                };
+                Ok((expr, Some(DBType::Timestamp)))
+            }
+            _ => Err(DBError::Execution(format!(
+                "Function {} does not exist at {}",
+                name.name, position
+            ))),
+        }
+    }
+
+    fn compile_bool_expr(source: &str, expr: ast::Expr, scope: &dyn Scope) -> errors::Result<Expr> {
+        let (compiled, dtype) = Query::compile_expr(source, expr.clone(), scope)?;
+        if dtype.is_some_and(|dtype| dtype != DBType::Bool) {
+            return Err(Query::type_mismatch(source, &expr, DBType::Bool));
+        }
+        Ok(compiled)
+    }
+
+    /// Type-checks a column default, which can't refer to columns. `scope` has no tables,
+    /// but may have the sequences.
+    fn compile_default(
+        source: &str,
+        expr: ast::Expr,
+        dtype: DBType,
+        scope: &TableScope,
+    ) -> errors::Result<Expr> {
+        let (compiled, expr_type) = Query::compile_expr(source, expr.clone(), scope)?;
+        if expr_type.is_some_and(|expr_type| !expr_type.can_coerce_to(dtype)) {
+            return Err(Query::type_mismatch(source, &expr, dtype));
+        }
+        Ok(compiled)
+    }
+
+    /// The DEFAULT of a column stored in the catalog, NULL if it has none.
+    fn column_default(column: &ColumnDef, scope: &TableScope) -> errors::Result<Expr> {
+        match column.default() {
+            Some(source) => {
+                let expr = Parser::parse_expression(source)?;
+                Query::compile_default(source, expr, column.dtype(), scope)
+            }
+            None => Ok(Expr::Literal(DBValue::Null)),
+        }
+    }
+
+    /// NOT NULL and CHECK constraints of the table's columns.
+    pub(crate) fn column_checks(table: &Table, now: Timestamp) -> errors::Result<Vec<ColumnCheck>> {
+        let scope = TableScope::new(table, now);
+        let mut checks = vec![];
+        for (i, column) in table.columns().iter().enumerate() {
+            if column.not_null() {
+                checks.push(ColumnCheck {
+                    column: i,
+                    rule: "NOT NULL".to_owned(),
+                    expr: None,
+                });
+            }
+            for source in column.checks() {
+                let expr = Parser::parse_expression(source)?;
+                checks.push(ColumnCheck {
+                    column: i,
+                    rule: format!("CHECK ({})", source),
+                    expr: Some(Query::compile_bool_expr(source, expr, &scope)?),
+                });
+            }
+        }
+        Ok(checks)
+    }
+
+    /// Definition of a declared column with its NOT NULL and DEFAULT constraints. Also
+    /// returns its CHECK expressions, which are bound once all columns are known, and its
+    /// key and foreign key constraints.
+    fn compile_column_decl(
+        source: &str,
+        decl: &ColumnDecl,
+        scope: &TableScope,
+    ) -> errors::Result<(ColumnDef, Vec<ast::Expr>, Vec<TableConstraint>)> {
+        let mut not_null = false;
+        let mut default = None;
+        let mut checks = vec![];
+        let mut keys = vec![];
+        for constraint in &decl.constraints {
+            let columns = vec![decl.name.clone()];
+            let kind = match &constraint.kind {
+                ColumnConstraintKind::PrimaryKey => TableConstraintKind::PrimaryKey(columns),
+                ColumnConstraintKind::Unique => TableConstraintKind::Unique(columns),
+                ColumnConstraintKind::NotNull => {
+                    not_null = true;
+                    continue;
+                }
+                ColumnConstraintKind::Default(expr) => {
+                    if default.is_some() {
+                        return Err(DBError::Execution(format!(
+                            "Multiple default values specified for column {} at {}",
+                            decl.name.name,
+                            expr.span.position(source)
+                        )));
+                    }
+                    Query::compile_default(source, expr.clone(), decl.dtype, scope)?;
+                    default = Some(expr.text(source).to_owned());
+                    continue;
+                }
+                ColumnConstraintKind::Check(expr) => {
+                    checks.push(expr.clone());
+                    continue;
+                }
+                ColumnConstraintKind::References(references) => TableConstraintKind::ForeignKey {
+                    columns,
+                    references: references.clone(),
+                },
+            };
+            keys.push(TableConstraint {
+                name: constraint.name.clone(),
+                kind,
+            });
+        }
+        let column = ColumnDef::new(decl.name.name.clone(), decl.dtype)
+            .with_not_null(not_null)
+            .with_default(default);
+        Ok((column, checks, keys))
+    }
+
+    fn compile_create_table(
+        db: &'a mut DB,
+        source: &str,
+        name: Ident,
+        columns: Vec<ColumnDecl>,
+        table_constraints: Vec<TableConstraint>,
+    ) -> errors::Result<Self> {
+        let mut column_decls = vec![];
+        let mut checks = vec![];
+        // Constraints of a column are constraints on that column only.
+        let mut constraints = vec![];
+        let mut serials = vec![];
+        let default_scope = TableScope::empty(db.now).with_sequences(&db.sequences);
+        for (i, decl) in columns.iter().enumerate() {
+            let (mut column, column_checks, keys) =
+                Query::compile_column_decl(source, decl, &default_scope)?;
+            if decl.serial {
+                // Like in Postgres, the sequence is named after the table and the column.
+                let sequence = format!("{}_{}_seq", name.name, decl.name.name);
+                if db.sequences.contains(&sequence) {
+                    return Err(DBError::Execution(format!(
+                        "Sequence {} already exists at {}",
+                        sequence,
+                        decl.name.span.position(source)
+                    )));
+                }
+                if column.default().is_some() {
+                    return Err(DBError::Execution(format!(
+                        "Multiple default values specified for column {} at {}",
+                        decl.name.name,
+                        decl.name.span.position(source)
+                    )));
+                }
+                column = column
+                    .with_not_null(true)
+                    .with_default(Some(format!("nextval('{}')", sequence)));
+                serials.push((sequence, i));
+            }
+            column_decls.push(column);
+            checks.push(column_checks);
+            constraints.extend(keys);
+        }
+        constraints.extend(table_constraints);
+
+        // Checks are bound to the rows of the new table.
+        let new_table = Table::new(0, name.name.clone(), column_decls.clone());
+        let scope = TableScope::new(&new_table, db.now);
+        for (column, checks) in column_decls.iter_mut().zip(checks) {
+            let mut sources = vec![];
+            for check in checks {
+                // Stored checks follow renames of the table, so they can't name it.
+                if let Some(qualified) =
+                    check.find(&|expr| matches!(expr.kind, ExprKind::Column { table: Some(_), .. }))
+                {
+                    return Err(DBError::Execution(format!(
+                        "Columns in CHECK can't be qualified with a table at {}",
+                        qualified.span.position(source)
+                    )));
+                }
+                Query::compile_bool_expr(source, check.clone(), &scope)?;
+                sources.push(check.text(source).to_owned());
+            }
+            *column = column.clone().with_checks(sources);
+        }
+
+        let mut keys = vec![];
+        // Foreign keys may reference the keys of the new table, so they come after them.
+        let mut references = vec![];
+        for TableConstraint {
+            name: constraint_name,
+            kind,
+        } in constraints
+        {
+            let (constraint, key_columns) = match kind {
+                TableConstraintKind::PrimaryKey(key_columns) => {
+                    (KeyConstraint::PrimaryKey, key_columns)
+                }
+                TableConstraintKind::Unique(key_columns) => (KeyConstraint::Unique, key_columns),
+                TableConstraintKind::ForeignKey {
+                    columns,
+                    references: referenced,
+                } => {
+                    references.push((constraint_name, columns, referenced));
+                    continue;
+                }
+            };
+            if constraint == KeyConstraint::PrimaryKey
+                && keys
+                    .iter()
+                    .any(|key: &KeyDef| key.constraint == KeyConstraint::PrimaryKey)
+            {
+                return Err(DBError::Execution(format!(
+                    "Multiple primary keys for table {} are not allowed at {}",
+                    name.name,
+                    key_columns[0].span.position(source)
+                )));
+            }
+            let positions = key_columns
+                .iter()
+                .map(|column| {
+                    columns
+                        .iter()
+                        .position(|decl| decl.name.name == column.name)
+                        .ok_or_else(|| {
+                            DBError::Execution(format!(
+                                "Column {} named in key does not exist at {}",
+                                column.name,
+                                column.span.position(source)
+                            ))
+                        })
+                })
+                .collect::<errors::Result<Vec<_>>>()?;
+            // Default names are the ones Postgres gives.
+            let key_name = match (constraint_name, constraint) {
+                (Some(constraint_name), _) => constraint_name.name,
+                (None, KeyConstraint::PrimaryKey) => format!("{}_pkey", name.name),
+                (None, KeyConstraint::Unique) => {
+                    let column_names = key_columns
+                        .iter()
+                        .map(|column| column.name.as_str())
+                        .collect::<Vec<_>>();
+                    format!("{}_{}_key", name.name, column_names.join("_"))
+                }
+            };
+            keys.push(KeyDef {
+                name: key_name,
+                constraint,
+                columns: positions,
+            });
+        }
+
+        let foreign_keys = references
+            .into_iter()
+            .map(|(constraint_name, columns, references)| {
+                Query::compile_foreign_key(
+                    db,
+                    source,
+                    &new_table,
+                    &keys,
+                    constraint_name,
+                    columns,
+                    references,
+                )
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<errors::Result<Vec<_>>>()?;
 
         Ok(Self::CreateTable {
             db,
-            table_name,
+            table_name: name.name,
             column_decls,
+            keys,
+            foreign_keys,
+            serials,
         })
     }
 
-    pub fn compile_drop_table(db: &'a mut DB, query: &str) -> errors::Result<Self> {
-        static RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^drop table\s+(?P<table>\w+)$").unwrap());
-        let caps = RGX
-            .captures(query)
-            .ok_or_else(|| DBError::Parse("Error parsing drop table statement".to_owned()))?;
+    /// Resolves a foreign key of the new table. The referenced columns, by default the
+    /// primary key, must have the types of the referencing ones and be the columns of a
+    /// PRIMARY KEY or UNIQUE constraint, whose index is used to look the keys up.
+    fn compile_foreign_key(
+        db: &DB,
+        source: &str,
+        new_table: &Table,
+        keys: &[KeyDef],
+        constraint_name: Option<Ident>,
+        columns: Vec<Ident>,
+        references: References,
+    ) -> errors::Result<ForeignKey> {
+        let resolve = |table_columns: &[ColumnDef], table_name: &str, names: &[Ident]| {
+            names
+                .iter()
+                .map(|name| {
+                    table_columns
+                        .iter()
+                        .position(|column| column.name() == name.name)
+                        .ok_or_else(|| {
+                            DBError::Execution(format!(
+                                "Column {} of table {} does not exist at {}",
+                                name.name,
+                                table_name,
+                                name.span.position(source)
+                            ))
+                        })
+                })
+                .collect::<errors::Result<Vec<_>>>()
+        };
+        let positions = resolve(new_table.columns(), new_table.name(), &columns)?;
 
-        let table_name = caps.name("table").unwrap().as_str().to_owned();
+        // A table referencing itself gets the next table id when it's created.
+        let parent_name = &references.table.name;
+        let (parent_id, parent_columns, parent_keys) = if parent_name == new_table.name() {
+            let keys = keys
+                .iter()
+                .map(|key| (key.constraint, key.columns.clone()))
+                .collect::<Vec<_>>();
+            (db.next_table_id, new_table.columns(), keys)
+        } else {
+            let parent = db.get_table(parent_name)?;
+            let keys = parent
+                .indexes()
+                .iter()
+                .filter_map(|index| Some((index.constraint()?, index.columns().to_vec())))
+                .collect();
+            (parent.id(), parent.columns(), keys)
+        };
+        let referenced = match &references.columns {
+            Some(names) => resolve(parent_columns, parent_name, names)?,
+            None => parent_keys
+                .iter()
+                .find(|(constraint, _)| *constraint == KeyConstraint::PrimaryKey)
+                .map(|(_, columns)| columns.clone())
+                .ok_or_else(|| {
+                    DBError::Execution(format!(
+                        "Table {} has no primary key to reference at {}",
+                        parent_name,
+                        references.table.span.position(source)
+                    ))
+                })?,
+        };
 
-        Ok(Self::DropTable { db, table_name })
-    }
+        if positions.len() != referenced.len() {
+            return Err(DBError::Execution(format!(
+                "Foreign key has {} columns but references {} at {}",
+                positions.len(),
+                referenced.len(),
+                references.table.span.position(source)
+            )));
+        }
+        for (name, (&position, &referenced)) in
+            columns.iter().zip(positions.iter().zip(&referenced))
+        {
+            let column = &new_table.columns()[position];
+            let referenced = &parent_columns[referenced];
+            if column.dtype() != referenced.dtype() {
+                return Err(DBError::Execution(format!(
+                    "Column {} of type {} can't reference column {} of type {} at {}",
+                    column.name(),
+                    column.dtype(),
+                    referenced.name(),
+                    referenced.dtype(),
+                    name.span.position(source)
+                )));
+            }
+        }
+        if !parent_keys.iter().any(|(_, key)| {
+            key.len() == referenced.len() && referenced.iter().all(|column| key.contains(column))
+        }) {
+            return Err(DBError::Execution(format!(
+                "No PRIMARY KEY or UNIQUE constraint of table {} has the referenced columns at {}",
+                parent_name,
+                references.table.span.position(source)
+            )));
+        }
 
-    pub fn compile_insert(db: &'a DB, query: &str) -> errors::Result<Self> {
-        static RGX: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"^insert into\s+(?P<table>\w+)\s+values\s+\((?P<vals>(?:.+?,\s*)*)(?P<last_val>.+?)\)$").unwrap()
+        let name = constraint_name.map(|name| name.name).unwrap_or_else(|| {
+            let column_names = columns
+                .iter()
+                .map(|column| column.name.as_str())
+                .collect::<Vec<_>>();
+            format!("{}_{}_fkey", new_table.name(), column_names.join("_"))
         });
-        let caps = RGX
-            .captures(query)
-            .ok_or_else(|| DBError::Parse("Error parsing insert statement".to_owned()))?;
-
-        let table_name = caps.name("table").unwrap().as_str().to_owned();
-
-        let values = caps
-            .name("vals")
-            .unwrap()
-            .as_str()
-            .split(',')
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .chain(iter::once(caps.name("last_val").unwrap().as_str()))
-            .map(|s| s.parse::<DBValue>())
-            .collect::<Result<Vec<_>, _>>()?;
+        let ids = |table_columns: &[ColumnDef], positions: &[usize]| {
+            positions
+                .iter()
+                .map(|&position| table_columns[position].id())
+                .collect()
+        };
+        Ok(ForeignKey::new(
+            name,
+            ids(new_table.columns(), &positions),
+            parent_id,
+            ids(parent_columns, &referenced),
+        )
+        .with_actions(references.on_delete, references.on_update))
+    }
+
+    fn compile_drop_table(db: &'a mut DB, name: Ident) -> errors::Result<Self> {
+        Ok(Self::DropTable {
+            db,
+            table_name: name.name,
+        })
+    }
+
+    fn compile_alter_table(
+        db: &'a mut DB,
+        source: &str,
+        table: Ident,
+        action: AlterAction,
+    ) -> errors::Result<Self> {
+        let scope = TableScope::new(db.get_table(&table.name)?, db.now);
+        let change = match action {
+            AlterAction::AddColumn(decl) => {
+                // Stored tuples get one value for the column, so its default can't take
+                // values from sequences.
+                let (column, checks, keys) =
+                    Query::compile_column_decl(source, &decl, &TableScope::empty(db.now))?;
+                if !checks.is_empty() || !keys.is_empty() || decl.serial {
+                    return Err(DBError::Execution(format!(
+                        "Column {} can't be added as serial or with CHECK or key constraints at {}",
+                        decl.name.name,
+                        decl.name.span.position(source)
+                    )));
+                }
+                // Tuples stored before have the value of the default.
+                let missing = Query::column_default(&column, &TableScope::empty(db.now))?
+                    .eval(&Tuple::new(vec![]))?;
+                TableChange::AddColumn(column.with_missing(missing.coerce_to(decl.dtype)?))
+            }
+            AlterAction::DropColumn(name) => {
+                TableChange::DropColumn(scope.column(source, &name)?.0)
+            }
+            AlterAction::RenameColumn { name, new_name } => {
+                TableChange::RenameColumn(scope.column(source, &name)?.0, new_name.name)
+            }
+            AlterAction::RenameTable(new_name) => TableChange::RenameTable(new_name.name),
+        };
+        Ok(Self::AlterTable {
+            db,
+            table_name: table.name,
+            change,
+        })
+    }
+
+    fn compile_create_index(
+        db: &'a mut DB,
+        source: &str,
+        name: Ident,
+        table: Ident,
+        columns: Vec<Ident>,
+    ) -> errors::Result<Self> {
+        let scope = TableScope::new(db.get_table(&table.name)?, db.now);
+        let columns = columns
+            .iter()
+            .map(|column| Ok(scope.column(source, column)?.0))
+            .collect::<errors::Result<Vec<_>>>()?;
+        Ok(Self::CreateIndex {
+            db,
+            index_name: name.name,
+            table_name: table.name,
+            columns,
+        })
+    }
 
+    fn compile_insert(
+        db: &'a mut DB,
+        source: &str,
+        table: Ident,
+        columns: Option<Vec<Ident>>,
+        values: Vec<ast::Expr>,
+    ) -> errors::Result<Self> {
+        let table_def = db.get_table(&table.name)?;
+        let scope = TableScope::new(table_def, db.now);
+        let targets = match columns {
+            Some(columns) => {
+                let mut targets = vec![];
+                for column in &columns {
+                    let (i, _) = scope.column(source, column)?;
+                    if targets.contains(&i) {
+                        return Err(DBError::Execution(format!(
+                            "Column {} is specified more than once at {}",
+                            column.name,
+                            column.span.position(source)
+                        )));
+                    }
+                    targets.push(i);
+                }
+                if values.len() != targets.len() {
+                    return Err(DBError::Execution(format!(
+                        "Insert values count ({}) does not match columns count ({})",
+                        values.len(),
+                        targets.len()
+                    )));
+                }
+                targets
+            }
+            None => {
+                if values.len() != table_def.columns().len() {
+                    return Err(DBError::Execution(format!(
+                        "Insert values count ({}) does not match table columns count ({})",
+                        values.len(),
+                        table_def.columns().len()
+                    )));
+                }
+                (0..values.len()).collect()
+            }
+        };
+        // Columns without a value get their default.
+        let values_scope = TableScope::empty(db.now).with_sequences(&db.sequences);
+        let mut row = table_def
+            .columns()
+            .iter()
+            .map(|column| Query::column_default(column, &values_scope))
+            .collect::<errors::Result<Vec<_>>>()?;
+        for (i, value) in targets.into_iter().zip(values) {
+            if value.kind != ExprKind::Default {
+                row[i] = Query::compile_expr(source, value, &values_scope)?.0;
+            }
+        }
+        let empty_tuple = Tuple::new(vec![]);
+        let values = row
+            .iter()
+            .map(|value| value.eval(&empty_tuple))
+            .collect::<errors::Result<Vec<_>>>()?;
+        let checks = Query::column_checks(table_def, db.now)?;
         Ok(Self::Insert {
             db,
-            table_name,
+            table_name: table.name,
             values,
+            checks,
         })
     }
 
-    pub fn compile_select(db: &'a DB, query: &str) -> errors::Result<Self> {
-        static RGX: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"^select\s+(\*|(?P<cols>(?:\w+,\s*)*)(?P<last_col>\w+))\s+from\s+(?P<table>\w+)(?:\s+where\s+(?P<where_clause>.+))?$").unwrap()
-        });
-        let caps = RGX
-            .captures(query)
-            .ok_or_else(|| DBError::Parse("Error parsing select statement".to_owned()))?;
+    /// Adds the tables of the FROM clause to `scope`, binding the join conditions in it.
+    fn compile_from(
+        db: &DB,
+        source: &str,
+        from: FromClause,
+        scope: &mut TableScope,
+    ) -> errors::Result<FromPlan> {
+        match from {
+            FromClause::Table(table_ref) => {
+                let table = db.get_table(&table_ref.name.name)?;
+                scope.add_table(source, table_ref.reference_name(), table)?;
+                Ok(FromPlan::Table(table_ref.name.name))
+            }
+            FromClause::Join(join) => {
+                let JoinClause {
+                    left,
+                    right,
+                    kind,
+                    on,
+                } = *join;
+                let left = Query::compile_from(db, source, left, scope)?;
+                let left_width = scope.width();
+                let table = db.get_table(&right.name.name)?;
+                // The condition may refer to the joined table and the tables before it.
+                scope.add_table(source, right.reference_name(), table)?;
+                let on = Query::compile_where_clause(source, on, scope)?;
+                Ok(FromPlan::Join(Box::new(JoinPlan {
+                    left,
+                    right: right.name.name,
+                    kind,
+                    on,
+                    left_width,
+                })))
+            }
+        }
+    }
 
-        let table_name = caps.name("table").unwrap().as_str().to_owned();
-        let table = db.get_table(&table_name)?;
-        let column_mapping = Query::get_column_mapping(table);
-
-        let column_names = if caps.name("last_col").is_some() {
-            caps.name("cols")
-                .unwrap()
-                .as_str()
-                .split(',')
-                .map(|s| s.trim())
-                .filter(|s| !s.is_empty())
-                .chain(iter::once(caps.name("last_col").unwrap().as_str()))
-                .collect::<Vec<_>>()
+    fn compile_select(
+        db: &'a mut DB,
+        source: &str,
+        statement: SelectStatement,
+    ) -> errors::Result<Self> {
+        let SelectStatement {
+            columns,
+            from,
+            where_clause,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        } = statement;
+        let mut table_scope = TableScope::empty(db.now).with_sequences(&db.sequences);
+        let from = match from {
+            Some(from) => Query::compile_from(db, source, from, &mut table_scope)?,
+            None => FromPlan::SingleRow,
+        };
+        let where_clause = Query::compile_where_clause(source, where_clause, &table_scope)?;
+
+        let has_aggregates = columns.iter().any(|item| match item {
+            SelectItem::Wildcard => false,
+            SelectItem::Expr { expr, .. } => expr.any(&is_aggregate_call),
+        }) || order_by
+            .iter()
+            .any(|item| item.expr.any(&is_aggregate_call));
+        let aggregate_scope = if has_aggregates || !group_by.is_empty() || having.is_some() {
+            let group_by = group_by
+                .into_iter()
+                .map(|expr| Ok(Query::compile_expr(source, expr, &table_scope)?.0))
+                .collect::<errors::Result<Vec<_>>>()?;
+            Some(AggregateScope::new(&table_scope, group_by))
         } else {
-            table
-                .columns()
-                .iter()
-                .map(|col| col.name())
-                .collect::<Vec<_>>()
+            None
+        };
+        let scope: &dyn Scope = match &aggregate_scope {
+            Some(aggregate_scope) => aggregate_scope,
+            None => &table_scope,
         };
-        let columns = column_names
-            .into_iter()
-            .map(|name| {
-                column_mapping
-                    .get(name)
-                    .map(|(index, _)| (name.to_owned(), *index))
-                    .ok_or_else(|| DBError::Execution(format!("Column {} does not exist", name,)))
-            })
-            .collect::<Result<Vec<_>, _>>()?;
 
-        let where_clause = Query::parse_where_clause(&caps, &column_mapping)?;
+        let mut output_columns = vec![];
+        for item in columns {
+            match item {
+                SelectItem::Wildcard => {
+                    for (i, column) in table_scope.columns() {
+                        let expr = match &aggregate_scope {
+                            Some(aggregate_scope) => {
+                                aggregate_scope.input_column(i, column.name())?
+                            }
+                            None => Expr::Column(i),
+                        };
+                        output_columns.push((column.name().to_owned(), expr));
+                    }
+                }
+                SelectItem::Expr { expr, alias } => {
+                    let name = match (&alias, &expr.kind) {
+                        (Some(alias), _) => alias.name.clone(),
+                        (None, ExprKind::Column { name, .. }) => name.name.clone(),
+                        (None, _) => expr.text(source).to_owned(),
+                    };
+                    let (expr, _) = Query::compile_expr(source, expr, scope)?;
+                    output_columns.push((name, expr));
+                }
+            }
+        }
+
+        let having = Query::compile_where_clause(source, having, scope)?;
+        let order_by = order_by
+            .into_iter()
+            .map(|item| Query::compile_order_by_item(source, item, &output_columns, scope))
+            .collect::<errors::Result<Vec<_>>>()?;
+        let aggregation = aggregate_scope.map(|aggregate_scope| {
+            let (group_by, aggregates) = aggregate_scope.into_parts();
+            Aggregation {
+                group_by,
+                aggregates,
+                having,
+            }
+        });
 
         Ok(Self::Select {
             db,
-            table_name,
-            where_clause,
-            columns,
+            select: Select {
+                from,
+                where_clause,
+                aggregation,
+                columns: output_columns,
+                order_by,
+                limit,
+                offset: offset.unwrap_or(0),
+            },
         })
     }
 
-    pub fn compile_update(db: &'a DB, query: &str) -> errors::Result<Self> {
-        static RGX: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"^update\s+(?P<table>\w+)\s+set\s+(?P<updates>(?:\w+\s*=\s*.+?,\s*)*)(?P<last_update>(?:\w+\s*=\s*.+?))(?:\s+where\s+(?P<where_clause>.+))?$").unwrap()
-        });
-        let caps = RGX
-            .captures(query)
-            .ok_or_else(|| DBError::Parse("Error parsing update statement".to_owned()))?;
+    /// Like in Postgres, a bare name refers to an output column if there is one with that name
+    /// and an integer literal is a 1-based output column position.
+    fn compile_order_by_item(
+        source: &str,
+        item: OrderByItem,
+        output_columns: &[(String, Expr)],
+        scope: &dyn Scope,
+    ) -> errors::Result<(Expr, SortKey)> {
+        let expr = match &item.expr.kind {
+            ExprKind::Column { table: None, name }
+                if let Some((_, expr)) = output_columns
+                    .iter()
+                    .find(|(output, _)| *output == name.name) =>
+            {
+                expr.clone()
+            }
+            ExprKind::Literal(DBValue::Int(position)) => {
+                let position = *position;
+                usize::try_from(position)
+                    .ok()
+                    .and_then(|position| position.checked_sub(1))
+                    .and_then(|index| output_columns.get(index))
+                    .map(|(_, expr)| expr.clone())
+                    .ok_or_else(|| {
+                        DBError::Execution(format!(
+                            "ORDER BY position {} is not in select list at {}",
+                            position,
+                            item.expr.span.position(source)
+                        ))
+                    })?
+            }
+            _ => Query::compile_expr(source, item.expr, scope)?.0,
+        };
+        let key = SortKey {
+            descending: item.descending,
+            // NULLs are larger than any other value unless specified otherwise.
+            nulls_first: item.nulls_first.unwrap_or(item.descending),
+        };
+        Ok((expr, key))
+    }
 
-        let table_name = caps.name("table").unwrap().as_str().to_owned();
+    fn compile_update(
+        db: &'a mut DB,
+        source: &str,
+        table: Ident,
+        assignments: Vec<Assignment>,
+        where_clause: Option<ast::Expr>,
+    ) -> errors::Result<Self> {
+        let table_name = table.name;
         let table = db.get_table(&table_name)?;
-        let column_mapping = Query::get_column_mapping(table);
-
-        let update_clauses = caps
-            .name("updates")
-            .unwrap()
-            .as_str()
-            .split(',')
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .chain(iter::once(caps.name("last_update").unwrap().as_str()))
-            .map(|s| s.split_once('=').unwrap())
-            .map(|(col_name, value_str)| {
-                value_str
-                    .trim()
-                    .parse::<DBValue>()
-                    .map(|value| (col_name.trim(), value))
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-        let update_clauses = update_clauses
+        let scope = TableScope::new(table, db.now).with_sequences(&db.sequences);
+
+        let update_clauses = assignments
             .into_iter()
-            .map(|(col_name, value)| {
-                column_mapping
-                    .get(col_name)
-                    .ok_or_else(|| {
-                        DBError::Execution(format!("Column {} does not exist", col_name))
-                    })
-                    .and_then(|(column_index, column)| {
-                        if value.dtype() == column.dtype() {
-                            Ok((*column_index, value))
-                        } else {
-                            Err(DBError::Execution(format!(
-                                "Type mismatch: {} is not of type {}",
-                                value,
-                                column.dtype()
-                            )))
-                        }
-                    })
+            .map(|Assignment { column, value }| {
+                let (column_index, column) = scope.column(source, &column)?;
+                if value.kind == ExprKind::Default {
+                    let default_scope = TableScope::empty(db.now).with_sequences(&db.sequences);
+                    return Ok((column_index, Query::column_default(column, &default_scope)?));
+                }
+                let (compiled, dtype) = Query::compile_expr(source, value.clone(), &scope)?;
+                if dtype.is_some_and(|dtype| !dtype.can_coerce_to(column.dtype())) {
+                    return Err(Query::type_mismatch(source, &value, column.dtype()));
+                }
+                Ok((column_index, compiled))
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<errors::Result<Vec<_>>>()?;
 
-        let where_clause = Query::parse_where_clause(&caps, &column_mapping)?;
+        let where_clause = Query::compile_where_clause(source, where_clause, &scope)?;
+        let checks = Query::column_checks(table, db.now)?;
 
         Ok(Self::Update {
             db,
             table_name,
             where_clause,
             update_clauses,
+            checks,
         })
     }
 
-    pub fn compile_delete(db: &'a DB, query: &str) -> errors::Result<Self> {
-        static RGX: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"^delete from\s+(?P<table>\w+)(?:\s+where\s+(?P<where_clause>.+))?$").unwrap()
-        });
-        let caps = RGX
-            .captures(query)
-            .ok_or_else(|| DBError::Parse("Error parsing delete statement".to_owned()))?;
-
-        let table_name = caps.name("table").unwrap().as_str().to_owned();
+    fn compile_delete(
+        db: &'a mut DB,
+        source: &str,
+        table: Ident,
+        where_clause: Option<ast::Expr>,
+    ) -> errors::Result<Self> {
+        let table_name = table.name;
         let table = db.get_table(&table_name)?;
-
-        let column_mapping = Query::get_column_mapping(table);
-        let where_clause = Query::parse_where_clause(&caps, &column_mapping)?;
+        let scope = TableScope::new(table, db.now).with_sequences(&db.sequences);
+        let where_clause = Query::compile_where_clause(source, where_clause, &scope)?;
 
         Ok(Self::Delete {
             db,
@@ -316,21 +1233,78 @@ impl<'a> Query<'a> {
     }
 
     pub fn compile(db: &'a mut DB, query: &str) -> errors::Result<Self> {
-        // TODO: sane parser
-        if query.starts_with("create table") {
-            Ok(Self::compile_create_table(db, query)?)
-        } else if query.starts_with("drop table") {
-            Ok(Self::compile_drop_table(db, query)?)
-        } else if query.starts_with("insert into") {
-            Ok(Self::compile_insert(db, query)?)
-        } else if query.starts_with("select") {
-            Ok(Self::compile_select(db, query)?)
-        } else if query.starts_with("update") {
-            Ok(Self::compile_update(db, query)?)
-        } else if query.starts_with("delete from") {
-            Ok(Self::compile_delete(db, query)?)
-        } else {
-            Err(DBError::Parse(format!("Unknown operation: {}", query)))
+        match Parser::parse(query)? {
+            Statement::CreateTable {
+                name,
+                columns,
+                constraints,
+            } => Self::compile_create_table(db, query, name, columns, constraints),
+            Statement::DropTable { name } => Self::compile_drop_table(db, name),
+            Statement::AlterTable { table, action } => {
+                Self::compile_alter_table(db, query, table, action)
+            }
+            Statement::CreateIndex {
+                name,
+                table,
+                columns,
+            } => Self::compile_create_index(db, query, name, table, columns),
+            Statement::DropIndex { name } => Ok(Self::DropIndex {
+                db,
+                index_name: name.name,
+            }),
+            Statement::Insert {
+                table,
+                columns,
+                values,
+            } => Self::compile_insert(db, query, table, columns, values),
+            Statement::Select(statement) => Self::compile_select(db, query, *statement),
+            Statement::Update {
+                table,
+                assignments,
+                where_clause,
+            } => Self::compile_update(db, query, table, assignments, where_clause),
+            Statement::Delete {
+                table,
+                where_clause,
+            } => Self::compile_delete(db, query, table, where_clause),
+            Statement::Begin => Ok(Self::Begin { db }),
+            Statement::Commit => Ok(Self::Commit { db }),
+            Statement::Rollback => Ok(Self::Rollback { db }),
+            Statement::CreateSequence {
+                name,
+                start,
+                increment,
+            } => {
+                let increment = increment.unwrap_or(1);
+                if increment == 0 {
+                    return Err(DBError::Execution(format!(
+                        "INCREMENT of sequence {} must not be zero",
+                        name.name
+                    )));
+                }
+                // Descending sequences start from -1 by default.
+                let start = start.unwrap_or(increment.signum());
+                Ok(Self::CreateSequence {
+                    db,
+                    name: name.name,
+                    sequence: Sequence::new(start, increment),
+                })
+            }
+            Statement::DropSequence { name } => Ok(Self::DropSequence {
+                db,
+                name: name.name,
+            }),
+            Statement::Vacuum { full, table } => {
+                let table_name = table.map(|table| table.name);
+                if let Some(table_name) = &table_name {
+                    db.get_table(table_name)?;
+                }
+                Ok(Self::Vacuum {
+                    db,
+                    table_name,
+                    full,
+                })
+            }
         }
     }
 }
@@ -0,0 +1,300 @@
+use crate::{
+    expr::{BinaryOp, CompareOp},
+    join::JoinKind,
+    sql::lexer::Span,
+    table::ReferentialAction,
+    values::{DBType, DBValue},
+};
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct ColumnDecl {
+    pub name: Ident,
+    pub dtype: DBType,
+    /// Declared with the `serial` type, an int with the values of a sequence by default.
+    pub serial: bool,
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+/// `[CONSTRAINT name] kind` after the type of a column.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct ColumnConstraint {
+    pub name: Option<Ident>,
+    pub kind: ColumnConstraintKind,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum ColumnConstraintKind {
+    /// `PRIMARY KEY`
+    PrimaryKey,
+    Unique,
+    /// `NOT NULL`
+    NotNull,
+    /// `DEFAULT expr`
+    Default(Expr),
+    /// `CHECK (expr)`
+    Check(Expr),
+    References(References),
+}
+
+/// `[CONSTRAINT name] kind` among the columns of CREATE TABLE.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct TableConstraint {
+    pub name: Option<Ident>,
+    pub kind: TableConstraintKind,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum TableConstraintKind {
+    /// `PRIMARY KEY (columns)`
+    PrimaryKey(Vec<Ident>),
+    /// `UNIQUE (columns)`
+    Unique(Vec<Ident>),
+    /// `FOREIGN KEY (columns) REFERENCES ...`
+    ForeignKey {
+        columns: Vec<Ident>,
+        references: References,
+    },
+}
+
+/// `REFERENCES table [(columns)] [ON DELETE action] [ON UPDATE action]`
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct References {
+    pub table: Ident,
+    /// `None` for the primary key of the table.
+    pub columns: Option<Vec<Ident>>,
+    pub on_delete: ReferentialAction,
+    pub on_update: ReferentialAction,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum ExprKind {
+    /// `[table.]name`
+    Column {
+        table: Option<Ident>,
+        name: Ident,
+    },
+    Literal(DBValue),
+    Negate(Box<Expr>),
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    Compare {
+        left: Box<Expr>,
+        op: CompareOp,
+        right: Box<Expr>,
+    },
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// `operand IS NULL`, or `IS NOT NULL` if `negated`.
+    IsNull {
+        operand: Box<Expr>,
+        negated: bool,
+    },
+    /// `EXTRACT(field FROM operand)`
+    Extract {
+        field: Ident,
+        operand: Box<Expr>,
+    },
+    /// `name([DISTINCT] args)`, or `name(*)` with `star` set and no arguments.
+    Function {
+        name: Ident,
+        args: Vec<Expr>,
+        distinct: bool,
+        star: bool,
+    },
+    /// `DEFAULT` in place of a value in INSERT or UPDATE.
+    Default,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    /// The expression as written in the query.
+    pub fn text<'s>(&self, source: &'s str) -> &'s str {
+        &source[self.span.start..self.span.end]
+    }
+
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Column { .. } | ExprKind::Literal(_) | ExprKind::Default => vec![],
+            ExprKind::Negate(operand) | ExprKind::Not(operand) => vec![operand],
+            ExprKind::IsNull { operand, .. } | ExprKind::Extract { operand, .. } => vec![operand],
+            ExprKind::Binary { left, right, .. }
+            | ExprKind::Compare { left, right, .. }
+            | ExprKind::And(left, right)
+            | ExprKind::Or(left, right) => vec![left, right],
+            ExprKind::Function { args, .. } => args.iter().collect(),
+        }
+    }
+
+    /// Whether `predicate` holds for this expression or any of its subexpressions.
+    pub fn any(&self, predicate: &impl Fn(&Expr) -> bool) -> bool {
+        predicate(self)
+            || self
+                .children()
+                .into_iter()
+                .any(|child| child.any(predicate))
+    }
+
+    /// This expression or the first of its subexpressions for which `predicate` holds.
+    pub fn find(&self, predicate: &impl Fn(&Expr) -> bool) -> Option<&Expr> {
+        if predicate(self) {
+            return Some(self);
+        }
+        self.children()
+            .into_iter()
+            .find_map(|child| child.find(predicate))
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum SelectItem {
+    /// `*`
+    Wildcard,
+    Expr {
+        expr: Expr,
+        alias: Option<Ident>,
+    },
+}
+
+/// `expr [ASC | DESC] [NULLS {FIRST | LAST}]`
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct OrderByItem {
+    pub expr: Expr,
+    pub descending: bool,
+    /// `None` if not specified, in which case NULLs sort as the largest values.
+    pub nulls_first: Option<bool>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct Assignment {
+    pub column: Ident,
+    pub value: Expr,
+}
+
+/// `name [[AS] alias]`
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct TableRef {
+    pub name: Ident,
+    pub alias: Option<Ident>,
+}
+
+impl TableRef {
+    /// Name qualifying the table's columns in the query.
+    pub fn reference_name(&self) -> &Ident {
+        self.alias.as_ref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum FromClause {
+    Table(TableRef),
+    Join(Box<JoinClause>),
+}
+
+/// `left [kind] JOIN right [ON on]`, joins are left-associative.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct JoinClause {
+    pub left: FromClause,
+    pub right: TableRef,
+    pub kind: JoinKind,
+    pub on: Option<Expr>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct SelectStatement {
+    pub columns: Vec<SelectItem>,
+    /// `None` for a SELECT of a single row of expressions, e.g. `SELECT nextval('ids')`.
+    pub from: Option<FromClause>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderByItem>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Change made by `ALTER TABLE`.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum AlterAction {
+    /// `ADD [COLUMN] name type [constraints]`
+    AddColumn(ColumnDecl),
+    /// `DROP [COLUMN] name`
+    DropColumn(Ident),
+    /// `RENAME [COLUMN] name TO new_name`
+    RenameColumn { name: Ident, new_name: Ident },
+    /// `RENAME TO new_name`
+    RenameTable(Ident),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum Statement {
+    CreateTable {
+        name: Ident,
+        columns: Vec<ColumnDecl>,
+        constraints: Vec<TableConstraint>,
+    },
+    DropTable {
+        name: Ident,
+    },
+    AlterTable {
+        table: Ident,
+        action: AlterAction,
+    },
+    /// `CREATE INDEX name ON table (columns)`
+    CreateIndex {
+        name: Ident,
+        table: Ident,
+        columns: Vec<Ident>,
+    },
+    DropIndex {
+        name: Ident,
+    },
+    /// `CREATE SEQUENCE name [START [WITH] n] [INCREMENT [BY] n]`
+    CreateSequence {
+        name: Ident,
+        start: Option<i64>,
+        increment: Option<i64>,
+    },
+    DropSequence {
+        name: Ident,
+    },
+    /// `INSERT INTO table [(columns)] VALUES (values)`
+    Insert {
+        table: Ident,
+        columns: Option<Vec<Ident>>,
+        values: Vec<Expr>,
+    },
+    Select(Box<SelectStatement>),
+    Update {
+        table: Ident,
+        assignments: Vec<Assignment>,
+        where_clause: Option<Expr>,
+    },
+    Delete {
+        table: Ident,
+        where_clause: Option<Expr>,
+    },
+    /// `BEGIN [TRANSACTION]`
+    Begin,
+    Commit,
+    Rollback,
+    /// `VACUUM [FULL] [table]`
+    Vacuum {
+        full: bool,
+        table: Option<Ident>,
+    },
+}
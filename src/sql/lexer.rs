@@ -0,0 +1,528 @@
+use std::fmt::{self, Display};
+
+use crate::errors::{self, DBError};
+
+/// Byte range of a token or AST node inside the query string.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub(crate) struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// Human-readable `line L, column C` position of the span start.
+    pub fn position(&self, source: &str) -> String {
+        let before = &source[..self.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap().chars().count() + 1;
+        format!("line {}, column {}", line, column)
+    }
+}
+
+macro_rules! keywords {
+    ($($variant:ident => $text:literal,)*) => {
+        #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+        pub(crate) enum Keyword {
+            $($variant,)*
+        }
+
+        impl Keyword {
+            fn lookup(word: &str) -> Option<Self> {
+                $(if word.eq_ignore_ascii_case($text) {
+                    return Some(Keyword::$variant);
+                })*
+                None
+            }
+        }
+
+        impl Display for Keyword {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    $(Keyword::$variant => write!(f, "{}", $text.to_uppercase()),)*
+                }
+            }
+        }
+    };
+}
+
+keywords! {
+    Alter => "alter",
+    And => "and",
+    As => "as",
+    Asc => "asc",
+    Begin => "begin",
+    By => "by",
+    Check => "check",
+    Column => "column",
+    Commit => "commit",
+    Constraint => "constraint",
+    Create => "create",
+    Cross => "cross",
+    Default => "default",
+    Delete => "delete",
+    Desc => "desc",
+    Distinct => "distinct",
+    Drop => "drop",
+    False => "false",
+    Foreign => "foreign",
+    From => "from",
+    Full => "full",
+    Group => "group",
+    Having => "having",
+    Inner => "inner",
+    Insert => "insert",
+    Into => "into",
+    Is => "is",
+    Join => "join",
+    Left => "left",
+    Limit => "limit",
+    Not => "not",
+    Null => "null",
+    Offset => "offset",
+    On => "on",
+    Or => "or",
+    Order => "order",
+    Outer => "outer",
+    Primary => "primary",
+    References => "references",
+    Right => "right",
+    Rollback => "rollback",
+    Select => "select",
+    Set => "set",
+    Table => "table",
+    To => "to",
+    True => "true",
+    Unique => "unique",
+    Update => "update",
+    Vacuum => "vacuum",
+    Values => "values",
+    Where => "where",
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum TokenKind {
+    Keyword(Keyword),
+    Ident(String),
+    /// Raw text of a numeric literal, e.g. `1`, `100.`, `2.5e3`.
+    Number(String),
+    /// Contents of a string literal with `''` escapes resolved.
+    String(String),
+    /// Bytes of a hex literal, e.g. `x'deadbeef'`.
+    Bytes(Vec<u8>),
+    LParen,
+    RParen,
+    Comma,
+    Dot,
+    Semicolon,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    Concat,
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Eof,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Keyword(kw) => write!(f, "{}", kw),
+            TokenKind::Ident(name) => write!(f, "identifier `{}`", name),
+            TokenKind::Number(text) => write!(f, "number {}", text),
+            TokenKind::String(_) => write!(f, "string literal"),
+            TokenKind::Bytes(_) => write!(f, "binary literal"),
+            TokenKind::LParen => write!(f, "`(`"),
+            TokenKind::RParen => write!(f, "`)`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Dot => write!(f, "`.`"),
+            TokenKind::Semicolon => write!(f, "`;`"),
+            TokenKind::Star => write!(f, "`*`"),
+            TokenKind::Plus => write!(f, "`+`"),
+            TokenKind::Minus => write!(f, "`-`"),
+            TokenKind::Slash => write!(f, "`/`"),
+            TokenKind::Percent => write!(f, "`%`"),
+            TokenKind::Concat => write!(f, "`||`"),
+            TokenKind::Eq => write!(f, "`=`"),
+            TokenKind::Neq => write!(f, "`!=`"),
+            TokenKind::Lt => write!(f, "`<`"),
+            TokenKind::Lte => write!(f, "`<=`"),
+            TokenKind::Gt => write!(f, "`>`"),
+            TokenKind::Gte => write!(f, "`>=`"),
+            TokenKind::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+pub(crate) struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self { source, pos: 0 }
+    }
+
+    /// Splits the whole source into tokens. The last token is always [`TokenKind::Eof`].
+    pub fn tokenize(mut self) -> errors::Result<Vec<Token>> {
+        let mut tokens = vec![];
+        loop {
+            let token = self.next_token()?;
+            let is_eof = token.kind == TokenKind::Eof;
+            tokens.push(token);
+            if is_eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn peek_second_char(&self) -> Option<char> {
+        self.source[self.pos..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, span: Span, msg: &str) -> DBError {
+        DBError::Parse(format!("{} at {}", msg, span.position(self.source)))
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> errors::Result<()> {
+        loop {
+            match (self.peek_char(), self.peek_second_char()) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('-'), Some('-')) => {
+                    while let Some(c) = self.bump() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    let start = self.pos;
+                    self.pos += 2;
+                    match self.source[self.pos..].find("*/") {
+                        Some(end) => self.pos += end + 2,
+                        None => {
+                            return Err(
+                                self.error(Span::new(start, start + 2), "Unterminated comment")
+                            );
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> errors::Result<Token> {
+        self.skip_whitespace_and_comments()?;
+        let start = self.pos;
+        let Some(c) = self.bump() else {
+            return Ok(Token {
+                kind: TokenKind::Eof,
+                span: Span::new(start, start),
+            });
+        };
+        let kind = match c {
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            ',' => TokenKind::Comma,
+            ';' => TokenKind::Semicolon,
+            '*' => TokenKind::Star,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '|' if self.peek_char() == Some('|') => {
+                self.bump();
+                TokenKind::Concat
+            }
+            '=' => TokenKind::Eq,
+            '!' if self.peek_char() == Some('=') => {
+                self.bump();
+                TokenKind::Neq
+            }
+            '<' => match self.peek_char() {
+                Some('=') => {
+                    self.bump();
+                    TokenKind::Lte
+                }
+                Some('>') => {
+                    self.bump();
+                    TokenKind::Neq
+                }
+                _ => TokenKind::Lt,
+            },
+            '>' => match self.peek_char() {
+                Some('=') => {
+                    self.bump();
+                    TokenKind::Gte
+                }
+                _ => TokenKind::Gt,
+            },
+            '\'' => self.string_literal(start)?,
+            '"' => self.quoted_ident(start)?,
+            c if c.is_ascii_digit()
+                || (c == '.' && self.peek_char().is_some_and(|c| c.is_ascii_digit())) =>
+            {
+                self.number(start)
+            }
+            '.' => TokenKind::Dot,
+            'x' | 'X' if self.peek_char() == Some('\'') => {
+                self.bump();
+                self.hex_literal(start)?
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while self
+                    .peek_char()
+                    .is_some_and(|c| c.is_alphanumeric() || c == '_')
+                {
+                    self.bump();
+                }
+                let word = &self.source[start..self.pos];
+                match Keyword::lookup(word) {
+                    Some(kw) => TokenKind::Keyword(kw),
+                    None => TokenKind::Ident(word.to_owned()),
+                }
+            }
+            c => {
+                return Err(self.error(
+                    Span::new(start, self.pos),
+                    &format!("Unexpected character `{}`", c),
+                ));
+            }
+        };
+        Ok(Token {
+            kind,
+            span: Span::new(start, self.pos),
+        })
+    }
+
+    fn string_literal(&mut self, start: usize) -> errors::Result<TokenKind> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') if self.peek_char() == Some('\'') => {
+                    self.bump();
+                    value.push('\'');
+                }
+                Some('\'') => return Ok(TokenKind::String(value)),
+                Some(c) => value.push(c),
+                None => {
+                    return Err(
+                        self.error(Span::new(start, self.pos), "Unterminated string literal")
+                    );
+                }
+            }
+        }
+    }
+
+    /// Parses the hex digits of `x'...'`, two per byte.
+    fn hex_literal(&mut self, start: usize) -> errors::Result<TokenKind> {
+        let TokenKind::String(digits) = self.string_literal(start)? else {
+            unreachable!();
+        };
+        // `from_str_radix` alone would take a sign, e.g. `+f`.
+        let bytes = (digits.len() % 2 == 0 && digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| {
+                (0..digits.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+                    .collect::<Option<Vec<_>>>()
+            })
+            .flatten();
+        bytes.map(TokenKind::Bytes).ok_or_else(|| {
+            self.error(
+                Span::new(start, self.pos),
+                "Invalid hex literal, expected pairs of hex digits",
+            )
+        })
+    }
+
+    fn quoted_ident(&mut self, start: usize) -> errors::Result<TokenKind> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') if self.peek_char() == Some('"') => {
+                    self.bump();
+                    value.push('"');
+                }
+                Some('"') if value.is_empty() => {
+                    return Err(self.error(Span::new(start, self.pos), "Empty quoted identifier"));
+                }
+                Some('"') => return Ok(TokenKind::Ident(value)),
+                Some(c) => value.push(c),
+                None => {
+                    return Err(
+                        self.error(Span::new(start, self.pos), "Unterminated quoted identifier")
+                    );
+                }
+            }
+        }
+    }
+
+    fn number(&mut self, start: usize) -> TokenKind {
+        while self
+            .peek_char()
+            .is_some_and(|c| c.is_ascii_digit() || c == '.')
+        {
+            self.bump();
+        }
+        if matches!(self.peek_char(), Some('e' | 'E')) {
+            let after_e = self.source[self.pos + 1..].chars().next();
+            let exponent_follows = match after_e {
+                Some('+' | '-') => self.source[self.pos + 2..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_digit()),
+                Some(c) => c.is_ascii_digit(),
+                None => false,
+            };
+            if exponent_follows {
+                self.pos += 2;
+                while self.peek_char().is_some_and(|c| c.is_ascii_digit()) {
+                    self.bump();
+                }
+            }
+        }
+        TokenKind::Number(self.source[start..self.pos].to_owned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::errors::DBError;
+
+    use super::{Keyword, Lexer, Span, TokenKind};
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        Lexer::new(source)
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_keywords_are_case_insensitive() {
+        assert_eq!(
+            kinds("SELECT * From meow"),
+            vec![
+                TokenKind::Keyword(Keyword::Select),
+                TokenKind::Star,
+                TokenKind::Keyword(Keyword::From),
+                TokenKind::Ident("meow".to_owned()),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(
+            kinds("'it''s, fine' 1 100. .5 2.5e-3 \"Quoted \"\"name\"\"\" x'00fF' X'' xy"),
+            vec![
+                TokenKind::String("it's, fine".to_owned()),
+                TokenKind::Number("1".to_owned()),
+                TokenKind::Number("100.".to_owned()),
+                TokenKind::Number(".5".to_owned()),
+                TokenKind::Number("2.5e-3".to_owned()),
+                TokenKind::Ident("Quoted \"name\"".to_owned()),
+                TokenKind::Bytes(vec![0x00, 0xff]),
+                TokenKind::Bytes(vec![]),
+                TokenKind::Ident("xy".to_owned()),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operators_and_comments() {
+        assert_eq!(
+            kinds("a<=b -- trailing comment\n/* block\ncomment */ <> != >= < > = ; + - * / % ||"),
+            vec![
+                TokenKind::Ident("a".to_owned()),
+                TokenKind::Lte,
+                TokenKind::Ident("b".to_owned()),
+                TokenKind::Neq,
+                TokenKind::Neq,
+                TokenKind::Gte,
+                TokenKind::Lt,
+                TokenKind::Gt,
+                TokenKind::Eq,
+                TokenKind::Semicolon,
+                TokenKind::Plus,
+                TokenKind::Minus,
+                TokenKind::Star,
+                TokenKind::Slash,
+                TokenKind::Percent,
+                TokenKind::Concat,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let tokens = Lexer::new("select  name").tokenize().unwrap();
+        assert_eq!(tokens[0].span, Span::new(0, 6));
+        assert_eq!(tokens[1].span, Span::new(8, 12));
+        assert_eq!(tokens[2].span, Span::new(12, 12));
+    }
+
+    #[test]
+    fn test_errors() {
+        let DBError::Parse(msg) = Lexer::new("select 'abc").tokenize().unwrap_err() else {
+            panic!("Expected parse error");
+        };
+        assert_eq!(msg, "Unterminated string literal at line 1, column 8");
+        let DBError::Parse(msg) = Lexer::new("select\n  #").tokenize().unwrap_err() else {
+            panic!("Expected parse error");
+        };
+        assert_eq!(msg, "Unexpected character `#` at line 2, column 3");
+        for literal in ["x'abc'", "x'+f'", "x'0g'"] {
+            let DBError::Parse(msg) = Lexer::new(&format!("select {}", literal))
+                .tokenize()
+                .unwrap_err()
+            else {
+                panic!("Expected parse error from {}", literal);
+            };
+            assert_eq!(
+                msg,
+                "Invalid hex literal, expected pairs of hex digits at line 1, column 8"
+            );
+        }
+    }
+}
@@ -0,0 +1,1897 @@
+use crate::{
+    errors::{self, DBError},
+    expr::{BinaryOp, CompareOp},
+    join::JoinKind,
+    numeric::{MAX_PRECISION, Numeric},
+    sql::{
+        ast::{
+            AlterAction, Assignment, ColumnConstraint, ColumnConstraintKind, ColumnDecl, Expr,
+            ExprKind, FromClause, Ident, JoinClause, OrderByItem, References, SelectItem,
+            SelectStatement, Statement, TableConstraint, TableConstraintKind, TableRef,
+        },
+        lexer::{Keyword, Lexer, Span, Token, TokenKind},
+    },
+    table::ReferentialAction,
+    values::{DBType, DBValue},
+};
+
+/// Recursive-descent parser turning a query string into a [`Statement`].
+pub(crate) struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a str) -> errors::Result<Self> {
+        let tokens = Lexer::new(source).tokenize()?;
+        Ok(Self {
+            source,
+            tokens,
+            pos: 0,
+        })
+    }
+
+    /// Parses exactly one statement, optionally terminated by `;`.
+    pub fn parse(source: &'a str) -> errors::Result<Statement> {
+        let mut parser = Self::new(source)?;
+        let statement = parser.parse_statement()?;
+        while parser.eat(&TokenKind::Semicolon) {}
+        if parser.peek().kind != TokenKind::Eof {
+            return Err(parser.unexpected("end of statement"));
+        }
+        Ok(statement)
+    }
+
+    /// Parses a whole string as an expression, e.g. a column default stored in the catalog.
+    pub fn parse_expression(source: &'a str) -> errors::Result<Expr> {
+        let mut parser = Self::new(source)?;
+        let expr = parser.parse_expr()?;
+        if parser.peek().kind != TokenKind::Eof {
+            return Err(parser.unexpected("end of expression"));
+        }
+        Ok(expr)
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    /// Returns the token after the next one, which is the end of input at the end.
+    fn peek_second(&self) -> &Token {
+        self.tokens
+            .get(self.pos + 1)
+            .unwrap_or_else(|| self.tokens.last().unwrap())
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, span: Span, msg: &str) -> DBError {
+        DBError::Parse(format!("{} at {}", msg, span.position(self.source)))
+    }
+
+    fn unexpected(&self, expected: &str) -> DBError {
+        let token = self.peek();
+        self.error(
+            token.span,
+            &format!("Expected {}, found {}", expected, token.kind),
+        )
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> errors::Result<Span> {
+        if self.peek().kind == kind {
+            Ok(self.advance().span)
+        } else {
+            Err(self.unexpected(&kind.to_string()))
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: Keyword) -> bool {
+        self.eat(&TokenKind::Keyword(keyword))
+    }
+
+    fn expect_keyword(&mut self, keyword: Keyword) -> errors::Result<Span> {
+        self.expect(TokenKind::Keyword(keyword))
+    }
+
+    /// Expects an identifier that is a keyword only in this position, e.g. `KEY`.
+    fn expect_word(&mut self, word: &str) -> errors::Result<Span> {
+        match &self.peek().kind {
+            TokenKind::Ident(name) if name.eq_ignore_ascii_case(word) => Ok(self.advance().span),
+            _ => Err(self.unexpected(&word.to_uppercase())),
+        }
+    }
+
+    /// Consumes an identifier that is a keyword only in this position, if it's next.
+    fn eat_word(&mut self, word: &str) -> bool {
+        match &self.peek().kind {
+            TokenKind::Ident(name) if name.eq_ignore_ascii_case(word) => {
+                self.advance();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_ident(&mut self) -> errors::Result<Ident> {
+        match &self.peek().kind {
+            TokenKind::Ident(name) => {
+                let name = name.clone();
+                let span = self.advance().span;
+                Ok(Ident { name, span })
+            }
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    /// Parses `item (',' item)*`.
+    fn parse_comma_separated<T>(
+        &mut self,
+        mut parse_item: impl FnMut(&mut Self) -> errors::Result<T>,
+    ) -> errors::Result<Vec<T>> {
+        let mut items = vec![parse_item(self)?];
+        while self.eat(&TokenKind::Comma) {
+            items.push(parse_item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn parse_statement(&mut self) -> errors::Result<Statement> {
+        match self.peek().kind {
+            TokenKind::Keyword(Keyword::Create) => self.parse_create(),
+            TokenKind::Keyword(Keyword::Drop) => self.parse_drop(),
+            TokenKind::Keyword(Keyword::Alter) => self.parse_alter_table(),
+            TokenKind::Keyword(Keyword::Insert) => self.parse_insert(),
+            TokenKind::Keyword(Keyword::Select) => self.parse_select(),
+            TokenKind::Keyword(Keyword::Update) => self.parse_update(),
+            TokenKind::Keyword(Keyword::Delete) => self.parse_delete(),
+            TokenKind::Keyword(Keyword::Begin) => {
+                self.parse_transaction_statement(Keyword::Begin, Statement::Begin)
+            }
+            TokenKind::Keyword(Keyword::Commit) => {
+                self.parse_transaction_statement(Keyword::Commit, Statement::Commit)
+            }
+            TokenKind::Keyword(Keyword::Rollback) => {
+                self.parse_transaction_statement(Keyword::Rollback, Statement::Rollback)
+            }
+            TokenKind::Keyword(Keyword::Vacuum) => self.parse_vacuum(),
+            _ => Err(self.unexpected("a statement")),
+        }
+    }
+
+    fn parse_create(&mut self) -> errors::Result<Statement> {
+        self.expect_keyword(Keyword::Create)?;
+        if self.eat_word("index") {
+            return self.parse_create_index();
+        }
+        if self.eat_word("sequence") {
+            return self.parse_create_sequence();
+        }
+        self.expect_keyword(Keyword::Table)?;
+        let name = self.parse_ident()?;
+        self.expect(TokenKind::LParen)?;
+        let mut columns = vec![];
+        let mut constraints = vec![];
+        loop {
+            if self.at_table_constraint() {
+                constraints.push(self.parse_table_constraint()?);
+            } else {
+                columns.push(self.parse_column_decl()?);
+            }
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        Ok(Statement::CreateTable {
+            name,
+            columns,
+            constraints,
+        })
+    }
+
+    fn at_table_constraint(&self) -> bool {
+        matches!(
+            self.peek().kind,
+            TokenKind::Keyword(
+                Keyword::Constraint | Keyword::Primary | Keyword::Unique | Keyword::Foreign
+            )
+        )
+    }
+
+    fn at_column_constraint(&self) -> bool {
+        self.at_table_constraint()
+            || matches!(
+                self.peek().kind,
+                TokenKind::Keyword(
+                    Keyword::Not | Keyword::Default | Keyword::Check | Keyword::References
+                )
+            )
+    }
+
+    /// `[CONSTRAINT name]`
+    fn parse_constraint_name(&mut self) -> errors::Result<Option<Ident>> {
+        if self.eat_keyword(Keyword::Constraint) {
+            Ok(Some(self.parse_ident()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn parse_table_constraint(&mut self) -> errors::Result<TableConstraint> {
+        let name = self.parse_constraint_name()?;
+        let kind = if self.eat_keyword(Keyword::Primary) {
+            self.expect_word("key")?;
+            TableConstraintKind::PrimaryKey(self.parse_column_list()?)
+        } else if self.eat_keyword(Keyword::Unique) {
+            TableConstraintKind::Unique(self.parse_column_list()?)
+        } else if self.eat_keyword(Keyword::Foreign) {
+            self.expect_word("key")?;
+            let columns = self.parse_column_list()?;
+            self.expect_keyword(Keyword::References)?;
+            TableConstraintKind::ForeignKey {
+                columns,
+                references: self.parse_references()?,
+            }
+        } else {
+            return Err(self.unexpected("PRIMARY KEY, UNIQUE or FOREIGN KEY"));
+        };
+        Ok(TableConstraint { name, kind })
+    }
+
+    fn parse_column_constraint(&mut self) -> errors::Result<ColumnConstraint> {
+        let name = self.parse_constraint_name()?;
+        let kind = if self.eat_keyword(Keyword::Primary) {
+            self.expect_word("key")?;
+            ColumnConstraintKind::PrimaryKey
+        } else if self.eat_keyword(Keyword::Unique) {
+            ColumnConstraintKind::Unique
+        } else if self.eat_keyword(Keyword::Not) {
+            self.expect_keyword(Keyword::Null)?;
+            ColumnConstraintKind::NotNull
+        } else if self.eat_keyword(Keyword::Default) {
+            ColumnConstraintKind::Default(self.parse_expr()?)
+        } else if self.eat_keyword(Keyword::Check) {
+            self.expect(TokenKind::LParen)?;
+            let check = self.parse_expr()?;
+            self.expect(TokenKind::RParen)?;
+            ColumnConstraintKind::Check(check)
+        } else if self.eat_keyword(Keyword::References) {
+            ColumnConstraintKind::References(self.parse_references()?)
+        } else {
+            return Err(self.unexpected("a column constraint"));
+        };
+        Ok(ColumnConstraint { name, kind })
+    }
+
+    /// `table [(columns)] [ON DELETE action] [ON UPDATE action]` after REFERENCES.
+    fn parse_references(&mut self) -> errors::Result<References> {
+        let table = self.parse_ident()?;
+        let columns = if self.peek().kind == TokenKind::LParen {
+            Some(self.parse_column_list()?)
+        } else {
+            None
+        };
+        let mut references = References {
+            table,
+            columns,
+            on_delete: ReferentialAction::Restrict,
+            on_update: ReferentialAction::Restrict,
+        };
+        while self.eat_keyword(Keyword::On) {
+            if self.eat_keyword(Keyword::Delete) {
+                references.on_delete = self.parse_referential_action()?;
+            } else if self.eat_keyword(Keyword::Update) {
+                references.on_update = self.parse_referential_action()?;
+            } else {
+                return Err(self.unexpected("DELETE or UPDATE"));
+            }
+        }
+        Ok(references)
+    }
+
+    /// `RESTRICT`, `NO ACTION`, `CASCADE` or `SET NULL`. Keys are checked as soon as they
+    /// change, so NO ACTION is the same as RESTRICT.
+    fn parse_referential_action(&mut self) -> errors::Result<ReferentialAction> {
+        if self.eat_keyword(Keyword::Set) {
+            self.expect_keyword(Keyword::Null)?;
+            return Ok(ReferentialAction::SetNull);
+        }
+        let action = match &self.peek().kind {
+            TokenKind::Ident(word) if word.eq_ignore_ascii_case("restrict") => {
+                ReferentialAction::Restrict
+            }
+            TokenKind::Ident(word) if word.eq_ignore_ascii_case("cascade") => {
+                ReferentialAction::Cascade
+            }
+            TokenKind::Ident(word) if word.eq_ignore_ascii_case("no") => {
+                self.advance();
+                self.expect_word("action")?;
+                return Ok(ReferentialAction::Restrict);
+            }
+            _ => return Err(self.unexpected("RESTRICT, NO ACTION, CASCADE or SET NULL")),
+        };
+        self.advance();
+        Ok(action)
+    }
+
+    /// `name [START [WITH] n] [INCREMENT [BY] n]` after CREATE SEQUENCE.
+    fn parse_create_sequence(&mut self) -> errors::Result<Statement> {
+        let name = self.parse_ident()?;
+        let (mut start, mut increment) = (None, None);
+        loop {
+            if self.eat_word("start") {
+                self.eat_word("with");
+                start = Some(self.parse_integer()?);
+            } else if self.eat_word("increment") {
+                self.eat_keyword(Keyword::By);
+                increment = Some(self.parse_integer()?);
+            } else {
+                break;
+            }
+        }
+        Ok(Statement::CreateSequence {
+            name,
+            start,
+            increment,
+        })
+    }
+
+    /// Parses an integer with an optional sign.
+    fn parse_integer(&mut self) -> errors::Result<i64> {
+        let negative = self.eat(&TokenKind::Minus);
+        if let TokenKind::Number(text) = &self.peek().kind
+            && let Ok(value) = format!("{}{}", if negative { "-" } else { "" }, text).parse()
+        {
+            self.advance();
+            return Ok(value);
+        }
+        Err(self.unexpected("an integer"))
+    }
+
+    fn parse_create_index(&mut self) -> errors::Result<Statement> {
+        let name = self.parse_ident()?;
+        self.expect_keyword(Keyword::On)?;
+        let table = self.parse_ident()?;
+        let columns = self.parse_column_list()?;
+        Ok(Statement::CreateIndex {
+            name,
+            table,
+            columns,
+        })
+    }
+
+    /// `(name, ...)`
+    fn parse_column_list(&mut self) -> errors::Result<Vec<Ident>> {
+        self.expect(TokenKind::LParen)?;
+        let columns = self.parse_comma_separated(Self::parse_ident)?;
+        self.expect(TokenKind::RParen)?;
+        Ok(columns)
+    }
+
+    fn parse_column_decl(&mut self) -> errors::Result<ColumnDecl> {
+        let name = self.parse_ident()?;
+        let serial_type = [
+            ("smallserial", DBType::SmallInt),
+            ("serial", DBType::Int),
+            ("bigserial", DBType::BigInt),
+        ]
+        .into_iter()
+        .find(|(word, _)| self.eat_word(word))
+        .map(|(_, dtype)| dtype);
+        let serial = serial_type.is_some();
+        let dtype = match serial_type {
+            Some(dtype) => dtype,
+            None => self.parse_type()?,
+        };
+        let mut constraints = vec![];
+        while self.at_column_constraint() {
+            constraints.push(self.parse_column_constraint()?);
+        }
+        Ok(ColumnDecl {
+            name,
+            dtype,
+            serial,
+            constraints,
+        })
+    }
+
+    fn parse_type(&mut self) -> errors::Result<DBType> {
+        let TokenKind::Ident(name) = &self.peek().kind else {
+            return Err(self.unexpected("column type"));
+        };
+        let mut type_name = name.clone();
+        let mut span = self.advance().span;
+        if type_name.eq_ignore_ascii_case("double")
+            && let TokenKind::Ident(next) = &self.peek().kind
+            && next.eq_ignore_ascii_case("precision")
+        {
+            type_name = "double precision".to_owned();
+            span = span.to(self.advance().span);
+        }
+        let dtype = type_name
+            .parse::<DBType>()
+            .map_err(|_| self.error(span, &format!("Invalid type `{}`", type_name)))?;
+        if dtype == DBType::Numeric(None) && self.peek().kind == TokenKind::LParen {
+            return self.parse_numeric_modifiers();
+        }
+        Ok(dtype)
+    }
+
+    /// `(precision[, scale])` of a numeric type.
+    fn parse_numeric_modifiers(&mut self) -> errors::Result<DBType> {
+        self.expect(TokenKind::LParen)?;
+        let precision_span = self.peek().span;
+        let precision = self.parse_integer()?;
+        if !(1..=MAX_PRECISION as i64).contains(&precision) {
+            return Err(self.error(
+                precision_span,
+                &format!(
+                    "NUMERIC precision {} must be between 1 and {}",
+                    precision, MAX_PRECISION
+                ),
+            ));
+        }
+        let mut scale = 0;
+        if self.eat(&TokenKind::Comma) {
+            let scale_span = self.peek().span;
+            scale = self.parse_integer()?;
+            if !(0..=precision).contains(&scale) {
+                return Err(self.error(
+                    scale_span,
+                    &format!(
+                        "NUMERIC scale {} must be between 0 and precision {}",
+                        scale, precision
+                    ),
+                ));
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        Ok(DBType::Numeric(Some((precision as u8, scale as u8))))
+    }
+
+    fn parse_drop(&mut self) -> errors::Result<Statement> {
+        self.expect_keyword(Keyword::Drop)?;
+        if self.eat_word("index") {
+            let name = self.parse_ident()?;
+            return Ok(Statement::DropIndex { name });
+        }
+        if self.eat_word("sequence") {
+            let name = self.parse_ident()?;
+            return Ok(Statement::DropSequence { name });
+        }
+        self.expect_keyword(Keyword::Table)?;
+        let name = self.parse_ident()?;
+        Ok(Statement::DropTable { name })
+    }
+
+    fn parse_alter_table(&mut self) -> errors::Result<Statement> {
+        self.expect_keyword(Keyword::Alter)?;
+        self.expect_keyword(Keyword::Table)?;
+        let table = self.parse_ident()?;
+        let action = if self.eat_word("add") {
+            self.eat_keyword(Keyword::Column);
+            AlterAction::AddColumn(self.parse_column_decl()?)
+        } else if self.eat_keyword(Keyword::Drop) {
+            self.eat_keyword(Keyword::Column);
+            AlterAction::DropColumn(self.parse_ident()?)
+        } else if self.eat_word("rename") {
+            if self.eat_keyword(Keyword::To) {
+                AlterAction::RenameTable(self.parse_ident()?)
+            } else {
+                self.eat_keyword(Keyword::Column);
+                let name = self.parse_ident()?;
+                self.expect_keyword(Keyword::To)?;
+                let new_name = self.parse_ident()?;
+                AlterAction::RenameColumn { name, new_name }
+            }
+        } else {
+            return Err(self.unexpected("ADD, DROP or RENAME"));
+        };
+        Ok(Statement::AlterTable { table, action })
+    }
+
+    /// `keyword [TRANSACTION]`
+    fn parse_transaction_statement(
+        &mut self,
+        keyword: Keyword,
+        statement: Statement,
+    ) -> errors::Result<Statement> {
+        self.expect_keyword(keyword)?;
+        self.eat_word("transaction");
+        Ok(statement)
+    }
+
+    fn parse_vacuum(&mut self) -> errors::Result<Statement> {
+        self.expect_keyword(Keyword::Vacuum)?;
+        let full = self.eat_keyword(Keyword::Full);
+        let table = match self.peek().kind {
+            TokenKind::Ident(_) => Some(self.parse_ident()?),
+            _ => None,
+        };
+        Ok(Statement::Vacuum { full, table })
+    }
+
+    fn parse_insert(&mut self) -> errors::Result<Statement> {
+        self.expect_keyword(Keyword::Insert)?;
+        self.expect_keyword(Keyword::Into)?;
+        let table = self.parse_ident()?;
+        let columns = if self.peek().kind == TokenKind::LParen {
+            Some(self.parse_column_list()?)
+        } else {
+            None
+        };
+        self.expect_keyword(Keyword::Values)?;
+        self.expect(TokenKind::LParen)?;
+        let values = self.parse_comma_separated(Self::parse_expr)?;
+        self.expect(TokenKind::RParen)?;
+        Ok(Statement::Insert {
+            table,
+            columns,
+            values,
+        })
+    }
+
+    fn parse_select(&mut self) -> errors::Result<Statement> {
+        self.expect_keyword(Keyword::Select)?;
+        let columns = self.parse_comma_separated(Self::parse_select_item)?;
+        // `*` needs tables to take the columns from.
+        let from = if columns.contains(&SelectItem::Wildcard)
+            || self.peek().kind == TokenKind::Keyword(Keyword::From)
+        {
+            self.expect_keyword(Keyword::From)?;
+            Some(self.parse_from_clause()?)
+        } else {
+            None
+        };
+        let where_clause = self.parse_where_clause()?;
+        let group_by = if self.eat_keyword(Keyword::Group) {
+            self.expect_keyword(Keyword::By)?;
+            self.parse_comma_separated(Self::parse_expr)?
+        } else {
+            vec![]
+        };
+        let having = if self.eat_keyword(Keyword::Having) {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        let order_by = if self.eat_keyword(Keyword::Order) {
+            self.expect_keyword(Keyword::By)?;
+            self.parse_comma_separated(Self::parse_order_by_item)?
+        } else {
+            vec![]
+        };
+        let limit = if self.eat_keyword(Keyword::Limit) {
+            Some(self.parse_row_count()?)
+        } else {
+            None
+        };
+        let offset = if self.eat_keyword(Keyword::Offset) {
+            Some(self.parse_row_count()?)
+        } else {
+            None
+        };
+        Ok(Statement::Select(Box::new(SelectStatement {
+            columns,
+            from,
+            where_clause,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        })))
+    }
+
+    fn parse_table_ref(&mut self) -> errors::Result<TableRef> {
+        let name = self.parse_ident()?;
+        let alias =
+            if self.eat_keyword(Keyword::As) || matches!(self.peek().kind, TokenKind::Ident(_)) {
+                Some(self.parse_ident()?)
+            } else {
+                None
+            };
+        Ok(TableRef { name, alias })
+    }
+
+    fn parse_from_clause(&mut self) -> errors::Result<FromClause> {
+        let mut from = FromClause::Table(self.parse_table_ref()?);
+        loop {
+            let kind = if self.eat(&TokenKind::Comma) {
+                JoinKind::Cross
+            } else if self.eat_keyword(Keyword::Cross) {
+                self.expect_keyword(Keyword::Join)?;
+                JoinKind::Cross
+            } else if self.eat_keyword(Keyword::Left) {
+                self.eat_keyword(Keyword::Outer);
+                self.expect_keyword(Keyword::Join)?;
+                JoinKind::Left
+            } else if self.eat_keyword(Keyword::Right) {
+                self.eat_keyword(Keyword::Outer);
+                self.expect_keyword(Keyword::Join)?;
+                JoinKind::Right
+            } else if self.eat_keyword(Keyword::Inner) {
+                self.expect_keyword(Keyword::Join)?;
+                JoinKind::Inner
+            } else if self.eat_keyword(Keyword::Join) {
+                JoinKind::Inner
+            } else {
+                return Ok(from);
+            };
+            let right = self.parse_table_ref()?;
+            let on = if kind == JoinKind::Cross {
+                None
+            } else {
+                self.expect_keyword(Keyword::On)?;
+                Some(self.parse_expr()?)
+            };
+            from = FromClause::Join(Box::new(JoinClause {
+                left: from,
+                right,
+                kind,
+                on,
+            }));
+        }
+    }
+
+    /// Parses the non-negative integer of `LIMIT` and `OFFSET`.
+    fn parse_row_count(&mut self) -> errors::Result<usize> {
+        if let TokenKind::Number(text) = &self.peek().kind
+            && let Ok(count) = text.parse::<usize>()
+        {
+            self.advance();
+            return Ok(count);
+        }
+        Err(self.unexpected("a non-negative integer"))
+    }
+
+    fn parse_order_by_item(&mut self) -> errors::Result<OrderByItem> {
+        let expr = self.parse_expr()?;
+        let descending = if self.eat_keyword(Keyword::Desc) {
+            true
+        } else {
+            self.eat_keyword(Keyword::Asc);
+            false
+        };
+        let nulls_first = if self.eat_word("nulls") {
+            if self.eat_word("first") {
+                Some(true)
+            } else {
+                self.expect_word("last")?;
+                Some(false)
+            }
+        } else {
+            None
+        };
+        Ok(OrderByItem {
+            expr,
+            descending,
+            nulls_first,
+        })
+    }
+
+    fn parse_select_item(&mut self) -> errors::Result<SelectItem> {
+        if self.eat(&TokenKind::Star) {
+            return Ok(SelectItem::Wildcard);
+        }
+        let expr = self.parse_expr()?;
+        let alias =
+            if self.eat_keyword(Keyword::As) || matches!(self.peek().kind, TokenKind::Ident(_)) {
+                Some(self.parse_ident()?)
+            } else {
+                None
+            };
+        Ok(SelectItem::Expr { expr, alias })
+    }
+
+    fn parse_update(&mut self) -> errors::Result<Statement> {
+        self.expect_keyword(Keyword::Update)?;
+        let table = self.parse_ident()?;
+        self.expect_keyword(Keyword::Set)?;
+        let assignments = self.parse_comma_separated(|parser| {
+            let column = parser.parse_ident()?;
+            parser.expect(TokenKind::Eq)?;
+            let value = parser.parse_expr()?;
+            Ok(Assignment { column, value })
+        })?;
+        let where_clause = self.parse_where_clause()?;
+        Ok(Statement::Update {
+            table,
+            assignments,
+            where_clause,
+        })
+    }
+
+    fn parse_delete(&mut self) -> errors::Result<Statement> {
+        self.expect_keyword(Keyword::Delete)?;
+        self.expect_keyword(Keyword::From)?;
+        let table = self.parse_ident()?;
+        let where_clause = self.parse_where_clause()?;
+        Ok(Statement::Delete {
+            table,
+            where_clause,
+        })
+    }
+
+    fn parse_where_clause(&mut self) -> errors::Result<Option<Expr>> {
+        if !self.eat_keyword(Keyword::Where) {
+            return Ok(None);
+        }
+        self.parse_expr().map(Some)
+    }
+
+    fn parse_expr(&mut self) -> errors::Result<Expr> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> errors::Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.eat_keyword(Keyword::Or) {
+            let right = self.parse_and()?;
+            let span = left.span.to(right.span);
+            left = Expr {
+                kind: ExprKind::Or(Box::new(left), Box::new(right)),
+                span,
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> errors::Result<Expr> {
+        let mut left = self.parse_not()?;
+        while self.eat_keyword(Keyword::And) {
+            let right = self.parse_not()?;
+            let span = left.span.to(right.span);
+            left = Expr {
+                kind: ExprKind::And(Box::new(left), Box::new(right)),
+                span,
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> errors::Result<Expr> {
+        let start = self.peek().span;
+        if self.eat_keyword(Keyword::Not) {
+            let operand = self.parse_not()?;
+            let span = start.to(operand.span);
+            return Ok(Expr {
+                kind: ExprKind::Not(Box::new(operand)),
+                span,
+            });
+        }
+        self.parse_is()
+    }
+
+    fn parse_is(&mut self) -> errors::Result<Expr> {
+        let mut expr = self.parse_comparison()?;
+        while self.eat_keyword(Keyword::Is) {
+            let negated = self.eat_keyword(Keyword::Not);
+            let end = self.expect_keyword(Keyword::Null)?;
+            let span = expr.span.to(end);
+            expr = Expr {
+                kind: ExprKind::IsNull {
+                    operand: Box::new(expr),
+                    negated,
+                },
+                span,
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_comparison(&mut self) -> errors::Result<Expr> {
+        let left = self.parse_concat()?;
+        let op = match self.peek().kind {
+            TokenKind::Eq => CompareOp::Eq,
+            TokenKind::Neq => CompareOp::Neq,
+            TokenKind::Lt => CompareOp::Lt,
+            TokenKind::Lte => CompareOp::Lte,
+            TokenKind::Gt => CompareOp::Gt,
+            TokenKind::Gte => CompareOp::Gte,
+            _ => return Ok(left),
+        };
+        self.advance();
+        let right = self.parse_concat()?;
+        let span = left.span.to(right.span);
+        Ok(Expr {
+            kind: ExprKind::Compare {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+            },
+            span,
+        })
+    }
+
+    /// Parses a left-associative chain of binary operators from `ops`,
+    /// with operands parsed by `parse_operand`.
+    fn parse_binary_chain(
+        &mut self,
+        ops: &[(TokenKind, BinaryOp)],
+        parse_operand: fn(&mut Self) -> errors::Result<Expr>,
+    ) -> errors::Result<Expr> {
+        let mut left = parse_operand(self)?;
+        while let Some((_, op)) = ops.iter().find(|(kind, _)| &self.peek().kind == kind) {
+            self.advance();
+            let right = parse_operand(self)?;
+            let span = left.span.to(right.span);
+            left = Expr {
+                kind: ExprKind::Binary {
+                    left: Box::new(left),
+                    op: *op,
+                    right: Box::new(right),
+                },
+                span,
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_concat(&mut self) -> errors::Result<Expr> {
+        self.parse_binary_chain(
+            &[(TokenKind::Concat, BinaryOp::Concat)],
+            Self::parse_additive,
+        )
+    }
+
+    fn parse_additive(&mut self) -> errors::Result<Expr> {
+        self.parse_binary_chain(
+            &[
+                (TokenKind::Plus, BinaryOp::Add),
+                (TokenKind::Minus, BinaryOp::Sub),
+            ],
+            Self::parse_multiplicative,
+        )
+    }
+
+    fn parse_multiplicative(&mut self) -> errors::Result<Expr> {
+        self.parse_binary_chain(
+            &[
+                (TokenKind::Star, BinaryOp::Mul),
+                (TokenKind::Slash, BinaryOp::Div),
+                (TokenKind::Percent, BinaryOp::Mod),
+            ],
+            Self::parse_unary,
+        )
+    }
+
+    fn parse_unary(&mut self) -> errors::Result<Expr> {
+        let start = self.peek().span;
+        if self.peek().kind != TokenKind::Minus {
+            return self.parse_primary();
+        }
+        if let TokenKind::Number(text) = &self.peek_second().kind {
+            // Fold the sign into the literal, so that e.g. `-2147483648` is still an int.
+            let text = format!("-{}", text);
+            self.advance();
+            let span = start.to(self.advance().span);
+            let value = self.parse_number(&text, span)?;
+            return Ok(Expr {
+                kind: ExprKind::Literal(value),
+                span,
+            });
+        }
+        self.advance();
+        let operand = self.parse_unary()?;
+        let span = start.to(operand.span);
+        Ok(Expr {
+            kind: ExprKind::Negate(Box::new(operand)),
+            span,
+        })
+    }
+
+    fn parse_primary(&mut self) -> errors::Result<Expr> {
+        let token = self.peek().clone();
+        let next = &self.peek_second().kind;
+        let kind = match token.kind {
+            TokenKind::Ident(name)
+                if matches!(next, TokenKind::String(_))
+                    && name.parse::<DBType>().is_ok_and(DBType::is_temporal) =>
+            {
+                return self.parse_typed_literal();
+            }
+            TokenKind::Ident(name)
+                if name.eq_ignore_ascii_case("extract") && *next == TokenKind::LParen =>
+            {
+                return self.parse_extract();
+            }
+            TokenKind::Ident(_) if *next == TokenKind::LParen => {
+                return self.parse_function_call();
+            }
+            // Functions called without parentheses.
+            TokenKind::Ident(name)
+                if ["current_date", "current_timestamp"]
+                    .iter()
+                    .any(|function| name.eq_ignore_ascii_case(function)) =>
+            {
+                ExprKind::Function {
+                    name: self.parse_ident()?,
+                    args: vec![],
+                    distinct: false,
+                    star: false,
+                }
+            }
+            TokenKind::Ident(_) => {
+                let name = self.parse_ident()?;
+                if self.eat(&TokenKind::Dot) {
+                    let column = self.parse_ident()?;
+                    let span = name.span.to(column.span);
+                    return Ok(Expr {
+                        kind: ExprKind::Column {
+                            table: Some(name),
+                            name: column,
+                        },
+                        span,
+                    });
+                }
+                ExprKind::Column { table: None, name }
+            }
+            TokenKind::LParen => {
+                self.advance();
+                let mut expr = self.parse_expr()?;
+                let end = self.expect(TokenKind::RParen)?;
+                expr.span = token.span.to(end);
+                return Ok(expr);
+            }
+            TokenKind::Number(text) => {
+                self.advance();
+                ExprKind::Literal(self.parse_number(&text, token.span)?)
+            }
+            TokenKind::String(value) => {
+                self.advance();
+                ExprKind::Literal(DBValue::String(value))
+            }
+            TokenKind::Bytes(value) => {
+                self.advance();
+                ExprKind::Literal(DBValue::Bytes(value))
+            }
+            TokenKind::Keyword(Keyword::True) => {
+                self.advance();
+                ExprKind::Literal(DBValue::Bool(true))
+            }
+            TokenKind::Keyword(Keyword::False) => {
+                self.advance();
+                ExprKind::Literal(DBValue::Bool(false))
+            }
+            TokenKind::Keyword(Keyword::Null) => {
+                self.advance();
+                ExprKind::Literal(DBValue::Null)
+            }
+            TokenKind::Keyword(Keyword::Default) => {
+                self.advance();
+                ExprKind::Default
+            }
+            _ => return Err(self.unexpected("expression")),
+        };
+        Ok(Expr {
+            kind,
+            span: token.span,
+        })
+    }
+
+    /// `type 'text'`, a date or time literal, e.g. `date '2024-01-31'`.
+    fn parse_typed_literal(&mut self) -> errors::Result<Expr> {
+        let start = self.peek().span;
+        let dtype = self.parse_type()?;
+        let TokenKind::String(text) = self.peek().kind.clone() else {
+            return Err(self.unexpected("string"));
+        };
+        let span = start.to(self.advance().span);
+        let value = DBValue::parse_temporal(&text, dtype)
+            .ok_or_else(|| self.error(span, &format!("Invalid {} `{}`", dtype, text)))?;
+        Ok(Expr {
+            kind: ExprKind::Literal(value),
+            span,
+        })
+    }
+
+    /// `EXTRACT(field FROM operand)`
+    fn parse_extract(&mut self) -> errors::Result<Expr> {
+        let start = self.advance().span;
+        self.expect(TokenKind::LParen)?;
+        let field = self.parse_ident()?;
+        self.expect_keyword(Keyword::From)?;
+        let operand = self.parse_expr()?;
+        let end = self.expect(TokenKind::RParen)?;
+        Ok(Expr {
+            kind: ExprKind::Extract {
+                field,
+                operand: Box::new(operand),
+            },
+            span: start.to(end),
+        })
+    }
+
+    fn parse_function_call(&mut self) -> errors::Result<Expr> {
+        let name = self.parse_ident()?;
+        self.expect(TokenKind::LParen)?;
+        let star = self.eat(&TokenKind::Star);
+        let distinct = !star && self.eat_keyword(Keyword::Distinct);
+        let args = if star || (!distinct && self.peek().kind == TokenKind::RParen) {
+            vec![]
+        } else {
+            self.parse_comma_separated(Self::parse_expr)?
+        };
+        let end = self.expect(TokenKind::RParen)?;
+        let span = name.span.to(end);
+        Ok(Expr {
+            kind: ExprKind::Function {
+                name,
+                args,
+                distinct,
+                star,
+            },
+            span,
+        })
+    }
+
+    fn parse_number(&self, text: &str, span: Span) -> errors::Result<DBValue> {
+        let is_integer = text.chars().all(|c| c.is_ascii_digit() || c == '-');
+        // Integers get the narrowest of int, bigint and numeric that fits, and decimals are
+        // exact numerics, which become floats when stored in or combined with a float.
+        if is_integer && let Ok(v) = text.parse::<i64>() {
+            return Ok(i32::try_from(v).map_or(DBValue::BigInt(v), DBValue::Int));
+        }
+        if let Ok(v) = text.parse::<Numeric>() {
+            return Ok(DBValue::Numeric(v));
+        }
+        text.parse::<f64>()
+            .map(DBValue::Double)
+            .map_err(|_| self.error(span, &format!("Invalid number `{}`", text)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        errors::DBError,
+        expr::CompareOp,
+        sql::{
+            ast::{
+                AlterAction, ColumnConstraint, ColumnConstraintKind, Expr, ExprKind, FromClause,
+                Ident, JoinClause, SelectItem, SelectStatement, Statement, TableConstraintKind,
+                TableRef,
+            },
+            lexer::Span,
+        },
+        table::ReferentialAction,
+        values::{DBType, DBValue},
+    };
+
+    use super::Parser;
+
+    fn parse_select(query: &str) -> SelectStatement {
+        let Statement::Select(select) = Parser::parse(query).unwrap() else {
+            panic!("Expected select");
+        };
+        *select
+    }
+
+    fn parse_error(query: &str) -> String {
+        match Parser::parse(query) {
+            Err(DBError::Parse(msg)) => msg,
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_create_table() {
+        let Statement::CreateTable { name, columns, .. } = Parser::parse(
+            "CREATE TABLE meow (id int, name string, height DOUBLE PRECISION, is_fox bool);",
+        )
+        .unwrap() else {
+            panic!("Expected create table");
+        };
+        assert_eq!(name.name, "meow");
+        let types = columns.iter().map(|c| c.dtype).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![DBType::Int, DBType::String, DBType::Double, DBType::Bool]
+        );
+        assert_eq!(columns[2].name.name, "height");
+
+        let Statement::CreateTable { columns, .. } =
+            Parser::parse("create table t (a smallint, b bigint, c real, d bigserial)").unwrap()
+        else {
+            panic!("Expected create table");
+        };
+        let types = columns.iter().map(|c| c.dtype).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                DBType::SmallInt,
+                DBType::BigInt,
+                DBType::Real,
+                DBType::BigInt
+            ]
+        );
+        assert!(columns[3].serial);
+
+        let Statement::CreateTable { columns, .. } =
+            Parser::parse("create table t (a numeric, b numeric(10, 2), c decimal(5))").unwrap()
+        else {
+            panic!("Expected create table");
+        };
+        let types = columns.iter().map(|c| c.dtype).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                DBType::Numeric(None),
+                DBType::Numeric(Some((10, 2))),
+                DBType::Numeric(Some((5, 0)))
+            ]
+        );
+        assert_eq!(
+            parse_error("create table t (a numeric(40, 2))"),
+            "NUMERIC precision 40 must be between 1 and 38 at line 1, column 27"
+        );
+        assert_eq!(
+            parse_error("create table t (a numeric(4, 5))"),
+            "NUMERIC scale 5 must be between 0 and precision 4 at line 1, column 30"
+        );
+    }
+
+    #[test]
+    fn test_key_constraints() {
+        let Statement::CreateTable {
+            columns,
+            constraints,
+            ..
+        } = Parser::parse(
+            "create table meow (id int primary key, name text constraint meow_name unique, \
+             a int, b int, unique (a, b), constraint meow_ab primary key (b, a))",
+        )
+        .unwrap()
+        else {
+            panic!("Expected create table");
+        };
+        assert_eq!(columns.len(), 4);
+        assert_eq!(
+            columns[0].constraints,
+            [ColumnConstraint {
+                name: None,
+                kind: ColumnConstraintKind::PrimaryKey
+            }]
+        );
+        assert_eq!(columns[1].constraints[0].kind, ColumnConstraintKind::Unique);
+        assert_eq!(
+            columns[1].constraints[0].name.as_ref().unwrap().name,
+            "meow_name"
+        );
+        assert!(columns[2].constraints.is_empty());
+        let names = |columns: &[Ident]| columns.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        let [first, second] = &constraints[..] else {
+            panic!("Expected two table constraints");
+        };
+        let TableConstraintKind::Unique(columns) = &first.kind else {
+            panic!("Expected unique");
+        };
+        assert_eq!(names(columns), ["a", "b"]);
+        let TableConstraintKind::PrimaryKey(columns) = &second.kind else {
+            panic!("Expected primary key");
+        };
+        assert_eq!(names(columns), ["b", "a"]);
+        assert_eq!(second.name.as_ref().unwrap().name, "meow_ab");
+
+        // KEY is only a keyword after PRIMARY.
+        assert!(Parser::parse("create table kv (key text primary key)").is_ok());
+        assert_eq!(
+            parse_error("create table meow (id int primary)"),
+            "Expected KEY, found `)` at line 1, column 34"
+        );
+        assert_eq!(
+            parse_error("create table meow (id int, constraint c (id))"),
+            "Expected PRIMARY KEY, UNIQUE or FOREIGN KEY, found `(` at line 1, column 41"
+        );
+    }
+
+    #[test]
+    fn test_column_constraints() {
+        let source = "create table t (a int not null default 1 + 1 check (a > 0) check (a < 9))";
+        let Statement::CreateTable { columns, .. } = Parser::parse(source).unwrap() else {
+            panic!("Expected create table");
+        };
+        let kinds = columns[0]
+            .constraints
+            .iter()
+            .map(|constraint| match &constraint.kind {
+                ColumnConstraintKind::Default(expr) => format!("default {}", expr.text(source)),
+                ColumnConstraintKind::Check(expr) => format!("check {}", expr.text(source)),
+                kind => format!("{:?}", kind),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            ["NotNull", "default 1 + 1", "check a > 0", "check a < 9"]
+        );
+
+        let Statement::Insert {
+            columns: Some(columns),
+            values,
+            ..
+        } = Parser::parse("insert into t (b, a) values (default, 1)").unwrap()
+        else {
+            panic!("Expected insert with columns");
+        };
+        assert_eq!(columns.len(), 2);
+        assert_eq!(values[0].kind, ExprKind::Default);
+        assert_eq!(
+            parse_error("create table t (a int not 1)"),
+            "Expected NULL, found number 1 at line 1, column 27"
+        );
+        assert_eq!(
+            parse_error("create table t (a int check a > 0)"),
+            "Expected `(`, found identifier `a` at line 1, column 29"
+        );
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let Statement::CreateTable {
+            columns,
+            constraints,
+            ..
+        } = Parser::parse(
+            "create table food (id int, meow_id int references meow on delete cascade, \
+             a int, b int, constraint food_ab foreign key (a, b) references pair (x, y) \
+             on update set null on delete no action)",
+        )
+        .unwrap()
+        else {
+            panic!("Expected create table");
+        };
+        let ColumnConstraintKind::References(references) = &columns[1].constraints[0].kind else {
+            panic!("Expected references");
+        };
+        assert_eq!(references.table.name, "meow");
+        assert_eq!(references.columns, None);
+        assert_eq!(references.on_delete, ReferentialAction::Cascade);
+        assert_eq!(references.on_update, ReferentialAction::Restrict);
+
+        let names = |columns: &[Ident]| columns.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        let TableConstraintKind::ForeignKey {
+            columns,
+            references,
+        } = &constraints[0].kind
+        else {
+            panic!("Expected foreign key");
+        };
+        assert_eq!(constraints[0].name.as_ref().unwrap().name, "food_ab");
+        assert_eq!(names(columns), ["a", "b"]);
+        assert_eq!(references.table.name, "pair");
+        assert_eq!(names(references.columns.as_ref().unwrap()), ["x", "y"]);
+        assert_eq!(references.on_delete, ReferentialAction::Restrict);
+        assert_eq!(references.on_update, ReferentialAction::SetNull);
+
+        assert_eq!(
+            parse_error("create table t (a int references meow on delete nothing)"),
+            "Expected RESTRICT, NO ACTION, CASCADE or SET NULL, found identifier `nothing` at \
+             line 1, column 49"
+        );
+        assert_eq!(
+            parse_error("create table t (a int, foreign key (a) meow)"),
+            "Expected REFERENCES, found identifier `meow` at line 1, column 40"
+        );
+    }
+
+    #[test]
+    fn test_sequences() {
+        let Statement::CreateSequence {
+            name,
+            start,
+            increment,
+        } = Parser::parse("create sequence ids start with -5 increment by 10").unwrap()
+        else {
+            panic!("Expected create sequence");
+        };
+        assert_eq!(name.name, "ids");
+        assert_eq!((start, increment), (Some(-5), Some(10)));
+        let Statement::CreateSequence {
+            start, increment, ..
+        } = Parser::parse("create sequence ids increment -1").unwrap()
+        else {
+            panic!("Expected create sequence");
+        };
+        assert_eq!((start, increment), (None, Some(-1)));
+        let Statement::DropSequence { name } = Parser::parse("drop sequence ids").unwrap() else {
+            panic!("Expected drop sequence");
+        };
+        assert_eq!(name.name, "ids");
+
+        let Statement::CreateTable { columns, .. } =
+            Parser::parse("create table note (id serial primary key, body text)").unwrap()
+        else {
+            panic!("Expected create table");
+        };
+        assert!(columns[0].serial);
+        assert_eq!(columns[0].dtype, DBType::Int);
+        assert!(!columns[1].serial);
+
+        assert_eq!(
+            parse_error("create sequence ids start with 'a'"),
+            "Expected an integer, found string literal at line 1, column 32"
+        );
+    }
+
+    #[test]
+    fn test_create_index() {
+        let Statement::CreateIndex {
+            name,
+            table,
+            columns,
+        } = Parser::parse("create index meow_name on meow (name, id)").unwrap()
+        else {
+            panic!("Expected create index");
+        };
+        assert_eq!(name.name, "meow_name");
+        assert_eq!(table.name, "meow");
+        let columns = columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(columns, ["name", "id"]);
+        let Statement::DropIndex { name } = Parser::parse("DROP INDEX meow_name").unwrap() else {
+            panic!("Expected drop index");
+        };
+        assert_eq!(name.name, "meow_name");
+        // INDEX is a keyword only after CREATE and DROP.
+        let Statement::CreateIndex { name, table, .. } =
+            Parser::parse("create index index on index (index)").unwrap()
+        else {
+            panic!("Expected create index");
+        };
+        assert_eq!(
+            (name.name.as_str(), table.name.as_str()),
+            ("index", "index")
+        );
+        assert!(Parser::parse("create table index (index int)").is_ok());
+        assert_eq!(
+            parse_error("create index i on meow ()"),
+            "Expected identifier, found `)` at line 1, column 25"
+        );
+    }
+
+    #[test]
+    fn test_alter_table() {
+        let Statement::AlterTable {
+            table,
+            action: AlterAction::AddColumn(column),
+        } = Parser::parse("ALTER TABLE meow ADD COLUMN age int DEFAULT 1 + 2").unwrap()
+        else {
+            panic!("Expected add column");
+        };
+        assert_eq!(table.name, "meow");
+        assert_eq!(
+            (column.name.name.as_str(), column.dtype),
+            ("age", DBType::Int)
+        );
+        let [
+            ColumnConstraint {
+                kind: ColumnConstraintKind::Default(default),
+                ..
+            },
+        ] = &column.constraints[..]
+        else {
+            panic!("Expected default");
+        };
+        assert!(matches!(default.kind, ExprKind::Binary { .. }));
+        let Statement::AlterTable {
+            action: AlterAction::AddColumn(column),
+            ..
+        } = Parser::parse("alter table meow add age int").unwrap()
+        else {
+            panic!("Expected add column without default");
+        };
+        assert!(column.constraints.is_empty());
+        let Statement::AlterTable {
+            action: AlterAction::DropColumn(column),
+            ..
+        } = Parser::parse("alter table meow drop column age").unwrap()
+        else {
+            panic!("Expected drop column");
+        };
+        assert_eq!(column.name, "age");
+        let Statement::AlterTable {
+            action: AlterAction::RenameColumn { name, new_name },
+            ..
+        } = Parser::parse("alter table meow rename name to nickname").unwrap()
+        else {
+            panic!("Expected rename column");
+        };
+        assert_eq!(
+            (name.name.as_str(), new_name.name.as_str()),
+            ("name", "nickname")
+        );
+        let Statement::AlterTable {
+            action: AlterAction::RenameTable(name),
+            ..
+        } = Parser::parse("alter table meow rename to woof").unwrap()
+        else {
+            panic!("Expected rename table");
+        };
+        assert_eq!(name.name, "woof");
+        // ADD and RENAME are keywords only after the table name.
+        let Statement::AlterTable {
+            table,
+            action: AlterAction::RenameColumn { name, new_name },
+        } = Parser::parse("alter table add rename add to rename").unwrap()
+        else {
+            panic!("Expected rename column");
+        };
+        assert_eq!(
+            [table, name, new_name].map(|ident| ident.name),
+            ["add", "add", "rename"]
+        );
+        assert!(Parser::parse("create table add (rename int)").is_ok());
+        assert_eq!(
+            parse_error("alter table meow add column"),
+            "Expected identifier, found end of input at line 1, column 28"
+        );
+        assert_eq!(
+            parse_error("alter table meow set age"),
+            "Expected ADD, DROP or RENAME, found SET at line 1, column 18"
+        );
+    }
+
+    #[test]
+    fn test_transaction_statements() {
+        assert_eq!(Parser::parse("begin").unwrap(), Statement::Begin);
+        assert_eq!(
+            Parser::parse("BEGIN TRANSACTION;").unwrap(),
+            Statement::Begin
+        );
+        assert_eq!(Parser::parse("commit").unwrap(), Statement::Commit);
+        assert_eq!(
+            Parser::parse("rollback transaction").unwrap(),
+            Statement::Rollback
+        );
+        assert_eq!(
+            parse_error("commit work"),
+            "Expected end of statement, found identifier `work` at line 1, column 8"
+        );
+        assert!(Parser::parse("select transaction from transaction").is_ok());
+    }
+
+    #[test]
+    fn test_vacuum() {
+        assert_eq!(
+            Parser::parse("vacuum").unwrap(),
+            Statement::Vacuum {
+                full: false,
+                table: None
+            }
+        );
+        let Statement::Vacuum {
+            full: true,
+            table: Some(table),
+        } = Parser::parse("VACUUM FULL meow").unwrap()
+        else {
+            panic!("Expected vacuum full");
+        };
+        assert_eq!(table.name, "meow");
+        assert_eq!(
+            parse_error("vacuum full meow woof"),
+            "Expected end of statement, found identifier `woof` at line 1, column 18"
+        );
+    }
+
+    #[test]
+    fn test_insert_with_comma_in_string() {
+        let Statement::Insert { table, values, .. } =
+            Parser::parse("insert into meow values (1, 'a, b', -182.5, true)").unwrap()
+        else {
+            panic!("Expected insert");
+        };
+        assert_eq!(table.name, "meow");
+        let values = values.into_iter().map(|v| v.kind).collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                ExprKind::Literal(DBValue::Int(1)),
+                ExprKind::Literal(DBValue::String("a, b".to_owned())),
+                ExprKind::Literal(DBValue::Numeric("-182.5".parse().unwrap())),
+                ExprKind::Literal(DBValue::Bool(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let SelectStatement {
+            columns,
+            from,
+            where_clause,
+            ..
+        } = parse_select("select id,name\n  from meow -- comment\n  where height >= 100.");
+        let SelectItem::Expr { expr, alias: None } = &columns[1] else {
+            panic!("Expected expression without alias");
+        };
+        assert_eq!(render(expr), "name");
+        assert_eq!(expr.span, Span::new(10, 14));
+        let Some(FromClause::Table(table)) = from else {
+            panic!("Expected table");
+        };
+        assert_eq!(table.name.name, "meow");
+        assert_eq!(table.alias, None);
+        let ExprKind::Compare { left, op, right } = where_clause.unwrap().kind else {
+            panic!("Expected comparison");
+        };
+        let ExprKind::Column { table: None, name } = left.kind else {
+            panic!("Expected unqualified column");
+        };
+        assert_eq!(name.name, "height");
+        assert_eq!(op, CompareOp::Gte);
+        assert_eq!(
+            right.kind,
+            ExprKind::Literal(DBValue::Numeric("100".parse().unwrap()))
+        );
+
+        let SelectStatement { columns, .. } = parse_select("SELECT * FROM meow");
+        assert_eq!(columns, vec![SelectItem::Wildcard]);
+        let SelectStatement { columns, from, .. } = parse_select("select 1, 'a'");
+        assert_eq!((columns.len(), from), (2, None));
+        assert_eq!(
+            parse_error("select * where true"),
+            "Expected FROM, found WHERE at line 1, column 10"
+        );
+    }
+
+    #[test]
+    fn test_joins() {
+        let parse_from = |query: &str| parse_select(query).from.unwrap();
+        // Renders the joins as (left kind right on).
+        fn render_from(from: &FromClause) -> String {
+            let render_table = |table: &TableRef| match &table.alias {
+                Some(alias) => format!("{} {}", table.name.name, alias.name),
+                None => table.name.name.clone(),
+            };
+            match from {
+                FromClause::Table(table) => render_table(table),
+                FromClause::Join(join) => {
+                    let JoinClause {
+                        left,
+                        right,
+                        kind,
+                        on,
+                    } = join.as_ref();
+                    let on = on
+                        .as_ref()
+                        .map_or(String::new(), |on| format!(" {}", render(on)));
+                    format!(
+                        "({} {:?} {}{})",
+                        render_from(left),
+                        kind,
+                        render_table(right),
+                        on
+                    )
+                }
+            }
+        }
+        assert_eq!(
+            render_from(&parse_from(
+                "select * from a x join b as y on x.id = y.a_id left outer join c on c.id = y.id"
+            )),
+            "((a x Inner b y (x.id = y.a_id)) Left c (c.id = y.id))"
+        );
+        assert_eq!(
+            render_from(&parse_from(
+                "select * from a, b cross join c right join d on true inner join e on 1 = 1"
+            )),
+            "((((a Cross b) Cross c) Right d true) Inner e (1 = 1))"
+        );
+        assert_eq!(
+            render(&parse_where("delete from a where a.x > .5")),
+            "(a.x > 0.5)"
+        );
+        assert_eq!(
+            parse_error("select * from a join b"),
+            "Expected ON, found end of input at line 1, column 23"
+        );
+        assert_eq!(
+            parse_error("select * from a left b on true"),
+            "Expected JOIN, found identifier `b` at line 1, column 22"
+        );
+        assert_eq!(
+            parse_error("select a. from a"),
+            "Expected identifier, found FROM at line 1, column 11"
+        );
+    }
+
+    #[test]
+    fn test_select_items() {
+        let SelectStatement { columns, .. } =
+            parse_select("select *, id + 1 as next, height * -2 double_height, -id from t");
+        let rendered = columns
+            .iter()
+            .map(|item| match item {
+                SelectItem::Wildcard => "*".to_owned(),
+                SelectItem::Expr { expr, alias } => match alias {
+                    Some(alias) => format!("{} as {}", render(expr), alias.name),
+                    None => render(expr),
+                },
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            vec![
+                "*",
+                "(id + 1) as next",
+                "(height * -2) as double_height",
+                "(-id)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_by_limit() {
+        let SelectStatement { order_by, .. } = parse_select(
+            "select * from t where a order by a, b + 1 desc, c asc nulls first, d desc nulls last",
+        );
+        let rendered = order_by
+            .iter()
+            .map(|item| (render(&item.expr), item.descending, item.nulls_first))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            vec![
+                ("a".to_owned(), false, None),
+                ("(b + 1)".to_owned(), true, None),
+                ("c".to_owned(), false, Some(true)),
+                ("d".to_owned(), true, Some(false)),
+            ]
+        );
+        // The words of NULLS FIRST and NULLS LAST are still names elsewhere.
+        let SelectStatement { order_by, .. } =
+            parse_select("select first from t order by nulls nulls first, last desc nulls last");
+        let rendered = order_by
+            .iter()
+            .map(|item| (render(&item.expr), item.descending, item.nulls_first))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            vec![
+                ("nulls".to_owned(), false, Some(true)),
+                ("last".to_owned(), true, Some(false)),
+            ]
+        );
+        let SelectStatement { limit, offset, .. } =
+            parse_select("select * from t order by a limit 10 offset 20");
+        assert_eq!((limit, offset), (Some(10), Some(20)));
+        let SelectStatement { limit, offset, .. } = parse_select("select * from t offset 5");
+        assert_eq!((limit, offset), (None, Some(5)));
+        assert_eq!(
+            parse_error("select * from t limit -1"),
+            "Expected a non-negative integer, found `-` at line 1, column 23"
+        );
+        assert_eq!(
+            parse_error("select * from t limit 1.5"),
+            "Expected a non-negative integer, found number 1.5 at line 1, column 23"
+        );
+        assert_eq!(
+            parse_error("select * from t order a"),
+            "Expected BY, found identifier `a` at line 1, column 23"
+        );
+        assert_eq!(
+            parse_error("select * from t order by a nulls"),
+            "Expected LAST, found end of input at line 1, column 33"
+        );
+    }
+
+    #[test]
+    fn test_group_by() {
+        let SelectStatement {
+            columns,
+            group_by,
+            having,
+            ..
+        } = parse_select(
+            "select a, count(*), sum(b * 2), count(distinct c) from t group by a, d having max(b) > 1",
+        );
+        let rendered = columns
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expr { expr, .. } => render(expr),
+                SelectItem::Wildcard => "*".to_owned(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            vec!["a", "count(*)", "sum((b * 2))", "count(distinct c)"]
+        );
+        let group_by = group_by.iter().map(render).collect::<Vec<_>>();
+        assert_eq!(group_by, vec!["a", "d"]);
+        assert_eq!(render(&having.unwrap()), "(max(b) > 1)");
+        assert_eq!(
+            parse_error("select count(distinct) from t"),
+            "Expected expression, found `)` at line 1, column 22"
+        );
+    }
+
+    /// Renders the expression tree with explicit parentheses.
+    fn render(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Column {
+                table: Some(table),
+                name,
+            } => format!("{}.{}", table.name, name.name),
+            ExprKind::Column { table: None, name } => name.name.clone(),
+            ExprKind::Literal(value) => value.to_string(),
+            ExprKind::Negate(operand) => format!("(-{})", render(operand)),
+            ExprKind::Binary { left, op, right } => {
+                format!("({} {} {})", render(left), op, render(right))
+            }
+            ExprKind::Compare { left, op, right } => {
+                format!("({} {} {})", render(left), op, render(right))
+            }
+            ExprKind::And(left, right) => format!("({} and {})", render(left), render(right)),
+            ExprKind::Or(left, right) => format!("({} or {})", render(left), render(right)),
+            ExprKind::Not(operand) => format!("(not {})", render(operand)),
+            ExprKind::IsNull { operand, negated } => {
+                let not = if *negated { "not " } else { "" };
+                format!("({} is {}null)", render(operand), not)
+            }
+            ExprKind::Function {
+                name,
+                args,
+                distinct,
+                star,
+            } => {
+                let args = if *star {
+                    "*".to_owned()
+                } else {
+                    args.iter().map(render).collect::<Vec<_>>().join(", ")
+                };
+                let distinct = if *distinct { "distinct " } else { "" };
+                format!("{}({}{})", name.name, distinct, args)
+            }
+            ExprKind::Extract { field, operand } => {
+                format!("extract({} from {})", field.name, render(operand))
+            }
+            ExprKind::Default => "default".to_owned(),
+        }
+    }
+
+    fn parse_where(query: &str) -> Expr {
+        let Statement::Delete { where_clause, .. } = Parser::parse(query).unwrap() else {
+            panic!("Expected delete");
+        };
+        where_clause.unwrap()
+    }
+
+    #[test]
+    fn test_boolean_precedence() {
+        let expr = parse_where("delete from t where a = 1 and (b > 2 or not c)");
+        assert_eq!(render(&expr), "((a = 1) and ((b > 2) or (not c)))");
+        assert_eq!(expr.span, Span::new(20, 46));
+        let expr = parse_where("delete from t where not a or b and not not c = false");
+        assert_eq!(
+            render(&expr),
+            "((not a) or (b and (not (not (c = false)))))"
+        );
+        let expr = parse_where("delete from t where 1 <= a");
+        assert_eq!(render(&expr), "(1 <= a)");
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        let expr = parse_where("delete from t where a + b * -c % 2 >= 'x' || 'y' || z - 1");
+        assert_eq!(
+            render(&expr),
+            "((a + ((b * (-c)) % 2)) >= (('x' || 'y') || (z - 1)))"
+        );
+        let expr = parse_where("delete from t where (a - 1) * 2 = a-1*2");
+        assert_eq!(render(&expr), "(((a - 1) * 2) = (a - (1 * 2)))");
+        let expr = parse_where("delete from t where - -1 < 0");
+        assert_eq!(render(&expr), "((--1) < 0)");
+        let expr = parse_where("delete from t where not a + 1 = b is not null and c is null");
+        assert_eq!(
+            render(&expr),
+            "((not (((a + 1) = b) is not null)) and (c is null))"
+        );
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let Statement::Insert { values, .. } = Parser::parse(
+            "insert into t values (-2147483648, 2147483648, 9223372036854775808, \
+             100000000000000000000000000000000000000, 12345678901234567.89, 2.5e-3)",
+        )
+        .unwrap() else {
+            panic!("Expected insert");
+        };
+        assert_eq!(values[0].kind, ExprKind::Literal(DBValue::Int(i32::MIN)));
+        assert_eq!(
+            values[1].kind,
+            ExprKind::Literal(DBValue::BigInt(2147483648))
+        );
+        assert_eq!(
+            values[2].kind,
+            ExprKind::Literal(DBValue::Numeric("9223372036854775808".parse().unwrap()))
+        );
+        assert_eq!(values[3].kind, ExprKind::Literal(DBValue::Double(1e38)));
+        // Decimals are exact unless they have an exponent.
+        assert_eq!(
+            values[4].kind,
+            ExprKind::Literal(DBValue::Numeric("12345678901234567.89".parse().unwrap()))
+        );
+        assert_eq!(values[5].kind, ExprKind::Literal(DBValue::Double(2.5e-3)));
+    }
+
+    #[test]
+    fn test_temporal_expressions() {
+        let expr = parse_where(
+            "delete from t where extract(year from at) = 2024 and at < timestamp '2024-06-01' \
+             + interval '1 day' and day <> current_date",
+        );
+        assert_eq!(
+            render(&expr),
+            "(((extract(year from at) = 2024) and (at < (2024-06-01 00:00:00 + 1 day))) \
+             and (day != current_date()))"
+        );
+        // Type names are only literals before a string.
+        let expr = parse_where("delete from t where date = time");
+        assert_eq!(render(&expr), "(date = time)");
+        assert_eq!(
+            parse_error("delete from t where d = date '2024-02-30'"),
+            "Invalid date `2024-02-30` at line 1, column 25"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse_error("insert into meow valuess (1)"),
+            "Expected VALUES, found identifier `valuess` at line 1, column 18"
+        );
+        assert_eq!(
+            parse_error("select id from"),
+            "Expected identifier, found end of input at line 1, column 15"
+        );
+        assert_eq!(
+            parse_error("create table t (id integer)"),
+            "Invalid type `integer` at line 1, column 20"
+        );
+        assert_eq!(
+            parse_error("delete from t; delete from t"),
+            "Expected end of statement, found DELETE at line 1, column 16"
+        );
+        assert_eq!(
+            parse_error("update t set a = 1 where a == 2"),
+            "Expected expression, found `=` at line 1, column 29"
+        );
+        assert_eq!(
+            parse_error("select * from t where (a = 1"),
+            "Expected `)`, found end of input at line 1, column 29"
+        );
+        assert_eq!(
+            parse_error("select"),
+            "Expected expression, found end of input at line 1, column 7"
+        );
+        assert_eq!(
+            parse_error("delete from t where"),
+            "Expected expression, found end of input at line 1, column 20"
+        );
+        assert_eq!(
+            parse_error("select -"),
+            "Expected expression, found end of input at line 1, column 9"
+        );
+        assert_eq!(
+            parse_error("meow"),
+            "Expected a statement, found identifier `meow` at line 1, column 1"
+        );
+    }
+}
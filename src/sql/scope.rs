@@ -0,0 +1,370 @@
+use std::cell::RefCell;
+
+use crate::{
+    aggregate::{Aggregate, AggregateFunc},
+    datetime::Timestamp,
+    errors::{self, DBError},
+    expr::Expr,
+    sequence::Sequences,
+    sql::{
+        Query,
+        ast::{self, ExprKind, Ident},
+    },
+    table::{ColumnDef, Table},
+    values::{DBType, DBValue},
+};
+
+/// Names visible to an expression while binding it.
+pub(crate) trait Scope {
+    /// Binds `expr` as a whole if the scope provides it, e.g. a column or an aggregate.
+    /// Returns `None` to have the expression bound from its parts.
+    fn resolve(
+        &self,
+        source: &str,
+        expr: &ast::Expr,
+    ) -> errors::Result<Option<(Expr, Option<DBType>)>>;
+
+    /// Time of the statement's transaction, which `now()` and `current_date` return.
+    fn now(&self) -> Timestamp;
+}
+
+struct ScopeTable {
+    /// Alias of the table, or its name if it has none.
+    name: String,
+    columns: Vec<ColumnDef>,
+    /// Index of the table's first column in the row.
+    offset: usize,
+}
+
+/// Columns of the tables in the FROM clause, evaluated on rows of their tuples concatenated
+/// in the order the tables are listed, and the sequences if the statement may use them.
+pub(crate) struct TableScope {
+    tables: Vec<ScopeTable>,
+    sequences: Option<Sequences>,
+    now: Timestamp,
+}
+
+impl TableScope {
+    pub fn new(table: &Table, now: Timestamp) -> Self {
+        let mut scope = Self::empty(now);
+        scope.tables.push(ScopeTable {
+            name: table.name().to_owned(),
+            columns: table.columns().clone(),
+            offset: 0,
+        });
+        scope
+    }
+
+    /// Scope of constant expressions.
+    pub fn empty(now: Timestamp) -> Self {
+        Self {
+            tables: vec![],
+            sequences: None,
+            now,
+        }
+    }
+
+    /// Makes `nextval` and `currval` of the sequences available.
+    pub fn with_sequences(self, sequences: &Sequences) -> Self {
+        Self {
+            sequences: Some(sequences.clone()),
+            ..self
+        }
+    }
+
+    /// Binds `nextval('name')` or `currval('name')`.
+    fn resolve_sequence_call(
+        &self,
+        source: &str,
+        expr: &ast::Expr,
+    ) -> errors::Result<Option<(Expr, Option<DBType>)>> {
+        let (Some(sequences), ExprKind::Function { name, args, .. }) =
+            (&self.sequences, &expr.kind)
+        else {
+            return Ok(None);
+        };
+        let next = if name.name.eq_ignore_ascii_case("nextval") {
+            true
+        } else if name.name.eq_ignore_ascii_case("currval") {
+            false
+        } else {
+            return Ok(None);
+        };
+        let [
+            ast::Expr {
+                kind: ExprKind::Literal(DBValue::String(sequence)),
+                span,
+            },
+        ] = args.as_slice()
+        else {
+            return Err(DBError::Execution(format!(
+                "Function {} takes the name of a sequence as a string at {}",
+                name.name,
+                expr.span.position(source)
+            )));
+        };
+        if !sequences.contains(sequence) {
+            return Err(DBError::Execution(format!(
+                "Sequence {} does not exist at {}",
+                sequence,
+                span.position(source)
+            )));
+        }
+        let (sequences, name) = (sequences.clone(), sequence.clone());
+        let expr = if next {
+            Expr::NextVal { sequences, name }
+        } else {
+            Expr::CurrVal { sequences, name }
+        };
+        Ok(Some((expr, Some(DBType::BigInt))))
+    }
+
+    /// Adds the columns of `table` after the columns already in scope, qualified by `name`.
+    pub fn add_table(&mut self, source: &str, name: &Ident, table: &Table) -> errors::Result<()> {
+        if self.tables.iter().any(|other| other.name == name.name) {
+            return Err(DBError::Execution(format!(
+                "Table name {} specified more than once at {}",
+                name.name,
+                name.span.position(source)
+            )));
+        }
+        self.tables.push(ScopeTable {
+            name: name.name.clone(),
+            columns: table.columns().clone(),
+            offset: self.width(),
+        });
+        Ok(())
+    }
+
+    /// Number of columns in the row.
+    pub fn width(&self) -> usize {
+        self.tables
+            .last()
+            .map_or(0, |table| table.offset + table.columns.len())
+    }
+
+    /// All columns in the row with their indexes.
+    pub fn columns(&self) -> impl Iterator<Item = (usize, &ColumnDef)> {
+        self.tables
+            .iter()
+            .flat_map(|table| (table.offset..).zip(&table.columns))
+    }
+
+    pub fn column(&self, source: &str, column: &Ident) -> errors::Result<(usize, &ColumnDef)> {
+        self.qualified_column(source, None, column)
+    }
+
+    /// Finds `[table.]column`. An unqualified name must be unique among the tables in scope.
+    pub fn qualified_column(
+        &self,
+        source: &str,
+        table: Option<&Ident>,
+        column: &Ident,
+    ) -> errors::Result<(usize, &ColumnDef)> {
+        let tables = match table {
+            Some(table) => {
+                let Some(scope_table) = self.tables.iter().find(|t| t.name == table.name) else {
+                    return Err(DBError::Execution(format!(
+                        "Table {} is not in the FROM clause at {}",
+                        table.name,
+                        table.span.position(source)
+                    )));
+                };
+                std::slice::from_ref(scope_table)
+            }
+            None => &self.tables,
+        };
+        let mut found = tables.iter().flat_map(|table| {
+            table
+                .columns
+                .iter()
+                .enumerate()
+                .filter(|(_, def)| def.name() == column.name)
+                .map(|(i, def)| (table.offset + i, def))
+        });
+        let Some(first) = found.next() else {
+            let name = match table {
+                Some(table) => format!("{}.{}", table.name, column.name),
+                None => column.name.clone(),
+            };
+            return Err(DBError::Execution(format!(
+                "Column {} does not exist at {}",
+                name,
+                column.span.position(source)
+            )));
+        };
+        if found.next().is_some() {
+            return Err(DBError::Execution(format!(
+                "Column reference {} is ambiguous at {}",
+                column.name,
+                column.span.position(source)
+            )));
+        }
+        Ok(first)
+    }
+}
+
+impl Scope for TableScope {
+    fn resolve(
+        &self,
+        source: &str,
+        expr: &ast::Expr,
+    ) -> errors::Result<Option<(Expr, Option<DBType>)>> {
+        let ExprKind::Column { table, name } = &expr.kind else {
+            return self.resolve_sequence_call(source, expr);
+        };
+        let (index, def) = self.qualified_column(source, table.as_ref(), name)?;
+        Ok(Some((Expr::Column(index), Some(def.dtype()))))
+    }
+
+    fn now(&self) -> Timestamp {
+        self.now
+    }
+}
+
+/// Whether the expression calls `nextval` or `currval`, which need the sequences in scope.
+pub(crate) fn is_sequence_call(expr: &ast::Expr) -> bool {
+    matches!(&expr.kind, ExprKind::Function { name, .. }
+        if name.name.eq_ignore_ascii_case("nextval") || name.name.eq_ignore_ascii_case("currval"))
+}
+
+pub(crate) fn is_aggregate_call(expr: &ast::Expr) -> bool {
+    matches!(&expr.kind, ExprKind::Function { name, .. } if AggregateFunc::from_name(&name.name).is_some())
+}
+
+/// Scope of the select list, HAVING and ORDER BY of an aggregate query. They are evaluated on
+/// rows of the group key values followed by the aggregate results, so they may only use
+/// columns through the group keys or the aggregates.
+pub(crate) struct AggregateScope<'s> {
+    input: &'s TableScope,
+    group_by: Vec<Expr>,
+    aggregates: RefCell<Vec<Aggregate>>,
+}
+
+impl<'s> AggregateScope<'s> {
+    pub fn new(input: &'s TableScope, group_by: Vec<Expr>) -> Self {
+        Self {
+            input,
+            group_by,
+            aggregates: RefCell::new(vec![]),
+        }
+    }
+
+    /// The group keys and the aggregates used by the expressions bound in this scope.
+    pub fn into_parts(self) -> (Vec<Expr>, Vec<Aggregate>) {
+        (self.group_by, self.aggregates.into_inner())
+    }
+
+    /// Binds an input column that is not written in the query, e.g. one expanded from `*`.
+    pub fn input_column(&self, index: usize, name: &str) -> errors::Result<Expr> {
+        self.group_by
+            .iter()
+            .position(|key| *key == Expr::Column(index))
+            .map(Expr::Column)
+            .ok_or_else(|| {
+                DBError::Execution(format!(
+                    "Column {} must appear in the GROUP BY clause or be used in an aggregate function",
+                    name
+                ))
+            })
+    }
+
+    fn resolve_aggregate(
+        &self,
+        source: &str,
+        expr: &ast::Expr,
+        func: AggregateFunc,
+    ) -> errors::Result<(Expr, Option<DBType>)> {
+        let ExprKind::Function {
+            args,
+            distinct,
+            star,
+            ..
+        } = &expr.kind
+        else {
+            unreachable!();
+        };
+        let position = expr.span.position(source);
+        let (arg, arg_type) = match (args.as_slice(), star) {
+            (_, true) if func == AggregateFunc::Count => {
+                (Expr::Literal(DBValue::Bool(true)), Some(DBType::Bool))
+            }
+            (_, true) => {
+                return Err(DBError::Execution(format!(
+                    "Function {}(*) does not exist at {}",
+                    func, position
+                )));
+            }
+            ([arg], false) => {
+                if arg.any(&is_aggregate_call) {
+                    return Err(DBError::Execution(format!(
+                        "Aggregate function calls cannot be nested at {}",
+                        position
+                    )));
+                }
+                Query::compile_expr(source, arg.clone(), self.input)?
+            }
+            _ => {
+                return Err(DBError::Execution(format!(
+                    "Function {} takes exactly one argument at {}",
+                    func, position
+                )));
+            }
+        };
+        let dtype = func.result_type(arg_type).ok_or_else(|| {
+            DBError::Execution(format!(
+                "Function {} is not defined for {} at {}",
+                func,
+                Query::type_name(arg_type),
+                position
+            ))
+        })?;
+        let aggregate = Aggregate {
+            func,
+            arg,
+            distinct: *distinct,
+        };
+        let mut aggregates = self.aggregates.borrow_mut();
+        let index = match aggregates.iter().position(|other| *other == aggregate) {
+            Some(index) => index,
+            None => {
+                aggregates.push(aggregate);
+                aggregates.len() - 1
+            }
+        };
+        Ok((Expr::Column(self.group_by.len() + index), Some(dtype)))
+    }
+}
+
+impl Scope for AggregateScope<'_> {
+    fn resolve(
+        &self,
+        source: &str,
+        expr: &ast::Expr,
+    ) -> errors::Result<Option<(Expr, Option<DBType>)>> {
+        if let ExprKind::Function { name, .. } = &expr.kind
+            && let Some(func) = AggregateFunc::from_name(&name.name)
+        {
+            return self.resolve_aggregate(source, expr, func).map(Some);
+        }
+        if !expr.any(&is_aggregate_call)
+            && let Ok((bound, dtype)) = Query::compile_expr(source, expr.clone(), self.input)
+            && let Some(index) = self.group_by.iter().position(|key| *key == bound)
+        {
+            return Ok(Some((Expr::Column(index), dtype)));
+        }
+        if let ExprKind::Column { table, name } = &expr.kind {
+            self.input.qualified_column(source, table.as_ref(), name)?;
+            return Err(DBError::Execution(format!(
+                "Column {} must appear in the GROUP BY clause or be used in an aggregate function at {}",
+                expr.text(source),
+                expr.span.position(source)
+            )));
+        }
+        Ok(None)
+    }
+
+    fn now(&self) -> Timestamp {
+        self.input.now()
+    }
+}
@@ -4,20 +4,61 @@ use std::{
 };
 
 use crate::{
+    btree::IndexEntry,
+    errors::{self, DBError},
     page_table::PageTable,
+    pager::Pager,
     serializer::{BinaryReader, BinaryWriter},
-    values::DBType,
+    tuple::Tuple,
+    values::{DBType, DBValue},
 };
 
 #[derive(PartialEq, Debug, Clone)]
 pub(crate) struct ColumnDef {
+    /// Identifies the column in the [`SchemaVersion`]s of the table, set by the table.
+    id: u32,
     name: String,
     dtype: DBType,
+    /// Value of the column in tuples written before it was added.
+    missing: DBValue,
+    not_null: bool,
+    /// Source of the DEFAULT expression.
+    default: Option<String>,
+    /// Sources of the CHECK expressions, which may use the other columns of the row.
+    checks: Vec<String>,
 }
 
 impl ColumnDef {
     pub fn new(name: String, dtype: DBType) -> Self {
-        Self { name, dtype }
+        Self {
+            id: 0,
+            name,
+            dtype,
+            missing: DBValue::Null,
+            not_null: false,
+            default: None,
+            checks: vec![],
+        }
+    }
+
+    pub fn with_missing(self, missing: DBValue) -> Self {
+        Self { missing, ..self }
+    }
+
+    pub fn with_not_null(self, not_null: bool) -> Self {
+        Self { not_null, ..self }
+    }
+
+    pub fn with_default(self, default: Option<String>) -> Self {
+        Self { default, ..self }
+    }
+
+    pub fn with_checks(self, checks: Vec<String>) -> Self {
+        Self { checks, ..self }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
     }
 
     pub fn name(&self) -> &str {
@@ -28,34 +69,366 @@ impl ColumnDef {
         self.dtype
     }
 
+    pub fn missing(&self) -> &DBValue {
+        &self.missing
+    }
+
+    pub fn not_null(&self) -> bool {
+        self.not_null
+    }
+
+    pub fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    pub fn checks(&self) -> &[String] {
+        &self.checks
+    }
+
+    fn read_optional_string(reader: &mut BinaryReader<impl Read>) -> io::Result<Option<String>> {
+        if reader.read_bool()? {
+            Ok(Some(reader.read_string()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn write_optional_string(
+        writer: &mut BinaryWriter<impl Write>,
+        value: Option<&str>,
+    ) -> io::Result<()> {
+        writer.write_bool(value.is_some())?;
+        if let Some(value) = value {
+            writer.write_string(value)?;
+        }
+        Ok(())
+    }
+
     fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        let id = reader.read_u32()?;
         let name = reader.read_string()?;
-        let dtype = match reader.read_u8()? {
-            0 => DBType::Bool,
-            1 => DBType::Int,
-            2 => DBType::Double,
-            3 => DBType::String,
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Invalid column type",
-                ));
-            }
-        };
-        Ok(Self { name, dtype })
+        let dtype = DBType::from_reader(reader)?;
+        let missing = DBValue::read_tagged(reader)?;
+        let not_null = reader.read_bool()?;
+        let default = Self::read_optional_string(reader)?;
+        let check_count = reader.read_u32()? as usize;
+        let checks = (0..check_count)
+            .map(|_| reader.read_string())
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            id,
+            name,
+            dtype,
+            missing,
+            not_null,
+            default,
+            checks,
+        })
+    }
+
+    fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_u32(self.id)?;
+        writer.write_string(&self.name)?;
+        self.dtype.write(writer)?;
+        self.missing.write_tagged(writer)?;
+        writer.write_bool(self.not_null)?;
+        Self::write_optional_string(writer, self.default())?;
+        writer.write_u32(self.checks.len() as u32)?;
+        for check in &self.checks {
+            writer.write_string(check)?;
+        }
+        Ok(())
+    }
+}
+
+/// Columns of the tuples written with a version of a table's schema. Adding or dropping a
+/// column starts a new version, and tuples keep the layout of the version they were
+/// written with until they are updated, see [`crate::tuple::Tuple::read`].
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct SchemaVersion {
+    /// Ids and types of the stored columns.
+    columns: Vec<(u32, DBType)>,
+}
+
+impl SchemaVersion {
+    fn new(columns: &[ColumnDef]) -> Self {
+        Self {
+            columns: columns
+                .iter()
+                .map(|column| (column.id, column.dtype))
+                .collect(),
+        }
+    }
+
+    pub fn columns(&self) -> &[(u32, DBType)] {
+        &self.columns
+    }
+
+    fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        let column_count = reader.read_u32()? as usize;
+        let columns = (0..column_count)
+            .map(|_| Ok((reader.read_u32()?, DBType::from_reader(reader)?)))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { columns })
+    }
+
+    fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_u32(self.columns.len() as u32)?;
+        for (id, dtype) in &self.columns {
+            writer.write_u32(*id)?;
+            dtype.write(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Constraint enforced with an index, which keeps the keys of the table unique. Like in SQL,
+/// keys with NULLs are not equal to any other key.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub(crate) enum KeyConstraint {
+    /// Also doesn't allow NULLs in the key columns.
+    PrimaryKey,
+    Unique,
+}
+
+impl KeyConstraint {
+    fn tag(constraint: Option<Self>) -> u8 {
+        match constraint {
+            None => 0,
+            Some(KeyConstraint::PrimaryKey) => 1,
+            Some(KeyConstraint::Unique) => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> io::Result<Option<Self>> {
+        match tag {
+            0 => Ok(None),
+            1 => Ok(Some(KeyConstraint::PrimaryKey)),
+            2 => Ok(Some(KeyConstraint::Unique)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid key constraint",
+            )),
+        }
+    }
+}
+
+/// B+tree index on some of the table's columns, see [`crate::btree::BTree`].
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct IndexDef {
+    id: u32,
+    name: String,
+    /// Indexes of the key columns in the table.
+    columns: Vec<usize>,
+    /// Set for the indexes of PRIMARY KEY and UNIQUE constraints, which have the name of the
+    /// constraint.
+    constraint: Option<KeyConstraint>,
+}
+
+impl IndexDef {
+    pub fn new(id: u32, name: String, columns: Vec<usize>) -> Self {
+        Self {
+            id,
+            name,
+            columns,
+            constraint: None,
+        }
+    }
+
+    pub fn with_constraint(self, constraint: KeyConstraint) -> Self {
+        Self {
+            constraint: Some(constraint),
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn constraint(&self) -> Option<KeyConstraint> {
+        self.constraint
+    }
+
+    pub fn columns(&self) -> &[usize] {
+        &self.columns
+    }
+
+    fn uses_column(&self, column: usize) -> bool {
+        self.columns.contains(&column)
+    }
+
+    pub fn get_file_path(&self, storage_dir: &Path) -> PathBuf {
+        storage_dir.join(format!("{}.idx", self.id))
+    }
+
+    /// Key values of the tuple.
+    pub fn key(&self, tuple: &Tuple) -> Vec<DBValue> {
+        self.columns
+            .iter()
+            .map(|&column| tuple.values[column].clone())
+            .collect()
+    }
+
+    pub fn entry(&self, tuple: &Tuple, tuple_id: (u32, u16)) -> IndexEntry {
+        IndexEntry {
+            values: self.key(tuple),
+            tuple_id,
+        }
+    }
+
+    fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        let id = reader.read_u32()?;
+        let name = reader.read_string()?;
+        let column_count = reader.read_u32()? as usize;
+        let columns = (0..column_count)
+            .map(|_| Ok(reader.read_u32()? as usize))
+            .collect::<io::Result<Vec<_>>>()?;
+        let constraint = KeyConstraint::from_tag(reader.read_u8()?)?;
+        Ok(Self {
+            id,
+            name,
+            columns,
+            constraint,
+        })
     }
 
     fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_u32(self.id)?;
         writer.write_string(&self.name)?;
-        let dtype = match &self.dtype {
-            DBType::Bool => 0,
-            DBType::Int => 1,
-            DBType::Double => 2,
-            DBType::String => 3,
-        };
-        writer.write_u8(dtype)?;
+        writer.write_u32(self.columns.len() as u32)?;
+        for column in &self.columns {
+            writer.write_u32(*column as u32)?;
+        }
+        writer.write_u8(KeyConstraint::tag(self.constraint))?;
+        Ok(())
+    }
+}
+
+/// What happens to the rows referencing a key when the key is deleted or updated.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub(crate) enum ReferentialAction {
+    /// Fails while the key is referenced, the default.
+    Restrict,
+    /// Deletes the referencing rows, or updates them to the new key.
+    Cascade,
+    SetNull,
+}
+
+impl ReferentialAction {
+    fn tag(self) -> u8 {
+        match self {
+            ReferentialAction::Restrict => 0,
+            ReferentialAction::Cascade => 1,
+            ReferentialAction::SetNull => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> io::Result<Self> {
+        match tag {
+            0 => Ok(ReferentialAction::Restrict),
+            1 => Ok(ReferentialAction::Cascade),
+            2 => Ok(ReferentialAction::SetNull),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid referential action",
+            )),
+        }
+    }
+}
+
+/// FOREIGN KEY constraint: unless one of the values is NULL, the values of the columns are
+/// a key of a PRIMARY KEY or UNIQUE constraint of the referenced table. Columns are
+/// identified by their ids, which stay the same when other columns are dropped.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct ForeignKey {
+    name: String,
+    columns: Vec<u32>,
+    table_id: u32,
+    referenced: Vec<u32>,
+    on_delete: ReferentialAction,
+    on_update: ReferentialAction,
+}
+
+impl ForeignKey {
+    pub fn new(name: String, columns: Vec<u32>, table_id: u32, referenced: Vec<u32>) -> Self {
+        Self {
+            name,
+            columns,
+            table_id,
+            referenced,
+            on_delete: ReferentialAction::Restrict,
+            on_update: ReferentialAction::Restrict,
+        }
+    }
+
+    pub fn with_actions(self, on_delete: ReferentialAction, on_update: ReferentialAction) -> Self {
+        Self {
+            on_delete,
+            on_update,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Ids of the referencing columns.
+    pub fn columns(&self) -> &[u32] {
+        &self.columns
+    }
+
+    /// Id of the referenced table.
+    pub fn table_id(&self) -> u32 {
+        self.table_id
+    }
+
+    /// Ids of the referenced columns.
+    pub fn referenced(&self) -> &[u32] {
+        &self.referenced
+    }
+
+    pub fn on_delete(&self) -> ReferentialAction {
+        self.on_delete
+    }
+
+    pub fn on_update(&self) -> ReferentialAction {
+        self.on_update
+    }
+
+    fn read_ids(reader: &mut BinaryReader<impl Read>) -> io::Result<Vec<u32>> {
+        let count = reader.read_u32()? as usize;
+        (0..count).map(|_| reader.read_u32()).collect()
+    }
+
+    fn write_ids(writer: &mut BinaryWriter<impl Write>, ids: &[u32]) -> io::Result<()> {
+        writer.write_u32(ids.len() as u32)?;
+        for id in ids {
+            writer.write_u32(*id)?;
+        }
         Ok(())
     }
+
+    fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        Ok(Self {
+            name: reader.read_string()?,
+            columns: Self::read_ids(reader)?,
+            table_id: reader.read_u32()?,
+            referenced: Self::read_ids(reader)?,
+            on_delete: ReferentialAction::from_tag(reader.read_u8()?)?,
+            on_update: ReferentialAction::from_tag(reader.read_u8()?)?,
+        })
+    }
+
+    fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_string(&self.name)?;
+        Self::write_ids(writer, &self.columns)?;
+        writer.write_u32(self.table_id)?;
+        Self::write_ids(writer, &self.referenced)?;
+        writer.write_u8(self.on_delete.tag())?;
+        writer.write_u8(self.on_update.tag())
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -63,11 +436,26 @@ pub(crate) struct Table {
     id: u32,
     name: String,
     columns: Vec<ColumnDef>,
+    indexes: Vec<IndexDef>,
+    /// Layouts of the stored tuples, the last one being the layout of `columns`.
+    versions: Vec<SchemaVersion>,
+    foreign_keys: Vec<ForeignKey>,
 }
 
 impl Table {
-    pub fn new(id: u32, name: String, columns: Vec<ColumnDef>) -> Self {
-        Self { id, name, columns }
+    pub fn new(id: u32, name: String, mut columns: Vec<ColumnDef>) -> Self {
+        for (i, column) in columns.iter_mut().enumerate() {
+            column.id = i as u32;
+        }
+        let versions = vec![SchemaVersion::new(&columns)];
+        Self {
+            id,
+            name,
+            columns,
+            indexes: vec![],
+            versions,
+            foreign_keys: vec![],
+        }
     }
 
     pub fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
@@ -78,7 +466,29 @@ impl Table {
         for _ in 0..column_count {
             columns.push(ColumnDef::from_reader(reader)?);
         }
-        Ok(Self { id, name, columns })
+        let index_count = reader.read_u32()? as usize;
+        let mut indexes = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            indexes.push(IndexDef::from_reader(reader)?);
+        }
+        let version_count = reader.read_u32()? as usize;
+        let mut versions = Vec::with_capacity(version_count);
+        for _ in 0..version_count {
+            versions.push(SchemaVersion::from_reader(reader)?);
+        }
+        let foreign_key_count = reader.read_u32()? as usize;
+        let mut foreign_keys = Vec::with_capacity(foreign_key_count);
+        for _ in 0..foreign_key_count {
+            foreign_keys.push(ForeignKey::from_reader(reader)?);
+        }
+        Ok(Self {
+            id,
+            name,
+            columns,
+            indexes,
+            versions,
+            foreign_keys,
+        })
     }
 
     pub fn write_metadata(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
@@ -88,6 +498,18 @@ impl Table {
         for column in &self.columns {
             column.write(writer)?;
         }
+        writer.write_u32(self.indexes.len() as u32)?;
+        for index in &self.indexes {
+            index.write(writer)?;
+        }
+        writer.write_u32(self.versions.len() as u32)?;
+        for version in &self.versions {
+            version.write(writer)?;
+        }
+        writer.write_u32(self.foreign_keys.len() as u32)?;
+        for foreign_key in &self.foreign_keys {
+            foreign_key.write(writer)?;
+        }
         Ok(())
     }
 
@@ -95,17 +517,140 @@ impl Table {
         storage_dir.join(format!("{}.tbl", self.id))
     }
 
-    pub fn get_page_table(&self, storage_dir: &Path) -> io::Result<PageTable> {
-        PageTable::load(self, self.get_table_file_path(storage_dir))
+    pub fn get_fsm_file_path(&self, storage_dir: &Path) -> PathBuf {
+        storage_dir.join(format!("{}.fsm", self.id))
+    }
+
+    pub fn get_toast_file_path(&self, storage_dir: &Path) -> PathBuf {
+        storage_dir.join(format!("{}.toast", self.id))
+    }
+
+    pub fn get_page_table<'a>(
+        &'a self,
+        storage_dir: &Path,
+        pager: &'a Pager,
+    ) -> io::Result<PageTable<'a>> {
+        PageTable::load(pager, self, self.get_table_file_path(storage_dir))
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
     }
 
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
     pub fn columns(&self) -> &Vec<ColumnDef> {
         &self.columns
     }
+
+    /// Version of the schema new tuples are written with.
+    pub fn schema_version(&self) -> u16 {
+        (self.versions.len() - 1) as u16
+    }
+
+    pub fn stored_columns(&self, version: u16) -> errors::Result<&[(u32, DBType)]> {
+        match self.versions.get(version as usize) {
+            Some(version) => Ok(version.columns()),
+            None => Err(DBError::Integrity(format!(
+                "Tuple has unknown schema version {} of table {}",
+                version, self.name
+            ))),
+        }
+    }
+
+    fn add_schema_version(&mut self) -> errors::Result<()> {
+        if self.versions.len() > u16::MAX as usize {
+            return Err(DBError::Execution(format!(
+                "Table {} has too many schema changes",
+                self.name
+            )));
+        }
+        self.versions.push(SchemaVersion::new(&self.columns));
+        Ok(())
+    }
+
+    /// Adds a column, which has its missing value in the tuples already stored.
+    pub fn add_column(&mut self, mut column: ColumnDef) -> errors::Result<()> {
+        column.id = self
+            .versions
+            .iter()
+            .flat_map(|version| version.columns())
+            .map(|&(id, _)| id + 1)
+            .max()
+            .unwrap_or(0);
+        self.columns.push(column);
+        self.add_schema_version()
+    }
+
+    /// Removes the column and returns the indexes that used it, which are removed too, like
+    /// its foreign keys. The values of the column stay in the stored tuples until they are
+    /// updated.
+    pub fn drop_column(&mut self, position: usize) -> errors::Result<Vec<IndexDef>> {
+        let column = self.columns.remove(position);
+        self.foreign_keys
+            .retain(|foreign_key| !foreign_key.columns.contains(&column.id));
+        let (dropped, mut kept): (Vec<_>, Vec<_>) = self
+            .indexes
+            .drain(..)
+            .partition(|index| index.uses_column(position));
+        for index in &mut kept {
+            for column in &mut index.columns {
+                if *column > position {
+                    *column -= 1;
+                }
+            }
+        }
+        self.indexes = kept;
+        self.add_schema_version()?;
+        Ok(dropped)
+    }
+
+    pub fn rename_column(&mut self, position: usize, name: String) {
+        self.columns[position].name = name;
+    }
+
+    pub fn set_checks(&mut self, position: usize, checks: Vec<String>) {
+        self.columns[position].checks = checks;
+    }
+
+    pub fn indexes(&self) -> &[IndexDef] {
+        &self.indexes
+    }
+
+    pub fn add_index(&mut self, index: IndexDef) {
+        self.indexes.push(index);
+    }
+
+    pub fn remove_index(&mut self, name: &str) -> Option<IndexDef> {
+        let position = self.indexes.iter().position(|index| index.name == name)?;
+        Some(self.indexes.remove(position))
+    }
+
+    pub fn foreign_keys(&self) -> &[ForeignKey] {
+        &self.foreign_keys
+    }
+
+    pub fn add_foreign_key(&mut self, foreign_key: ForeignKey) {
+        self.foreign_keys.push(foreign_key);
+    }
+
+    /// Positions of the columns with the given ids, which are all columns of the table.
+    pub fn column_positions(&self, ids: &[u32]) -> Vec<usize> {
+        ids.iter()
+            .map(|&id| {
+                self.columns
+                    .iter()
+                    .position(|column| column.id == id)
+                    .unwrap()
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,334 @@
+use std::{
+    fmt,
+    io::{self, Cursor, Read, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    errors,
+    page::PAGE_SIZE,
+    pager::Pager,
+    serializer::{BinaryReader, BinaryWriter},
+};
+
+const META_PAGE_ID: u32 = 0;
+/// Chunk pages start with the id of the next page of the value (0 for the last one) and the
+/// number of bytes used.
+const CHUNK_HEADER_SIZE: usize = 4 + 2;
+const CHUNK_DATA_SIZE: usize = PAGE_SIZE - CHUNK_HEADER_SIZE;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = MIN_MATCH + u8::MAX as usize;
+const MAX_DISTANCE: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/// LZSS compression. Every group of up to 8 items starts with a flag byte, where a set bit
+/// marks a match (distance `u16` and length minus [`MIN_MATCH`] `u8`) and a clear bit a
+/// literal byte.
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2);
+    let mut last_seen = vec![usize::MAX; 1 << HASH_BITS];
+    let mut i = 0;
+    while i < data.len() {
+        let flags = out.len();
+        out.push(0);
+        for bit in 0..8 {
+            if i >= data.len() {
+                break;
+            }
+            let mut best = (0, 0);
+            if i + MIN_MATCH <= data.len() {
+                let bucket = hash(&data[i..]);
+                let candidate = last_seen[bucket];
+                last_seen[bucket] = i;
+                if candidate != usize::MAX && i - candidate <= MAX_DISTANCE {
+                    let len = data[candidate..]
+                        .iter()
+                        .zip(&data[i..])
+                        .take(MAX_MATCH)
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    best = (len, i - candidate);
+                }
+            }
+            let (len, distance) = best;
+            if len >= MIN_MATCH {
+                out[flags] |= 1 << bit;
+                out.extend_from_slice(&(distance as u16).to_be_bytes());
+                out.push((len - MIN_MATCH) as u8);
+                i += len;
+            } else {
+                out.push(data[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn decompress(data: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    let corrupted = || io::Error::new(io::ErrorKind::InvalidData, "Corrupted compressed value");
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    while out.len() < len {
+        let flags = *data.get(i).ok_or_else(corrupted)?;
+        i += 1;
+        for bit in 0..8 {
+            if out.len() >= len {
+                break;
+            }
+            if flags & (1 << bit) == 0 {
+                out.push(*data.get(i).ok_or_else(corrupted)?);
+                i += 1;
+                continue;
+            }
+            let item = data.get(i..i + 3).ok_or_else(corrupted)?;
+            let distance = u16::from_be_bytes([item[0], item[1]]) as usize;
+            let match_len = item[2] as usize + MIN_MATCH;
+            i += 3;
+            if distance == 0 || distance > out.len() {
+                return Err(corrupted());
+            }
+            // Byte by byte, as a match may overlap the bytes it produces.
+            let start = out.len() - distance;
+            for k in 0..match_len {
+                out.push(out[start + k]);
+            }
+        }
+    }
+    if out.len() != len || i != data.len() {
+        return Err(corrupted());
+    }
+    Ok(out)
+}
+
+/// Reference to a value stored in a [`Toast`] file, kept in the tuple instead of the value.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ToastPointer {
+    /// Length of the value.
+    pub len: usize,
+    first_page: u32,
+    /// Length of the stored bytes, which may be compressed.
+    stored_len: usize,
+    compressed: bool,
+}
+
+impl ToastPointer {
+    /// Size of the pointer in a tuple, after the length of the value.
+    pub fn size() -> usize {
+        4 + 4 + 1
+    }
+
+    pub fn read(reader: &mut BinaryReader<impl Read>, len: usize) -> io::Result<Self> {
+        Ok(Self {
+            len,
+            first_page: reader.read_u32()?,
+            stored_len: reader.read_u32()? as usize,
+            compressed: reader.read_bool()?,
+        })
+    }
+
+    pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_u32(self.first_page)?;
+        writer.write_u32(self.stored_len as u32)?;
+        writer.write_bool(self.compressed)
+    }
+}
+
+/// Out-of-line storage of the long values of a table, in a file next to the table file.
+/// A value is compressed if that makes it smaller, and is split into chunks stored in a
+/// chain of pages.
+///
+/// The first page holds the head of a list of free pages. The pages of freed values are
+/// added to it, and are used before the file grows.
+#[derive(Clone)]
+pub(crate) struct Toast<'a> {
+    pager: &'a Pager,
+    file_path: PathBuf,
+}
+
+impl fmt::Debug for Toast<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Toast")
+            .field("file_path", &self.file_path)
+            .finish()
+    }
+}
+
+impl PartialEq for Toast<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.file_path == other.file_path
+    }
+}
+
+impl<'a> Toast<'a> {
+    pub fn new(pager: &'a Pager, file_path: PathBuf) -> Self {
+        Self { pager, file_path }
+    }
+
+    fn read_chunk(&self, page_id: u32) -> errors::Result<(u32, Vec<u8>)> {
+        let page = self.pager.read_page(&self.file_path, page_id)?;
+        let mut reader = BinaryReader::new(Cursor::new(&*page));
+        let next = reader.read_u32()?;
+        let len = reader.read_u16()? as usize;
+        let mut data = vec![0; len];
+        reader.read_exact(&mut data)?;
+        Ok((next, data))
+    }
+
+    fn write_chunk(&self, page_id: u32, next: u32, data: &[u8]) -> errors::Result<()> {
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        let mut writer = BinaryWriter::new(&mut page);
+        writer.write_u32(next)?;
+        writer.write_u16(data.len() as u16)?;
+        writer.write_all(data)?;
+        self.pager.write_page(&self.file_path, page_id, page);
+        Ok(())
+    }
+
+    fn free_list_head(&self) -> errors::Result<u32> {
+        let meta = self.pager.read_page(&self.file_path, META_PAGE_ID)?;
+        Ok(BinaryReader::new(Cursor::new(&*meta)).read_u32()?)
+    }
+
+    fn set_free_list_head(&self, page_id: u32) -> errors::Result<()> {
+        let mut meta = vec![];
+        BinaryWriter::new(&mut meta).write_u32(page_id)?;
+        self.pager.write_page(&self.file_path, META_PAGE_ID, meta);
+        Ok(())
+    }
+
+    /// Returns ids of `count` pages to write a value to, taking free pages first.
+    fn allocate_pages(&self, count: usize) -> errors::Result<Vec<u32>> {
+        let mut page_count = match self.pager.page_count(&self.file_path) {
+            Ok(page_count) => page_count,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.set_free_list_head(0)?;
+                1
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let mut pages = Vec::with_capacity(count);
+        let mut head = self.free_list_head()?;
+        while pages.len() < count {
+            if head != 0 {
+                pages.push(head);
+                head = self.read_chunk(head)?.0;
+            } else {
+                pages.push(page_count);
+                page_count += 1;
+            }
+        }
+        self.set_free_list_head(head)?;
+        Ok(pages)
+    }
+
+    pub fn store(&self, value: &[u8]) -> errors::Result<ToastPointer> {
+        let compressed = compress(value);
+        let (data, is_compressed) = if compressed.len() < value.len() {
+            (compressed.as_slice(), true)
+        } else {
+            (value, false)
+        };
+        let chunks = data.chunks(CHUNK_DATA_SIZE).collect::<Vec<_>>();
+        let pages = self.allocate_pages(chunks.len().max(1))?;
+        for (i, &page_id) in pages.iter().enumerate() {
+            let next = pages.get(i + 1).copied().unwrap_or(0);
+            self.write_chunk(page_id, next, chunks.get(i).copied().unwrap_or_default())?;
+        }
+        Ok(ToastPointer {
+            len: value.len(),
+            first_page: pages[0],
+            stored_len: data.len(),
+            compressed: is_compressed,
+        })
+    }
+
+    pub fn load(&self, pointer: &ToastPointer) -> errors::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(pointer.stored_len);
+        let mut page_id = pointer.first_page;
+        while data.len() < pointer.stored_len {
+            let (next, chunk) = self.read_chunk(page_id)?;
+            data.extend_from_slice(&chunk);
+            page_id = next;
+        }
+        if pointer.compressed {
+            Ok(decompress(&data, pointer.len)?)
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Adds the pages of the value to the free list.
+    pub fn free(&self, pointer: &ToastPointer) -> errors::Result<()> {
+        let mut last = pointer.first_page;
+        let mut stored = 0;
+        loop {
+            let (next, chunk) = self.read_chunk(last)?;
+            stored += chunk.len();
+            if next == 0 || stored >= pointer.stored_len {
+                self.write_chunk(last, self.free_list_head()?, &[])?;
+                break;
+            }
+            last = next;
+        }
+        self.set_free_list_head(pointer.first_page)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use temp_dir::TempDir;
+
+    use crate::{pager::Pager, utils::test::random_string};
+
+    use super::{Toast, compress, decompress};
+
+    #[test]
+    fn test_compression() {
+        for data in [
+            vec![],
+            b"a".to_vec(),
+            b"meow meow meow meow meow".to_vec(),
+            "fox".repeat(10000).into_bytes(),
+            random_string().into_bytes(),
+        ] {
+            let compressed = compress(&data);
+            assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        }
+        assert!(compress("fox".repeat(10000).as_bytes()).len() < 1000);
+        assert!(decompress(&[1, 0, 5, 0], 3).is_err());
+    }
+
+    #[test]
+    fn test_toast() {
+        let dir = TempDir::new().unwrap();
+        let pager = Pager::new(dir.path().join("wal"));
+        let path = dir.path().join("test.toast");
+        let toast = Toast::new(&pager, path.clone());
+
+        let repetitive = "meow".repeat(20000).into_bytes();
+        let random = (0..30000).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        let first = toast.store(&repetitive).unwrap();
+        assert!(first.compressed);
+        let second = toast.store(&random).unwrap();
+        assert!(!second.compressed);
+        assert_eq!(toast.load(&first).unwrap(), repetitive);
+        assert_eq!(toast.load(&second).unwrap(), random);
+
+        // Freed pages are used again.
+        let page_count = pager.page_count(&path).unwrap();
+        toast.free(&second).unwrap();
+        let third = toast.store(&random).unwrap();
+        assert_eq!(pager.page_count(&path).unwrap(), page_count);
+        assert_eq!(toast.load(&third).unwrap(), random);
+        assert_eq!(toast.load(&first).unwrap(), repetitive);
+        let empty = toast.store(&[]).unwrap();
+        assert_eq!(toast.load(&empty).unwrap(), Vec::<u8>::new());
+    }
+}
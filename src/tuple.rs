@@ -1,12 +1,26 @@
-use std::io::{Read, Write};
+use std::{
+    cmp::Reverse,
+    io::{self, Read, Write},
+};
 
 use crate::{
     errors::{self, DBError},
+    page::PAGE_DATA_SIZE,
     serializer::{BinaryReader, BinaryWriter},
     table::Table,
-    values::DBValue,
+    toast::{Toast, ToastPointer},
+    values::{DBType, DBValue},
 };
 
+/// Tuples larger than this have their longest strings and binary strings stored in the
+/// table's [`Toast`] file until they fit.
+pub(crate) const TOAST_THRESHOLD: usize = PAGE_DATA_SIZE / 4;
+/// Set in the length of a string or binary string stored out of line, which is followed by
+/// a [`ToastPointer`] instead of its contents.
+const EXTERNAL_FLAG: u32 = 1 << 31;
+/// Stored tuples start with the version of the table's schema they were written with.
+const HEADER_SIZE: usize = 2;
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Tuple {
     pub(crate) values: Vec<DBValue>,
@@ -25,19 +39,125 @@ impl Tuple {
         self.values
     }
 
-    pub(crate) fn read(table: &Table, reader: &mut BinaryReader<impl Read>) -> errors::Result<Self> {
-        let mut values = Vec::with_capacity(table.columns().len());
-        for column in table.columns() {
-            let value = DBValue::from_reader(reader, column.dtype())?;
+    fn null_bitmap_size(column_count: usize) -> usize {
+        column_count.div_ceil(8)
+    }
+
+    /// Reads a tuple stored as the version of the table's schema it was written with, a null
+    /// bitmap (bit `i` set if column `i` of that version is NULL) and the values of the
+    /// non-null columns. Strings and binary strings stored out of line are read from
+    /// `toast`.
+    ///
+    /// The values are returned in the current columns of the table, with the missing values
+    /// of the columns added since the tuple was written.
+    pub(crate) fn read(
+        table: &Table,
+        reader: &mut BinaryReader<impl Read>,
+        toast: Option<&Toast>,
+    ) -> errors::Result<Self> {
+        let version = reader.read_u16()?;
+        let stored_columns = table.stored_columns(version)?;
+        let mut null_bitmap = vec![0; Self::null_bitmap_size(stored_columns.len())];
+        reader.read_exact(&mut null_bitmap)?;
+        let mut values = Vec::with_capacity(stored_columns.len());
+        for (i, &(_, dtype)) in stored_columns.iter().enumerate() {
+            let value = if null_bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                DBValue::Null
+            } else if dtype.is_variable_length() {
+                let data = match Self::read_length(reader)? {
+                    Ok(len) => {
+                        let mut data = vec![0; len];
+                        reader.read_exact(&mut data)?;
+                        data
+                    }
+                    Err(pointer) => {
+                        let toast = toast.ok_or_else(|| {
+                            DBError::Integrity(
+                                "Tuple has a value stored out of line, but no storage for it"
+                                    .to_owned(),
+                            )
+                        })?;
+                        toast.load(&pointer)?
+                    }
+                };
+                match dtype {
+                    DBType::String => DBValue::String(String::from_utf8(data).map_err(|_| {
+                        io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8")
+                    })?),
+                    _ => DBValue::Bytes(data),
+                }
+            } else {
+                DBValue::from_reader(reader, dtype)?
+            };
             values.push(value);
         }
+        if version == table.schema_version() {
+            return Ok(Tuple { values });
+        }
+        let mut stored_values = stored_columns
+            .iter()
+            .zip(values)
+            .map(|(&(id, _), value)| (id, value))
+            .collect::<Vec<_>>();
+        let values = table
+            .columns()
+            .iter()
+            .map(
+                |column| match stored_values.iter_mut().find(|(id, _)| *id == column.id()) {
+                    Some((_, value)) => std::mem::replace(value, DBValue::Null),
+                    None => column.missing().clone(),
+                },
+            )
+            .collect();
         Ok(Tuple { values })
     }
 
+    /// Reads the length of a string or binary string, or the pointer to its contents if
+    /// they're stored out of line.
+    fn read_length(
+        reader: &mut BinaryReader<impl Read>,
+    ) -> io::Result<Result<usize, ToastPointer>> {
+        let len = reader.read_u32()?;
+        if len & EXTERNAL_FLAG == 0 {
+            return Ok(Ok(len as usize));
+        }
+        let len = (len & !EXTERNAL_FLAG) as usize;
+        Ok(Err(ToastPointer::read(reader, len)?))
+    }
+
+    /// Returns the pointers to the values of a stored tuple that are stored out of line.
+    pub(crate) fn external_values(
+        table: &Table,
+        reader: &mut BinaryReader<impl Read>,
+    ) -> errors::Result<Vec<ToastPointer>> {
+        let stored_columns = table.stored_columns(reader.read_u16()?)?;
+        let mut null_bitmap = vec![0; Self::null_bitmap_size(stored_columns.len())];
+        reader.read_exact(&mut null_bitmap)?;
+        let mut pointers = vec![];
+        for (i, &(_, dtype)) in stored_columns.iter().enumerate() {
+            if null_bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                continue;
+            }
+            if !dtype.is_variable_length() {
+                DBValue::from_reader(reader, dtype)?;
+                continue;
+            }
+            match Self::read_length(reader)? {
+                Ok(len) => reader.read_exact(&mut vec![0; len])?,
+                Err(pointer) => pointers.push(pointer),
+            }
+        }
+        Ok(pointers)
+    }
+
+    /// Writes the tuple in the format read by [`Tuple::read`], with the current version of
+    /// the table's schema. With `toast`, long strings and binary strings are stored there
+    /// instead, see [`Tuple::stored_size`].
     pub(crate) fn write(
         &self,
         table: &Table,
         writer: &mut BinaryWriter<impl Write>,
+        toast: Option<&Toast>,
     ) -> errors::Result<()> {
         if self.values.len() != table.columns().len() {
             return Err(DBError::Execution(format!(
@@ -46,21 +166,84 @@ impl Tuple {
                 table.columns().len()
             )));
         }
+        let mut null_bitmap = vec![0; Self::null_bitmap_size(self.values.len())];
+        for (i, value) in self.values.iter().enumerate() {
+            if value.is_null() {
+                null_bitmap[i / 8] |= 1 << (i % 8);
+            }
+        }
+        writer.write_u16(table.schema_version())?;
+        writer.write_all(&null_bitmap)?;
+        let external = match toast {
+            Some(_) => self.external_columns(),
+            None => vec![],
+        };
         for (i, column) in table.columns().iter().enumerate() {
-            if self.values[i].dtype() != column.dtype() {
+            if let Some(dtype) = self.values[i].dtype()
+                && dtype != column.dtype().unconstrained()
+            {
                 return Err(DBError::Execution(format!(
                     "Tuple write error: tuple value type does not match table column type: {} != {}",
-                    self.values[i].dtype(),
+                    dtype,
                     column.dtype(),
                 )));
             }
-            self.values[i].write(writer)?;
+            if let Some(toast) = toast
+                && external.contains(&i)
+                && let Some(value) = self.values[i].as_bytes()
+            {
+                let pointer = toast.store(value)?;
+                writer.write_u32(EXTERNAL_FLAG | value.len() as u32)?;
+                pointer.write(writer)?;
+            } else {
+                self.values[i].write(writer)?;
+            }
         }
         Ok(())
     }
 
     pub fn size(&self) -> usize {
-        self.values.iter().map(|x| x.len()).sum()
+        HEADER_SIZE
+            + Self::null_bitmap_size(self.values.len())
+            + self.values.iter().map(|x| x.len()).sum::<usize>()
+    }
+
+    /// Columns with strings or binary strings that are stored out of line, the longest first until the rest of
+    /// the tuple fits in [`TOAST_THRESHOLD`].
+    fn external_columns(&self) -> Vec<usize> {
+        let mut size = self.size();
+        if size <= TOAST_THRESHOLD {
+            return vec![];
+        }
+        let mut strings = self
+            .values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| match value.as_bytes() {
+                Some(value) if value.len() > ToastPointer::size() => Some((i, value.len())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        strings.sort_by_key(|&(i, len)| (Reverse(len), i));
+        let mut columns = vec![];
+        for (i, len) in strings {
+            if size <= TOAST_THRESHOLD {
+                break;
+            }
+            size -= len - ToastPointer::size();
+            columns.push(i);
+        }
+        columns
+    }
+
+    /// Size of the tuple in a page of a table with a [`Toast`] file.
+    pub fn stored_size(&self) -> usize {
+        self.size()
+            - self
+                .external_columns()
+                .iter()
+                .map(|&i| self.values[i].len() - 4 - ToastPointer::size())
+                .sum::<usize>()
     }
 }
 
@@ -70,8 +253,8 @@ mod test {
 
     use crate::{
         serializer::{BinaryReader, BinaryWriter},
-        table::test::sample_table,
-        values::DBValue,
+        table::{ColumnDef, test::sample_table},
+        values::{DBType, DBValue},
     };
 
     use super::Tuple;
@@ -80,6 +263,8 @@ mod test {
     fn test_tuple() {
         let table = sample_table();
         let data = vec![
+            0, 0, // schema version
+            0, // null bitmap
             0, 0, 0, 1, // id
             0, 0, 0, 4, // name length
             b't', b'e', b's', b't', // name
@@ -87,7 +272,7 @@ mod test {
             1,   // active
         ];
         let reader = &mut BinaryReader::new(Cursor::new(data.clone()));
-        let tuple = Tuple::read(&table, reader).unwrap();
+        let tuple = Tuple::read(&table, reader, None).unwrap();
         assert_eq!(tuple.values.len(), 4);
         assert_eq!(tuple.values[0], DBValue::Int(1));
         assert_eq!(tuple.values[1], DBValue::String("test".to_string()));
@@ -96,10 +281,84 @@ mod test {
 
         let mut new_data = vec![];
         let mut writer = BinaryWriter::new(Cursor::new(&mut new_data));
-        tuple.write(&table, &mut writer).unwrap();
+        tuple.write(&table, &mut writer, None).unwrap();
+        assert_eq!(new_data, data);
+    }
+
+    #[test]
+    fn test_tuple_with_nulls() {
+        let table = sample_table();
+        let data = vec![
+            0, 0,      // schema version
+            0b1010, // null bitmap: name and is_fox are NULL
+            0, 0, 0, 1, // id
+            63, 253, 251, 231, 108, 139, 67, 150, // height (1.874)
+        ];
+        let reader = &mut BinaryReader::new(Cursor::new(data.clone()));
+        let tuple = Tuple::read(&table, reader, None).unwrap();
+        assert_eq!(
+            tuple.values,
+            vec![
+                DBValue::Int(1),
+                DBValue::Null,
+                DBValue::Double(1.874),
+                DBValue::Null
+            ]
+        );
+        assert_eq!(tuple.size(), data.len());
+
+        let mut new_data = vec![];
+        let mut writer = BinaryWriter::new(Cursor::new(&mut new_data));
+        tuple.write(&table, &mut writer, None).unwrap();
         assert_eq!(new_data, data);
     }
 
+    #[test]
+    fn test_tuple_of_old_schema_version() {
+        let mut table = sample_table();
+        let tuple = Tuple::new(vec![
+            DBValue::Int(1),
+            DBValue::String("test".to_string()),
+            DBValue::Double(1.874),
+            DBValue::Bool(true),
+        ]);
+        let mut data = vec![];
+        tuple
+            .write(&table, &mut BinaryWriter::new(&mut data), None)
+            .unwrap();
+
+        table
+            .add_column(
+                ColumnDef::new("age".to_string(), DBType::Int).with_missing(DBValue::Int(5)),
+            )
+            .unwrap();
+        table.drop_column(1).unwrap();
+        let reader = &mut BinaryReader::new(Cursor::new(data));
+        assert_eq!(
+            Tuple::read(&table, reader, None).unwrap().values,
+            vec![
+                DBValue::Int(1),
+                DBValue::Double(1.874),
+                DBValue::Bool(true),
+                DBValue::Int(5)
+            ]
+        );
+
+        let new_tuple = Tuple::new(vec![
+            DBValue::Int(2),
+            DBValue::Null,
+            DBValue::Bool(false),
+            DBValue::Int(3),
+        ]);
+        let mut data = vec![];
+        new_tuple
+            .write(&table, &mut BinaryWriter::new(&mut data), None)
+            .unwrap();
+        assert_eq!(&data[..2], [0, 2]);
+        let reader = &mut BinaryReader::new(Cursor::new(data));
+        assert_eq!(Tuple::read(&table, reader, None).unwrap(), new_tuple);
+    }
+
     // #[test]
     // fn test_bad_tuple() {
     //     todo!();
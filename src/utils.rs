@@ -2,6 +2,11 @@ pub fn escape(s: &str) -> String {
     s.replace("'", "''")
 }
 
+/// The bytes as lowercase hexadecimal digits, two per byte.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use rand::Rng;
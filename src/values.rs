@@ -5,59 +5,272 @@ use std::{
 };
 
 use crate::{
-    errors,
+    datetime::{Date, Interval, Time, Timestamp},
+    errors::{self, DBError},
+    numeric::Numeric,
     serializer::{BinaryReader, BinaryWriter},
-    utils::escape,
+    utils::{escape, hex},
 };
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum DBType {
     Bool,
+    SmallInt,
     Int,
+    BigInt,
+    /// Exact decimal, with the precision and scale of the values of a column if given.
+    Numeric(Option<(u8, u8)>),
+    Real,
     Double,
     String,
+    /// Binary string.
+    Bytes,
+    Date,
+    Time,
+    /// Date and time without a time zone.
+    Timestamp,
+    Interval,
 }
 
 impl Display for DBType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DBType::Bool => write!(f, "bool"),
+            DBType::SmallInt => write!(f, "smallint"),
             DBType::Int => write!(f, "int"),
+            DBType::BigInt => write!(f, "bigint"),
+            DBType::Numeric(None) => write!(f, "numeric"),
+            DBType::Numeric(Some((precision, scale))) => {
+                write!(f, "numeric({},{})", precision, scale)
+            }
+            DBType::Real => write!(f, "real"),
             DBType::Double => write!(f, "double precision"),
             DBType::String => write!(f, "text"),
+            DBType::Bytes => write!(f, "bytea"),
+            DBType::Date => write!(f, "date"),
+            DBType::Time => write!(f, "time"),
+            DBType::Timestamp => write!(f, "timestamp"),
+            DBType::Interval => write!(f, "interval"),
         }
     }
 }
 
+impl DBType {
+    /// Number identifying the type in the on-disk formats.
+    pub fn tag(self) -> u8 {
+        match self {
+            DBType::Bool => 0,
+            DBType::Int => 1,
+            DBType::Double => 2,
+            DBType::String => 3,
+            DBType::SmallInt => 4,
+            DBType::BigInt => 5,
+            DBType::Real => 6,
+            DBType::Numeric(_) => 7,
+            DBType::Date => 8,
+            DBType::Time => 9,
+            DBType::Timestamp => 10,
+            DBType::Interval => 11,
+            DBType::Bytes => 12,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> io::Result<Self> {
+        match tag {
+            0 => Ok(DBType::Bool),
+            1 => Ok(DBType::Int),
+            2 => Ok(DBType::Double),
+            3 => Ok(DBType::String),
+            4 => Ok(DBType::SmallInt),
+            5 => Ok(DBType::BigInt),
+            6 => Ok(DBType::Real),
+            7 => Ok(DBType::Numeric(None)),
+            8 => Ok(DBType::Date),
+            9 => Ok(DBType::Time),
+            10 => Ok(DBType::Timestamp),
+            11 => Ok(DBType::Interval),
+            12 => Ok(DBType::Bytes),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid type")),
+        }
+    }
+
+    /// Reads a type written by [`DBType::write`].
+    pub fn from_reader(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        let dtype = DBType::from_tag(reader.read_u8()?)?;
+        if dtype == DBType::Numeric(None) && reader.read_bool()? {
+            let precision = reader.read_u8()?;
+            return Ok(DBType::Numeric(Some((precision, reader.read_u8()?))));
+        }
+        Ok(dtype)
+    }
+
+    /// Writes the type with its modifiers, unlike [`DBType::tag`].
+    pub fn write(self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_u8(self.tag())?;
+        if let DBType::Numeric(modifiers) = self {
+            writer.write_bool(modifiers.is_some())?;
+            if let Some((precision, scale)) = modifiers {
+                writer.write_u8(precision)?;
+                writer.write_u8(scale)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The type without modifiers, which is the type of the values of a column of this type.
+    pub fn unconstrained(self) -> Self {
+        match self {
+            DBType::Numeric(_) => DBType::Numeric(None),
+            dtype => dtype,
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        matches!(self, DBType::SmallInt | DBType::Int | DBType::BigInt)
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, DBType::Real | DBType::Double)
+    }
+
+    pub fn is_numeric(self) -> bool {
+        self.is_integer() || self.is_float() || matches!(self, DBType::Numeric(_))
+    }
+
+    /// Whether values of the type are stored with their length, and can be stored out of
+    /// line when they are long.
+    pub fn is_variable_length(self) -> bool {
+        matches!(self, DBType::String | DBType::Bytes)
+    }
+
+    pub fn is_temporal(self) -> bool {
+        matches!(
+            self,
+            DBType::Date | DBType::Time | DBType::Timestamp | DBType::Interval
+        )
+    }
+
+    /// The type numbers of the two types are widened to for arithmetic and comparisons:
+    /// the wider one, with numeric wider than integer types and floating point types wider
+    /// than both. `None` if one of the types isn't numeric.
+    pub fn common_numeric(self, other: DBType) -> Option<DBType> {
+        let rank = |dtype| match dtype {
+            DBType::SmallInt => Some(0),
+            DBType::Int => Some(1),
+            DBType::BigInt => Some(2),
+            DBType::Numeric(_) => Some(3),
+            DBType::Real => Some(4),
+            DBType::Double => Some(5),
+            _ => None,
+        };
+        let wider = if rank(self)? >= rank(other)? {
+            self
+        } else {
+            other
+        };
+        Some(wider.unconstrained())
+    }
+
+    /// Whether a value of this type can be stored in a column of type `target`. Integers
+    /// fit any numeric column, and numeric and floating point values any numeric column but
+    /// an integer one, if the value is in the range of the column type (see
+    /// [`DBValue::coerce_to`]). Dates fit timestamp columns, and strings date and time
+    /// columns if they are valid values of the type.
+    pub fn can_coerce_to(self, target: DBType) -> bool {
+        self.unconstrained() == target.unconstrained()
+            || (self.is_integer() && target.is_numeric())
+            || (self.is_numeric() && target.is_numeric() && !target.is_integer())
+            || (self == DBType::Date && target == DBType::Timestamp)
+            || (self == DBType::String && target.is_temporal())
+    }
+
+    /// Whether values of the two types can be compared: numbers with numbers, dates with
+    /// timestamps, and other values with values of the same type.
+    pub fn is_comparable_with(self, other: DBType) -> bool {
+        let is_datetime = |dtype| matches!(dtype, DBType::Date | DBType::Timestamp);
+        self.unconstrained() == other.unconstrained()
+            || self.common_numeric(other).is_some()
+            || (is_datetime(self) && is_datetime(other))
+    }
+
+    /// Error for a number that doesn't fit in the type.
+    pub fn out_of_range(self) -> DBError {
+        let name = match self {
+            DBType::SmallInt => "Smallint",
+            DBType::Int => "Integer",
+            DBType::BigInt => "Bigint",
+            DBType::Numeric(_) => "Numeric",
+            DBType::Date => "Date",
+            DBType::Time => "Time",
+            DBType::Timestamp => "Timestamp",
+            DBType::Interval => "Interval",
+            _ => "Real",
+        };
+        DBError::Execution(format!("{} out of range", name))
+    }
+}
+
 impl FromStr for DBType {
     type Err = errors::DBError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
             "bool" => Ok(DBType::Bool),
+            "smallint" => Ok(DBType::SmallInt),
             "int" => Ok(DBType::Int),
+            "bigint" => Ok(DBType::BigInt),
+            "numeric" | "decimal" => Ok(DBType::Numeric(None)),
+            "real" => Ok(DBType::Real),
             "double" | "double precision" => Ok(DBType::Double),
             "text" | "string" => Ok(DBType::String),
+            "bytea" | "blob" => Ok(DBType::Bytes),
+            "date" => Ok(DBType::Date),
+            "time" => Ok(DBType::Time),
+            "timestamp" => Ok(DBType::Timestamp),
+            "interval" => Ok(DBType::Interval),
             _ => Err(errors::DBError::Parse(format!("Invalid type: {}", s))),
         }
     }
 }
 
+/// Type tag of NULL in [`DBValue::write_tagged`].
+const NULL_TAG: u8 = u8::MAX;
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum DBValue {
+    Null,
     Bool(bool),
+    SmallInt(i16),
     Int(i32),
+    BigInt(i64),
+    Numeric(Numeric),
+    Real(f32),
     Double(f64),
     String(String),
+    Bytes(Vec<u8>),
+    Date(Date),
+    Time(Time),
+    Timestamp(Timestamp),
+    Interval(Interval),
 }
 
 impl Display for DBValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            DBValue::Null => write!(f, "NULL"),
             DBValue::Bool(v) => write!(f, "{}", v),
+            DBValue::SmallInt(v) => write!(f, "{}", v),
             DBValue::Int(v) => write!(f, "{}", v),
+            DBValue::BigInt(v) => write!(f, "{}", v),
+            DBValue::Numeric(v) => write!(f, "{}", v),
+            DBValue::Real(v) => write!(f, "{}", v),
             DBValue::Double(v) => write!(f, "{}", v),
             DBValue::String(v) => write!(f, "'{}'", escape(v)),
+            DBValue::Bytes(v) => write!(f, "x'{}'", hex(v)),
+            DBValue::Date(v) => write!(f, "{}", v),
+            DBValue::Time(v) => write!(f, "{}", v),
+            DBValue::Timestamp(v) => write!(f, "{}", v),
+            DBValue::Interval(v) => write!(f, "{}", v),
         }
     }
 }
@@ -66,67 +279,280 @@ impl DBValue {
     pub fn from_reader(reader: &mut BinaryReader<impl Read>, dtype: DBType) -> io::Result<Self> {
         match dtype {
             DBType::Bool => Ok(DBValue::Bool(reader.read_bool()?)),
+            DBType::SmallInt => Ok(DBValue::SmallInt(reader.read_i16()?)),
             DBType::Int => Ok(DBValue::Int(reader.read_i32()?)),
+            DBType::BigInt => Ok(DBValue::BigInt(reader.read_i64()?)),
+            DBType::Numeric(_) => Ok(DBValue::Numeric(Numeric::from_reader(reader)?)),
+            DBType::Real => Ok(DBValue::Real(reader.read_f32()?)),
             DBType::Double => Ok(DBValue::Double(reader.read_f64()?)),
             DBType::String => Ok(DBValue::String(reader.read_string()?)),
+            DBType::Bytes => Ok(DBValue::Bytes(reader.read_bytes()?)),
+            DBType::Date => Ok(DBValue::Date(Date::from_reader(reader)?)),
+            DBType::Time => Ok(DBValue::Time(Time::from_reader(reader)?)),
+            DBType::Timestamp => Ok(DBValue::Timestamp(Timestamp::from_reader(reader)?)),
+            DBType::Interval => Ok(DBValue::Interval(Interval::from_reader(reader)?)),
         }
     }
 
+    /// Writes the value. NULLs occupy no space, they are tracked by the tuple's null bitmap.
     pub fn write(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
         match self {
+            DBValue::Null => Ok(()),
             DBValue::Bool(v) => writer.write_bool(*v),
+            DBValue::SmallInt(v) => writer.write_i16(*v),
             DBValue::Int(v) => writer.write_i32(*v),
+            DBValue::BigInt(v) => writer.write_i64(*v),
+            DBValue::Numeric(v) => v.write(writer),
+            DBValue::Real(v) => writer.write_f32(*v),
             DBValue::Double(v) => writer.write_f64(*v),
             DBValue::String(v) => writer.write_string(v),
+            DBValue::Bytes(v) => writer.write_bytes(v),
+            DBValue::Date(v) => v.write(writer),
+            DBValue::Time(v) => v.write(writer),
+            DBValue::Timestamp(v) => v.write(writer),
+            DBValue::Interval(v) => v.write(writer),
         }
     }
 
-    pub fn dtype(&self) -> DBType {
+    /// Writes the value preceded by its type tag, so it can be read back without a schema.
+    pub fn write_tagged(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        writer.write_u8(self.dtype().map_or(NULL_TAG, DBType::tag))?;
+        self.write(writer)
+    }
+
+    /// Writes the value so that values equal in comparisons have equal encodings, for hashing
+    /// them, e.g. as join or group keys. NULLs are encoded like [`DBValue::write_tagged`].
+    pub fn write_key(&self, writer: &mut BinaryWriter<impl Write>) -> io::Result<()> {
+        // Equal numbers must have equal encodings, whatever their type, so numbers are
+        // encoded as decimals without trailing zeros, and integers as bigints. That also
+        // matches -0.
+        let number = self
+            .as_numeric()
+            .or_else(|| self.as_f64().and_then(Numeric::from_f64));
+        let value = match (number, self) {
+            (_, DBValue::Null) => DBValue::Null,
+            (Some(number), _) => match number.to_i64() {
+                Some(v) => DBValue::BigInt(v),
+                None => DBValue::Numeric(number.normalized()),
+            },
+            // Dates equal the timestamps of their midnights, and intervals equal the ones
+            // of the same length, e.g. 1 day and 24 hours.
+            (None, DBValue::Date(date)) => DBValue::Timestamp(date.at_midnight()),
+            (None, DBValue::Interval(interval)) => DBValue::Interval(interval.justified()),
+            (None, value) => value.clone(),
+        };
+        value.write_tagged(writer)
+    }
+
+    pub fn read_tagged(reader: &mut BinaryReader<impl Read>) -> io::Result<Self> {
+        match reader.read_u8()? {
+            NULL_TAG => Ok(DBValue::Null),
+            tag => DBValue::from_reader(reader, DBType::from_tag(tag)?),
+        }
+    }
+
+    /// Type of the value, `None` for NULL.
+    pub fn dtype(&self) -> Option<DBType> {
         match self {
-            DBValue::Bool(_) => DBType::Bool,
-            DBValue::Int(_) => DBType::Int,
-            DBValue::Double(_) => DBType::Double,
-            DBValue::String(_) => DBType::String,
+            DBValue::Null => None,
+            DBValue::Bool(_) => Some(DBType::Bool),
+            DBValue::SmallInt(_) => Some(DBType::SmallInt),
+            DBValue::Int(_) => Some(DBType::Int),
+            DBValue::BigInt(_) => Some(DBType::BigInt),
+            DBValue::Numeric(_) => Some(DBType::Numeric(None)),
+            DBValue::Real(_) => Some(DBType::Real),
+            DBValue::Double(_) => Some(DBType::Double),
+            DBValue::String(_) => Some(DBType::String),
+            DBValue::Bytes(_) => Some(DBType::Bytes),
+            DBValue::Date(_) => Some(DBType::Date),
+            DBValue::Time(_) => Some(DBType::Time),
+            DBValue::Timestamp(_) => Some(DBType::Timestamp),
+            DBValue::Interval(_) => Some(DBType::Interval),
         }
     }
 
-    pub fn len(&self) -> usize {
+    /// Name of the value's type for error messages.
+    pub fn type_name(&self) -> String {
+        match self.dtype() {
+            Some(dtype) => dtype.to_string(),
+            None => "null".to_owned(),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, DBValue::Null)
+    }
+
+    /// Converts the value for storage in a column of type `dtype` if an implicit
+    /// conversion exists (see [`DBType::can_coerce_to`]), otherwise returns it unchanged.
+    /// Fails if the number is out of the range of the type, or the string isn't a valid
+    /// value of the type.
+    pub fn coerce_to(self, dtype: DBType) -> errors::Result<Self> {
+        match self.dtype() {
+            Some(from) if from != dtype && from.can_coerce_to(dtype) => match dtype {
+                DBType::Numeric(modifiers) => self.to_numeric(modifiers).map(DBValue::Numeric),
+                DBType::Timestamp if from == DBType::Date => {
+                    Ok(DBValue::Timestamp(self.as_timestamp().unwrap()))
+                }
+                _ if dtype.is_temporal() => {
+                    let text = self.to_raw_string();
+                    DBValue::parse_temporal(&text, dtype)
+                        .ok_or_else(|| DBError::Execution(format!("Invalid {} `{}`", dtype, text)))
+                }
+                _ if dtype.is_integer() => DBValue::from_i64(self.as_i64().unwrap(), dtype),
+                _ => DBValue::from_f64(self.as_f64().unwrap(), dtype),
+            },
+            _ => Ok(self),
+        }
+    }
+
+    /// Converts the number to a numeric, rounded to the scale and checked against the
+    /// precision if given.
+    fn to_numeric(&self, modifiers: Option<(u8, u8)>) -> errors::Result<Numeric> {
+        let dtype = DBType::Numeric(modifiers);
+        let number = match self.as_numeric() {
+            Some(number) => number,
+            None => {
+                Numeric::from_f64(self.as_f64().unwrap()).ok_or_else(|| dtype.out_of_range())?
+            }
+        };
+        let Some((precision, scale)) = modifiers else {
+            return Ok(number);
+        };
+        match number.with_scale(scale) {
+            Some(number) if number.fits_precision(precision) => Ok(number),
+            _ => Err(DBError::Execution(format!(
+                "Numeric field overflow, {} doesn't fit in {}",
+                self, dtype
+            ))),
+        }
+    }
+
+    /// The integer as a value of the integer type `dtype`.
+    pub fn from_i64(value: i64, dtype: DBType) -> errors::Result<Self> {
+        let out_of_range = |_| dtype.out_of_range();
+        match dtype {
+            DBType::SmallInt => Ok(DBValue::SmallInt(value.try_into().map_err(out_of_range)?)),
+            DBType::Int => Ok(DBValue::Int(value.try_into().map_err(out_of_range)?)),
+            _ => Ok(DBValue::BigInt(value)),
+        }
+    }
+
+    /// The number as a value of the floating point type `dtype`, rounded for `real`.
+    pub fn from_f64(value: f64, dtype: DBType) -> errors::Result<Self> {
+        match dtype {
+            DBType::Real if value.is_finite() && (value as f32).is_infinite() => {
+                Err(dtype.out_of_range())
+            }
+            DBType::Real => Ok(DBValue::Real(value as f32)),
+            _ => Ok(DBValue::Double(value)),
+        }
+    }
+
+    /// Reads a date, time, timestamp or interval written in ISO 8601 format, `None` if the
+    /// text isn't a valid value of the type.
+    pub fn parse_temporal(text: &str, dtype: DBType) -> Option<Self> {
+        let text = text.trim();
+        match dtype {
+            DBType::Date => text.parse().ok().map(DBValue::Date),
+            DBType::Time => text.parse().ok().map(DBValue::Time),
+            DBType::Timestamp => text.parse().ok().map(DBValue::Timestamp),
+            DBType::Interval => text.parse().ok().map(DBValue::Interval),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer of any width.
+    pub fn as_i64(&self) -> Option<i64> {
         match self {
-            DBValue::Bool(_) => 1,
-            DBValue::Int(_) => 4,
-            DBValue::Double(_) => 8,
-            DBValue::String(s) => 4 + s.len(),
+            DBValue::SmallInt(v) => Some(*v as i64),
+            DBValue::Int(v) => Some(*v as i64),
+            DBValue::BigInt(v) => Some(*v),
+            _ => None,
         }
     }
-}
 
-impl FromStr for DBValue {
-    type Err = errors::DBError;
+    /// The value of a numeric or an integer.
+    pub fn as_numeric(&self) -> Option<Numeric> {
+        match self {
+            DBValue::Numeric(v) => Some(*v),
+            v => v.as_i64().map(Numeric::from_i64),
+        }
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Ok(v) = s.parse::<bool>() {
-            return Ok(DBValue::Bool(v));
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            DBValue::Real(v) => Some(*v as f64),
+            DBValue::Double(v) => Some(*v),
+            DBValue::Numeric(v) => Some(v.to_f64()),
+            v => v.as_i64().map(|v| v as f64),
         }
-        if let Ok(v) = s.parse::<i32>() {
-            return Ok(DBValue::Int(v));
+    }
+
+    /// The value of a timestamp, or of a date as the timestamp of its midnight.
+    pub fn as_timestamp(&self) -> Option<Timestamp> {
+        match self {
+            DBValue::Date(v) => Some(v.at_midnight()),
+            DBValue::Timestamp(v) => Some(*v),
+            _ => None,
         }
-        if let Ok(v) = s.parse::<f64>() {
-            return Ok(DBValue::Double(v));
+    }
+
+    /// The contents of a string or binary string, which are stored with their length.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            DBValue::String(v) => Some(v.as_bytes()),
+            DBValue::Bytes(v) => Some(v),
+            _ => None,
         }
-        // TODO: handle '
-        if s.starts_with('\'') && s.ends_with('\'') {
-            let value = s[1..s.len() - 1].to_string();
-            return Ok(DBValue::String(value));
+    }
+
+    /// Like [`Display`], but without quoting strings.
+    pub fn to_raw_string(&self) -> String {
+        match self {
+            DBValue::String(v) => v.clone(),
+            v => v.to_string(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            DBValue::Null => 0,
+            DBValue::Bool(_) => 1,
+            DBValue::SmallInt(_) => 2,
+            DBValue::Int(_) | DBValue::Real(_) | DBValue::Date(_) => 4,
+            DBValue::BigInt(_) | DBValue::Double(_) => 8,
+            DBValue::Time(_) | DBValue::Timestamp(_) => 8,
+            DBValue::Interval(_) => 16,
+            DBValue::Numeric(_) => 17,
+            DBValue::String(s) => 4 + s.len(),
+            DBValue::Bytes(b) => 4 + b.len(),
         }
-        Err(errors::DBError::Parse(format!("Invalid DBValue: {}", s)))
     }
 }
 
 impl PartialOrd for DBValue {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        // Numbers of different types compare as the wider type.
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            return a.partial_cmp(&b);
+        }
+        if let (Some(a), Some(b)) = (self.as_numeric(), other.as_numeric()) {
+            return a.partial_cmp(&b);
+        }
+        if let (Some(a), Some(b)) = (self.as_f64(), other.as_f64()) {
+            return a.partial_cmp(&b);
+        }
+        // Dates compare with timestamps as their midnights.
+        if let (Some(a), Some(b)) = (self.as_timestamp(), other.as_timestamp()) {
+            return a.partial_cmp(&b);
+        }
         match (self, other) {
-            (DBValue::Int(a), DBValue::Int(b)) => a.partial_cmp(b),
-            (DBValue::Double(a), DBValue::Double(b)) => a.partial_cmp(b),
+            (DBValue::String(a), DBValue::String(b)) => a.partial_cmp(b),
+            (DBValue::Bytes(a), DBValue::Bytes(b)) => a.partial_cmp(b),
+            (DBValue::Bool(a), DBValue::Bool(b)) => a.partial_cmp(b),
+            (DBValue::Time(a), DBValue::Time(b)) => a.partial_cmp(b),
+            (DBValue::Interval(a), DBValue::Interval(b)) => a.partial_cmp(b),
             _ => None,
         }
     }
@@ -0,0 +1,169 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::serializer::{BinaryReader, BinaryWriter};
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Syncs a directory, so that files created, renamed or removed in it survive a crash.
+pub(crate) fn sync_dir(path: &Path) -> io::Result<()> {
+    // Directories can't be opened as files on Windows, and don't need to be synced there.
+    if cfg!(unix) {
+        File::open(path)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Redo log of committed changes. A commit appends a batch with all of its changes and
+/// syncs it before anything else is written, so a crash while the changes are being applied
+/// can be repaired by applying the batch again.
+///
+/// Each batch is prefixed with its length and checksum. A batch that was only partially
+/// written when the crash happened fails the checksum and is ignored, as its commit never
+/// finished.
+pub(crate) struct Wal {
+    path: PathBuf,
+}
+
+impl Wal {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Directory with the files the log refers to.
+    pub fn dir(&self) -> &Path {
+        self.path.parent().unwrap()
+    }
+
+    /// Appends a batch and waits until it's on disk.
+    pub fn append(&self, batch: &[u8]) -> io::Result<()> {
+        let existed = self.path.exists();
+        let mut file = File::options().create(true).append(true).open(&self.path)?;
+        let mut data = Vec::with_capacity(batch.len() + 8);
+        let mut writer = BinaryWriter::new(&mut data);
+        writer.write_u32(batch.len() as u32)?;
+        writer.write_u32(crc32(batch))?;
+        writer.write_all(batch)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        if !existed {
+            sync_dir(self.dir())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the complete batches of the log, oldest first.
+    pub fn read_batches(&self) -> io::Result<Vec<Vec<u8>>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+        let mut batches = vec![];
+        let mut rest = data.as_slice();
+        while rest.len() >= 8 {
+            let mut reader = BinaryReader::new(rest);
+            let len = reader.read_u32()? as usize;
+            let checksum = reader.read_u32()?;
+            let Some(batch) = rest.get(8..8 + len) else {
+                break;
+            };
+            if crc32(batch) != checksum {
+                break;
+            }
+            batches.push(batch.to_vec());
+            rest = &rest[8 + len..];
+        }
+        Ok(batches)
+    }
+
+    pub fn size(&self) -> io::Result<u64> {
+        match fs::metadata(&self.path) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Empties the log once all of its batches are applied.
+    pub fn truncate(&self) -> io::Result<()> {
+        match File::options().write(true).open(&self.path) {
+            Ok(file) => {
+                file.set_len(0)?;
+                file.sync_all()
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use temp_dir::TempDir;
+
+    use super::{Wal, crc32};
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn test_torn_batch_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        let wal = Wal::new(dir.path().join("wal"));
+        assert_eq!(wal.read_batches().unwrap(), Vec::<Vec<u8>>::new());
+        wal.append(b"meow").unwrap();
+        wal.append(b"fox").unwrap();
+        assert_eq!(
+            wal.read_batches().unwrap(),
+            vec![b"meow".to_vec(), b"fox".to_vec()]
+        );
+
+        // A batch cut short by a crash.
+        let mut file = std::fs::File::options()
+            .append(true)
+            .open(dir.path().join("wal"))
+            .unwrap();
+        file.write_all(&10u32.to_le_bytes()).unwrap();
+        file.write_all(&0u32.to_le_bytes()).unwrap();
+        file.write_all(b"mew").unwrap();
+        assert_eq!(wal.read_batches().unwrap().len(), 2);
+
+        wal.truncate().unwrap();
+        assert_eq!(wal.read_batches().unwrap(), Vec::<Vec<u8>>::new());
+    }
+}